@@ -0,0 +1,76 @@
+// Shared by the benches, each one only uses some of the helpers
+#![allow(dead_code)]
+
+use bulletproofs::RangeProof;
+use merlin::Transcript;
+use xelis_common::{
+    config::XELIS_ASSET,
+    crypto::{
+        elgamal::{Ciphertext, PedersenCommitment, PedersenOpening},
+        proofs::{CiphertextValidityProof, CommitmentEqProof, BP_GENS, BULLET_PROOF_SIZE, PC_GENS},
+        Hash,
+        KeyPair
+    },
+    transaction::{BurnPayload, Reference, SourceCommitment, Transaction, TransactionType, TransferPayload}
+};
+
+// Build a transaction with the requested transfers count
+// Proofs are valid but the transaction is never verified, only serialized
+pub fn transfers_transaction(transfers: usize) -> Transaction {
+    let keypair = KeyPair::new();
+    let destination = KeyPair::new();
+    let opening = PedersenOpening::generate_new();
+    let commitment = PedersenCommitment::new_with_opening(1u64, &opening).compress();
+    let sender_handle = keypair.get_public_key().decrypt_handle(&opening).compress();
+    let receiver_handle = destination.get_public_key().decrypt_handle(&opening).compress();
+    let proof = CiphertextValidityProof::new(destination.get_public_key(), 1, &opening, &mut Transcript::new(b"bench"));
+    let transfer = TransferPayload::new(XELIS_ASSET, destination.get_public_key().compress(), None, commitment, sender_handle, receiver_handle, proof);
+
+    transaction(&keypair, TransactionType::Transfers(vec![transfer; transfers]), &opening)
+}
+
+// Build a transaction burning some coins
+pub fn burn_transaction() -> Transaction {
+    let keypair = KeyPair::new();
+    let payload = BurnPayload {
+        asset: XELIS_ASSET,
+        amount: 1,
+        extra_data: None
+    };
+
+    transaction(&keypair, TransactionType::Burn(payload), &PedersenOpening::generate_new())
+}
+
+// A transaction needs at least one source commitment to be read back
+fn transaction(keypair: &KeyPair, data: TransactionType, opening: &PedersenOpening) -> Transaction {
+    let source_opening = PedersenOpening::generate_new();
+    let source_commitment = PedersenCommitment::new_with_opening(1u64, &source_opening).compress();
+    let proof = CommitmentEqProof::new(keypair, &Ciphertext::zero(), &source_opening, 1, &mut Transcript::new(b"bench"));
+    let source_commitments = vec![SourceCommitment::new(source_commitment, proof, XELIS_ASSET)];
+
+    // The range proof aggregates the source commitment and the transfers, padded to a power of two
+    let parties = (1 + data.transfer_count()).next_power_of_two();
+    let values = vec![1; parties];
+    let mut blindings = vec![opening.as_scalar(); parties];
+    blindings[0] = source_opening.as_scalar();
+    let (range_proof, _) = RangeProof::prove_multiple(&BP_GENS, &PC_GENS, &mut Transcript::new(b"bench"), &values, &blindings, BULLET_PROOF_SIZE).unwrap();
+    let reference = Reference {
+        hash: Hash::zero(),
+        topoheight: 0
+    };
+
+    Transaction::new(
+        1,
+        keypair.get_public_key().compress(),
+        None,
+        data,
+        0,
+        None,
+        0,
+        source_commitments,
+        range_proof,
+        reference,
+        keypair.sign(b"bench"),
+        Vec::new()
+    )
+}
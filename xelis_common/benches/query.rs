@@ -0,0 +1,61 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use xelis_common::api::{
+    query::{clear_regex_cache, QueryValue},
+    DataValue
+};
+
+const QUERY: &str = r#"{"matches":"^(?:[a-z0-9._-]+)@(?:[a-z0-9-]+\\.)+[a-z]{2,}$"}"#;
+const MATCHES: usize = 10_000;
+
+// Deserialize the same pattern query as received from RPC and match it against a value
+// If clear_cache is set, the pattern is compiled again for each query
+fn deserialize_and_match(value: &DataValue, clear_cache: bool) -> usize {
+    let mut count = 0;
+    for _ in 0..MATCHES {
+        if clear_cache {
+            clear_regex_cache();
+        }
+
+        let query: QueryValue = serde_json::from_str(QUERY).unwrap();
+        if query.verify(value) {
+            count += 1;
+        }
+    }
+    count
+}
+
+fn bench_regex_cache(c: &mut Criterion) {
+    let value = DataValue::String("alice@xelis.io".to_string());
+
+    c.bench_function("pattern query 10k cached", |b| b.iter(|| {
+        black_box(deserialize_and_match(&value, false))
+    }));
+
+    c.bench_function("pattern query 10k uncached", |b| b.iter(|| {
+        black_box(deserialize_and_match(&value, true))
+    }));
+}
+
+// Previous behavior: both sides converted to a new String on each comparison
+fn naive_starts_with(value: &DataValue, prefix: &DataValue) -> bool {
+    value.to_string().starts_with(&prefix.to_string())
+}
+
+fn bench_string_queries(c: &mut Criterion) {
+    let values: Vec<_> = (0..MATCHES)
+        .map(|i| DataValue::String(format!("user_{}@xelis.io", i)))
+        .collect();
+    let prefix = DataValue::String("user_1".to_string());
+    let query = QueryValue::StartsWith(prefix.clone().into());
+
+    c.bench_function("starts_with 10k naive", |b| b.iter(|| {
+        black_box(values.iter().filter(|value| naive_starts_with(value, &prefix)).count())
+    }));
+
+    c.bench_function("starts_with 10k cached", |b| b.iter(|| {
+        black_box(values.iter().filter(|value| query.verify(value)).count())
+    }));
+}
+
+criterion_group!(benches, bench_regex_cache, bench_string_queries);
+criterion_main!(benches);
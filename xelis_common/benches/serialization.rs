@@ -0,0 +1,74 @@
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use xelis_common::{
+    api::{DataElement, DataValue},
+    serializer::{Serializer, Writer},
+    transaction::Transaction
+};
+
+mod common;
+
+use common::{burn_transaction, transfers_transaction};
+
+// Benchmark write, read and size of a value
+// Throughput is reported from its serialized size
+fn bench_value<T: Serializer>(c: &mut Criterion, group: &str, name: &str, value: &T) {
+    let bytes = value.to_bytes();
+    if let Err(e) = T::from_bytes(&bytes) {
+        panic!("{} {} can't be read back: {}", group, name, e);
+    }
+
+    let mut group = c.benchmark_group(group);
+    group.throughput(Throughput::Bytes(bytes.len() as u64));
+
+    group.bench_with_input(BenchmarkId::new("write", name), value, |b, value| b.iter(|| {
+        let mut writer = Writer::with_capacity(bytes.len());
+        value.write(&mut writer);
+        black_box(writer.bytes())
+    }));
+
+    group.bench_with_input(BenchmarkId::new("read", name), &bytes, |b, bytes| b.iter(|| {
+        black_box(T::from_bytes(bytes).unwrap())
+    }));
+
+    group.bench_with_input(BenchmarkId::new("size", name), value, |b, value| b.iter(|| {
+        black_box(value.size())
+    }));
+
+    group.finish();
+}
+
+fn bench_transaction(c: &mut Criterion) {
+    let transactions: [(&str, Transaction); 3] = [
+        ("1 transfer", transfers_transaction(1)),
+        ("100 transfers", transfers_transaction(100)),
+        ("burn", burn_transaction()),
+    ];
+
+    for (name, tx) in &transactions {
+        bench_value(c, "transaction", name, tx);
+    }
+}
+
+// Build a tree nested depth times
+// Each level holds a few values next to the nested element
+fn data_element(depth: usize) -> DataElement {
+    let mut element = DataElement::Value(DataValue::U64(0));
+    for i in 0..depth {
+        element = DataElement::Array(vec![
+            DataElement::Value(DataValue::U64(i as u64)),
+            DataElement::Value(DataValue::String(format!("level {}", i))),
+            DataElement::Value(DataValue::Bool(i % 2 == 0)),
+            element,
+        ]);
+    }
+    element
+}
+
+fn bench_data_element(c: &mut Criterion) {
+    for depth in [1, 8, 32] {
+        bench_value(c, "data element", &format!("depth {}", depth), &data_element(depth));
+    }
+}
+
+criterion_group!(benches, bench_transaction, bench_data_element);
+criterion_main!(benches);
@@ -0,0 +1,31 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use xelis_common::serializer::{Serializer, Writer};
+
+mod common;
+
+use common::transfers_transaction;
+
+const TRANSFERS: usize = 200;
+
+// Previous behavior: the buffer grows while writing
+fn naive_to_bytes<T: Serializer>(value: &T) -> Vec<u8> {
+    let mut writer = Writer::new();
+    value.write(&mut writer);
+    writer.bytes()
+}
+
+fn bench_to_bytes(c: &mut Criterion) {
+    let tx = transfers_transaction(TRANSFERS);
+    assert_eq!(naive_to_bytes(&tx), tx.to_bytes());
+
+    c.bench_function("tx 200 transfers to_bytes naive", |b| b.iter(|| {
+        black_box(naive_to_bytes(&tx))
+    }));
+
+    c.bench_function("tx 200 transfers to_bytes pre-sized", |b| b.iter(|| {
+        black_box(tx.to_bytes())
+    }));
+}
+
+criterion_group!(benches, bench_to_bytes);
+criterion_main!(benches);
@@ -0,0 +1,29 @@
+#![no_main]
+
+// Run it with `cargo fuzz run fuzz_transaction_read` from the xelis_common directory
+// corpus/fuzz_transaction_read contains a few valid transactions to start from
+
+use libfuzzer_sys::fuzz_target;
+use xelis_common::{
+    serializer::{Reader, Serializer},
+    transaction::Transaction
+};
+
+fuzz_target!(|data: &[u8]| {
+    // Any input must be rejected cleanly, never panic
+    let mut reader = Reader::new(data);
+    let Ok(tx) = Transaction::read(&mut reader) else {
+        return
+    };
+
+    // A transaction read must be written back the same way it was received,
+    // otherwise another encoding of it exists and its hash could be changed
+    let bytes = tx.to_bytes();
+    assert_eq!(bytes.len(), tx.size());
+    assert_eq!(bytes, &data[..reader.total_read()], "transaction encoding is malleable");
+
+    // Written bytes must be read back to the same transaction
+    let read = Transaction::from_bytes(&bytes).expect("written transaction can't be read back");
+    assert_eq!(read.to_bytes(), bytes);
+    assert_eq!(read.hash(), tx.hash());
+});
@@ -11,7 +11,8 @@ use crate::{
     crypto::{Address, Hash},
     difficulty::{CumulativeDifficulty, Difficulty},
     network::Network,
-    time::{TimestampMillis, TimestampSeconds}
+    time::{TimestampMillis, TimestampSeconds},
+    transaction::summary::TransactionSummary
 };
 use super::RPCTransaction;
 
@@ -604,6 +605,20 @@ pub struct TransactionExecutedEvent<'a> {
     pub topoheight: u64,
 }
 
+// Transaction events sent to wallet clients
+// Only the public summary is included, never the commitments or proofs
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(tag = "event", content = "value", rename_all = "snake_case")]
+pub enum DaemonEvent {
+    // A new transaction has been added in mempool
+    NewTransaction {
+        hash: Hash,
+        summary: TransactionSummary
+    },
+    // A transaction executed in a block is not reintroduced in mempool
+    TransactionOrphaned(Hash)
+}
+
 // Value of NotifyEvent::PeerConnected
 pub type PeerConnectedEvent = PeerEntry<'static>;
 
@@ -629,4 +644,65 @@ pub struct PeerPeerDisconnectedEvent {
     pub peer_id: u64,
     // address of the peer that disconnected from him
     pub peer_addr: SocketAddr
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use crate::{
+        crypto::KeyPair,
+        transaction::summary::{TransactionTypeSummary, TransferSummary}
+    };
+    use super::*;
+
+    fn summary(data: TransactionTypeSummary) -> TransactionSummary {
+        TransactionSummary {
+            version: 1,
+            source: KeyPair::new().get_public_key().compress(),
+            fee: 25_000,
+            nonce: 3,
+            data
+        }
+    }
+
+    fn assert_round_trip(event: &DaemonEvent) -> serde_json::Value {
+        let json = serde_json::to_value(event).unwrap();
+        let decoded: DaemonEvent = serde_json::from_value(json.clone()).unwrap();
+        assert_eq!(&decoded, event);
+        json
+    }
+
+    #[test]
+    fn test_daemon_event_new_transaction() {
+        let transfers = TransactionTypeSummary::Transfers(vec![TransferSummary {
+            asset: Hash::zero(),
+            destination: KeyPair::new().get_public_key().compress()
+        }]);
+        let burn = TransactionTypeSummary::Burn {
+            asset: Hash::zero(),
+            amount: 100
+        };
+
+        for data in [transfers, burn] {
+            let event = DaemonEvent::NewTransaction {
+                hash: Hash::max(),
+                summary: summary(data)
+            };
+
+            let json = assert_round_trip(&event);
+            assert_eq!(json["event"], "new_transaction");
+            assert_eq!(json["value"]["hash"], Hash::max().to_hex());
+            assert_eq!(json["value"]["summary"]["fee"], 25_000);
+        }
+    }
+
+    #[test]
+    fn test_daemon_event_transaction_orphaned() {
+        let event = DaemonEvent::TransactionOrphaned(Hash::max());
+        let json = assert_round_trip(&event);
+        assert_eq!(json, json!({
+            "event": "transaction_orphaned",
+            "value": Hash::max().to_hex()
+        }));
+    }
+}
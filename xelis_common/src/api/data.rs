@@ -1,14 +1,29 @@
-use std::collections::HashMap;
-
-use serde::{Serialize, Deserialize};
+use std::{borrow::Cow, cmp::Ordering, collections::HashMap, fmt};
+
+use serde::{
+    de::{Error as SerdeError, MapAccess, Visitor},
+    Deserialize,
+    Deserializer,
+    Serialize,
+    Serializer as SerdeSerializer
+};
 use thiserror::Error;
 
 use crate::{
     serializer::{Reader, ReaderError, Serializer, Writer},
     crypto::Hash
 };
+use super::depth::{DepthGuard, ELEMENT_DEPTH};
+
+// Maximum size of a DataValue::Bytes
+pub const MAX_BYTES_SIZE: usize = 8 * 1024;
+// Maximum nesting of arrays and fields accepted when reading a DataElement
+// from bytes or from JSON
+pub const MAX_ELEMENT_DEPTH: usize = 32;
+// Maximum count of items in an array or fields, their count is written on a byte
+pub const MAX_ELEMENT_ITEMS: usize = u8::MAX as usize;
 
-#[derive(Debug, Error)]
+#[derive(Debug, Error, Clone)]
 pub enum DataConversionError {
     #[error("Expected a value")]
     ExpectedValue,
@@ -20,10 +35,20 @@ pub enum DataConversionError {
     ExpectedMap,
     #[error("Unexpected value type {:?}", _0)]
     UnexpectedValue(ValueType),
+    #[error("Value {} is out of range for the requested type", _0)]
+    OutOfRange(String),
+    #[error("Unsupported JSON value: {}", _0)]
+    UnsupportedJson(serde_json::Value),
+    #[error("Element is nested deeper than {}", MAX_ELEMENT_DEPTH)]
+    TooDeep,
+    #[error("Element has {} items while the maximum is {}", _0, MAX_ELEMENT_ITEMS)]
+    TooManyItems(usize),
+    #[error("Bytes value has {} bytes while the maximum is {}", _0, MAX_BYTES_SIZE)]
+    TooManyBytes(usize),
 }
 
 // All types availables
-#[derive(Debug, Serialize, Deserialize, Eq, PartialEq, Hash, Clone, Copy)]
+#[derive(Debug, Serialize, Deserialize, Eq, PartialEq, Ord, PartialOrd, Hash, Clone, Copy)]
 pub enum ValueType {
     Bool,
     String,
@@ -32,7 +57,13 @@ pub enum ValueType {
     U32,
     U64,
     U128,
-    Hash
+    Hash,
+    I8,
+    I16,
+    I32,
+    I64,
+    I128,
+    Bytes
 }
 
 #[derive(Debug, Serialize, Deserialize, Eq, PartialEq, Hash, Clone)]
@@ -52,7 +83,12 @@ impl ValueType {
             Self::U64 |
             Self::U32 |
             Self::U16 |
-            Self::U8 => true,
+            Self::U8 |
+            Self::I128 |
+            Self::I64 |
+            Self::I32 |
+            Self::I16 |
+            Self::I8 => true,
             _ => false
         }
     }
@@ -69,6 +105,12 @@ impl Serializer for ValueType {
             5 => Self::U64,
             6 => Self::U128,
             7 => Self::Hash,
+            8 => Self::I8,
+            9 => Self::I16,
+            10 => Self::I32,
+            11 => Self::I64,
+            12 => Self::I128,
+            13 => Self::Bytes,
             _ => return Err(ReaderError::InvalidValue)
         })
     }
@@ -82,7 +124,13 @@ impl Serializer for ValueType {
             Self::U32 => 4,
             Self::U64 => 5,
             Self::U128 => 6,
-            Self::Hash => 7
+            Self::Hash => 7,
+            Self::I8 => 8,
+            Self::I16 => 9,
+            Self::I32 => 10,
+            Self::I64 => 11,
+            Self::I128 => 12,
+            Self::Bytes => 13
         });
     }
 
@@ -94,14 +142,70 @@ impl Serializer for ValueType {
 
 // This enum allows complex structures with multi depth if necessary
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
-#[serde(untagged)]
+#[serde(remote = "Self", untagged)]
 pub enum DataElement {
     Value(DataValue),
     // For two next variants, we support up to 255 (u8::MAX) elements maximum
     Array(Vec<DataElement>),
+    #[serde(deserialize_with = "deserialize_fields")]
     Fields(HashMap<DataValue, DataElement>)
 }
 
+impl Serialize for DataElement {
+    fn serialize<S: SerdeSerializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        DataElement::serialize(self, serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for DataElement {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let guard = DepthGuard::enter(&ELEMENT_DEPTH);
+
+        // The top level element is at depth 0
+        if guard.get_depth() > MAX_ELEMENT_DEPTH + 1 {
+            return Err(SerdeError::custom(format!("element is nested deeper than {}", MAX_ELEMENT_DEPTH)))
+        }
+
+        let element = DataElement::deserialize(deserializer)?;
+        let items = match &element {
+            DataElement::Array(values) => values.len(),
+            DataElement::Fields(fields) => fields.len(),
+            DataElement::Value(_) => 0
+        };
+
+        if items > MAX_ELEMENT_ITEMS {
+            return Err(SerdeError::custom(DataConversionError::TooManyItems(items)))
+        }
+
+        Ok(element)
+    }
+}
+
+// Same as for a HashMap, but a duplicated key is an error instead of overwriting the previous value
+fn deserialize_fields<'de, D: Deserializer<'de>>(deserializer: D) -> Result<HashMap<DataValue, DataElement>, D::Error> {
+    struct FieldsVisitor;
+
+    impl<'de> Visitor<'de> for FieldsVisitor {
+        type Value = HashMap<DataValue, DataElement>;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            formatter.write_str("a map of fields")
+        }
+
+        fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+            let mut fields = HashMap::new();
+            while let Some((key, value)) = map.next_entry()? {
+                if fields.insert(key, value).is_some() {
+                    return Err(A::Error::custom("duplicated key in fields"))
+                }
+            }
+            Ok(fields)
+        }
+    }
+
+    deserializer.deserialize_map(FieldsVisitor)
+}
+
 impl DataElement {
     pub fn has_key(&self, key: &DataValue) -> bool {
         let Self::Fields(fields) = &self else {
@@ -129,6 +233,45 @@ impl DataElement {
         Some(value)
     }
 
+    // Walk through the fields by key and the arrays by index
+    // None if a key is missing or an index is out of bounds
+    pub fn get_path(&self, path: &[DataValue]) -> Option<&DataElement> {
+        let mut element = self;
+        for key in path {
+            element = match element {
+                Self::Fields(fields) => fields.get(key)?,
+                Self::Array(array) => array.get(usize::try_from(key.as_number()?).ok()?)?,
+                Self::Value(_) => return None
+            };
+        }
+        Some(element)
+    }
+
+    // Iterate over every value with the path to reach it, see get_path
+    // Array indexes are given as U64
+    pub fn leaves(&self) -> impl Iterator<Item = (Vec<DataValue>, &DataValue)> {
+        let mut stack = vec![(Vec::new(), self)];
+        std::iter::from_fn(move || {
+            while let Some((path, element)) = stack.pop() {
+                match element {
+                    Self::Value(value) => return Some((path, value)),
+                    // Reversed so they are visited in order
+                    Self::Array(array) => for (i, element) in array.iter().enumerate().rev() {
+                        let mut path = path.clone();
+                        path.push(DataValue::U64(i as u64));
+                        stack.push((path, element));
+                    },
+                    Self::Fields(fields) => for (key, element) in fields {
+                        let mut path = path.clone();
+                        path.push(key.clone());
+                        stack.push((path, element));
+                    }
+                }
+            }
+            None
+        })
+    }
+
     pub fn get_value_by_string_key(&self, name: String, value_type: ValueType) -> Option<&DataValue> {
         self.get_value_by_key(&DataValue::String(name), Some(value_type))
     }
@@ -182,20 +325,53 @@ impl DataElement {
             _ => Err(DataConversionError::ExpectedMap)
         }
     }
+
+    // Verify that the element can be written, and so read back:
+    // up to MAX_ELEMENT_ITEMS items per array or fields,
+    // nested up to MAX_ELEMENT_DEPTH times, and valid values
+    pub fn validate(&self) -> Result<(), DataConversionError> {
+        self.validate_at(0)
+    }
+
+    fn validate_at(&self, depth: usize) -> Result<(), DataConversionError> {
+        if depth > MAX_ELEMENT_DEPTH {
+            return Err(DataConversionError::TooDeep)
+        }
+
+        match self {
+            Self::Value(value) => value.validate(),
+            Self::Array(values) => {
+                if values.len() > MAX_ELEMENT_ITEMS {
+                    return Err(DataConversionError::TooManyItems(values.len()))
+                }
+                values.iter().try_for_each(|value| value.validate_at(depth + 1))
+            },
+            Self::Fields(fields) => {
+                if fields.len() > MAX_ELEMENT_ITEMS {
+                    return Err(DataConversionError::TooManyItems(fields.len()))
+                }
+                fields.values().try_for_each(|value| value.validate_at(depth + 1))
+            }
+        }
+    }
 } 
 
-impl Serializer for DataElement {
-    // Don't do any pre-allocation because of infinite depth
+impl DataElement {
+    // Don't do any pre-allocation because of the depth
     // Otherwise an attacker could generate big depth with high size until max limit
     // which can create OOM on low devices
-    fn read(reader: &mut Reader) -> Result<Self, ReaderError> {
+    fn read_with_depth(reader: &mut Reader, depth: usize) -> Result<Self, ReaderError> {
+        if depth > MAX_ELEMENT_DEPTH {
+            return Err(ReaderError::InvalidValue)
+        }
+
         Ok(match reader.read_u8()? {
             0 => Self::Value(DataValue::read(reader)?),
             1 => {
                 let size = reader.read_u8()?;
                 let mut values = Vec::new();
                 for _ in 0..size {
-                    values.push(DataElement::read(reader)?)
+                    values.push(DataElement::read_with_depth(reader, depth + 1)?)
                 }
                 Self::Array(values)
             },
@@ -204,8 +380,11 @@ impl Serializer for DataElement {
                 let mut fields = HashMap::new();
                 for _ in 0..size {
                     let key = DataValue::read(reader)?;
-                    let value = DataElement::read(reader)?;
-                    fields.insert(key, value);
+                    let value = DataElement::read_with_depth(reader, depth + 1)?;
+                    // A duplicated key would be lost
+                    if fields.insert(key, value).is_some() {
+                        return Err(ReaderError::InvalidValue)
+                    }
                 }
                 Self::Fields(fields)
             },
@@ -213,7 +392,14 @@ impl Serializer for DataElement {
         })
     }
 
-    fn write(&self, writer: &mut Writer) {
+    // Writing an invalid element is a bug: it could not be read back,
+    // so it must be checked with validate() when built from untrusted input
+    fn write_with_depth(&self, writer: &mut Writer, depth: usize) {
+        assert!(depth <= MAX_ELEMENT_DEPTH, "DataElement is nested deeper than {}", MAX_ELEMENT_DEPTH);
+
+        let items = |len: usize| u8::try_from(len)
+            .unwrap_or_else(|_| panic!("DataElement has {} items while the maximum is {}", len, MAX_ELEMENT_ITEMS));
+
         match self {
             Self::Value(value) => {
                 writer.write_u8(0);
@@ -221,21 +407,32 @@ impl Serializer for DataElement {
             }
             Self::Array(values) => {
                 writer.write_u8(1);
-                writer.write_u8(values.len() as u8); // we accept up to 255 values
+                writer.write_u8(items(values.len()));
                 for value in values {
-                    value.write(writer);    
+                    value.write_with_depth(writer, depth + 1);
                 }
             }
             Self::Fields(fields) => {
                 writer.write_u8(2);
-                writer.write_u8(fields.len() as u8);
+                writer.write_u8(items(fields.len()));
                 for (key, value) in fields {
                     key.write(writer);
-                    value.write(writer);
+                    value.write_with_depth(writer, depth + 1);
                 }
             }
         }
     }
+}
+
+impl Serializer for DataElement {
+    fn read(reader: &mut Reader) -> Result<Self, ReaderError> {
+        Self::read_with_depth(reader, 0)
+    }
+
+    // Panics if the element is not valid, see DataElement::validate
+    fn write(&self, writer: &mut Writer) {
+        self.write_with_depth(writer, 0)
+    }
 
     fn size(&self) -> usize {
         1 + match self {
@@ -269,9 +466,55 @@ pub enum DataValue {
     U64(u64),
     U128(u128),
     Hash(Hash),
+    I8(i8),
+    I16(i16),
+    I32(i32),
+    I64(i64),
+    I128(i128),
+    // JSON has no bytes type, they are written as {"bytes": "<hex>"}
+    // A plain hexadecimal string would be read back as a String
+    #[serde(serialize_with = "serialize_bytes", deserialize_with = "deserialize_bytes")]
+    Bytes(Vec<u8>),
+}
+
+// JSON representation of DataValue::Bytes
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TaggedBytes<'a> {
+    bytes: Cow<'a, str>
+}
+
+pub fn serialize_bytes<S: SerdeSerializer>(bytes: &[u8], s: S) -> Result<S::Ok, S::Error> {
+    TaggedBytes { bytes: Cow::Owned(hex::encode(bytes)) }.serialize(s)
+}
+
+// Deserialize the bytes from their hexadecimal string
+pub fn deserialize_bytes<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+    let TaggedBytes { bytes: hex } = TaggedBytes::deserialize(deserializer)?;
+    let bytes = hex::decode(hex.as_ref()).map_err(SerdeError::custom)?;
+    if bytes.len() > MAX_BYTES_SIZE {
+        return Err(SerdeError::custom(DataConversionError::TooManyBytes(bytes.len())))
+    }
+    Ok(bytes)
 }
 
 impl DataValue {
+    // Bytes are limited to MAX_BYTES_SIZE
+    pub fn bytes(bytes: Vec<u8>) -> Result<Self, DataConversionError> {
+        if bytes.len() > MAX_BYTES_SIZE {
+            return Err(DataConversionError::TooManyBytes(bytes.len()))
+        }
+        Ok(Self::Bytes(bytes))
+    }
+
+    // Verify that the value can be written, and so read back
+    pub fn validate(&self) -> Result<(), DataConversionError> {
+        match self {
+            Self::Bytes(bytes) if bytes.len() > MAX_BYTES_SIZE => Err(DataConversionError::TooManyBytes(bytes.len())),
+            _ => Ok(())
+        }
+    }
+
     pub fn kind(&self) -> ValueType {
         match self {
             Self::Bool(_) => ValueType::Bool,
@@ -281,7 +524,13 @@ impl DataValue {
             Self::U32(_) => ValueType::U32,
             Self::U64(_) => ValueType::U64,
             Self::U128(_) => ValueType::U128,
-            Self::Hash(_) => ValueType::Hash
+            Self::Hash(_) => ValueType::Hash,
+            Self::I8(_) => ValueType::I8,
+            Self::I16(_) => ValueType::I16,
+            Self::I32(_) => ValueType::I32,
+            Self::I64(_) => ValueType::I64,
+            Self::I128(_) => ValueType::I128,
+            Self::Bytes(_) => ValueType::Bytes
         }
     }
 
@@ -334,6 +583,48 @@ impl DataValue {
         }
     }
 
+    pub fn to_i8(self) -> Result<i8, DataConversionError> {
+        match self {
+            Self::I8(v) => Ok(v),
+            _ => Err(DataConversionError::UnexpectedValue(self.kind()))
+        }
+    }
+
+    pub fn to_i16(self) -> Result<i16, DataConversionError> {
+        match self {
+            Self::I16(v) => Ok(v),
+            _ => Err(DataConversionError::UnexpectedValue(self.kind()))
+        }
+    }
+
+    pub fn to_i32(self) -> Result<i32, DataConversionError> {
+        match self {
+            Self::I32(v) => Ok(v),
+            _ => Err(DataConversionError::UnexpectedValue(self.kind()))
+        }
+    }
+
+    pub fn to_i64(self) -> Result<i64, DataConversionError> {
+        match self {
+            Self::I64(v) => Ok(v),
+            _ => Err(DataConversionError::UnexpectedValue(self.kind()))
+        }
+    }
+
+    pub fn to_i128(self) -> Result<i128, DataConversionError> {
+        match self {
+            Self::I128(v) => Ok(v),
+            _ => Err(DataConversionError::UnexpectedValue(self.kind()))
+        }
+    }
+
+    pub fn to_bytes_vec(self) -> Result<Vec<u8>, DataConversionError> {
+        match self {
+            Self::Bytes(v) => Ok(v),
+            _ => Err(DataConversionError::UnexpectedValue(self.kind()))
+        }
+    }
+
     pub fn to_hash(self) -> Result<Hash, DataConversionError> {
         match self {
             Self::Hash(v) => Ok(v),
@@ -355,6 +646,13 @@ impl DataValue {
         }
     }
 
+    pub fn as_str(&self) -> Result<&str, DataConversionError> {
+        match self {
+            Self::String(v) => Ok(v),
+            _ => Err(DataConversionError::UnexpectedValue(self.kind()))
+        }
+    }
+
     pub fn as_u8(&self) -> Result<u8, DataConversionError> {
         match self {
             Self::U8(v) => Ok(*v),
@@ -390,6 +688,48 @@ impl DataValue {
         }
     }
 
+    pub fn as_i8(&self) -> Result<i8, DataConversionError> {
+        match self {
+            Self::I8(v) => Ok(*v),
+            _ => Err(DataConversionError::UnexpectedValue(self.kind()))
+        }
+    }
+
+    pub fn as_i16(&self) -> Result<i16, DataConversionError> {
+        match self {
+            Self::I16(v) => Ok(*v),
+            _ => Err(DataConversionError::UnexpectedValue(self.kind()))
+        }
+    }
+
+    pub fn as_i32(&self) -> Result<i32, DataConversionError> {
+        match self {
+            Self::I32(v) => Ok(*v),
+            _ => Err(DataConversionError::UnexpectedValue(self.kind()))
+        }
+    }
+
+    pub fn as_i64(&self) -> Result<i64, DataConversionError> {
+        match self {
+            Self::I64(v) => Ok(*v),
+            _ => Err(DataConversionError::UnexpectedValue(self.kind()))
+        }
+    }
+
+    pub fn as_i128(&self) -> Result<i128, DataConversionError> {
+        match self {
+            Self::I128(v) => Ok(*v),
+            _ => Err(DataConversionError::UnexpectedValue(self.kind()))
+        }
+    }
+
+    pub fn as_bytes(&self) -> Result<&[u8], DataConversionError> {
+        match self {
+            Self::Bytes(v) => Ok(v),
+            _ => Err(DataConversionError::UnexpectedValue(self.kind()))
+        }
+    }
+
     pub fn as_hash(&self) -> Result<&Hash, DataConversionError> {
         match self {
             Self::Hash(v) => Ok(v),
@@ -397,6 +737,66 @@ impl DataValue {
         }
     }
 
+    // Get the value of any integer width, None if not a number or negative
+    pub fn as_number(&self) -> Option<u128> {
+        match self {
+            Self::U8(v) => Some(*v as u128),
+            Self::U16(v) => Some(*v as u128),
+            Self::U32(v) => Some(*v as u128),
+            Self::U64(v) => Some(*v as u128),
+            Self::U128(v) => Some(*v),
+            Self::I8(v) => u128::try_from(*v).ok(),
+            Self::I16(v) => u128::try_from(*v).ok(),
+            Self::I32(v) => u128::try_from(*v).ok(),
+            Self::I64(v) => u128::try_from(*v).ok(),
+            Self::I128(v) => u128::try_from(*v).ok(),
+            _ => None
+        }
+    }
+
+    // Get the value of any integer width and sign, None if not a number or above i128::MAX
+    pub fn as_signed_number(&self) -> Option<i128> {
+        match self {
+            Self::I8(v) => Some(*v as i128),
+            Self::I16(v) => Some(*v as i128),
+            Self::I32(v) => Some(*v as i128),
+            Self::I64(v) => Some(*v as i128),
+            Self::I128(v) => Some(*v),
+            Self::U128(v) => i128::try_from(*v).ok(),
+            _ => self.as_number().map(|v| v as i128)
+        }
+    }
+
+    // Compare the values of two integers whatever their width and sign
+    // None if one of them is not a number
+    pub fn cmp_number(&self, other: &DataValue) -> Option<Ordering> {
+        if !self.kind().is_number() || !other.kind().is_number() {
+            return None
+        }
+
+        Some(match (self.as_number(), other.as_number()) {
+            (Some(a), Some(b)) => a.cmp(&b),
+            // A negative value is lower than any positive one
+            (Some(_), None) => Ordering::Greater,
+            (None, Some(_)) => Ordering::Less,
+            // Both are negative
+            (None, None) => self.as_signed_number()?.cmp(&other.as_signed_number()?)
+        })
+    }
+
+    // Order of the types when comparing values of different types
+    // All numbers share the same rank so they are compared by value
+    fn type_rank(&self) -> u8 {
+        match self {
+            Self::Bool(_) => 0,
+            Self::U8(_) | Self::U16(_) | Self::U32(_) | Self::U64(_) | Self::U128(_)
+            | Self::I8(_) | Self::I16(_) | Self::I32(_) | Self::I64(_) | Self::I128(_) => 1,
+            Self::String(_) => 2,
+            Self::Hash(_) => 3,
+            Self::Bytes(_) => 4
+        }
+    }
+
     fn read_with_type(reader: &mut Reader, value_type: ValueType) -> Result<Self, ReaderError> {
         Ok(match value_type {
             ValueType::Bool => Self::Bool(reader.read_bool()?),
@@ -406,7 +806,20 @@ impl DataValue {
             ValueType::U32 => Self::U32(reader.read_u32()?),
             ValueType::U64 => Self::U64(reader.read_u64()?),
             ValueType::U128 => Self::U128(reader.read_u128()?),
-            ValueType::Hash => Self::Hash(reader.read_hash()?)
+            ValueType::Hash => Self::Hash(reader.read_hash()?),
+            // Signed integers are written in two's complement
+            ValueType::I8 => Self::I8(reader.read_u8()? as i8),
+            ValueType::I16 => Self::I16(reader.read_u16()? as i16),
+            ValueType::I32 => Self::I32(reader.read_u32()? as i32),
+            ValueType::I64 => Self::I64(reader.read_u64()? as i64),
+            ValueType::I128 => Self::I128(reader.read_u128()? as i128),
+            ValueType::Bytes => {
+                let size = reader.read_u16()? as usize;
+                if size > MAX_BYTES_SIZE {
+                    return Err(ReaderError::InvalidSize)
+                }
+                Self::Bytes(reader.read_bytes_ref(size)?.to_vec())
+            }
         })
     }
 
@@ -435,11 +848,56 @@ impl DataValue {
             },
             Self::Hash(hash) => {
                 writer.write_hash(hash);
+            },
+            Self::I8(value) => {
+                writer.write_u8(*value as u8);
+            },
+            Self::I16(value) => {
+                writer.write_u16(*value as u16);
+            },
+            Self::I32(value) => {
+                writer.write_u32(&(*value as u32));
+            },
+            Self::I64(value) => {
+                writer.write_u64(&(*value as u64));
+            },
+            Self::I128(value) => {
+                writer.write_u128(&(*value as u128));
+            },
+            // Writing more would make it unreadable, see DataValue::validate
+            Self::Bytes(bytes) => {
+                assert!(bytes.len() <= MAX_BYTES_SIZE, "DataValue has {} bytes while the maximum is {}", bytes.len(), MAX_BYTES_SIZE);
+                writer.write_u16(bytes.len() as u16);
+                writer.write_bytes(bytes);
             }
         };
     }
 }
 
+// Numbers are compared by value whatever their width and sign,
+// if the values are equal the order of the types is used to stay consistent with Eq
+// Values of different types are ordered as Bool < numbers < String < Hash < Bytes
+impl Ord for DataValue {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (Self::Bool(a), Self::Bool(b)) => a.cmp(b),
+            (Self::String(a), Self::String(b)) => a.cmp(b),
+            (Self::Hash(a), Self::Hash(b)) => a.as_bytes().cmp(b.as_bytes()),
+            (Self::Bytes(a), Self::Bytes(b)) => a.cmp(b),
+            _ => match self.cmp_number(other) {
+                Some(ordering) => ordering.then_with(|| self.kind().cmp(&other.kind())),
+                None => self.type_rank().cmp(&other.type_rank())
+            }
+        }
+    }
+}
+
+impl PartialOrd for DataValue {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
 impl ToString for DataValue {
     fn to_string(&self) -> String {
         match self {
@@ -450,7 +908,13 @@ impl ToString for DataValue {
             Self::U32(v) => format!("{}", v),
             Self::U64(v) => format!("{}", v),
             Self::U128(v) => format!("{}", v),
-            Self::Hash(v) => format!("{}", v)
+            Self::Hash(v) => format!("{}", v),
+            Self::I8(v) => format!("{}", v),
+            Self::I16(v) => format!("{}", v),
+            Self::I32(v) => format!("{}", v),
+            Self::I64(v) => format!("{}", v),
+            Self::I128(v) => format!("{}", v),
+            Self::Bytes(v) => hex::encode(v)
         }
     }
 }
@@ -475,7 +939,14 @@ impl Serializer for DataValue {
             Self::U32(v) => v.size(),
             Self::U64(v) => v.size(),
             Self::U128(v) => v.size(),
-            Self::Hash(hash) => hash.size()
+            Self::Hash(hash) => hash.size(),
+            Self::I8(_) => 1,
+            Self::I16(_) => 2,
+            Self::I32(_) => 4,
+            Self::I64(_) => 8,
+            Self::I128(_) => 16,
+            // 2 bytes for the length
+            Self::Bytes(bytes) => 2 + bytes.len()
         };
         // 1 byte for the type
         size + 1
@@ -548,19 +1019,11 @@ macro_rules! impl_data_value {
                 }
             }
 
-            impl Into<$type2> for DataValue {
-                fn into(self) -> $type2 {
-                    match self {
-                        Self::$type(v) => v,
-                        _ => panic!("Unexpected value type")
-                    }
-                }
-            }
-
             impl Into<$type2> for DataElement {
                 fn into(self) -> $type2 {
                     match self {
-                        DataElement::Value(v) => v.into(),
+                        DataElement::Value(DataValue::$type(v)) => v,
+                        DataElement::Value(_) => panic!("Unexpected value type"),
                         _ => panic!("Unexpected element type")
                     }
                 }
@@ -578,6 +1041,174 @@ macro_rules! impl_data_value {
     };
 }
 
+impl DataValue {
+    // Integers use the smallest width able to hold them,
+    // unsigned if positive and signed if negative
+    fn from_json_number(number: &serde_json::Number) -> Option<Self> {
+        if let Some(v) = number.as_u64() {
+            Some(if let Ok(v) = u8::try_from(v) {
+                Self::U8(v)
+            } else if let Ok(v) = u16::try_from(v) {
+                Self::U16(v)
+            } else if let Ok(v) = u32::try_from(v) {
+                Self::U32(v)
+            } else {
+                Self::U64(v)
+            })
+        } else {
+            let v = number.as_i64()?;
+            Some(if let Ok(v) = i8::try_from(v) {
+                Self::I8(v)
+            } else if let Ok(v) = i16::try_from(v) {
+                Self::I16(v)
+            } else if let Ok(v) = i32::try_from(v) {
+                Self::I32(v)
+            } else {
+                Self::I64(v)
+            })
+        }
+    }
+}
+
+impl DataElement {
+    fn from_json(value: serde_json::Value, depth: usize) -> Result<Self, DataConversionError> {
+        if depth > MAX_ELEMENT_DEPTH {
+            return Err(DataConversionError::TooDeep)
+        }
+
+        Ok(match value {
+            serde_json::Value::Bool(v) => Self::Value(DataValue::Bool(v)),
+            serde_json::Value::String(v) => Self::Value(DataValue::String(v)),
+            serde_json::Value::Number(number) => match DataValue::from_json_number(&number) {
+                Some(v) => Self::Value(v),
+                None => return Err(DataConversionError::UnsupportedJson(serde_json::Value::Number(number)))
+            },
+            serde_json::Value::Array(values) if values.len() > MAX_ELEMENT_ITEMS => return Err(DataConversionError::TooManyItems(values.len())),
+            serde_json::Value::Array(values) => Self::Array(values.into_iter()
+                .map(|value| Self::from_json(value, depth + 1))
+                .collect::<Result<_, _>>()?
+            ),
+            serde_json::Value::Object(map) if map.len() > MAX_ELEMENT_ITEMS => return Err(DataConversionError::TooManyItems(map.len())),
+            serde_json::Value::Object(map) => Self::Fields(map.into_iter()
+                .map(|(key, value)| Ok((DataValue::String(key), Self::from_json(value, depth + 1)?)))
+                .collect::<Result<_, DataConversionError>>()?
+            ),
+            value @ serde_json::Value::Null => return Err(DataConversionError::UnsupportedJson(value))
+        })
+    }
+}
+
+// Floats and null have no DataValue equivalent and are rejected
+// Integers are imported with the smallest width able to hold them:
+// U8 to U64 if positive, I8 to I64 if negative
+impl TryFrom<serde_json::Value> for DataElement {
+    type Error = DataConversionError;
+
+    fn try_from(value: serde_json::Value) -> Result<Self, Self::Error> {
+        Self::from_json(value, 0)
+    }
+}
+
+// Keys of fields are converted to strings
+// and numbers not fitting in JSON (above u64 or below i64) are written as strings
+impl From<DataValue> for serde_json::Value {
+    fn from(value: DataValue) -> Self {
+        match value {
+            DataValue::Bool(v) => Self::Bool(v),
+            DataValue::String(v) => Self::String(v),
+            DataValue::U8(v) => Self::from(v),
+            DataValue::U16(v) => Self::from(v),
+            DataValue::U32(v) => Self::from(v),
+            DataValue::U64(v) => Self::from(v),
+            DataValue::U128(v) => match u64::try_from(v) {
+                Ok(v) => Self::from(v),
+                Err(_) => Self::String(v.to_string())
+            },
+            DataValue::I8(v) => Self::from(v),
+            DataValue::I16(v) => Self::from(v),
+            DataValue::I32(v) => Self::from(v),
+            DataValue::I64(v) => Self::from(v),
+            DataValue::I128(v) => match i64::try_from(v) {
+                Ok(v) => Self::from(v),
+                Err(_) => Self::String(v.to_string())
+            },
+            DataValue::Hash(v) => Self::String(v.to_hex()),
+            DataValue::Bytes(v) => Self::String(hex::encode(v))
+        }
+    }
+}
+
+impl From<DataElement> for serde_json::Value {
+    fn from(element: DataElement) -> Self {
+        match element {
+            DataElement::Value(value) => value.into(),
+            DataElement::Array(values) => Self::Array(values.into_iter().map(Self::from).collect()),
+            DataElement::Fields(fields) => Self::Object(fields.into_iter()
+                .map(|(key, value)| (ToString::to_string(&key), value.into()))
+                .collect()
+            )
+        }
+    }
+}
+
+// Vec<u8> can't use the macro as it's already an array of u8 for DataElement
+impl TryFrom<Vec<u8>> for DataValue {
+    type Error = DataConversionError;
+
+    fn try_from(value: Vec<u8>) -> Result<Self, Self::Error> {
+        Self::bytes(value)
+    }
+}
+
+macro_rules! impl_data_value_integer {
+    ($($type:ty),*) => {
+        $(
+            // Any integer width is accepted as long as the value fits
+            impl TryFrom<DataValue> for $type {
+                type Error = DataConversionError;
+
+                fn try_from(value: DataValue) -> Result<Self, Self::Error> {
+                    if !value.kind().is_number() {
+                        return Err(DataConversionError::UnexpectedValue(value.kind()))
+                    }
+
+                    let converted = match value.as_number() {
+                        Some(v) => <$type>::try_from(v).ok(),
+                        None => value.as_signed_number().and_then(|v| <$type>::try_from(v).ok())
+                    };
+                    converted.ok_or_else(|| DataConversionError::OutOfRange(ToString::to_string(&value)))
+                }
+            }
+        )*
+    };
+}
+
+impl_data_value_integer!(u8, u16, u32, u64, u128, i8, i16, i32, i64, i128);
+
+impl TryFrom<DataValue> for String {
+    type Error = DataConversionError;
+
+    fn try_from(value: DataValue) -> Result<Self, Self::Error> {
+        value.to_string()
+    }
+}
+
+impl TryFrom<DataValue> for Hash {
+    type Error = DataConversionError;
+
+    fn try_from(value: DataValue) -> Result<Self, Self::Error> {
+        value.to_hash()
+    }
+}
+
+impl TryFrom<DataValue> for bool {
+    type Error = DataConversionError;
+
+    fn try_from(value: DataValue) -> Result<Self, Self::Error> {
+        value.to_bool()
+    }
+}
+
 impl_data_value!(
     (String, String),
     (Hash, Hash),
@@ -586,6 +1217,11 @@ impl_data_value!(
     (U32, u32),
     (U64, u64),
     (U128, u128),
+    (I8, i8),
+    (I16, i16),
+    (I32, i32),
+    (I64, i64),
+    (I128, i128),
     (Bool, bool)
 );
 
@@ -604,4 +1240,458 @@ mod tests {
         let array2: Vec<u64> = array.into();
         assert_eq!(array2, vec![0, 24, 37, 55]);
     }
+
+    #[test]
+    fn test_data_element_get_path() {
+        let key = |k: &str| DataValue::String(k.to_string());
+        let mut c = HashMap::new();
+        c.insert(key("c"), DataElement::Value(DataValue::U8(42)));
+        let mut b = HashMap::new();
+        b.insert(key("b"), DataElement::Fields(c));
+        b.insert(key("list"), DataElement::Array(vec![
+            DataElement::Value(DataValue::U8(1)),
+            DataElement::Value(DataValue::U8(2))
+        ]));
+        let mut a = HashMap::new();
+        a.insert(key("a"), DataElement::Fields(b));
+        let element = DataElement::Fields(a);
+
+        // Deep field
+        assert_eq!(element.get_path(&[key("a"), key("b"), key("c")]), Some(&DataElement::Value(DataValue::U8(42))));
+        assert!(matches!(element.get_path(&[key("a"), key("b")]), Some(DataElement::Fields(_))));
+        assert_eq!(element.get_path(&[]), Some(&element));
+
+        // Array index of any width
+        assert_eq!(element.get_path(&[key("a"), key("list"), DataValue::U8(1)]), Some(&DataElement::Value(DataValue::U8(2))));
+        assert_eq!(element.get_path(&[key("a"), key("list"), DataValue::I64(0)]), Some(&DataElement::Value(DataValue::U8(1))));
+        assert_eq!(element.get_path(&[key("a"), key("list"), DataValue::U8(2)]), None);
+        assert_eq!(element.get_path(&[key("a"), key("list"), DataValue::I64(-1)]), None);
+        assert_eq!(element.get_path(&[key("a"), key("list"), key("0")]), None);
+
+        // Missing segment
+        assert_eq!(element.get_path(&[key("a"), key("x"), key("c")]), None);
+        // Past a value
+        assert_eq!(element.get_path(&[key("a"), key("b"), key("c"), key("d")]), None);
+    }
+
+    #[test]
+    fn test_data_element_leaves() {
+        let key = |k: &str| DataValue::String(k.to_string());
+        let mut profile = HashMap::new();
+        profile.insert(key("name"), DataElement::Value(key("alice")));
+        profile.insert(key("tags"), DataElement::Array(vec![
+            DataElement::Value(DataValue::U8(1)),
+            DataElement::Array(vec![DataElement::Value(DataValue::Bool(true))]),
+            DataElement::Fields(HashMap::new())
+        ]));
+        let mut fields = HashMap::new();
+        fields.insert(key("profile"), DataElement::Fields(profile));
+        fields.insert(DataValue::U8(7), DataElement::Value(DataValue::I64(-1)));
+        fields.insert(key("empty"), DataElement::Array(Vec::new()));
+        let element = DataElement::Fields(fields);
+
+        let mut leaves: Vec<_> = element.leaves().map(|(path, value)| (path, value.clone())).collect();
+        leaves.sort();
+        let mut expected = vec![
+            (vec![key("profile"), key("name")], key("alice")),
+            (vec![key("profile"), key("tags"), DataValue::U64(0)], DataValue::U8(1)),
+            (vec![key("profile"), key("tags"), DataValue::U64(1), DataValue::U64(0)], DataValue::Bool(true)),
+            (vec![DataValue::U8(7)], DataValue::I64(-1))
+        ];
+        expected.sort();
+        assert_eq!(leaves, expected);
+
+        // Each path leads back to its value
+        for (path, value) in element.leaves() {
+            assert_eq!(element.get_path(&path), Some(&DataElement::Value(value.clone())));
+        }
+
+        // Arrays are in order
+        let array: DataElement = vec![1u8, 2, 3].into();
+        assert_eq!(array.leaves().map(|(_, v)| v.clone()).collect::<Vec<_>>(), vec![DataValue::U8(1), DataValue::U8(2), DataValue::U8(3)]);
+
+        // A single value has an empty path
+        let value = DataElement::Value(DataValue::U8(0));
+        assert_eq!(value.leaves().collect::<Vec<_>>(), vec![(Vec::new(), &DataValue::U8(0))]);
+    }
+
+    #[test]
+    fn test_data_element_serializer() {
+        let mut inner = HashMap::new();
+        inner.insert(DataValue::String("name".to_string()), DataElement::Value(DataValue::String("xelis".to_string())));
+        inner.insert(DataValue::U64(1), DataElement::Array(vec![
+            DataElement::Value(DataValue::Bool(true)),
+            DataElement::Array(Vec::new()),
+            DataElement::Fields(HashMap::new())
+        ]));
+
+        let mut fields = HashMap::new();
+        fields.insert(DataValue::Hash(Hash::zero()), DataElement::Fields(inner));
+        fields.insert(DataValue::I8(-1), DataElement::Value(DataValue::U128(u128::MAX)));
+        let element = DataElement::Array(vec![
+            DataElement::Fields(fields),
+            DataElement::Value(DataValue::U16(7))
+        ]);
+
+        let bytes = element.to_bytes();
+        assert_eq!(bytes.len(), element.size());
+        assert_eq!(DataElement::from_bytes(&bytes).unwrap(), element);
+
+        // Every byte is needed
+        assert!(DataElement::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+        // Unknown element type
+        assert!(DataElement::from_bytes(&[3]).is_err());
+    }
+
+    #[test]
+    fn test_data_element_size() {
+        let values = vec![
+            DataValue::Bool(true),
+            DataValue::String(String::new()),
+            DataValue::String("xelis".to_string()),
+            DataValue::U8(1),
+            DataValue::U16(1),
+            DataValue::U32(1),
+            DataValue::U64(1),
+            DataValue::U128(1),
+            DataValue::Hash(Hash::zero()),
+            DataValue::I8(-1),
+            DataValue::I16(-1),
+            DataValue::I32(-1),
+            DataValue::I64(-1),
+            DataValue::I128(-1),
+            DataValue::Bytes(vec![1; 100])
+        ];
+
+        let mut fields = HashMap::new();
+        for (i, value) in values.iter().enumerate() {
+            assert_eq!(value.size(), value.to_bytes().len());
+            let element = DataElement::Value(value.clone());
+            assert_eq!(element.size(), element.to_bytes().len());
+            fields.insert(DataValue::U8(i as u8), DataElement::Array(vec![element.clone(), element]));
+        }
+
+        let element = DataElement::Array(vec![
+            DataElement::Fields(fields),
+            DataElement::Array(Vec::new()),
+            DataElement::Fields(HashMap::new())
+        ]);
+        assert_eq!(element.size(), element.to_bytes().len());
+    }
+
+    #[test]
+    fn test_data_element_json_depth() {
+        let nested = |depth: usize| format!("{}1{}", "[".repeat(depth), "]".repeat(depth));
+
+        let element: DataElement = serde_json::from_str(&nested(MAX_ELEMENT_DEPTH)).unwrap();
+        assert_eq!(element.to_bytes(), {
+            let mut expected = DataElement::Value(DataValue::U8(1));
+            for _ in 0..MAX_ELEMENT_DEPTH {
+                expected = DataElement::Array(vec![expected]);
+            }
+            expected.to_bytes()
+        });
+        assert!(serde_json::from_str::<DataElement>(&nested(MAX_ELEMENT_DEPTH + 1)).is_err());
+
+        // Same for fields
+        let nested = |depth: usize| format!("{}1{}", "{\"a\":".repeat(depth), "}".repeat(depth));
+        assert!(serde_json::from_str::<DataElement>(&nested(MAX_ELEMENT_DEPTH)).is_ok());
+        assert!(serde_json::from_str::<DataElement>(&nested(MAX_ELEMENT_DEPTH + 1)).is_err());
+
+        // The depth is reset after an error
+        assert!(serde_json::from_str::<DataElement>(&nested(MAX_ELEMENT_DEPTH)).is_ok());
+        assert_eq!(serde_json::to_string(&DataElement::Array(vec![DataElement::Value(DataValue::U8(1))])).unwrap(), "[1]");
+    }
+
+    #[test]
+    fn test_data_element_json_value() {
+        let json = serde_json::json!({
+            "name": "xelis",
+            "enabled": true,
+            "list": [0, 255, 256, 65536, u32::MAX as u64 + 1, u64::MAX],
+            "signed": [-1, -129, -32769, i32::MIN as i64 - 1, i64::MIN],
+            "nested": { "empty": [], "object": {} }
+        });
+
+        let element = DataElement::try_from(json.clone()).unwrap();
+        let key = |k: &str| DataValue::String(k.to_string());
+        let values = |path: &str| element.get_path(&[key(path)]).unwrap().as_array().unwrap()
+            .iter()
+            .map(|e| e.as_value().unwrap().kind())
+            .collect::<Vec<_>>();
+
+        // Tightest width
+        assert_eq!(values("list"), vec![ValueType::U8, ValueType::U8, ValueType::U16, ValueType::U32, ValueType::U64, ValueType::U64]);
+        assert_eq!(values("signed"), vec![ValueType::I8, ValueType::I16, ValueType::I32, ValueType::I64, ValueType::I64]);
+        assert_eq!(element.get_path(&[key("list"), DataValue::U8(5)]), Some(&DataElement::Value(DataValue::U64(u64::MAX))));
+        assert_eq!(element.get_path(&[key("nested"), key("object")]), Some(&DataElement::Fields(HashMap::new())));
+
+        // Round trip
+        assert_eq!(serde_json::Value::from(element), json);
+
+        // No equivalent
+        assert!(DataElement::try_from(serde_json::json!(1.5)).is_err());
+        assert!(DataElement::try_from(serde_json::json!([1, null])).is_err());
+
+        let mut deep = serde_json::json!(1);
+        for _ in 0..=MAX_ELEMENT_DEPTH {
+            deep = serde_json::json!([deep]);
+        }
+        assert!(matches!(DataElement::try_from(deep), Err(DataConversionError::TooDeep)));
+
+        // Values out of the JSON range are written as strings
+        assert_eq!(serde_json::Value::from(DataValue::U128(u128::MAX)), serde_json::json!(u128::MAX.to_string()));
+        assert_eq!(serde_json::Value::from(DataValue::U128(5)), serde_json::json!(5));
+        let mut fields = HashMap::new();
+        fields.insert(DataValue::U8(1), DataElement::Value(DataValue::Bytes(vec![0xAB])));
+        assert_eq!(serde_json::Value::from(DataElement::Fields(fields)), serde_json::json!({ "1": "ab" }));
+    }
+
+    #[test]
+    fn test_data_element_read_limits() {
+        let nested = |depth: usize| {
+            let mut element = DataElement::Value(DataValue::U8(0));
+            for _ in 0..depth {
+                element = DataElement::Array(vec![element]);
+            }
+            element
+        };
+
+        let element = nested(MAX_ELEMENT_DEPTH);
+        assert_eq!(DataElement::from_bytes(&element.to_bytes()).unwrap(), element);
+        // One more array around it, it can't be written
+        let mut bytes = vec![1, 1];
+        bytes.extend(element.to_bytes());
+        assert!(DataElement::from_bytes(&bytes).is_err());
+
+        // Same key twice
+        let mut writer = Writer::new();
+        writer.write_u8(2);
+        writer.write_u8(2);
+        for _ in 0..2 {
+            DataValue::U8(1).write(&mut writer);
+            DataElement::Value(DataValue::U8(1)).write(&mut writer);
+        }
+        assert!(DataElement::from_bytes(&writer.bytes()).is_err());
+    }
+
+    #[test]
+    fn test_data_element_validate() {
+        let array = |len: usize| DataElement::Array(vec![DataElement::Value(DataValue::U8(0)); len]);
+        assert!(array(MAX_ELEMENT_ITEMS).validate().is_ok());
+        assert!(matches!(array(MAX_ELEMENT_ITEMS + 1).validate(), Err(DataConversionError::TooManyItems(256))));
+
+        let fields = (0..=MAX_ELEMENT_ITEMS as u16).map(|i| (DataValue::U16(i), DataElement::Value(DataValue::U8(0)))).collect();
+        assert!(matches!(DataElement::Fields(fields).validate(), Err(DataConversionError::TooManyItems(256))));
+
+        // Checked in nested elements too
+        let mut element = array(MAX_ELEMENT_ITEMS + 1);
+        for _ in 0..MAX_ELEMENT_DEPTH {
+            element = DataElement::Array(vec![element]);
+        }
+        assert!(matches!(element.validate(), Err(DataConversionError::TooManyItems(256))));
+
+        let mut element = DataElement::Value(DataValue::U8(0));
+        for _ in 0..=MAX_ELEMENT_DEPTH {
+            element = DataElement::Array(vec![element]);
+        }
+        assert!(matches!(element.validate(), Err(DataConversionError::TooDeep)));
+
+        // Rejected from JSON as well
+        let json = serde_json::json!(vec![0; MAX_ELEMENT_ITEMS + 1]);
+        assert!(serde_json::from_value::<DataElement>(json.clone()).is_err());
+        assert!(matches!(DataElement::try_from(json), Err(DataConversionError::TooManyItems(256))));
+        assert!(serde_json::from_value::<DataElement>(serde_json::json!(vec![0; MAX_ELEMENT_ITEMS])).is_ok());
+    }
+
+    #[test]
+    #[should_panic(expected = "DataElement has 256 items")]
+    fn test_data_element_write_too_many_items() {
+        DataElement::Array(vec![DataElement::Value(DataValue::U8(0)); MAX_ELEMENT_ITEMS + 1]).to_bytes();
+    }
+
+    #[test]
+    fn test_data_element_json_duplicated_key() {
+        assert!(serde_json::from_str::<DataElement>(r#"{"a": 1, "a": 2}"#).is_err());
+        assert!(serde_json::from_str::<DataElement>(r#"{"a": {"b": 1, "b": 1}}"#).is_err());
+
+        let element: DataElement = serde_json::from_str(r#"{"a": 1, "b": 2}"#).unwrap();
+        assert_eq!(element.as_map().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_data_value_try_from() {
+        // From primitives
+        assert_eq!(DataValue::from(5u8), DataValue::U8(5));
+        assert_eq!(DataValue::from(5u64), DataValue::U64(5));
+        assert_eq!(DataValue::from(-5i32), DataValue::I32(-5));
+        assert_eq!(DataValue::from("xelis".to_string()), DataValue::String("xelis".to_string()));
+        assert_eq!(DataValue::from(true), DataValue::Bool(true));
+
+        // Any width with the same value
+        assert_eq!(u64::try_from(DataValue::U8(5)).unwrap(), 5);
+        assert_eq!(u8::try_from(DataValue::U128(255)).unwrap(), 255);
+        assert_eq!(i8::try_from(DataValue::U64(127)).unwrap(), 127);
+        assert_eq!(u16::try_from(DataValue::I64(300)).unwrap(), 300);
+        assert_eq!(i64::try_from(DataValue::I8(-5)).unwrap(), -5);
+        assert_eq!(u128::try_from(DataValue::U128(u128::MAX)).unwrap(), u128::MAX);
+
+        // Overflow
+        assert!(matches!(u8::try_from(DataValue::U16(256)), Err(DataConversionError::OutOfRange(_))));
+        assert!(matches!(u64::try_from(DataValue::I8(-1)), Err(DataConversionError::OutOfRange(_))));
+        assert!(matches!(i128::try_from(DataValue::U128(u128::MAX)), Err(DataConversionError::OutOfRange(_))));
+
+        // Not a number
+        assert!(matches!(u64::try_from(DataValue::String("5".to_string())), Err(DataConversionError::UnexpectedValue(ValueType::String))));
+        assert_eq!(String::try_from(DataValue::String("a".to_string())).unwrap(), "a");
+        assert!(bool::try_from(DataValue::U8(1)).is_err());
+        assert_eq!(Hash::try_from(DataValue::Hash(Hash::zero())).unwrap(), Hash::zero());
+    }
+
+    #[test]
+    fn test_data_value_signed() {
+        let values = [
+            DataValue::I8(i8::MIN),
+            DataValue::I16(-300),
+            DataValue::I32(-70_000),
+            DataValue::I64(-5),
+            DataValue::I128(i128::MIN),
+            DataValue::I64(i64::MAX)
+        ];
+
+        for value in values {
+            let bytes = value.to_bytes();
+            assert_eq!(bytes.len(), value.size());
+            assert_eq!(DataValue::from_bytes(&bytes).unwrap(), value);
+
+            let element = DataElement::Value(value.clone());
+            assert_eq!(DataElement::from_bytes(&element.to_bytes()).unwrap(), element);
+        }
+
+        assert_eq!(ToString::to_string(&DataValue::I64(-5)), "-5");
+        assert_eq!(ToString::to_string(&DataValue::I128(i128::MIN)), i128::MIN.to_string());
+        assert_eq!(DataValue::I8(-5).kind(), ValueType::I8);
+        assert!(ValueType::I128.is_number());
+
+        // JSON keeps the sign
+        let value: DataValue = serde_json::from_str("-5").unwrap();
+        assert_eq!(value, DataValue::I8(-5));
+        assert_eq!(serde_json::to_string(&DataValue::I64(-5)).unwrap(), "-5");
+
+        // Ordered with the unsigned ones
+        assert!(DataValue::I64(-5) < DataValue::U8(0));
+        assert!(DataValue::I128(-1) < DataValue::I8(0));
+        assert!(DataValue::I8(5) > DataValue::U128(4));
+        assert!(DataValue::I128(i128::MAX) < DataValue::U128(u128::MAX));
+        assert_eq!(DataValue::I8(5).cmp_number(&DataValue::U64(5)), Some(Ordering::Equal));
+        assert_ne!(DataValue::I8(5).cmp(&DataValue::U64(5)), Ordering::Equal);
+    }
+
+    #[test]
+    fn test_data_value_bytes() {
+        for value in [DataValue::Bytes(Vec::new()), DataValue::Bytes(vec![1, 2, 3]), DataValue::Bytes(vec![0xAB; MAX_BYTES_SIZE])] {
+            let bytes = value.to_bytes();
+            assert_eq!(bytes.len(), value.size());
+            assert_eq!(DataValue::from_bytes(&bytes).unwrap(), value);
+        }
+
+        let element = DataElement::Array(vec![DataElement::Value(DataValue::Bytes(vec![0xFF; 32]))]);
+        assert_eq!(DataElement::from_bytes(&element.to_bytes()).unwrap(), element);
+
+        // Too big to be read
+        let mut writer = Writer::new();
+        ValueType::Bytes.write(&mut writer);
+        writer.write_u16(MAX_BYTES_SIZE as u16 + 1);
+        writer.write_bytes(&[0; MAX_BYTES_SIZE + 1]);
+        assert!(matches!(DataValue::from_bytes(&writer.bytes()), Err(ReaderError::InvalidSize)));
+
+        // And so to be built
+        assert!(matches!(DataValue::bytes(vec![0; MAX_BYTES_SIZE + 1]), Err(DataConversionError::TooManyBytes(_))));
+        assert!(matches!(DataValue::try_from(vec![0; MAX_BYTES_SIZE + 1]), Err(DataConversionError::TooManyBytes(_))));
+        assert_eq!(DataValue::bytes(vec![0; MAX_BYTES_SIZE]).unwrap(), DataValue::Bytes(vec![0; MAX_BYTES_SIZE]));
+        let element = DataElement::Array(vec![DataElement::Value(DataValue::Bytes(vec![0; MAX_BYTES_SIZE + 1]))]);
+        assert!(matches!(element.validate(), Err(DataConversionError::TooManyBytes(_))));
+
+        // Truncated
+        let mut bytes = DataValue::Bytes(vec![1, 2, 3]).to_bytes();
+        bytes.pop();
+        assert!(DataValue::from_bytes(&bytes).is_err());
+
+        assert_eq!(ToString::to_string(&DataValue::Bytes(vec![0xDE, 0xAD])), "dead");
+        assert_eq!(DataValue::try_from(vec![1u8]).unwrap().as_bytes().unwrap(), &[1]);
+    }
+
+    #[test]
+    #[should_panic(expected = "DataValue has 8193 bytes")]
+    fn test_data_value_write_too_many_bytes() {
+        DataValue::Bytes(vec![0; MAX_BYTES_SIZE + 1]).to_bytes();
+    }
+
+    #[test]
+    fn test_data_value_bytes_json() {
+        let value = DataValue::Bytes(vec![0xDE, 0xAD]);
+        let json = serde_json::to_string(&value).unwrap();
+        assert_eq!(json, r#"{"bytes":"dead"}"#);
+        assert_eq!(serde_json::from_str::<DataValue>(&json).unwrap(), value);
+
+        // A hexadecimal string stays a string
+        assert_eq!(serde_json::from_str::<DataValue>(r#""dead""#).unwrap(), DataValue::String("dead".to_string()));
+
+        // Round trip inside elements
+        let mut fields = HashMap::new();
+        fields.insert(DataValue::String("data".to_string()), DataElement::Value(DataValue::Bytes(vec![1, 2, 3])));
+        fields.insert(DataValue::String("list".to_string()), DataElement::Array(vec![
+            DataElement::Value(DataValue::Bytes(Vec::new())),
+            DataElement::Value(DataValue::String("ab".to_string()))
+        ]));
+        let element = DataElement::Fields(fields);
+        let json = serde_json::to_string(&element).unwrap();
+        assert_eq!(serde_json::from_str::<DataElement>(&json).unwrap(), element);
+
+        // Size and format are checked
+        let too_big = format!(r#"{{"bytes":"{}"}}"#, "00".repeat(MAX_BYTES_SIZE + 1));
+        assert!(serde_json::from_str::<DataValue>(&too_big).is_err());
+        assert!(serde_json::from_str::<DataValue>(r#"{"bytes":"zz"}"#).is_err());
+        assert!(serde_json::from_str::<DataValue>(r#"{"bytes":"00","other":1}"#).is_err());
+    }
+
+    #[test]
+    fn test_data_value_ord() {
+        let widths = |v: u8| vec![
+            DataValue::U8(v),
+            DataValue::U16(v as u16),
+            DataValue::U32(v as u32),
+            DataValue::U64(v as u64),
+            DataValue::U128(v as u128)
+        ];
+
+        for a in widths(5) {
+            for b in widths(6) {
+                assert!(a < b);
+                assert!(b > a);
+            }
+
+            for b in widths(5) {
+                assert_eq!(a.as_number(), b.as_number());
+                // Same value is only equal for the same width
+                assert_eq!(a.cmp(&b) == Ordering::Equal, a == b);
+            }
+        }
+
+        assert!(DataValue::U128(u64::MAX as u128 + 1) > DataValue::U64(u64::MAX));
+        assert!(DataValue::U8(255) > DataValue::U64(254));
+
+        // Mixed types
+        let string = DataValue::String("a".to_string());
+        let hash = DataValue::Hash(Hash::zero());
+        assert!(DataValue::Bool(true) < DataValue::U8(0));
+        assert!(DataValue::U128(u128::MAX) < string);
+        assert!(string < hash);
+        assert!(DataValue::Bool(false) < DataValue::Bool(true));
+        assert!(DataValue::String("a".to_string()) < DataValue::String("b".to_string()));
+
+        let mut values = vec![hash.clone(), string.clone(), DataValue::U64(1), DataValue::U8(2), DataValue::Bool(true)];
+        values.sort();
+        assert_eq!(values, vec![DataValue::Bool(true), DataValue::U64(1), DataValue::U8(2), string, hash]);
+    }
 }
\ No newline at end of file
@@ -0,0 +1,41 @@
+use std::{cell::Cell, thread::LocalKey};
+
+thread_local! {
+    // Current nesting while deserializing a DataElement
+    pub static ELEMENT_DEPTH: Cell<usize> = const { Cell::new(0) };
+    // Current nesting while deserializing a Query
+    pub static QUERY_DEPTH: Cell<usize> = const { Cell::new(0) };
+}
+
+// Track the nesting of a recursive type while deserializing it
+// serde has no depth limit, so each level enters a guard and checks its depth
+// The counter is decreased when the guard is dropped, even on error
+pub struct DepthGuard {
+    counter: &'static LocalKey<Cell<usize>>,
+    depth: usize
+}
+
+impl DepthGuard {
+    pub fn enter(counter: &'static LocalKey<Cell<usize>>) -> Self {
+        let depth = counter.with(|depth| {
+            depth.set(depth.get() + 1);
+            depth.get()
+        });
+
+        Self {
+            counter,
+            depth
+        }
+    }
+
+    // Nesting of the current level, the top level is at depth 1
+    pub fn get_depth(&self) -> usize {
+        self.depth
+    }
+}
+
+impl Drop for DepthGuard {
+    fn drop(&mut self) {
+        self.counter.with(|depth| depth.set(depth.get() - 1));
+    }
+}
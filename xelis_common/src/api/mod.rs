@@ -1,13 +1,18 @@
 mod data;
+mod depth;
 pub mod wallet;
 pub mod daemon;
 pub mod query;
+pub mod rpc;
+#[cfg(feature = "msgpack")]
+pub mod msgpack;
 
 use std::borrow::Cow;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use bulletproofs::RangeProof;
 use crate::{
+    config::XELIS_ASSET,
     crypto::{
         elgamal::{CompressedCommitment, CompressedHandle},
         proofs::CiphertextValidityProof,
@@ -17,6 +22,7 @@ use crate::{
     },
     transaction::{
         extra_data::UnknownExtraDataFormat,
+        multisig::{MultiSigPayload, SignatureId},
         BurnPayload,
         Reference,
         SourceCommitment,
@@ -123,10 +129,16 @@ pub struct RPCTransaction<'a> {
     pub version: u8,
     // Source of the transaction
     pub source: Address,
+    /// Co-signers required by the transaction
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub multisig: Option<Cow<'a, MultiSigPayload>>,
     /// Type of the transaction
     pub data: RPCTransactionType<'a>,
-    /// Fees in XELIS
+    /// Fees paid in the fee asset
     pub fee: u64,
+    /// Asset used to pay the fees, None means XELIS
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fee_asset: Option<Cow<'a, Hash>>,
     /// nonce must be equal to the one on chain account
     /// used to prevent replay attacks and have ordered transactions
     pub nonce: u64,
@@ -138,6 +150,9 @@ pub struct RPCTransaction<'a> {
     pub reference: Cow<'a, Reference>,
     /// Signature of the transaction
     pub signature: Cow<'a, Signature>,
+    /// Signatures of the co-signers
+    #[serde(default)]
+    pub multisig_signatures: Cow<'a, Vec<SignatureId>>,
 }
 
 impl<'a> RPCTransaction<'a> {
@@ -146,13 +161,18 @@ impl<'a> RPCTransaction<'a> {
             hash: Cow::Borrowed(hash),
             version: tx.get_version(),
             source: tx.get_source().as_address(mainnet),
+            multisig: tx.get_multisig().map(Cow::Borrowed),
             data: RPCTransactionType::from_type(tx.get_data(), mainnet),
             fee: tx.get_fee(),
+            fee_asset: Some(tx.get_fee_asset())
+                .filter(|asset| **asset != XELIS_ASSET)
+                .map(Cow::Borrowed),
             nonce: tx.get_nonce(),
             source_commitments: Cow::Borrowed(tx.get_source_commitments()),
             range_proof: Cow::Borrowed(tx.get_range_proof()),
             reference: Cow::Borrowed(tx.get_reference()),
             signature: Cow::Borrowed(tx.get_signature()),
+            multisig_signatures: Cow::Borrowed(tx.get_multisig_signatures()),
         }
     }
 }
@@ -160,14 +180,18 @@ impl<'a> RPCTransaction<'a> {
 impl<'a> From<RPCTransaction<'a>> for Transaction {
     fn from(tx: RPCTransaction<'a>) -> Self {
         Transaction::new(
+            tx.version,
             tx.source.to_public_key(),
+            tx.multisig.map(Cow::into_owned),
             tx.data.into(),
             tx.fee,
+            tx.fee_asset.map(Cow::into_owned),
             tx.nonce,
             tx.source_commitments.into_owned(),
             tx.range_proof.into_owned(),
             tx.reference.into_owned(),
-            tx.signature.into_owned()
+            tx.signature.into_owned(),
+            tx.multisig_signatures.into_owned()
         )
     }
 }
@@ -0,0 +1,111 @@
+use serde::{de::DeserializeOwned, Serialize};
+use thiserror::Error;
+use crate::transaction::summary::TransactionSummary;
+use super::query::{Query, QueryResult};
+
+// MessagePack encoding of the API types, a denser alternative to JSON
+// Structs are written as maps with their field names, so the serde
+// attributes (tagged and untagged enums, flatten) behave exactly like in JSON
+
+#[derive(Error, Debug)]
+pub enum MsgPackError {
+    #[error(transparent)]
+    Encode(#[from] rmp_serde::encode::Error),
+    #[error(transparent)]
+    Decode(#[from] rmp_serde::decode::Error)
+}
+
+pub trait MsgPack: Serialize + DeserializeOwned {
+    fn to_msgpack(&self) -> Result<Vec<u8>, MsgPackError> {
+        Ok(rmp_serde::to_vec_named(self)?)
+    }
+
+    fn from_msgpack(bytes: &[u8]) -> Result<Self, MsgPackError> {
+        Ok(rmp_serde::from_slice(bytes)?)
+    }
+}
+
+impl MsgPack for TransactionSummary {}
+impl MsgPack for Query {}
+impl MsgPack for QueryResult {}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, fmt};
+    use indexmap::IndexMap;
+    use crate::{
+        api::{DataElement, DataValue},
+        crypto::{Hash, KeyPair},
+        transaction::summary::{TransactionTypeSummary, TransferSummary}
+    };
+    use super::*;
+
+    // Decoding the msgpack form must give back the original value
+    fn assert_round_trip<T: MsgPack + PartialEq + fmt::Debug>(value: &T) {
+        let bytes = value.to_msgpack().unwrap();
+        let json = serde_json::to_string(value).unwrap();
+        assert!(bytes.len() <= json.len());
+
+        assert_eq!(T::from_msgpack(&bytes).unwrap(), *value);
+    }
+
+    #[test]
+    fn test_msgpack_transaction_summary() {
+        let transfers = TransactionTypeSummary::Transfers(vec![TransferSummary {
+            asset: Hash::zero(),
+            destination: KeyPair::new().get_public_key().compress()
+        }]);
+        let burn = TransactionTypeSummary::Burn {
+            asset: Hash::max(),
+            amount: 1000
+        };
+
+        for data in [transfers, burn] {
+            let summary = TransactionSummary {
+                version: 1,
+                source: KeyPair::new().get_public_key().compress(),
+                fee: 25_000,
+                nonce: 7,
+                data
+            };
+            assert_round_trip(&summary);
+        }
+    }
+
+    #[test]
+    fn test_msgpack_query() {
+        let json = r#"{"or": [
+            {"and": [{"length": {"greater": 3}}, {"not": {"equal": "xel"}}]},
+            {"matches": "^x.*s$"},
+            {"has_key": {"key": "name", "query": null}},
+            {"between": {"min": 1, "max": 10, "inclusive": true}}
+        ]}"#;
+        let query: Query = serde_json::from_str(json).unwrap();
+        assert_round_trip(&query);
+
+        let decoded = Query::from_msgpack(&query.to_msgpack().unwrap()).unwrap();
+        for value in ["xelis", "xel", "xx"] {
+            let value = DataValue::String(value.to_owned());
+            assert_eq!(decoded.verify_value(&value), query.verify_value(&value));
+        }
+    }
+
+    #[test]
+    fn test_msgpack_query_result() {
+        let mut fields = HashMap::new();
+        fields.insert(DataValue::String("name".to_owned()), DataElement::Value(DataValue::String("alice".to_owned())));
+        fields.insert(DataValue::String("tags".to_owned()), DataElement::Array(vec![
+            DataElement::Value(DataValue::U8(1)),
+            DataElement::Value(DataValue::Bool(false))
+        ]));
+
+        let mut entries = IndexMap::new();
+        entries.insert(DataValue::String("user".to_owned()), DataElement::Fields(fields));
+        // Values are untagged: like with JSON, a hash would be read back as its hex string
+        entries.insert(DataValue::U8(2), DataElement::Value(DataValue::U64(u64::MAX)));
+        let result = QueryResult { entries, next: Some(10) };
+        assert_round_trip(&result);
+
+        assert!(QueryResult::from_msgpack(&[0xc1]).is_err());
+    }
+}
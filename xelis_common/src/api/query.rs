@@ -1,94 +1,385 @@
-use indexmap::IndexMap;
-use regex::Regex;
-use serde::{Deserialize, Serialize};
-use super::{DataElement, DataValue, ElementType, ValueType};
+use std::{
+    borrow::Cow,
+    cmp::Ordering,
+    fmt,
+    iter,
+    num::NonZeroUsize,
+    sync::{Mutex, MutexGuard, PoisonError}
+};
+use indexmap::{IndexMap, IndexSet};
+use lazy_static::lazy_static;
+use log::warn;
+use lru::LruCache;
+use regex::{Regex, RegexBuilder};
+use serde::{de::Error, Deserialize, Deserializer, Serialize, Serializer as SerdeSerializer};
+use thiserror::Error;
+use crate::{
+    crypto::Hash,
+    serializer::{Reader, ReaderError, Serializer, Writer}
+};
+use super::{
+    depth::{DepthGuard, QUERY_DEPTH},
+    DataElement,
+    DataValue,
+    ElementType,
+    ValueType
+};
 
-#[derive(Debug, Serialize, Deserialize)]
+// Maximum count of compiled patterns kept in the cache
+pub const REGEX_CACHE_SIZE: usize = 256;
+// Maximum size in bytes of a compiled pattern
+// regex crate has no backtracking, matching is linear in the input size,
+// so the only way to exhaust resources is with a huge automaton like "(a{1000}){1000}"
+pub const REGEX_SIZE_LIMIT: usize = 1024 * 1024;
+// Maximum count of entries in a QueryResult, their count is written on two bytes
+pub const MAX_QUERY_RESULT_ENTRIES: usize = u16::MAX as usize;
+
+// Compiled patterns shared by identical queries
+// A Regex is reference counted, cloning it reuses the same automaton
+// Least recently used patterns are evicted first so untrusted queries can't grow it forever
+pub struct RegexCache {
+    entries: Mutex<LruCache<String, Regex>>
+}
+
+impl RegexCache {
+    pub fn new(capacity: NonZeroUsize) -> Self {
+        Self {
+            entries: Mutex::new(LruCache::new(capacity))
+        }
+    }
+
+    // A panic while holding the lock can't leave the cache half updated
+    fn lock(&self) -> MutexGuard<'_, LruCache<String, Regex>> {
+        self.entries.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    // Get the compiled regex for this pattern, it is compiled only once
+    pub fn get_or_compile(&self, pattern: &str) -> Result<Regex, regex::Error> {
+        if let Some(regex) = self.lock().get(pattern) {
+            return Ok(regex.clone())
+        }
+
+        // Compile without holding the lock so a slow pattern doesn't block other queries
+        // Two threads may compile the same pattern, the last one is kept
+        let regex = compile_regex(pattern)?;
+        self.lock().put(pattern.to_owned(), regex.clone());
+
+        Ok(regex)
+    }
+
+    // Remove all the compiled patterns
+    pub fn clear(&self) {
+        self.lock().clear();
+    }
+}
+
+lazy_static! {
+    static ref REGEX_CACHE: RegexCache = RegexCache::new(NonZeroUsize::new(REGEX_CACHE_SIZE).unwrap());
+}
+
+// Compile a pattern with the size limits applied to every query
+fn compile_regex(pattern: &str) -> Result<Regex, regex::Error> {
+    RegexBuilder::new(pattern)
+        .size_limit(REGEX_SIZE_LIMIT)
+        .dfa_size_limit(REGEX_SIZE_LIMIT)
+        .build()
+        .map_err(|e| {
+            warn!("Rejected pattern query: {}", e);
+            e
+        })
+}
+
+// Get the compiled regex for this pattern from the shared cache
+pub fn get_or_compile_regex(pattern: &str) -> Result<Regex, regex::Error> {
+    REGEX_CACHE.get_or_compile(pattern)
+}
+
+// Remove all the compiled patterns from the shared cache
+pub fn clear_regex_cache() {
+    REGEX_CACHE.clear();
+}
+
+// Same format as serde_regex, but compiled patterns are shared through the cache
+mod cached_regex {
+    use regex::Regex;
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(regex: &Regex, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(regex.as_str())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Regex, D::Error> {
+        let pattern = String::deserialize(deserializer)?;
+        super::get_or_compile_regex(&pattern).map_err(D::Error::custom)
+    }
+}
+
+// Thresholds cover the whole u64 and i64 ranges, so they are kept as i128
+// Queries are untagged and serde can't buffer a 128 bits integer,
+// so they are written as a u64 or an i64 whenever they fit
+mod threshold {
+    use std::fmt;
+    use serde::{de::{Error, Visitor}, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(value: &i128, serializer: S) -> Result<S::Ok, S::Error> {
+        if let Ok(v) = u64::try_from(*value) {
+            serializer.serialize_u64(v)
+        } else if let Ok(v) = i64::try_from(*value) {
+            serializer.serialize_i64(v)
+        } else {
+            serializer.serialize_i128(*value)
+        }
+    }
+
+    struct ThresholdVisitor;
+
+    impl<'de> Visitor<'de> for ThresholdVisitor {
+        type Value = i128;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            formatter.write_str("an integer")
+        }
+
+        fn visit_i64<E: Error>(self, v: i64) -> Result<i128, E> {
+            Ok(v as i128)
+        }
+
+        fn visit_u64<E: Error>(self, v: u64) -> Result<i128, E> {
+            Ok(v as i128)
+        }
+
+        fn visit_i128<E: Error>(self, v: i128) -> Result<i128, E> {
+            Ok(v)
+        }
+
+        fn visit_u128<E: Error>(self, v: u128) -> Result<i128, E> {
+            i128::try_from(v).map_err(|_| E::custom("threshold is above i128::MAX"))
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<i128, D::Error> {
+        deserializer.deserialize_any(ThresholdVisitor)
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
 pub enum QueryNumber {
     // >
-    Greater(usize),
+    Greater(#[serde(with = "threshold")] i128),
     // >=
-    GreaterOrEqual(usize),
+    GreaterOrEqual(#[serde(with = "threshold")] i128),
     // <
-    Lesser(usize),
+    Lesser(#[serde(with = "threshold")] i128),
     // <=
-    LesserOrEqual(usize),
+    LesserOrEqual(#[serde(with = "threshold")] i128),
+    // min < x < max, or min <= x <= max if inclusive
+    Between {
+        #[serde(with = "threshold")]
+        min: i128,
+        #[serde(with = "threshold")]
+        max: i128,
+        inclusive: bool
+    },
 }
 
 impl QueryNumber {
     pub fn verify(&self, v: &DataValue) -> bool {
+        // All widths and signs are compared on the same scale
+        let cmp = |threshold: &i128| v.cmp_number(&DataValue::I128(*threshold));
         match self {
-            Self::Greater(value) => match v {
-                DataValue::U128(v) => *v > *value as u128,
-                DataValue::U64(v) => *v > *value as u64,
-                DataValue::U32(v) => *v as usize > *value,
-                DataValue::U16(v) => *v as usize > *value,
-                DataValue::U8(v) => *v as usize > *value,
-                _ => false
-            },
-            Self::GreaterOrEqual(value) => match v {
-                DataValue::U128(v) => *v >= *value as u128,
-                DataValue::U64(v) => *v >= *value as u64,
-                DataValue::U32(v) => *v as usize >= *value,
-                DataValue::U16(v) => *v as usize >= *value,
-                DataValue::U8(v) => *v as usize >= *value,
-                _ => false
-            },
-            Self::Lesser(value) => match v {
-                DataValue::U128(v) => *v < *value as u128,
-                DataValue::U64(v) => *v < *value as u64,
-                DataValue::U32(v) => (*v as usize) < *value,
-                DataValue::U16(v) => (*v as usize) < *value,
-                DataValue::U8(v) => (*v as usize) < *value,
-                _ => false
-            },
-            Self::LesserOrEqual(value) => match v {
-                DataValue::U128(v) => *v <= *value as u128,
-                DataValue::U64(v) => *v <= *value as u64,
-                DataValue::U32(v) => *v as usize <= *value,
-                DataValue::U16(v) => *v as usize <= *value,
-                DataValue::U8(v) => *v as usize <= *value,
-                _ => false
+            Self::Greater(value) => cmp(value) == Some(Ordering::Greater),
+            Self::GreaterOrEqual(value) => matches!(cmp(value), Some(Ordering::Greater | Ordering::Equal)),
+            Self::Lesser(value) => cmp(value) == Some(Ordering::Less),
+            Self::LesserOrEqual(value) => matches!(cmp(value), Some(Ordering::Less | Ordering::Equal)),
+            Self::Between { min, max, inclusive } => {
+                // Invalid range matches nothing
+                if min > max {
+                    return false
+                }
+
+                match (cmp(min), cmp(max)) {
+                    (Some(low), Some(high)) => if *inclusive {
+                        low != Ordering::Less && high != Ordering::Greater
+                    } else {
+                        low == Ordering::Greater && high == Ordering::Less
+                    },
+                    _ => false
+                }
             }
         }
     }
 }
 
+// Operand of the string queries
+// Its text form is computed once when the query is built instead of on each verification
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(from = "DataValue", into = "DataValue")]
+pub struct QueryText {
+    value: DataValue,
+    text: String,
+    lowercase: String
+}
+
+impl QueryText {
+    pub fn get_value(&self) -> &DataValue {
+        &self.value
+    }
+
+    pub fn get_text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn get_lowercase(&self) -> &str {
+        &self.lowercase
+    }
+}
+
+impl From<DataValue> for QueryText {
+    fn from(value: DataValue) -> Self {
+        let text = ToString::to_string(&value);
+        let lowercase = text.to_lowercase();
+        Self {
+            value,
+            text,
+            lowercase
+        }
+    }
+}
+
+impl From<QueryText> for DataValue {
+    fn from(text: QueryText) -> Self {
+        text.value
+    }
+}
+
+// Text form of a value, borrowed when it is already a string
+fn value_text(v: &DataValue) -> Cow<'_, str> {
+    match v.as_str() {
+        Ok(s) => Cow::Borrowed(s),
+        Err(_) => Cow::Owned(ToString::to_string(v))
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
 pub enum QueryValue {
     // ==
     Equal(DataValue),
+    // !=
+    NotEqual(DataValue),
+    // == on any of the values
+    OneOf(Vec<DataValue>),
     // Following are transformed to string and compared
-    StartsWith(DataValue),
-    EndsWith(DataValue),
-    ContainsValue(DataValue),
+    StartsWith(QueryText),
+    EndsWith(QueryText),
+    ContainsValue(QueryText),
+    // Same as above but ignoring the case
+    StartsWithCi(QueryText),
+    EndsWithCi(QueryText),
+    ContainsValueCi(QueryText),
     // Check if value type is the one researched
     IsOfType(ValueType),
     // Regex pattern on DataValue only
-    #[serde(with = "serde_regex")]
+    #[serde(with = "cached_regex")]
     Matches(Regex),
+    // Count of characters of a string value or count of bytes
+    Length(QueryNumber),
     #[serde(untagged)]
     NumberOp(QueryNumber)
 }
 
+// Regex has no PartialEq, patterns are compared by their source
+impl PartialEq for QueryValue {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::Equal(a), Self::Equal(b)) | (Self::NotEqual(a), Self::NotEqual(b)) => a == b,
+            (Self::OneOf(a), Self::OneOf(b)) => a == b,
+            (Self::StartsWith(a), Self::StartsWith(b))
+            | (Self::EndsWith(a), Self::EndsWith(b))
+            | (Self::ContainsValue(a), Self::ContainsValue(b))
+            | (Self::StartsWithCi(a), Self::StartsWithCi(b))
+            | (Self::EndsWithCi(a), Self::EndsWithCi(b))
+            | (Self::ContainsValueCi(a), Self::ContainsValueCi(b)) => a == b,
+            (Self::IsOfType(a), Self::IsOfType(b)) => a == b,
+            (Self::Matches(a), Self::Matches(b)) => a.as_str() == b.as_str(),
+            (Self::Length(a), Self::Length(b)) | (Self::NumberOp(a), Self::NumberOp(b)) => a == b,
+            _ => false
+        }
+    }
+}
+
 impl QueryValue {
     pub fn verify(&self, v: &DataValue) -> bool {
         match self {
-            Self::Equal(expected) => *v == *expected,
-            Self::StartsWith(value) => v.to_string().starts_with(&value.to_string()),
-            Self::EndsWith(value) => v.to_string().ends_with(&value.to_string()),
-            Self::ContainsValue(value) => v.to_string().contains(&value.to_string()),
+            Self::Equal(expected) => Self::is_equal(v, expected),
+            Self::NotEqual(expected) => !Self::is_equal(v, expected),
+            Self::OneOf(values) => values.iter().any(|expected| Self::is_equal(v, expected)),
+            Self::StartsWith(value) => value_text(v).starts_with(value.get_text()),
+            Self::EndsWith(value) => value_text(v).ends_with(value.get_text()),
+            Self::ContainsValue(value) => value_text(v).contains(value.get_text()),
+            Self::StartsWithCi(value) => value_text(v).to_lowercase().starts_with(value.get_lowercase()),
+            Self::EndsWithCi(value) => value_text(v).to_lowercase().ends_with(value.get_lowercase()),
+            Self::ContainsValueCi(value) => value_text(v).to_lowercase().contains(value.get_lowercase()),
             Self::IsOfType(expected) => v.kind() == *expected,
-            Self::Matches(pattern) => pattern.is_match(&v.to_string()),
+            Self::Matches(pattern) => pattern.is_match(&value_text(v)),
+            Self::Length(query) => match v {
+                DataValue::String(s) => query.verify(&DataValue::U64(s.chars().count() as u64)),
+                DataValue::Bytes(bytes) => query.verify(&DataValue::U64(bytes.len() as u64)),
+                _ => false
+            },
             Self::NumberOp(query) => query.verify(v)
         }
     }
+
+    // Numbers are equal if they have the same value whatever their width
+    fn is_equal(v: &DataValue, expected: &DataValue) -> bool {
+        match v.cmp_number(expected) {
+            Some(ordering) => ordering == Ordering::Equal,
+            None => *v == *expected
+        }
+    }
+
+    pub fn estimated_cost(&self) -> u64 {
+        match self {
+            Self::OneOf(values) => QUERY_COST_COMPARISON.saturating_mul(values.len().max(1) as u64),
+            Self::StartsWith(_) | Self::EndsWith(_) | Self::ContainsValue(_)
+            | Self::StartsWithCi(_) | Self::EndsWithCi(_) | Self::ContainsValueCi(_) => QUERY_COST_STRING,
+            Self::Matches(_) => QUERY_COST_REGEX,
+            _ => QUERY_COST_COMPARISON
+        }
+    }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-#[serde(rename_all = "snake_case")]
+// Maximum nesting of queries
+// Deeper queries are rejected on deserialization and match nothing
+// Each Query is one level, a QueryElement is not one: its sub queries are
+// one level below the query holding it. Deserialization, verification,
+// validation and cost estimation all count the depth this way
+pub const MAX_QUERY_DEPTH: usize = 64;
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum QueryError {
+    #[error("Element query used on a value")]
+    ElementQueryOnValue,
+    #[error("Query is nested deeper than {}", MAX_QUERY_DEPTH)]
+    TooDeep,
+}
+
+// Weights used to estimate the cost of a query before evaluating it
+// Cost of a simple comparison (==, !=, numbers, type checks)
+pub const QUERY_COST_COMPARISON: u64 = 1;
+// Cost of a string operator, values are converted to strings first
+pub const QUERY_COST_STRING: u64 = 5;
+// Cost of a regex match
+pub const QUERY_COST_REGEX: u64 = 100;
+// Cost of a combinator (Not, And, Or) or an element lookup
+pub const QUERY_COST_NODE: u64 = 2;
+// Additional cost for each level of nesting of a node
+pub const QUERY_COST_DEPTH: u64 = 10;
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(remote = "Self", rename_all = "snake_case")]
 pub enum Query {
     // !
     Not(Box<Query>),
@@ -96,63 +387,125 @@ pub enum Query {
     And(Vec<Query>),
     // ||
     Or(Vec<Query>),
+    // Exactly one of the queries
+    Xor(Vec<Query>),
     #[serde(untagged)]
     Element(QueryElement),
     #[serde(untagged)]
     Value(QueryValue)
 }
 
+impl Serialize for Query {
+    fn serialize<S: SerdeSerializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        Query::serialize(self, serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Query {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let guard = DepthGuard::enter(&QUERY_DEPTH);
+
+        if guard.get_depth() > MAX_QUERY_DEPTH {
+            return Err(D::Error::custom(format!("query is nested deeper than {}", MAX_QUERY_DEPTH)))
+        }
+
+        Query::deserialize(deserializer)
+    }
+}
+
 impl Query {
     pub fn verify_element(&self, element: &DataElement) -> bool {
-        match self {
-            Self::Element(query) => query.verify(element),
+        self.verify_element_at(element, 0).unwrap_or(false)
+    }
+
+    // Returns None if the query is too deep to be evaluated
+    fn verify_element_at(&self, element: &DataElement, depth: usize) -> Option<bool> {
+        if depth >= MAX_QUERY_DEPTH {
+            return None
+        }
+
+        Some(match self {
+            Self::Element(query) => query.verify_at(element, depth)?,
             Self::Value(query) => if let DataElement::Value(value) = element {
                 query.verify(value)
             } else {
                 false
             },
-            Self::Not(op) => !op.verify_element(element),
+            Self::Not(op) => !op.verify_element_at(element, depth + 1)?,
             Self::Or(operations) => {
                 for op in operations {
-                    if op.verify_element(element) {
-                        return true
+                    if op.verify_element_at(element, depth + 1)? {
+                        return Some(true)
                     }
                 }
                 false
             }
             Self::And(operations) => {
                 for op in operations {
-                    if !op.verify_element(element) {
-                        return false
+                    if !op.verify_element_at(element, depth + 1)? {
+                        return Some(false)
                     }
                 }
                 true
             }
-        }
+            Self::Xor(operations) => {
+                let mut found = false;
+                for op in operations {
+                    if op.verify_element_at(element, depth + 1)? {
+                        if found {
+                            return Some(false)
+                        }
+                        found = true;
+                    }
+                }
+                found
+            }
+        })
     }
 
     pub fn verify_value(&self, value: &DataValue) -> bool {
-        match self {
+        self.verify_value_at(value, 0).unwrap_or(false)
+    }
+
+    // Returns None if the query is too deep to be evaluated
+    fn verify_value_at(&self, value: &DataValue, depth: usize) -> Option<bool> {
+        if depth >= MAX_QUERY_DEPTH {
+            return None
+        }
+
+        Some(match self {
             Self::Element(_) => false,
             Self::Value(query) => query.verify(value),
-            Self::Not(op) => !op.verify_value(value),
+            Self::Not(op) => !op.verify_value_at(value, depth + 1)?,
             Self::Or(operations) => {
                 for op in operations {
-                    if op.verify_value(value) {
-                        return true
+                    if op.verify_value_at(value, depth + 1)? {
+                        return Some(true)
                     }
                 }
                 false
             }
             Self::And(operations) => {
                 for op in operations {
-                    if !op.verify_value(value) {
-                        return false
+                    if !op.verify_value_at(value, depth + 1)? {
+                        return Some(false)
                     }
                 }
                 true
             }
-        }
+            Self::Xor(operations) => {
+                let mut found = false;
+                for op in operations {
+                    if op.verify_value_at(value, depth + 1)? {
+                        if found {
+                            return Some(false)
+                        }
+                        found = true;
+                    }
+                }
+                found
+            }
+        })
     }
 
     pub fn is_for_element(&self) -> bool {
@@ -161,10 +514,116 @@ impl Query {
             _ => false
         }
     }
+
+    // Verify that the query can match something in this context
+    // An element query never matches a value
+    pub fn validate(&self, is_element: bool) -> Result<(), QueryError> {
+        self.validate_at(is_element, 0)
+    }
+
+    fn validate_at(&self, is_element: bool, depth: usize) -> Result<(), QueryError> {
+        if depth >= MAX_QUERY_DEPTH {
+            return Err(QueryError::TooDeep)
+        }
+
+        match self {
+            Self::Element(element) => if is_element {
+                element.validate_at(depth)
+            } else {
+                Err(QueryError::ElementQueryOnValue)
+            },
+            Self::Value(_) => Ok(()),
+            Self::Not(op) => op.validate_at(is_element, depth + 1),
+            Self::And(operations) | Self::Or(operations) | Self::Xor(operations) => {
+                for op in operations {
+                    op.validate_at(is_element, depth + 1)?;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    // Estimate the cost of evaluating the query once
+    // Callers can compare it against a budget before running it
+    pub fn estimated_cost(&self) -> u64 {
+        self.estimated_cost_at(0)
+    }
+
+    fn estimated_cost_at(&self, depth: usize) -> u64 {
+        let depth_cost = QUERY_COST_DEPTH.saturating_mul(depth as u64);
+        let cost = match self {
+            Self::Element(element) => element.estimated_cost_at(depth),
+            Self::Value(value) => value.estimated_cost(),
+            Self::Not(op) => QUERY_COST_NODE.saturating_add(op.estimated_cost_at(depth + 1)),
+            Self::And(operations) | Self::Or(operations) | Self::Xor(operations) => operations.iter()
+                .fold(QUERY_COST_NODE, |cost, op| cost.saturating_add(op.estimated_cost_at(depth + 1)))
+        };
+        cost.saturating_add(depth_cost)
+    }
+
+    // Simplify the query without changing what it matches:
+    // nested And/Or of the same kind are flattened,
+    // And/Or with a single query are replaced by it
+    // and double negations are removed
+    // The query is validated first, otherwise removing nodes
+    // could make a query too deep to be evaluated accepted
+    pub fn optimize(self, is_element: bool) -> Result<Query, QueryError> {
+        self.validate(is_element)?;
+        self.optimize_at(0)
+    }
+
+    fn optimize_at(self, depth: usize) -> Result<Query, QueryError> {
+        if depth >= MAX_QUERY_DEPTH {
+            return Err(QueryError::TooDeep)
+        }
+
+        Ok(match self {
+            Self::Not(op) => match op.optimize_at(depth + 1)? {
+                Self::Not(inner) => *inner,
+                op => Self::Not(Box::new(op))
+            },
+            Self::And(operations) => {
+                let mut flattened = Vec::with_capacity(operations.len());
+                for op in operations {
+                    match op.optimize_at(depth + 1)? {
+                        Self::And(inner) => flattened.extend(inner),
+                        op => flattened.push(op)
+                    }
+                }
+
+                if flattened.len() == 1 {
+                    flattened.remove(0)
+                } else {
+                    Self::And(flattened)
+                }
+            },
+            Self::Or(operations) => {
+                let mut flattened = Vec::with_capacity(operations.len());
+                for op in operations {
+                    match op.optimize_at(depth + 1)? {
+                        Self::Or(inner) => flattened.extend(inner),
+                        op => flattened.push(op)
+                    }
+                }
+
+                if flattened.len() == 1 {
+                    flattened.remove(0)
+                } else {
+                    Self::Or(flattened)
+                }
+            },
+            // Xor can't be flattened: exactly one of (a, Xor(b, c)) is not exactly one of (a, b, c)
+            Self::Xor(operations) => Self::Xor(operations.into_iter()
+                .map(|op| op.optimize_at(depth + 1))
+                .collect::<Result<_, _>>()?),
+            Self::Element(element) => Self::Element(element.optimize_at(depth)?),
+            Self::Value(value) => Self::Value(value)
+        })
+    }
 }
 
 // This is used to do query in daemon (in future for Smart Contracts) and wallet
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")] 
 pub enum QueryElement {
     // Check if DataElement::Fields has key and optional check on value
@@ -175,29 +634,73 @@ pub enum QueryElement {
     Len(QueryNumber),
     // Only array supported
     ContainsElement(DataElement),
+    // Array contains every element
+    ArrayContainsAll(Vec<DataElement>),
     // Verify with query the element at position
     // This is only for array
     AtPosition { position: usize, query: Box<Query> },
+    // Follow the keys for fields and the indexes for arrays
+    // and verify the optional query on the element found
+    // Without query, it only checks that the path exists
+    KeyPath { path: Vec<DataValue>, value: Option<Box<Query>> },
     // Check value type
     Type(ElementType),
 }
 
 impl QueryElement {
+    // Optimize the sub queries, see Query::optimize
+    fn optimize_at(self, depth: usize) -> Result<QueryElement, QueryError> {
+        Ok(match self {
+            Self::HasKey { key, query } => Self::HasKey { key, query: query.map(|query| query.optimize_at(depth + 1).map(Box::new)).transpose()? },
+            Self::AtKey { key, query } => Self::AtKey { key, query: Box::new(query.optimize_at(depth + 1)?) },
+            Self::AtPosition { position, query } => Self::AtPosition { position, query: Box::new(query.optimize_at(depth + 1)?) },
+            Self::KeyPath { path, value } => Self::KeyPath { path, value: value.map(|query| query.optimize_at(depth + 1).map(Box::new)).transpose()? },
+            element => element
+        })
+    }
+
     pub fn verify(&self, data: &DataElement) -> bool {
+        self.verify_at(data, 0).unwrap_or(false)
+    }
+
+    // See Query::estimated_cost
+    fn estimated_cost_at(&self, depth: usize) -> u64 {
         match self {
+            Self::HasKey { query, .. } => QUERY_COST_NODE.saturating_add(query.as_ref().map_or(0, |query| query.estimated_cost_at(depth + 1))),
+            Self::KeyPath { path, value } => QUERY_COST_NODE.saturating_mul(path.len().max(1) as u64)
+                .saturating_add(value.as_ref().map_or(0, |query| query.estimated_cost_at(depth + 1))),
+            Self::AtKey { query, .. } | Self::AtPosition { query, .. } => QUERY_COST_NODE.saturating_add(query.estimated_cost_at(depth + 1)),
+            Self::ArrayContainsAll(elements) => QUERY_COST_NODE.saturating_mul(elements.len().max(1) as u64),
+            Self::ContainsElement(_) => QUERY_COST_NODE,
+            Self::Len(_) | Self::Type(_) => QUERY_COST_COMPARISON
+        }
+    }
+
+    // Sub queries are always applied on elements
+    fn validate_at(&self, depth: usize) -> Result<(), QueryError> {
+        match self {
+            Self::HasKey { query: Some(query), .. } | Self::KeyPath { value: Some(query), .. } => query.validate_at(true, depth + 1),
+            Self::AtKey { query, .. } | Self::AtPosition { query, .. } => query.validate_at(true, depth + 1),
+            _ => Ok(())
+        }
+    }
+
+    // Returns None if a sub query is too deep to be evaluated
+    fn verify_at(&self, data: &DataElement, depth: usize) -> Option<bool> {
+        Some(match self {
             Self::HasKey { key, query } => if let DataElement::Fields(fields) = data {
-                fields.get(key).map(|v|
-                    if let Some(query) = query {
-                        query.verify_element(v)
-                    } else {
-                        false
-                    }
-                ).unwrap_or(false)
+                match (fields.get(key), query) {
+                    (Some(v), Some(query)) => query.verify_element_at(v, depth + 1)?,
+                    _ => false
+                }
             } else {
                 false
             },
             Self::AtKey { key, query } => if let DataElement::Fields(fields) = data {
-                fields.get(key).map(|v| query.verify_element(v)).unwrap_or(false)
+                match fields.get(key) {
+                    Some(v) => query.verify_element_at(v, depth + 1)?,
+                    None => false
+                }
             } else {
                 false
             },
@@ -210,93 +713,1335 @@ impl QueryElement {
                 DataElement::Array(array) => array.contains(query),
                 _ => false
             },
+            Self::ArrayContainsAll(elements) => match data {
+                DataElement::Array(array) => elements.iter().all(|element| array.contains(element)),
+                _ => false
+            },
             Self::AtPosition { position, query } => if let DataElement::Array(array) = data {
                 if let Some(element) = array.get(*position) {
-                    query.verify_element(element)
+                    query.verify_element_at(element, depth + 1)?
                 } else {
                     false
                 }
             } else {
                 false
             },
+            Self::KeyPath { path, value } => match (data.get_path(path), value) {
+                (Some(element), Some(query)) => query.verify_element_at(element, depth + 1)?,
+                (Some(_), None) => true,
+                (None, _) => false
+            },
             Self::Type(expected) => data.kind() == *expected
-        }
+        })
     }
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
 pub struct QueryResult {
     pub entries: IndexMap<DataValue, DataElement>,
     pub next: Option<usize>
 }
 
-#[cfg(test)]
-mod tests {
-    use std::collections::HashMap;
+impl Serializer for QueryResult {
+    fn read(reader: &mut Reader) -> Result<Self, ReaderError> {
+        let count = reader.read_u16()?;
+        let mut entries = IndexMap::new();
+        for _ in 0..count {
+            let key = DataValue::read(reader)?;
+            let element = DataElement::read(reader)?;
+            // A duplicated key would be lost
+            if entries.insert(key, element).is_some() {
+                return Err(ReaderError::InvalidValue)
+            }
+        }
 
-    use super::*;
+        let next = Option::<u64>::read(reader)?
+            .map(|next| usize::try_from(next).map_err(|_| ReaderError::InvalidValue))
+            .transpose()?;
 
-    #[test]
-    fn test_query_number() {
-        let query = QueryNumber::Greater(5);
-        assert!(query.verify(&DataValue::U8(6)));
-        assert!(!query.verify(&DataValue::U8(5)));
+        Ok(Self {
+            entries,
+            next
+        })
+    }
 
-        let query = QueryNumber::GreaterOrEqual(5);
-        assert!(query.verify(&DataValue::U8(5)));
-        assert!(query.verify(&DataValue::U8(6)));
-        assert!(!query.verify(&DataValue::U8(4)));
+    // Panics above MAX_QUERY_RESULT_ENTRIES entries, run_query never returns more
+    fn write(&self, writer: &mut Writer) {
+        let count = u16::try_from(self.entries.len())
+            .unwrap_or_else(|_| panic!("QueryResult has {} entries while the maximum is {}", self.entries.len(), MAX_QUERY_RESULT_ENTRIES));
+        writer.write_u16(count);
+        for (key, element) in &self.entries {
+            key.write(writer);
+            element.write(writer);
+        }
+        self.next.map(|next| next as u64).write(writer);
+    }
 
-        let query = QueryNumber::Lesser(5);
-        assert!(query.verify(&DataValue::U8(4)));
-        assert!(!query.verify(&DataValue::U8(5)));
-        assert!(!query.verify(&DataValue::U8(6)));
+    fn size(&self) -> usize {
+        self.entries.size() + self.next.map(|next| next as u64).size()
+    }
+}
 
-        let query = QueryNumber::LesserOrEqual(5);
-        assert!(query.verify(&DataValue::U8(4)));
-        assert!(query.verify(&DataValue::U8(5)));
-        assert!(!query.verify(&DataValue::U8(6)));
+// Quote a CSV cell if needed, following RFC 4180
+fn escape_csv(cell: &str) -> Cow<'_, str> {
+    if cell.contains(|c| matches!(c, ',' | '"' | '\n' | '\r')) {
+        Cow::Owned(format!("\"{}\"", cell.replace('"', "\"\"")))
+    } else {
+        Cow::Borrowed(cell)
     }
+}
 
-    #[test]
-    fn test_query_value() {
-        let query = QueryValue::Equal(DataValue::U8(5));
-        assert!(query.verify(&DataValue::U8(5)));
-        assert!(!query.verify(&DataValue::U8(6)));
+// Flatten an element into (column, cell) pairs
+// Fields and array items are appended to the path with a dot, fields being sorted by key
+fn flatten_element(element: &DataElement, path: String, cells: &mut Vec<(String, String)>) {
+    let join = |key: String| if path.is_empty() { key } else { format!("{}.{}", path, key) };
+    match element {
+        DataElement::Value(value) => {
+            let column = if path.is_empty() { "value".to_owned() } else { path };
+            cells.push((column, ToString::to_string(value)));
+        },
+        DataElement::Array(values) => for (i, value) in values.iter().enumerate() {
+            flatten_element(value, join(i.to_string()), cells);
+        },
+        DataElement::Fields(fields) => {
+            let mut fields: Vec<_> = fields.iter().collect();
+            fields.sort_by(|(a, _), (b, _)| a.cmp(b));
+            for (key, value) in fields {
+                flatten_element(value, join(ToString::to_string(key)), cells);
+            }
+        }
+    }
+}
 
-        let query = QueryValue::StartsWith(DataValue::String("hello".to_string()));
-        assert!(query.verify(&DataValue::String("hello world".to_string())));
-        assert!(!query.verify(&DataValue::String("world".to_string())));
+impl QueryResult {
+    // Export the entries as CSV, one row per entry, with a header line
+    // The first column is the entry key, then each value found in the element:
+    // - a plain value goes in the "value" column
+    // - a field goes in a column named by its path, such as "user.name"
+    // - an array item goes in a column named by its index, such as "tags.0"
+    // Columns are ordered by first appearance, and cells missing in a row are left empty
+    // Values use their string form, bytes being hex encoded
+    pub fn to_csv(&self) -> String {
+        let rows: Vec<_> = self.entries.iter()
+            .map(|(key, element)| {
+                let mut cells = Vec::new();
+                flatten_element(element, String::new(), &mut cells);
+                (ToString::to_string(key), cells.into_iter().collect::<IndexMap<_, _>>())
+            })
+            .collect();
 
-        let query = QueryValue::EndsWith(DataValue::String("world".to_string()));
-        assert!(query.verify(&DataValue::String("hello world".to_string())));
-        assert!(!query.verify(&DataValue::String("hello".to_string())));
+        let mut columns = IndexSet::new();
+        for (_, cells) in &rows {
+            columns.extend(cells.keys().map(String::as_str));
+        }
 
-        let query = QueryValue::ContainsValue(DataValue::String("world".to_string()));
-        assert!(query.verify(&DataValue::String("hello world".to_string())));
-        assert!(!query.verify(&DataValue::String("hello".to_string())));
+        let mut csv = String::new();
+        let header = iter::once("key").chain(columns.iter().copied());
+        csv.push_str(&header.map(escape_csv).collect::<Vec<_>>().join(","));
+        csv.push('\n');
 
-        let query = QueryValue::IsOfType(ValueType::String);
-        assert!(query.verify(&DataValue::String("hello".to_string())));
-        assert!(!query.verify(&DataValue::U8(5)));
+        for (key, cells) in &rows {
+            let values = iter::once(key.as_str())
+                .chain(columns.iter().map(|column| cells.get(*column).map(String::as_str).unwrap_or("")));
+            csv.push_str(&values.map(escape_csv).collect::<Vec<_>>().join(","));
+            csv.push('\n');
+        }
 
-        let query = QueryValue::Matches(Regex::new(r"^\d{3}-\d{3}-\d{4}$").unwrap());
-        assert!(query.verify(&DataValue::String("123-456-7890".to_string())));
-        assert!(!query.verify(&DataValue::String("hello".to_string())));
+        csv
     }
+}
 
-    #[test]
-    fn test_query_element() {
-        let mut fields = HashMap::new();
-        fields.insert(DataValue::String("owner".to_string()), DataElement::Value(DataValue::String("Slixe".to_string())));
-        fields.insert(DataValue::String("balance".to_string()), DataElement::Value(DataValue::U8(25)));
+// Order of the entries in a QueryResult
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SortBy {
+    // Path to the value to sort on in each element, see QueryElement::KeyPath
+    // None to sort on the entry key
+    #[serde(default)]
+    pub key: Option<Vec<DataValue>>,
+    #[serde(default)]
+    pub descending: bool
+}
 
-        let element = DataElement::Fields(fields.clone());
+impl SortBy {
+    // Entries without a value at the path are always last
+    // Ties are broken by the entry key, in ascending order
+    fn compare<'a>(&self, a: (&DataValue, &'a DataElement), b: (&DataValue, &'a DataElement)) -> Ordering {
+        let ordering = match &self.key {
+            Some(path) => {
+                let value = |element: &'a DataElement| match element.get_path(path) {
+                    Some(DataElement::Value(value)) => Some(value),
+                    _ => None
+                };
 
-        let query = QueryElement::HasKey {
-            key: DataValue::String("owner".to_string()),
-            query: Some(Box::new(Query::Value(QueryValue::Equal(DataValue::String("Slixe".to_string())))))
-        };
+                match (value(a.1), value(b.1)) {
+                    (Some(x), Some(y)) => if self.descending { y.cmp(x) } else { x.cmp(y) },
+                    (Some(_), None) => Ordering::Less,
+                    (None, Some(_)) => Ordering::Greater,
+                    (None, None) => Ordering::Equal
+                }
+            },
+            None => if self.descending { b.0.cmp(a.0) } else { a.0.cmp(b.0) }
+        };
+
+        ordering.then_with(|| a.0.cmp(b.0))
+    }
+}
+
+// Filter the entries whose value match the query
+// and sort them if requested
+// offset is the count of matching entries to skip
+// and next is the offset to use to get the following entries, if any
+// No more than MAX_QUERY_RESULT_ENTRIES entries are returned whatever the limit
+// A limit of zero returns no entries and no next offset to not loop forever
+pub fn run_query(entries: &IndexMap<DataValue, DataElement>, query: &Query, sort: Option<&SortBy>, offset: usize, limit: usize) -> QueryResult {
+    let limit = limit.min(MAX_QUERY_RESULT_ENTRIES);
+    if limit == 0 {
+        return QueryResult {
+            entries: IndexMap::new(),
+            next: None
+        }
+    }
+
+    let matching = entries.iter()
+        .filter(|(_, element)| query.verify_element(element));
+
+    // One more entry is kept to know if there is a following page
+    let page: Vec<(&DataValue, &DataElement)> = match sort {
+        Some(sort) => {
+            let mut matching: Vec<_> = matching.collect();
+            matching.sort_by(|a, b| sort.compare(*a, *b));
+            matching.into_iter()
+                .skip(offset)
+                .take(limit + 1)
+                .collect()
+        },
+        // Without sorting, we can stop verifying the entries after the page
+        None => matching.skip(offset)
+            .take(limit + 1)
+            .collect()
+    };
+
+    let next = if page.len() > limit {
+        Some(offset + limit)
+    } else {
+        None
+    };
+
+    let result = page.into_iter()
+        .take(limit)
+        .map(|(key, element)| (key.clone(), element.clone()))
+        .collect();
+
+    QueryResult {
+        entries: result,
+        next
+    }
+}
+
+// Errors when parsing a query written in the DSL, see Query::parse_dsl
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum QueryParseError {
+    #[error("Unexpected end of input")]
+    UnexpectedEnd,
+    #[error("Unexpected '{}' at position {}", _1, _0)]
+    Unexpected(usize, String),
+    #[error("Unterminated string starting at position {}", _0)]
+    UnterminatedString(usize),
+    #[error("Invalid number at position {}", _0)]
+    InvalidNumber(usize),
+    #[error("Invalid hash at position {}", _0)]
+    InvalidHash(usize),
+    #[error("Invalid bytes at position {}", _0)]
+    InvalidBytes(usize),
+    #[error("Unknown type '{}' at position {}", _1, _0)]
+    UnknownType(usize, String),
+    #[error("Invalid regex at position {}: {}", _0, _1)]
+    InvalidRegex(usize, String),
+    #[error("Invalid JSON at position {}: {}", _0, _1)]
+    InvalidJson(usize, String),
+    #[error("Query is nested deeper than {}", MAX_QUERY_DEPTH)]
+    TooDeep,
+}
+
+// Words having a meaning in the DSL, keys using them must be quoted
+const DSL_KEYWORDS: [&str; 17] = [
+    "AND", "OR", "XOR", "NOT", "JSON",
+    "STARTS", "ENDS", "CONTAINS", "ISTARTS", "IENDS", "ICONTAINS",
+    "MATCHES", "IN", "IS", "BETWEEN", "EXCLUSIVE", "LENGTH"
+];
+
+#[derive(Debug, Clone, PartialEq)]
+enum DslToken {
+    // Identifiers and keywords
+    Word(String),
+    Str(String),
+    // Integer literal with its optional type suffix
+    Number(String),
+    Symbol(&'static str)
+}
+
+impl fmt::Display for DslToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Word(word) => write!(f, "{}", word),
+            Self::Str(s) => write!(f, "\"{}\"", s),
+            Self::Number(literal) => write!(f, "{}", literal),
+            Self::Symbol(symbol) => write!(f, "{}", symbol)
+        }
+    }
+}
+
+// Recursive descent parser, precedence from the lowest: OR, XOR, AND, NOT
+struct DslParser<'a> {
+    input: &'a str,
+    pos: usize,
+    depth: usize
+}
+
+impl<'a> DslParser<'a> {
+    fn lex(&mut self) -> Result<Option<(usize, DslToken)>, QueryParseError> {
+        let rest = &self.input[self.pos..];
+        let trimmed = rest.trim_start();
+        self.pos += rest.len() - trimmed.len();
+
+        let start = self.pos;
+        let mut chars = trimmed.chars();
+        let Some(c) = chars.next() else {
+            return Ok(None)
+        };
+
+        let next = chars.next();
+        let (token, len) = match c {
+            '(' => (DslToken::Symbol("("), 1),
+            ')' => (DslToken::Symbol(")"), 1),
+            '[' => (DslToken::Symbol("["), 1),
+            ']' => (DslToken::Symbol("]"), 1),
+            ',' => (DslToken::Symbol(","), 1),
+            '.' => (DslToken::Symbol("."), 1),
+            '$' => (DslToken::Symbol("$"), 1),
+            '=' => (DslToken::Symbol("="), if next == Some('=') { 2 } else { 1 }),
+            '!' if next == Some('=') => (DslToken::Symbol("!="), 2),
+            '>' if next == Some('=') => (DslToken::Symbol(">="), 2),
+            '>' => (DslToken::Symbol(">"), 1),
+            '<' if next == Some('=') => (DslToken::Symbol("<="), 2),
+            '<' => (DslToken::Symbol("<"), 1),
+            '"' => {
+                let mut value = String::new();
+                let mut escaped = false;
+                let mut end = None;
+                for (i, c) in trimmed.char_indices().skip(1) {
+                    match c {
+                        _ if escaped => {
+                            // Unknown escapes are kept as is so "\d" can be used in patterns
+                            if c != '"' && c != '\\' {
+                                value.push('\\');
+                            }
+                            value.push(c);
+                            escaped = false;
+                        },
+                        '\\' => escaped = true,
+                        '"' => {
+                            end = Some(i + 1);
+                            break;
+                        },
+                        c => value.push(c)
+                    }
+                }
+
+                let end = end.ok_or(QueryParseError::UnterminatedString(start))?;
+                (DslToken::Str(value), end)
+            },
+            c if c.is_ascii_digit() || (c == '-' && next.is_some_and(|c| c.is_ascii_digit())) => {
+                let len = 1 + trimmed[1..].find(|c: char| !c.is_ascii_alphanumeric() && c != '_').unwrap_or(trimmed.len() - 1);
+                (DslToken::Number(trimmed[..len].to_string()), len)
+            },
+            c if c.is_ascii_alphabetic() || c == '_' => {
+                let len = trimmed.find(|c: char| !c.is_ascii_alphanumeric() && c != '_').unwrap_or(trimmed.len());
+                (DslToken::Word(trimmed[..len].to_string()), len)
+            },
+            c => return Err(QueryParseError::Unexpected(start, c.to_string()))
+        };
+
+        self.pos += len;
+        Ok(Some((start, token)))
+    }
+
+    fn peek(&mut self) -> Result<Option<DslToken>, QueryParseError> {
+        let pos = self.pos;
+        let token = self.lex();
+        self.pos = pos;
+        token.map(|token| token.map(|(_, token)| token))
+    }
+
+    fn next(&mut self) -> Result<(usize, DslToken), QueryParseError> {
+        self.lex()?.ok_or(QueryParseError::UnexpectedEnd)
+    }
+
+    // Consume the token if it's the expected one
+    fn eat(&mut self, expected: &DslToken) -> Result<bool, QueryParseError> {
+        if self.peek()?.as_ref() == Some(expected) {
+            self.next()?;
+            Ok(true)
+        } else {
+            Ok(false)
+        }
+    }
+
+    fn eat_keyword(&mut self, keyword: &str) -> Result<bool, QueryParseError> {
+        self.eat(&DslToken::Word(keyword.to_string()))
+    }
+
+    fn expect(&mut self, expected: DslToken) -> Result<(), QueryParseError> {
+        let (pos, token) = self.next()?;
+        if token != expected {
+            return Err(QueryParseError::Unexpected(pos, token.to_string()))
+        }
+        Ok(())
+    }
+
+    // Parse a sequence of operands separated by the keyword
+    fn parse_chain(&mut self, keyword: &str, operand: fn(&mut Self) -> Result<Query, QueryParseError>, group: fn(Vec<Query>) -> Query) -> Result<Query, QueryParseError> {
+        let mut operations = vec![operand(self)?];
+        while self.eat_keyword(keyword)? {
+            operations.push(operand(self)?);
+        }
+
+        Ok(if operations.len() == 1 {
+            operations.remove(0)
+        } else {
+            group(operations)
+        })
+    }
+
+    fn parse_or(&mut self) -> Result<Query, QueryParseError> {
+        self.parse_chain("OR", Self::parse_xor, Query::Or)
+    }
+
+    fn parse_xor(&mut self) -> Result<Query, QueryParseError> {
+        self.parse_chain("XOR", Self::parse_and, Query::Xor)
+    }
+
+    fn parse_and(&mut self) -> Result<Query, QueryParseError> {
+        self.parse_chain("AND", Self::parse_unary, Query::And)
+    }
+
+    fn parse_unary(&mut self) -> Result<Query, QueryParseError> {
+        self.depth += 1;
+        if self.depth > MAX_QUERY_DEPTH {
+            return Err(QueryParseError::TooDeep)
+        }
+
+        let query = if self.eat_keyword("NOT")? {
+            Query::Not(Box::new(self.parse_unary()?))
+        } else if self.eat(&DslToken::Symbol("("))? {
+            let query = self.parse_or()?;
+            self.expect(DslToken::Symbol(")"))?;
+            query
+        } else if self.eat_keyword("JSON")? {
+            self.parse_json()?
+        } else {
+            self.parse_predicate()?
+        };
+
+        self.depth -= 1;
+        Ok(query)
+    }
+
+    // Any query can be written in JSON
+    fn parse_json(&mut self) -> Result<Query, QueryParseError> {
+        let rest = &self.input[self.pos..];
+        let start = self.pos + (rest.len() - rest.trim_start().len());
+        let mut stream = serde_json::Deserializer::from_str(rest).into_iter::<Query>();
+        let query = stream.next()
+            .ok_or(QueryParseError::UnexpectedEnd)?
+            .map_err(|e| QueryParseError::InvalidJson(start, e.to_string()))?;
+        self.pos += stream.byte_offset();
+        Ok(query)
+    }
+
+    fn parse_literal(&mut self) -> Result<DataValue, QueryParseError> {
+        let (pos, token) = self.next()?;
+        Ok(match token {
+            DslToken::Str(value) => DataValue::String(value),
+            DslToken::Number(literal) => number_to_value(pos, &literal)?,
+            DslToken::Word(word) if word == "true" => DataValue::Bool(true),
+            DslToken::Word(word) if word == "false" => DataValue::Bool(false),
+            DslToken::Word(word) if word == "hash" => {
+                self.expect(DslToken::Symbol("("))?;
+                let (pos, token) = self.next()?;
+                let DslToken::Str(hex) = token else {
+                    return Err(QueryParseError::Unexpected(pos, token.to_string()))
+                };
+                let bytes: [u8; 32] = hex::decode(hex).ok()
+                    .and_then(|bytes| bytes.try_into().ok())
+                    .ok_or(QueryParseError::InvalidHash(pos))?;
+                self.expect(DslToken::Symbol(")"))?;
+                DataValue::Hash(Hash::new(bytes))
+            },
+            DslToken::Word(word) if word == "bytes" => {
+                self.expect(DslToken::Symbol("("))?;
+                let (pos, token) = self.next()?;
+                let DslToken::Str(hex) = token else {
+                    return Err(QueryParseError::Unexpected(pos, token.to_string()))
+                };
+                let bytes = hex::decode(hex).ok()
+                    .and_then(|bytes| DataValue::bytes(bytes).ok())
+                    .ok_or(QueryParseError::InvalidBytes(pos))?;
+                self.expect(DslToken::Symbol(")"))?;
+                bytes
+            },
+            token => return Err(QueryParseError::Unexpected(pos, token.to_string()))
+        })
+    }
+
+    // Thresholds of number operators have no type suffix
+    fn parse_threshold(&mut self) -> Result<i128, QueryParseError> {
+        match self.next()? {
+            (pos, DslToken::Number(literal)) if !literal.contains(|c: char| c.is_ascii_alphabetic()) => literal.parse().map_err(|_| QueryParseError::InvalidNumber(pos)),
+            (pos, token) => Err(QueryParseError::Unexpected(pos, token.to_string()))
+        }
+    }
+
+    fn parse_number_op(&mut self) -> Result<QueryNumber, QueryParseError> {
+        let (pos, token) = self.next()?;
+        Ok(match token {
+            DslToken::Symbol(">") => QueryNumber::Greater(self.parse_threshold()?),
+            DslToken::Symbol(">=") => QueryNumber::GreaterOrEqual(self.parse_threshold()?),
+            DslToken::Symbol("<") => QueryNumber::Lesser(self.parse_threshold()?),
+            DslToken::Symbol("<=") => QueryNumber::LesserOrEqual(self.parse_threshold()?),
+            DslToken::Word(word) if word == "BETWEEN" => {
+                let min = self.parse_threshold()?;
+                let (pos, token) = self.next()?;
+                if token != DslToken::Word("AND".to_string()) {
+                    return Err(QueryParseError::Unexpected(pos, token.to_string()))
+                }
+                let max = self.parse_threshold()?;
+                let inclusive = !self.eat_keyword("EXCLUSIVE")?;
+                QueryNumber::Between { min, max, inclusive }
+            },
+            token => return Err(QueryParseError::Unexpected(pos, token.to_string()))
+        })
+    }
+
+    fn parse_operator(&mut self) -> Result<QueryValue, QueryParseError> {
+        let (pos, token) = match self.peek()? {
+            Some(DslToken::Symbol(">" | ">=" | "<" | "<=")) => return Ok(QueryValue::NumberOp(self.parse_number_op()?)),
+            Some(DslToken::Word(word)) if word == "BETWEEN" => return Ok(QueryValue::NumberOp(self.parse_number_op()?)),
+            _ => self.next()?
+        };
+
+        let word = match token {
+            DslToken::Symbol("=") => return Ok(QueryValue::Equal(self.parse_literal()?)),
+            DslToken::Symbol("!=") => return Ok(QueryValue::NotEqual(self.parse_literal()?)),
+            DslToken::Word(word) => word,
+            token => return Err(QueryParseError::Unexpected(pos, token.to_string()))
+        };
+
+        Ok(match word.as_str() {
+            "STARTS" => QueryValue::StartsWith(self.parse_literal()?.into()),
+            "ENDS" => QueryValue::EndsWith(self.parse_literal()?.into()),
+            "CONTAINS" => QueryValue::ContainsValue(self.parse_literal()?.into()),
+            "ISTARTS" => QueryValue::StartsWithCi(self.parse_literal()?.into()),
+            "IENDS" => QueryValue::EndsWithCi(self.parse_literal()?.into()),
+            "ICONTAINS" => QueryValue::ContainsValueCi(self.parse_literal()?.into()),
+            "LENGTH" => QueryValue::Length(self.parse_number_op()?),
+            "MATCHES" => match self.next()? {
+                (pos, DslToken::Str(pattern)) => QueryValue::Matches(get_or_compile_regex(&pattern).map_err(|e| QueryParseError::InvalidRegex(pos, e.to_string()))?),
+                (pos, token) => return Err(QueryParseError::Unexpected(pos, token.to_string()))
+            },
+            "IN" => {
+                self.expect(DslToken::Symbol("["))?;
+                let mut values = Vec::new();
+                if !self.eat(&DslToken::Symbol("]"))? {
+                    loop {
+                        values.push(self.parse_literal()?);
+                        if self.eat(&DslToken::Symbol("]"))? {
+                            break;
+                        }
+                        self.expect(DslToken::Symbol(","))?;
+                    }
+                }
+                QueryValue::OneOf(values)
+            },
+            "IS" => match self.next()? {
+                (pos, DslToken::Word(name)) => QueryValue::IsOfType(value_type_from_name(&name).ok_or(QueryParseError::UnknownType(pos, name))?),
+                (pos, token) => return Err(QueryParseError::Unexpected(pos, token.to_string()))
+            },
+            _ => return Err(QueryParseError::Unexpected(pos, word))
+        })
+    }
+
+    // $ for the value itself, otherwise a key or a path of keys separated by dots
+    fn parse_predicate(&mut self) -> Result<Query, QueryParseError> {
+        let mut path = Vec::new();
+        if !self.eat(&DslToken::Symbol("$"))? {
+            loop {
+                let key = match self.next()? {
+                    (_, DslToken::Word(word)) => DataValue::String(word),
+                    (_, DslToken::Str(key)) => DataValue::String(key),
+                    (pos, DslToken::Number(literal)) => number_to_value(pos, &literal)?,
+                    (pos, token) => return Err(QueryParseError::Unexpected(pos, token.to_string()))
+                };
+                path.push(key);
+
+                if !self.eat(&DslToken::Symbol("."))? {
+                    break;
+                }
+            }
+        }
+
+        let query = Query::Value(self.parse_operator()?);
+        Ok(match path.len() {
+            0 => query,
+            1 => Query::Element(QueryElement::AtKey { key: path.remove(0), query: Box::new(query) }),
+            _ => Query::Element(QueryElement::KeyPath { path, value: Some(Box::new(query)) })
+        })
+    }
+}
+
+// Numbers without suffix are u64 or i64 if negative,
+// or their 128 bits version if they don't fit
+fn number_to_value(pos: usize, literal: &str) -> Result<DataValue, QueryParseError> {
+    let invalid = |_| QueryParseError::InvalidNumber(pos);
+    let (digits, suffix) = literal.split_at(literal.find(|c: char| c.is_ascii_alphabetic()).unwrap_or(literal.len()));
+    Ok(match suffix {
+        "u8" => DataValue::U8(digits.parse().map_err(invalid)?),
+        "u16" => DataValue::U16(digits.parse().map_err(invalid)?),
+        "u32" => DataValue::U32(digits.parse().map_err(invalid)?),
+        "u64" => DataValue::U64(digits.parse().map_err(invalid)?),
+        "u128" => DataValue::U128(digits.parse().map_err(invalid)?),
+        "i8" => DataValue::I8(digits.parse().map_err(invalid)?),
+        "i16" => DataValue::I16(digits.parse().map_err(invalid)?),
+        "i32" => DataValue::I32(digits.parse().map_err(invalid)?),
+        "i64" => DataValue::I64(digits.parse().map_err(invalid)?),
+        "i128" => DataValue::I128(digits.parse().map_err(invalid)?),
+        "" if digits.starts_with('-') => match digits.parse() {
+            Ok(value) => DataValue::I64(value),
+            Err(_) => DataValue::I128(digits.parse().map_err(invalid)?)
+        },
+        "" => match digits.parse() {
+            Ok(value) => DataValue::U64(value),
+            Err(_) => DataValue::U128(digits.parse().map_err(invalid)?)
+        },
+        _ => return Err(QueryParseError::InvalidNumber(pos))
+    })
+}
+
+fn value_type_from_name(name: &str) -> Option<ValueType> {
+    Some(match name {
+        "bool" => ValueType::Bool,
+        "string" => ValueType::String,
+        "u8" => ValueType::U8,
+        "u16" => ValueType::U16,
+        "u32" => ValueType::U32,
+        "u64" => ValueType::U64,
+        "u128" => ValueType::U128,
+        "hash" => ValueType::Hash,
+        "i8" => ValueType::I8,
+        "i16" => ValueType::I16,
+        "i32" => ValueType::I32,
+        "i64" => ValueType::I64,
+        "i128" => ValueType::I128,
+        "bytes" => ValueType::Bytes,
+        _ => return None
+    })
+}
+
+fn value_type_name(value_type: ValueType) -> &'static str {
+    match value_type {
+        ValueType::Bool => "bool",
+        ValueType::String => "string",
+        ValueType::U8 => "u8",
+        ValueType::U16 => "u16",
+        ValueType::U32 => "u32",
+        ValueType::U64 => "u64",
+        ValueType::U128 => "u128",
+        ValueType::Hash => "hash",
+        ValueType::I8 => "i8",
+        ValueType::I16 => "i16",
+        ValueType::I32 => "i32",
+        ValueType::I64 => "i64",
+        ValueType::I128 => "i128",
+        ValueType::Bytes => "bytes"
+    }
+}
+
+fn dsl_string(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+fn dsl_literal(value: &DataValue) -> String {
+    match value {
+        DataValue::Bool(v) => v.to_string(),
+        DataValue::String(v) => dsl_string(v),
+        DataValue::U8(v) => format!("{}u8", v),
+        DataValue::U16(v) => format!("{}u16", v),
+        DataValue::U32(v) => format!("{}u32", v),
+        DataValue::U64(v) => v.to_string(),
+        DataValue::U128(v) => format!("{}u128", v),
+        DataValue::Hash(v) => format!("hash(\"{}\")", v),
+        DataValue::I8(v) => format!("{}i8", v),
+        DataValue::I16(v) => format!("{}i16", v),
+        DataValue::I32(v) => format!("{}i32", v),
+        DataValue::I64(v) if *v < 0 => v.to_string(),
+        DataValue::I64(v) => format!("{}i64", v),
+        DataValue::I128(v) => format!("{}i128", v),
+        DataValue::Bytes(v) => format!("bytes(\"{}\")", hex::encode(v))
+    }
+}
+
+// None if the key can't be written in a path
+fn dsl_key(key: &DataValue) -> Option<String> {
+    match key {
+        DataValue::String(key) => {
+            let is_word = key.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+                && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+                && !DSL_KEYWORDS.contains(&key.as_str());
+            Some(if is_word { key.clone() } else { dsl_string(key) })
+        },
+        DataValue::Bool(_) | DataValue::Hash(_) | DataValue::Bytes(_) => None,
+        number => Some(dsl_literal(number))
+    }
+}
+
+fn dsl_number_op(query: &QueryNumber) -> String {
+    match query {
+        QueryNumber::Greater(v) => format!("> {}", v),
+        QueryNumber::GreaterOrEqual(v) => format!(">= {}", v),
+        QueryNumber::Lesser(v) => format!("< {}", v),
+        QueryNumber::LesserOrEqual(v) => format!("<= {}", v),
+        QueryNumber::Between { min, max, inclusive } => format!("BETWEEN {} AND {}{}", min, max, if *inclusive { "" } else { " EXCLUSIVE" })
+    }
+}
+
+fn dsl_operator(query: &QueryValue) -> String {
+    let literals = |values: &[DataValue]| values.iter().map(dsl_literal).collect::<Vec<_>>().join(", ");
+    match query {
+        QueryValue::Equal(v) => format!("= {}", dsl_literal(v)),
+        QueryValue::NotEqual(v) => format!("!= {}", dsl_literal(v)),
+        QueryValue::OneOf(values) => format!("IN [{}]", literals(values)),
+        QueryValue::StartsWith(v) => format!("STARTS {}", dsl_literal(v.get_value())),
+        QueryValue::EndsWith(v) => format!("ENDS {}", dsl_literal(v.get_value())),
+        QueryValue::ContainsValue(v) => format!("CONTAINS {}", dsl_literal(v.get_value())),
+        QueryValue::StartsWithCi(v) => format!("ISTARTS {}", dsl_literal(v.get_value())),
+        QueryValue::EndsWithCi(v) => format!("IENDS {}", dsl_literal(v.get_value())),
+        QueryValue::ContainsValueCi(v) => format!("ICONTAINS {}", dsl_literal(v.get_value())),
+        QueryValue::IsOfType(kind) => format!("IS {}", value_type_name(*kind)),
+        QueryValue::Matches(pattern) => format!("MATCHES {}", dsl_string(pattern.as_str())),
+        QueryValue::Length(query) => format!("LENGTH {}", dsl_number_op(query)),
+        QueryValue::NumberOp(query) => dsl_number_op(query)
+    }
+}
+
+impl Query {
+    // Parse a query written like `age > 18 AND name STARTS "al"`
+    // See the Display implementation for the syntax
+    pub fn parse_dsl(input: &str) -> Result<Query, QueryParseError> {
+        let mut parser = DslParser { input, pos: 0, depth: 0 };
+        let query = parser.parse_or()?;
+        if let Some((pos, token)) = parser.lex()? {
+            return Err(QueryParseError::Unexpected(pos, token.to_string()))
+        }
+
+        query.validate(true).map_err(|_| QueryParseError::TooDeep)?;
+        Ok(query)
+    }
+
+    // Wrap the groups in parentheses when used as operand
+    fn fmt_operand(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::And(operations) | Self::Or(operations) | Self::Xor(operations) if operations.len() > 1 => write!(f, "({})", self),
+            _ => write!(f, "{}", self)
+        }
+    }
+
+    fn fmt_group(f: &mut fmt::Formatter<'_>, operations: &[Query], keyword: &str) -> fmt::Result {
+        for (i, op) in operations.iter().enumerate() {
+            if i > 0 {
+                write!(f, " {} ", keyword)?;
+            }
+            op.fmt_operand(f)?;
+        }
+        Ok(())
+    }
+}
+
+// Write the query in the DSL accepted by Query::parse_dsl:
+// `$` is the value itself, `key` and `a.b.c` are the value at the key or path
+// followed by an operator (= != > >= < <= BETWEEN STARTS ENDS CONTAINS
+// ISTARTS IENDS ICONTAINS MATCHES IN IS LENGTH)
+// combined with NOT, AND, XOR, OR and parentheses
+// Queries without DSL syntax are written as `JSON` followed by their JSON
+impl fmt::Display for Query {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Not(op) => {
+                write!(f, "NOT ")?;
+                op.fmt_operand(f)
+            },
+            Self::And(operations) if operations.len() > 1 => Self::fmt_group(f, operations, "AND"),
+            Self::Or(operations) if operations.len() > 1 => Self::fmt_group(f, operations, "OR"),
+            Self::Xor(operations) if operations.len() > 1 => Self::fmt_group(f, operations, "XOR"),
+            Self::Value(query) => write!(f, "$ {}", dsl_operator(query)),
+            Self::Element(QueryElement::AtKey { key, query }) => match (dsl_key(key), query.as_ref()) {
+                (Some(key), Self::Value(query)) => write!(f, "{} {}", key, dsl_operator(query)),
+                _ => write!(f, "JSON {}", serde_json::to_string(self).map_err(|_| fmt::Error)?)
+            },
+            Self::Element(QueryElement::KeyPath { path, value: Some(query) }) if path.len() > 1 => {
+                match (path.iter().map(dsl_key).collect::<Option<Vec<_>>>(), query.as_ref()) {
+                    (Some(keys), Self::Value(query)) => write!(f, "{} {}", keys.join("."), dsl_operator(query)),
+                    _ => write!(f, "JSON {}", serde_json::to_string(self).map_err(|_| fmt::Error)?)
+                }
+            },
+            _ => write!(f, "JSON {}", serde_json::to_string(self).map_err(|_| fmt::Error)?)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use crate::api::MAX_BYTES_SIZE;
+    use super::*;
+
+    #[test]
+    fn test_query_number() {
+        let query = QueryNumber::Greater(5);
+        assert!(query.verify(&DataValue::U8(6)));
+        assert!(!query.verify(&DataValue::U8(5)));
+
+        let query = QueryNumber::GreaterOrEqual(5);
+        assert!(query.verify(&DataValue::U8(5)));
+        assert!(query.verify(&DataValue::U8(6)));
+        assert!(!query.verify(&DataValue::U8(4)));
+
+        let query = QueryNumber::Lesser(5);
+        assert!(query.verify(&DataValue::U8(4)));
+        assert!(!query.verify(&DataValue::U8(5)));
+        assert!(!query.verify(&DataValue::U8(6)));
+
+        let query = QueryNumber::LesserOrEqual(5);
+        assert!(query.verify(&DataValue::U8(4)));
+        assert!(query.verify(&DataValue::U8(5)));
+        assert!(!query.verify(&DataValue::U8(6)));
+    }
+
+    #[test]
+    fn test_query_number_unsigned_range() {
+        // Thresholds above i64::MAX are kept as is
+        let query: Query = serde_json::from_str(&format!(r#"{{"len":{{"greater_or_equal":{}}}}}"#, u64::MAX)).unwrap();
+        assert!(matches!(query, Query::Element(QueryElement::Len(QueryNumber::GreaterOrEqual(v))) if v == u64::MAX as i128));
+
+        let query = QueryNumber::GreaterOrEqual(u64::MAX as i128);
+        assert_eq!(serde_json::to_string(&query).unwrap(), format!(r#"{{"greater_or_equal":{}}}"#, u64::MAX));
+        assert!(query.verify(&DataValue::U64(u64::MAX)));
+        assert!(!query.verify(&DataValue::U64(u64::MAX - 1)));
+        assert!(query.verify(&DataValue::U128(u64::MAX as u128 + 1)));
+
+        let query = Query::parse_dsl(&format!("$ > {}", u64::MAX - 1)).unwrap();
+        assert!(query.verify_value(&DataValue::U64(u64::MAX)));
+        assert!(!query.verify_value(&DataValue::U64(u64::MAX - 1)));
+    }
+
+    #[test]
+    fn test_query_number_signed() {
+        let query: Query = serde_json::from_str(r#"{"len":{"lesser":-1}}"#).unwrap();
+        assert!(matches!(query, Query::Element(QueryElement::Len(QueryNumber::Lesser(-1)))));
+
+        let value = DataValue::I64(-5);
+        assert!(QueryNumber::Greater(-10).verify(&value));
+        assert!(QueryNumber::Lesser(0).verify(&value));
+        assert!(QueryNumber::LesserOrEqual(-5).verify(&value));
+        assert!(!QueryNumber::Greater(-5).verify(&value));
+        assert!(QueryNumber::Between { min: -10, max: 0, inclusive: false }.verify(&value));
+        assert!(!QueryNumber::Between { min: 0, max: 10, inclusive: true }.verify(&value));
+
+        // Every width and sign on the same scale
+        for value in [DataValue::I8(-1), DataValue::I16(-1), DataValue::I32(-1), DataValue::I128(-1)] {
+            assert!(QueryNumber::Lesser(0).verify(&value));
+            assert!(QueryNumber::GreaterOrEqual(-1).verify(&value));
+        }
+        assert!(QueryNumber::Greater(0).verify(&DataValue::I8(1)));
+        assert!(QueryNumber::Greater(-1).verify(&DataValue::U8(0)));
+        assert!(QueryNumber::Greater(i128::MAX).verify(&DataValue::U128(u128::MAX)));
+        assert!(QueryNumber::Lesser(i64::MIN as i128).verify(&DataValue::I128(i128::MIN)));
+        assert!(!QueryNumber::Lesser(i64::MIN as i128).verify(&DataValue::I64(i64::MIN)));
+        assert!(!QueryNumber::Greater(-10).verify(&DataValue::String("-5".to_string())));
+
+        let query = Query::parse_dsl("$ < -1 AND $ >= -5").unwrap();
+        assert!(query.verify_value(&value));
+        assert_eq!(query.to_string(), "$ < -1 AND $ >= -5");
+    }
+
+    #[test]
+    fn test_query_value_signed() {
+        let value = DataValue::I64(-5);
+        // Text operators use the signed representation
+        assert!(QueryValue::StartsWith(DataValue::String("-".to_string()).into()).verify(&value));
+        assert!(QueryValue::Equal(DataValue::I8(-5)).verify(&value));
+        assert!(!QueryValue::Equal(DataValue::U64(5)).verify(&value));
+
+        let query = Query::parse_dsl("$ = -5 AND $ != -6i8").unwrap();
+        assert!(query.verify_value(&value));
+        assert_eq!(query.to_string(), "$ = -5 AND $ != -6i8");
+    }
+
+    #[test]
+    fn test_query_number_between() {
+        let query = QueryNumber::Between { min: 10, max: 20, inclusive: true };
+        assert!(query.verify(&DataValue::U8(10)));
+        assert!(query.verify(&DataValue::U16(15)));
+        assert!(query.verify(&DataValue::U32(20)));
+        assert!(!query.verify(&DataValue::U64(9)));
+        assert!(!query.verify(&DataValue::U128(21)));
+        assert!(!query.verify(&DataValue::String("15".to_string())));
+
+        let query = QueryNumber::Between { min: 10, max: 20, inclusive: false };
+        assert!(!query.verify(&DataValue::U8(10)));
+        assert!(query.verify(&DataValue::U8(11)));
+        assert!(query.verify(&DataValue::U64(19)));
+        assert!(!query.verify(&DataValue::U128(20)));
+
+        // min > max matches nothing
+        let query = QueryNumber::Between { min: 20, max: 10, inclusive: true };
+        assert!(!query.verify(&DataValue::U8(10)));
+        assert!(!query.verify(&DataValue::U8(15)));
+        assert!(!query.verify(&DataValue::U8(20)));
+    }
+
+    #[test]
+    fn test_query_value() {
+        let query = QueryValue::Equal(DataValue::U8(5));
+        assert!(query.verify(&DataValue::U8(5)));
+        assert!(!query.verify(&DataValue::U8(6)));
+
+        let query = QueryValue::StartsWith(DataValue::String("hello".to_string()).into());
+        assert!(query.verify(&DataValue::String("hello world".to_string())));
+        assert!(!query.verify(&DataValue::String("world".to_string())));
+
+        let query = QueryValue::EndsWith(DataValue::String("world".to_string()).into());
+        assert!(query.verify(&DataValue::String("hello world".to_string())));
+        assert!(!query.verify(&DataValue::String("hello".to_string())));
+
+        let query = QueryValue::ContainsValue(DataValue::String("world".to_string()).into());
+        assert!(query.verify(&DataValue::String("hello world".to_string())));
+        assert!(!query.verify(&DataValue::String("hello".to_string())));
+
+        let query = QueryValue::IsOfType(ValueType::String);
+        assert!(query.verify(&DataValue::String("hello".to_string())));
+        assert!(!query.verify(&DataValue::U8(5)));
+
+        let query = QueryValue::Matches(Regex::new(r"^\d{3}-\d{3}-\d{4}$").unwrap());
+        assert!(query.verify(&DataValue::String("123-456-7890".to_string())));
+        assert!(!query.verify(&DataValue::String("hello".to_string())));
+    }
+
+    #[test]
+    fn test_query_value_not_equal() {
+        let values = [DataValue::U8(5), DataValue::U8(6), DataValue::String("5".to_string())];
+        let equal = QueryValue::Equal(DataValue::U8(5));
+        let not_equal = QueryValue::NotEqual(DataValue::U8(5));
+        for value in &values {
+            assert_eq!(not_equal.verify(value), !equal.verify(value));
+        }
+        assert!(!not_equal.verify(&DataValue::U8(5)));
+
+        let query = Query::And(vec![
+            Query::Value(QueryValue::NotEqual(DataValue::U8(5))),
+            Query::Value(QueryValue::NotEqual(DataValue::U8(6)))
+        ]);
+        assert!(query.verify_value(&DataValue::U8(7)));
+        assert!(!query.verify_value(&DataValue::U8(6)));
+
+        let query = Query::Or(vec![
+            Query::Value(QueryValue::NotEqual(DataValue::U8(5))),
+            Query::Value(QueryValue::Equal(DataValue::U8(5)))
+        ]);
+        assert!(query.verify_value(&DataValue::U8(5)));
+
+        let json = serde_json::to_string(&not_equal).unwrap();
+        assert!(json.starts_with("{\"not_equal\""));
+        let query: Query = serde_json::from_str(&json).unwrap();
+        assert!(query.verify_value(&DataValue::U8(6)));
+    }
+
+    #[test]
+    fn test_query_value_equal_widths() {
+        let widths = |v: u8| vec![
+            DataValue::U8(v),
+            DataValue::U16(v as u16),
+            DataValue::U32(v as u32),
+            DataValue::U64(v as u64),
+            DataValue::U128(v as u128)
+        ];
+
+        for expected in widths(5) {
+            for value in widths(5) {
+                assert!(QueryValue::Equal(expected.clone()).verify(&value));
+                assert!(!QueryValue::NotEqual(expected.clone()).verify(&value));
+            }
+
+            for value in widths(6) {
+                assert!(!QueryValue::Equal(expected.clone()).verify(&value));
+                assert!(QueryValue::NotEqual(expected.clone()).verify(&value));
+            }
+
+            assert!(QueryValue::OneOf(vec![DataValue::U8(1), expected.clone()]).verify(&DataValue::U64(5)));
+        }
+
+        // Strings are never equal to numbers
+        let string = DataValue::String("5".to_string());
+        assert!(!QueryValue::Equal(string.clone()).verify(&DataValue::U8(5)));
+        assert!(!QueryValue::Equal(DataValue::U8(5)).verify(&string));
+        assert!(QueryValue::NotEqual(string).verify(&DataValue::U8(5)));
+    }
+
+    #[test]
+    fn test_query_value_one_of() {
+        let query = QueryValue::OneOf(vec![DataValue::String("abc".to_string()), DataValue::U8(5)]);
+        assert!(query.verify(&DataValue::String("abc".to_string())));
+        assert!(query.verify(&DataValue::U8(5)));
+        // Same value with another width
+        assert!(query.verify(&DataValue::U16(5)));
+        assert!(!query.verify(&DataValue::U16(6)));
+        // Not a substring match
+        assert!(!query.verify(&DataValue::String("ab".to_string())));
+        assert!(!query.verify(&DataValue::String("abcd".to_string())));
+
+        let query = QueryValue::OneOf(Vec::new());
+        assert!(!query.verify(&DataValue::U8(5)));
+    }
+
+    #[test]
+    fn test_query_value_string_operators() {
+        let value = DataValue::String("abcabc".to_string());
+        let starts_with = |v: &str| QueryValue::StartsWith(DataValue::String(v.to_string()).into()).verify(&value);
+        let ends_with = |v: &str| QueryValue::EndsWith(DataValue::String(v.to_string()).into()).verify(&value);
+        let contains = |v: &str| QueryValue::ContainsValue(DataValue::String(v.to_string()).into()).verify(&value);
+
+        // Present at both ends
+        assert!(starts_with("abc") && ends_with("abc") && contains("abc"));
+
+        // Prefix only
+        assert!(starts_with("ab"));
+        assert!(!ends_with("ab"));
+        assert!(contains("ab"));
+
+        // Suffix only
+        assert!(!starts_with("bc"));
+        assert!(ends_with("bc"));
+        assert!(contains("bc"));
+
+        // Middle only
+        assert!(!starts_with("cab"));
+        assert!(!ends_with("cab"));
+        assert!(contains("cab"));
+
+        // Absent
+        assert!(!starts_with("ba") && !ends_with("ba") && !contains("ba"));
+    }
+
+    #[test]
+    fn test_query_value_case_insensitive() {
+        let value = DataValue::String("Hello".to_string());
+        let lower = DataValue::String("hello".to_string());
+        assert!(!QueryValue::StartsWith(lower.clone().into()).verify(&value));
+        assert!(!QueryValue::EndsWith(lower.clone().into()).verify(&value));
+        assert!(!QueryValue::ContainsValue(lower.clone().into()).verify(&value));
+
+        assert!(QueryValue::StartsWithCi(lower.clone().into()).verify(&value));
+        assert!(QueryValue::EndsWithCi(lower.clone().into()).verify(&value));
+        assert!(QueryValue::ContainsValueCi(lower.clone().into()).verify(&value));
+        assert!(QueryValue::ContainsValueCi(DataValue::String("ELL".to_string()).into()).verify(&value));
+        assert!(!QueryValue::ContainsValueCi(DataValue::String("world".to_string()).into()).verify(&value));
+
+        // Non ASCII characters
+        let value = DataValue::String("ÉCOLE".to_string());
+        assert!(QueryValue::StartsWithCi(DataValue::String("éc".to_string()).into()).verify(&value));
+        assert!(!QueryValue::StartsWith(DataValue::String("éc".to_string()).into()).verify(&value));
+    }
+
+    #[test]
+    fn test_query_value_length() {
+        let value = DataValue::String("hello".to_string());
+        assert!(QueryValue::Length(QueryNumber::Greater(3)).verify(&value));
+        assert!(QueryValue::Length(QueryNumber::LesserOrEqual(5)).verify(&value));
+        assert!(!QueryValue::Length(QueryNumber::Lesser(5)).verify(&value));
+
+        // Characters are counted, not bytes
+        let value = DataValue::String("héllo".to_string());
+        assert!(QueryValue::Length(QueryNumber::LesserOrEqual(5)).verify(&value));
+
+        // Bytes are counted
+        let value = DataValue::Bytes(vec![0; 5]);
+        assert!(QueryValue::Length(QueryNumber::Greater(3)).verify(&value));
+        assert!(!QueryValue::Length(QueryNumber::Lesser(5)).verify(&value));
+
+        // Only strings and bytes have a length
+        assert!(!QueryValue::Length(QueryNumber::GreaterOrEqual(0)).verify(&DataValue::U8(5)));
+
+        // Not mixed up with the element length
+        let array = DataElement::Array((0..200).map(|i| DataElement::Value(DataValue::U16(i))).collect());
+        assert!(QueryElement::Len(QueryNumber::Greater(100)).verify(&array));
+        assert!(!Query::Value(QueryValue::Length(QueryNumber::GreaterOrEqual(0))).verify_element(&array));
+    }
+
+    #[test]
+    fn test_regex_cache() {
+        let json = r#"{"matches":"^[a-z]+-\\d+$"}"#;
+        let first: QueryValue = serde_json::from_str(json).unwrap();
+        let second: QueryValue = serde_json::from_str(json).unwrap();
+        match (&first, &second) {
+            (QueryValue::Matches(a), QueryValue::Matches(b)) => assert_eq!(a.as_str(), b.as_str()),
+            _ => panic!("expected a pattern")
+        }
+        assert!(first.verify(&DataValue::String("abc-123".to_string())));
+        assert!(!second.verify(&DataValue::String("abc".to_string())));
+
+        // Same format as before
+        assert_eq!(serde_json::to_string(&first).unwrap(), json);
+
+        // Invalid patterns are still rejected
+        assert!(serde_json::from_str::<QueryValue>(r#"{"matches":"("}"#).is_err());
+    }
+
+    // Tests use their own cache, the shared one is used concurrently by other tests
+    #[test]
+    fn test_regex_cache_lru() {
+        let cache = RegexCache::new(NonZeroUsize::new(2).unwrap());
+        let cached = |pattern: &str| cache.lock().contains(pattern);
+
+        cache.get_or_compile("a").unwrap();
+        cache.get_or_compile("b").unwrap();
+        // Using "a" again makes "b" the least recently used
+        assert!(cache.get_or_compile("a").unwrap().is_match("a"));
+        cache.get_or_compile("c").unwrap();
+        assert!(cached("a"));
+        assert!(!cached("b"));
+        assert!(cached("c"));
+        assert_eq!(cache.lock().len(), 2);
+
+        // Rejected patterns are not cached
+        assert!(cache.get_or_compile("(").is_err());
+        assert!(!cached("("));
+        assert!(cached("a"));
+
+        cache.clear();
+        assert!(cache.lock().is_empty());
+    }
+
+    #[test]
+    fn test_regex_cache_poisoned() {
+        let cache = RegexCache::new(NonZeroUsize::new(2).unwrap());
+        std::thread::scope(|scope| {
+            let _ = scope.spawn(|| {
+                let _entries = cache.entries.lock().unwrap();
+                panic!("poison the cache");
+            }).join();
+        });
+        assert!(cache.entries.is_poisoned());
+
+        // Limits still apply and the cache is still used
+        assert!(matches!(cache.get_or_compile("(a{1000}){1000}"), Err(regex::Error::CompiledTooBig(_))));
+        assert!(cache.get_or_compile("^poisoned$").unwrap().is_match("poisoned"));
+        assert!(cache.lock().contains("^poisoned$"));
+    }
+
+    #[test]
+    fn test_regex_pathological() {
+        // Exponential with a backtracking engine, linear here
+        let query: QueryValue = serde_json::from_str(r#"{"matches":"^(a+)+$"}"#).unwrap();
+        let value = DataValue::String(format!("{}!", "a".repeat(10_000)));
+        assert!(!query.verify(&value));
+
+        // Huge automaton is rejected before being compiled
+        assert!(matches!(get_or_compile_regex("(a{1000}){1000}"), Err(regex::Error::CompiledTooBig(limit)) if limit == REGEX_SIZE_LIMIT));
+        assert!(serde_json::from_str::<QueryValue>(r#"{"matches":"(a{1000}){1000}"}"#).is_err());
+    }
+
+    #[test]
+    fn test_query_depth_limit() {
+        let mut query = Query::Value(QueryValue::Equal(DataValue::U8(5)));
+        for _ in 0..10_000 {
+            query = Query::Not(Box::new(query));
+        }
+
+        // Too deep, matches nothing even under an even count of Not
+        assert!(!query.verify_value(&DataValue::U8(5)));
+        assert!(!query.verify_element(&DataElement::Value(DataValue::U8(5))));
+
+        let query = Query::Element(QueryElement::AtPosition {
+            position: 0,
+            query: Box::new(query)
+        });
+        assert!(!query.verify_element(&DataElement::Array(vec![DataElement::Value(DataValue::U8(5))])));
+
+        // Under the limit it is evaluated normally
+        let mut query = Query::Value(QueryValue::Equal(DataValue::U8(5)));
+        for _ in 0..MAX_QUERY_DEPTH - 2 {
+            query = Query::Not(Box::new(query));
+        }
+        assert!(query.verify_value(&DataValue::U8(5)));
+
+        let nested = |depth: usize| format!("{}{}{}", r#"{"not":"#.repeat(depth), r#"{"equal":5}"#, "}".repeat(depth));
+        let query: Query = serde_json::from_str(&nested(MAX_QUERY_DEPTH - 1)).unwrap();
+        assert!(!query.verify_value(&DataValue::U8(5)));
+        assert!(serde_json::from_str::<Query>(&nested(MAX_QUERY_DEPTH)).is_err());
+
+        // Depth counter is restored after an error
+        assert!(serde_json::from_str::<Query>(&nested(1)).is_ok());
+    }
+
+    #[test]
+    fn test_query_depth_boundary() {
+        // An element query holding a chain of Not, with exactly `depth` queries in total
+        let build = |depth: usize| {
+            let mut query = Query::Value(QueryValue::Equal(DataValue::U8(5)));
+            for _ in 0..depth - 2 {
+                query = Query::Not(Box::new(query));
+            }
+            Query::Element(QueryElement::AtPosition { position: 0, query: Box::new(query) })
+        };
+        let array = DataElement::Array(vec![DataElement::Value(DataValue::U8(5))]);
+
+        // Exactly at the limit: accepted everywhere
+        let query = build(MAX_QUERY_DEPTH);
+        assert!(query.verify_element(&array));
+        assert_eq!(query.validate(true), Ok(()));
+        let json = serde_json::to_string(&query).unwrap();
+        let query: Query = serde_json::from_str(&json).unwrap();
+        assert!(query.verify_element(&array));
+        assert!(Query::parse_dsl(&query.to_string()).is_ok());
+        assert!(query.estimated_cost() > build(MAX_QUERY_DEPTH - 2).estimated_cost());
+
+        // One level more: rejected everywhere
+        let query = build(MAX_QUERY_DEPTH + 1);
+        assert!(!query.verify_element(&array));
+        assert_eq!(query.validate(true), Err(QueryError::TooDeep));
+        assert!(serde_json::from_str::<Query>(&serde_json::to_string(&query).unwrap()).is_err());
+        assert!(Query::parse_dsl(&query.to_string()).is_err());
+        // Removing the double negations must not make it accepted
+        assert!(matches!(query.optimize(true), Err(QueryError::TooDeep)));
+    }
+
+    #[test]
+    fn test_query_optimize() {
+        let equal = |v: u8| Query::Value(QueryValue::Equal(DataValue::U8(v)));
+        let greater = |v: i128| Query::Value(QueryValue::NumberOp(QueryNumber::Greater(v)));
+        // Same query built twice as it can't be cloned
+        let build = || Query::Or(vec![
+            Query::And(vec![
+                Query::And(vec![greater(1), Query::Not(Box::new(Query::Not(Box::new(greater(3)))))]),
+                Query::And(vec![Query::Not(Box::new(equal(7)))])
+            ]),
+            Query::Or(vec![Query::Or(vec![equal(0)])]),
+            Query::Element(QueryElement::AtPosition {
+                position: 0,
+                query: Box::new(Query::And(vec![Query::Not(Box::new(Query::Not(Box::new(equal(9)))))]))
+            })
+        ]);
+
+        let original = build();
+        let optimized = build().optimize(true).unwrap();
+        match &optimized {
+            Query::Or(operations) => {
+                assert_eq!(operations.len(), 3);
+                match &operations[0] {
+                    Query::And(inner) => {
+                        assert_eq!(inner.len(), 3);
+                        assert!(matches!(inner[1], Query::Value(QueryValue::NumberOp(QueryNumber::Greater(3)))));
+                        assert!(matches!(inner[2], Query::Not(_)));
+                    },
+                    _ => panic!("expected an And")
+                }
+                assert!(matches!(operations[1], Query::Value(QueryValue::Equal(DataValue::U8(0)))));
+                match &operations[2] {
+                    Query::Element(QueryElement::AtPosition { query, .. }) => assert!(matches!(**query, Query::Value(QueryValue::Equal(DataValue::U8(9))))),
+                    _ => panic!("expected an AtPosition")
+                }
+            },
+            _ => panic!("expected an Or")
+        }
+
+        for v in 0..12u8 {
+            let value = DataValue::U8(v);
+            assert_eq!(original.verify_value(&value), optimized.verify_value(&value));
+
+            let element = DataElement::Value(value.clone());
+            assert_eq!(original.verify_element(&element), optimized.verify_element(&element));
+
+            let element = DataElement::Array(vec![DataElement::Value(value)]);
+            assert_eq!(original.verify_element(&element), optimized.verify_element(&element));
+        }
+    }
+
+    #[test]
+    fn test_query_estimated_cost() {
+        let equal = Query::Value(QueryValue::Equal(DataValue::U8(5)));
+        assert_eq!(equal.estimated_cost(), QUERY_COST_COMPARISON);
+
+        let equals = Query::And((0..5).map(|i| Query::Value(QueryValue::Equal(DataValue::U8(i)))).collect());
+        let regexes = Query::And((0..5).map(|i| Query::Value(QueryValue::Matches(Regex::new(&format!("^{}$", i)).unwrap()))).collect());
+        assert!(regexes.estimated_cost() > equals.estimated_cost());
+
+        // Same count of comparisons but nested
+        let mut nested = Query::Value(QueryValue::Equal(DataValue::U8(0)));
+        for i in 1..5 {
+            nested = Query::And(vec![nested, Query::Value(QueryValue::Equal(DataValue::U8(i)))]);
+        }
+        assert!(nested.estimated_cost() > equals.estimated_cost());
+
+        // Sub queries of elements are counted
+        let element = Query::Element(QueryElement::AtKey {
+            key: DataValue::U8(0),
+            query: Box::new(Query::Value(QueryValue::Matches(Regex::new("a").unwrap())))
+        });
+        assert!(element.estimated_cost() > QUERY_COST_REGEX);
+    }
+
+    #[test]
+    fn test_query_validate() {
+        let value = || Query::Value(QueryValue::Equal(DataValue::U8(5)));
+        let element = || Query::Element(QueryElement::Len(QueryNumber::Greater(1)));
+
+        assert_eq!(value().validate(false), Ok(()));
+        assert_eq!(value().validate(true), Ok(()));
+        assert_eq!(element().validate(true), Ok(()));
+        assert_eq!(element().validate(false), Err(QueryError::ElementQueryOnValue));
+
+        // Nested in operators
+        assert_eq!(Query::Not(Box::new(element())).validate(false), Err(QueryError::ElementQueryOnValue));
+        assert_eq!(Query::And(vec![value(), element()]).validate(false), Err(QueryError::ElementQueryOnValue));
+        assert_eq!(Query::Or(vec![value(), element()]).validate(false), Err(QueryError::ElementQueryOnValue));
+        assert_eq!(Query::Or(vec![value(), element()]).validate(true), Ok(()));
+
+        // Sub queries of an element query are applied on elements
+        let query = Query::Element(QueryElement::AtKey {
+            key: DataValue::String("a".to_string()),
+            query: Box::new(element())
+        });
+        assert_eq!(query.validate(true), Ok(()));
+
+        let mut query = value();
+        for _ in 0..MAX_QUERY_DEPTH {
+            query = Query::Not(Box::new(query));
+        }
+        assert_eq!(query.validate(false), Err(QueryError::TooDeep));
+    }
+
+    #[test]
+    fn test_query_element() {
+        let mut fields = HashMap::new();
+        fields.insert(DataValue::String("owner".to_string()), DataElement::Value(DataValue::String("Slixe".to_string())));
+        fields.insert(DataValue::String("balance".to_string()), DataElement::Value(DataValue::U8(25)));
+
+        let element = DataElement::Fields(fields.clone());
+
+        let query = QueryElement::HasKey {
+            key: DataValue::String("owner".to_string()),
+            query: Some(Box::new(Query::Value(QueryValue::Equal(DataValue::String("Slixe".to_string())))))
+        };
         assert!(query.verify(&element));
 
         let query = QueryElement::AtKey {
@@ -321,6 +2066,319 @@ mod tests {
         assert!(query.verify(&DataElement::Fields(fields)));
     }
 
+    #[test]
+    fn test_query_array_contains_all() {
+        let value = |v: u8| DataElement::Value(DataValue::U8(v));
+        let array = DataElement::Array(vec![value(1), value(2), value(3)]);
+
+        let query = QueryElement::ArrayContainsAll(vec![value(3), value(1)]);
+        assert!(query.verify(&array));
+
+        let query = QueryElement::ArrayContainsAll(vec![value(1), value(4)]);
+        assert!(!query.verify(&array));
+
+        let query = QueryElement::ArrayContainsAll(Vec::new());
+        assert!(query.verify(&array));
+        assert!(query.verify(&DataElement::Array(Vec::new())));
+        // Not an array
+        assert!(!query.verify(&value(1)));
+    }
+
+    #[test]
+    fn test_query_key_path() {
+        let key = |k: &str| DataValue::String(k.to_string());
+        let mut c = HashMap::new();
+        c.insert(key("c"), DataElement::Value(DataValue::U8(42)));
+        let mut b = HashMap::new();
+        b.insert(key("b"), DataElement::Fields(c));
+        b.insert(key("list"), DataElement::Array(vec![
+            DataElement::Value(DataValue::U8(1)),
+            DataElement::Value(DataValue::U8(2))
+        ]));
+        let mut a = HashMap::new();
+        a.insert(key("a"), DataElement::Fields(b));
+        let element = DataElement::Fields(a);
+
+        let equal = |v: u8| Some(Box::new(Query::Value(QueryValue::Equal(DataValue::U8(v)))));
+
+        let query = QueryElement::KeyPath { path: vec![key("a"), key("b"), key("c")], value: equal(42) };
+        assert!(query.verify(&element));
+        let query = QueryElement::KeyPath { path: vec![key("a"), key("b"), key("c")], value: equal(41) };
+        assert!(!query.verify(&element));
+        // Only the existence
+        let query = QueryElement::KeyPath { path: vec![key("a"), key("b")], value: None };
+        assert!(query.verify(&element));
+
+        // Missing intermediate key
+        let query = QueryElement::KeyPath { path: vec![key("a"), key("x"), key("c")], value: None };
+        assert!(!query.verify(&element));
+        // Too long
+        let query = QueryElement::KeyPath { path: vec![key("a"), key("b"), key("c"), key("d")], value: None };
+        assert!(!query.verify(&element));
+
+        // Array index
+        let query = QueryElement::KeyPath { path: vec![key("a"), key("list"), DataValue::U8(1)], value: equal(2) };
+        assert!(query.verify(&element));
+        let query = QueryElement::KeyPath { path: vec![key("a"), key("list"), DataValue::U64(2)], value: None };
+        assert!(!query.verify(&element));
+        let query = QueryElement::KeyPath { path: vec![key("a"), key("list"), key("0")], value: None };
+        assert!(!query.verify(&element));
+    }
+
+    #[test]
+    fn test_run_query() {
+        let entries: IndexMap<DataValue, DataElement> = (0..10u8)
+            .map(|i| (DataValue::U8(i), DataElement::Value(DataValue::U8(i))))
+            .collect();
+        // Only the even values
+        let even = Query::Value(QueryValue::OneOf((0..10u8).step_by(2).map(DataValue::U8).collect()));
+
+        let result = run_query(&entries, &even, None, 0, 2);
+        assert_eq!(result.entries.keys().collect::<Vec<_>>(), vec![&DataValue::U8(0), &DataValue::U8(2)]);
+        assert_eq!(result.next, Some(2));
+
+        let result = run_query(&entries, &even, None, 2, 2);
+        assert_eq!(result.entries.keys().collect::<Vec<_>>(), vec![&DataValue::U8(4), &DataValue::U8(6)]);
+        assert_eq!(result.next, Some(4));
+
+        // Last page is exactly filled
+        let result = run_query(&entries, &even, None, 4, 1);
+        assert_eq!(result.entries.keys().collect::<Vec<_>>(), vec![&DataValue::U8(8)]);
+        assert_eq!(result.next, None);
+
+        // Limit above the remaining entries
+        let result = run_query(&entries, &even, None, 3, 10);
+        assert_eq!(result.entries.len(), 2);
+        assert_eq!(result.next, None);
+
+        // Offset past the end
+        let result = run_query(&entries, &even, None, 5, 10);
+        assert!(result.entries.is_empty());
+        assert_eq!(result.next, None);
+
+        // Nothing match
+        let none = Query::Value(QueryValue::Equal(DataValue::U8(42)));
+        let result = run_query(&entries, &none, None, 0, 10);
+        assert!(result.entries.is_empty());
+        assert_eq!(result.next, None);
+
+        // No limit must not return the same offset forever
+        let result = run_query(&entries, &even, None, 0, 0);
+        assert!(result.entries.is_empty());
+        assert_eq!(result.next, None);
+    }
+
+    #[test]
+    fn test_query_result_serializer() {
+        let mut fields = HashMap::new();
+        fields.insert(DataValue::String("list".to_string()), DataElement::Array(vec![DataElement::Value(DataValue::I64(-1))]));
+        let mut entries = IndexMap::new();
+        entries.insert(DataValue::U8(2), DataElement::Fields(fields));
+        entries.insert(DataValue::String("b".to_string()), DataElement::Value(DataValue::Bytes(vec![1, 2])));
+
+        for next in [None, Some(2)] {
+            let result = QueryResult { entries: entries.clone(), next };
+            let bytes = result.to_bytes();
+            assert_eq!(bytes.len(), result.size());
+
+            let read = QueryResult::from_bytes(&bytes).unwrap();
+            assert_eq!(read.entries, result.entries);
+            // Order is kept
+            assert_eq!(read.entries.keys().collect::<Vec<_>>(), result.entries.keys().collect::<Vec<_>>());
+            assert_eq!(read.next, next);
+        }
+
+        // Same key twice
+        let mut writer = Writer::new();
+        writer.write_u16(2);
+        for _ in 0..2 {
+            DataValue::U8(1).write(&mut writer);
+            DataElement::Value(DataValue::U8(1)).write(&mut writer);
+        }
+        None::<u64>.write(&mut writer);
+        assert!(matches!(QueryResult::from_bytes(&writer.bytes()), Err(ReaderError::InvalidValue)));
+    }
+
+    #[test]
+    fn test_run_query_max_entries() {
+        let entries: IndexMap<_, _> = (0..MAX_QUERY_RESULT_ENTRIES as u32 + 10)
+            .map(|i| (DataValue::U32(i), DataElement::Value(DataValue::U8(0))))
+            .collect();
+
+        let all = Query::Element(QueryElement::Type(ElementType::Value(ValueType::U8)));
+        let result = run_query(&entries, &all, None, 0, usize::MAX);
+        assert_eq!(result.entries.len(), MAX_QUERY_RESULT_ENTRIES);
+        assert_eq!(result.next, Some(MAX_QUERY_RESULT_ENTRIES));
+        assert_eq!(QueryResult::from_bytes(&result.to_bytes()).unwrap().entries.len(), MAX_QUERY_RESULT_ENTRIES);
+
+        let result = run_query(&entries, &all, None, MAX_QUERY_RESULT_ENTRIES, usize::MAX);
+        assert_eq!(result.entries.len(), 10);
+        assert_eq!(result.next, None);
+    }
+
+    #[test]
+    fn test_run_query_sorted() {
+        let key = |k: &str| DataValue::String(k.to_string());
+        let user = |age: Option<u8>| {
+            let mut profile = HashMap::new();
+            if let Some(age) = age {
+                profile.insert(key("age"), DataElement::Value(DataValue::U8(age)));
+            }
+            let mut fields = HashMap::new();
+            fields.insert(key("profile"), DataElement::Fields(profile));
+            DataElement::Fields(fields)
+        };
+
+        let mut entries = IndexMap::new();
+        entries.insert(key("bob"), user(Some(30)));
+        entries.insert(key("alice"), user(Some(25)));
+        entries.insert(key("dave"), user(None));
+        entries.insert(key("carol"), user(Some(30)));
+
+        let all = Query::Element(QueryElement::Type(ElementType::Fields));
+        let keys = |result: &QueryResult| result.entries.keys().map(|k| k.as_string().unwrap().as_str()).collect::<Vec<_>>().join(",");
+
+        // By entry key
+        let sort = SortBy { key: None, descending: false };
+        assert_eq!(keys(&run_query(&entries, &all, Some(&sort), 0, 10)), "alice,bob,carol,dave");
+        let sort = SortBy { key: None, descending: true };
+        assert_eq!(keys(&run_query(&entries, &all, Some(&sort), 0, 10)), "dave,carol,bob,alice");
+
+        // By nested field, ties by key and missing values last
+        let path = Some(vec![key("profile"), key("age")]);
+        let sort = SortBy { key: path.clone(), descending: false };
+        assert_eq!(keys(&run_query(&entries, &all, Some(&sort), 0, 10)), "alice,bob,carol,dave");
+        let sort = SortBy { key: path, descending: true };
+        assert_eq!(keys(&run_query(&entries, &all, Some(&sort), 0, 10)), "bob,carol,alice,dave");
+
+        // Sorted before the pagination
+        let result = run_query(&entries, &all, Some(&sort), 1, 2);
+        assert_eq!(keys(&result), "carol,alice");
+        assert_eq!(result.next, Some(3));
+
+        // Without sort, the map order is kept
+        assert_eq!(keys(&run_query(&entries, &all, None, 0, 10)), "bob,alice,dave,carol");
+    }
+
+    #[test]
+    fn test_query_xor() {
+        let equal = |v: u8| Query::Value(QueryValue::Equal(DataValue::U8(v)));
+        let greater = |v: i128| Query::Value(QueryValue::NumberOp(QueryNumber::Greater(v)));
+        let value = DataValue::U8(5);
+        let element = DataElement::Value(value.clone());
+
+        // None match
+        let query = Query::Xor(vec![equal(1), equal(2), equal(3)]);
+        assert!(!query.verify_value(&value));
+        assert!(!query.verify_element(&element));
+
+        // One match
+        let query = Query::Xor(vec![equal(1), equal(5), equal(3)]);
+        assert!(query.verify_value(&value));
+        assert!(query.verify_element(&element));
+
+        // Two match
+        let query = Query::Xor(vec![equal(5), greater(1), equal(3)]);
+        assert!(!query.verify_value(&value));
+        assert!(!query.verify_element(&element));
+
+        // Three match
+        let query = Query::Xor(vec![equal(5), greater(1), greater(2)]);
+        assert!(!query.verify_value(&value));
+        assert!(!query.verify_element(&element));
+
+        // Empty
+        assert!(!Query::Xor(Vec::new()).verify_value(&value));
+
+        // Nested Xor is kept by optimize
+        let query = Query::Xor(vec![equal(5), Query::Xor(vec![greater(1), equal(3)])]).optimize(false).unwrap();
+        assert!(!query.verify_value(&value));
+
+        let query: Query = serde_json::from_str(r#"{"xor":[{"equal":1},{"equal":5}]}"#).unwrap();
+        assert!(query.verify_value(&value));
+    }
+
+    #[test]
+    fn test_query_dsl() {
+        let query = Query::parse_dsl(r#"age > 18 AND name STARTS "al""#).unwrap();
+        let mut fields = HashMap::new();
+        fields.insert(DataValue::String("age".to_string()), DataElement::Value(DataValue::U8(20)));
+        fields.insert(DataValue::String("name".to_string()), DataElement::Value(DataValue::String("alice".to_string())));
+        let element = DataElement::Fields(fields);
+        assert!(query.verify_element(&element));
+        assert_eq!(query.to_string(), r#"age > 18 AND name STARTS "al""#);
+
+        // Precedence: NOT, AND, XOR then OR
+        let query = Query::parse_dsl("$ = 1 OR $ = 2 AND NOT $ = 3").unwrap();
+        assert!(matches!(&query, Query::Or(operations) if matches!(operations[1], Query::And(_))));
+        assert!(query.verify_value(&DataValue::U8(1)));
+        assert!(query.verify_value(&DataValue::U8(2)));
+        assert!(!query.verify_value(&DataValue::U8(3)));
+
+        let query = Query::parse_dsl("$ = 1 XOR ($ >= 1 AND $ <= 2)").unwrap();
+        assert!(!query.verify_value(&DataValue::U8(1)));
+        assert!(query.verify_value(&DataValue::U8(2)));
+
+        let query = Query::parse_dsl(r#"profile."full name".first ICONTAINS "LI""#).unwrap();
+        assert!(matches!(&query, Query::Element(QueryElement::KeyPath { path, .. }) if path.len() == 3));
+    }
+
+    #[test]
+    fn test_query_dsl_round_trip() {
+        let inputs = [
+            r#"age > 18 AND name STARTS "al""#,
+            r#"$ = 5u8 OR $ != "abc" XOR $ IN [1, 2u16, true, "x"]"#,
+            r#"NOT (a = 1 OR b = 2) AND "my key" ENDS "z""#,
+            r#"a.b.c BETWEEN 1 AND 10 EXCLUSIVE AND $ BETWEEN 2 AND 3"#,
+            r#"name MATCHES "^\d{3}-\"x\"$" OR name IS string OR name LENGTH <= 5"#,
+            r#"$ = hash("0000000000000000000000000000000000000000000000000000000000000000")"#,
+            r#"$ = bytes("00ff") OR $ IS bytes"#,
+            r#"items.0u8 ISTARTS "a" AND NOT NOT $ IENDS "b""#,
+            r#"((a = 1 AND b = 2) OR c = 3) XOR d = 340282366920938463463374607431768211455u128"#,
+            r#"JSON {"len":{"greater":2}} AND "AND" = 1"#,
+        ];
+
+        for input in inputs {
+            let query = Query::parse_dsl(input).unwrap();
+            let displayed = query.to_string();
+            let reparsed = Query::parse_dsl(&displayed).unwrap();
+            assert_eq!(displayed, reparsed.to_string(), "{}", input);
+        }
+
+        // Queries without syntax use the JSON fallback
+        let query = Query::Element(QueryElement::ContainsElement(DataElement::Value(DataValue::U8(1))));
+        let displayed = query.to_string();
+        assert!(displayed.starts_with("JSON "));
+        assert_eq!(Query::parse_dsl(&displayed).unwrap().to_string(), displayed);
+    }
+
+    #[test]
+    fn test_query_dsl_errors() {
+        assert_eq!(Query::parse_dsl("").unwrap_err(), QueryParseError::UnexpectedEnd);
+        assert_eq!(Query::parse_dsl("age >").unwrap_err(), QueryParseError::UnexpectedEnd);
+        assert_eq!(Query::parse_dsl("age > 1 AND").unwrap_err(), QueryParseError::UnexpectedEnd);
+        assert!(matches!(Query::parse_dsl("age > 1 age").unwrap_err(), QueryParseError::Unexpected(8, _)));
+        assert!(matches!(Query::parse_dsl("(age > 1").unwrap_err(), QueryParseError::UnexpectedEnd));
+        assert!(matches!(Query::parse_dsl("age ~ 1").unwrap_err(), QueryParseError::Unexpected(4, _)));
+        assert!(matches!(Query::parse_dsl("age LIKE 1").unwrap_err(), QueryParseError::Unexpected(4, _)));
+        assert_eq!(Query::parse_dsl(r#"name = "abc"#).unwrap_err(), QueryParseError::UnterminatedString(7));
+        assert_eq!(Query::parse_dsl("$ = 300u8").unwrap_err(), QueryParseError::InvalidNumber(4));
+        assert_eq!(Query::parse_dsl("$ > 1u8").unwrap_err(), QueryParseError::Unexpected(4, "1u8".to_string()));
+        assert_eq!(Query::parse_dsl("$ IS float").unwrap_err(), QueryParseError::UnknownType(5, "float".to_string()));
+        assert_eq!(Query::parse_dsl(r#"$ = hash("00")"#).unwrap_err(), QueryParseError::InvalidHash(9));
+        assert_eq!(Query::parse_dsl(r#"$ = bytes("0")"#).unwrap_err(), QueryParseError::InvalidBytes(10));
+        let too_big = format!(r#"$ = bytes("{}")"#, "00".repeat(MAX_BYTES_SIZE + 1));
+        assert_eq!(Query::parse_dsl(&too_big).unwrap_err(), QueryParseError::InvalidBytes(10));
+        assert!(Query::parse_dsl(&format!(r#"$ = bytes("{}")"#, "00".repeat(MAX_BYTES_SIZE))).is_ok());
+        assert!(matches!(Query::parse_dsl(r#"$ MATCHES "(""#).unwrap_err(), QueryParseError::InvalidRegex(10, _)));
+        assert!(matches!(Query::parse_dsl("JSON {").unwrap_err(), QueryParseError::InvalidJson(5, _)));
+
+        let deep = format!("{}$ = 1{}", "(".repeat(MAX_QUERY_DEPTH + 1), ")".repeat(MAX_QUERY_DEPTH + 1));
+        assert_eq!(Query::parse_dsl(&deep).unwrap_err(), QueryParseError::TooDeep);
+        let deep = format!("{}$ = 1", "NOT ".repeat(10_000));
+        assert_eq!(Query::parse_dsl(&deep).unwrap_err(), QueryParseError::TooDeep);
+    }
+
     #[test]
     fn test_query_and() {
         let mut fields = HashMap::new();
@@ -386,4 +2444,69 @@ mod tests {
         ]);
         assert!(!query.verify_element(&element));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_query_result_csv_flat() {
+        let mut entries = IndexMap::new();
+        entries.insert(DataValue::String("alice".to_owned()), DataElement::Value(DataValue::U64(10)));
+        entries.insert(DataValue::U8(2), DataElement::Value(DataValue::String("hello, \"world\"".to_owned())));
+        entries.insert(DataValue::Bytes(vec![0xab, 0xcd]), DataElement::Value(DataValue::Bool(true)));
+        let result = QueryResult { entries, next: None };
+
+        assert_eq!(result.to_csv(), "key,value\nalice,10\n2,\"hello, \"\"world\"\"\"\nabcd,true\n");
+        assert_eq!(QueryResult { entries: IndexMap::new(), next: None }.to_csv(), "key\n");
+    }
+
+    #[test]
+    fn test_query_result_csv_nested() {
+        let user = |name: &str, city: Option<&str>, tags: &[&str]| {
+            let mut address = HashMap::new();
+            if let Some(city) = city {
+                address.insert(DataValue::String("city".to_owned()), DataElement::Value(DataValue::String(city.to_owned())));
+            }
+
+            let mut fields = HashMap::new();
+            fields.insert(DataValue::String("name".to_owned()), DataElement::Value(DataValue::String(name.to_owned())));
+            fields.insert(DataValue::String("address".to_owned()), DataElement::Fields(address));
+            fields.insert(DataValue::String("tags".to_owned()), DataElement::Array(
+                tags.iter().map(|tag| DataElement::Value(DataValue::String(tag.to_string()))).collect()
+            ));
+            DataElement::Fields(fields)
+        };
+
+        let mut entries = IndexMap::new();
+        entries.insert(DataValue::U64(1), user("alice", Some("Paris"), &["admin"]));
+        entries.insert(DataValue::U64(2), user("bob", None, &["dev", "ops"]));
+        let result = QueryResult { entries, next: Some(2) };
+
+        let csv = result.to_csv();
+        let lines: Vec<_> = csv.lines().collect();
+        assert_eq!(lines, vec![
+            "key,address.city,name,tags.0,tags.1",
+            "1,Paris,alice,admin,",
+            "2,,bob,dev,ops"
+        ]);
+    }
+
+    #[test]
+    fn test_query_text_cached() {
+        let query: QueryValue = serde_json::from_str(r#"{"starts_with_ci":"HeLLo"}"#).unwrap();
+        let QueryValue::StartsWithCi(text) = &query else {
+            panic!("unexpected query {:?}", query)
+        };
+        assert_eq!(text.get_value(), &DataValue::String("HeLLo".to_owned()));
+        assert_eq!(text.get_text(), "HeLLo");
+        assert_eq!(text.get_lowercase(), "hello");
+
+        // Still serialized as the plain value
+        assert_eq!(serde_json::to_string(&query).unwrap(), r#"{"starts_with_ci":"HeLLo"}"#);
+        assert!(query.verify(&DataValue::String("hello world".to_owned())));
+
+        // Values that are not strings are compared using their text form
+        let query = QueryValue::ContainsValue(DataValue::U64(23).into());
+        assert!(query.verify(&DataValue::U32(1234)));
+        assert!(!query.verify(&DataValue::U32(1324)));
+        assert_eq!(DataValue::String("a".to_owned()).as_str().unwrap(), "a");
+        assert!(DataValue::U8(1).as_str().is_err());
+    }
+}
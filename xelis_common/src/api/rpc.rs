@@ -0,0 +1,172 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+// Typed JSON-RPC 2.0 envelopes shared by the daemon and wallet APIs
+// Params and result are generic so they can carry a Query and a QueryResult
+// without going through an intermediate serde_json::Value
+
+pub const JSON_RPC_VERSION: &str = "2.0";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Id {
+    String(String),
+    Number(usize),
+}
+
+// Params are kept as a serde_json::Value by default,
+// the RPC server parses them once the method is known
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RpcRequest<P = Value> {
+    pub jsonrpc: String,
+    // No id means the request is a notification
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<Id>,
+    pub method: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub params: Option<P>
+}
+
+impl<P> RpcRequest<P> {
+    pub fn new(id: Option<Id>, method: impl Into<String>, params: Option<P>) -> Self {
+        Self {
+            jsonrpc: JSON_RPC_VERSION.to_owned(),
+            id,
+            method: method.into(),
+            params
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RpcError {
+    pub code: i16,
+    pub message: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data: Option<Value>
+}
+
+// A response contains either a result or an error, never both
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RpcOutcome<R> {
+    Result(R),
+    Error(RpcError)
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RpcResponse<R> {
+    pub jsonrpc: String,
+    // Null if the request id could not be determined
+    pub id: Option<Id>,
+    #[serde(flatten)]
+    pub outcome: RpcOutcome<R>
+}
+
+impl<R> RpcResponse<R> {
+    pub fn result(id: Option<Id>, result: R) -> Self {
+        Self {
+            jsonrpc: JSON_RPC_VERSION.to_owned(),
+            id,
+            outcome: RpcOutcome::Result(result)
+        }
+    }
+
+    pub fn error(id: Option<Id>, error: RpcError) -> Self {
+        Self {
+            jsonrpc: JSON_RPC_VERSION.to_owned(),
+            id,
+            outcome: RpcOutcome::Error(error)
+        }
+    }
+
+    // Convert the response into the result or the error returned
+    pub fn into_result(self) -> Result<R, RpcError> {
+        match self.outcome {
+            RpcOutcome::Result(result) => Ok(result),
+            RpcOutcome::Error(error) => Err(error)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use indexmap::IndexMap;
+    use serde_json::json;
+    use crate::api::{
+        query::{Query, QueryResult},
+        DataElement,
+        DataValue
+    };
+    use super::*;
+
+    #[test]
+    fn test_request_with_query() {
+        let json = r#"{
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "query_db",
+            "params": {"and": [{"length": {"greater": 3}}, {"not": {"equal": "xel"}}]}
+        }"#;
+
+        let request: RpcRequest<Query> = serde_json::from_str(json).unwrap();
+        assert_eq!(request.jsonrpc, JSON_RPC_VERSION);
+        assert_eq!(request.id, Some(Id::Number(1)));
+        assert_eq!(request.method, "query_db");
+
+        let query = request.params.unwrap();
+        assert!(matches!(query, Query::And(ref queries) if queries.len() == 2));
+        assert!(query.verify_value(&DataValue::String("xelis".to_owned())));
+        assert!(!query.verify_value(&DataValue::String("xel".to_owned())));
+
+        // Params are kept as a JSON value by default
+        let request: RpcRequest = serde_json::from_str(json).unwrap();
+        assert!(matches!(request.params, Some(Value::Object(_))));
+
+        // Notification without params
+        let request: RpcRequest<Query> = serde_json::from_str(r#"{"jsonrpc":"2.0","method":"ping"}"#).unwrap();
+        assert!(request.id.is_none() && request.params.is_none());
+        assert_eq!(serde_json::to_value(&request).unwrap(), json!({"jsonrpc": "2.0", "method": "ping"}));
+    }
+
+    #[test]
+    fn test_response_with_query_result() {
+        let mut entries = IndexMap::new();
+        entries.insert(DataValue::String("key".to_owned()), DataElement::Value(DataValue::U64(42)));
+        let response = RpcResponse::result(Some(Id::String("a".to_owned())), QueryResult {
+            entries,
+            next: Some(1)
+        });
+
+        let value = serde_json::to_value(&response).unwrap();
+        assert_eq!(value["jsonrpc"], "2.0");
+        assert_eq!(value["id"], "a");
+        assert!(value.get("error").is_none());
+        assert_eq!(value["result"]["next"], 1);
+
+        let decoded: RpcResponse<QueryResult> = serde_json::from_value(value).unwrap();
+        let result = decoded.into_result().unwrap();
+        assert_eq!(result.next, Some(1));
+        assert_eq!(result.entries.len(), 1);
+    }
+
+    #[test]
+    fn test_response_error() {
+        let error = RpcError {
+            code: -32601,
+            message: "Method 'foo' in request was not found".to_owned(),
+            data: None
+        };
+        let response: RpcResponse<QueryResult> = RpcResponse::error(None, error.clone());
+
+        let value = serde_json::to_value(&response).unwrap();
+        assert_eq!(value, json!({
+            "jsonrpc": "2.0",
+            "id": null,
+            "error": {"code": -32601, "message": "Method 'foo' in request was not found"}
+        }));
+
+        let decoded: RpcResponse<QueryResult> = serde_json::from_value(value).unwrap();
+        assert_eq!(decoded.into_result().err(), Some(error));
+    }
+}
@@ -9,6 +9,7 @@ use crate::{
         Hashable,
         Hash,
     },
+    config::MAX_TRANSACTION_SIZE,
     immutable::Immutable,
     transaction::Transaction,
     serializer::{Serializer, Writer, Reader, ReaderError},
@@ -32,7 +33,7 @@ impl Block {
 
     pub fn with(mut block: BlockHeader, transactions: Vec<Transaction>) -> Self {
         transactions.iter().for_each(|tx| {
-            block.txs_hashes.insert(tx.hash());
+            block.txs_hashes.insert(tx.hash().clone());
         });
 
         Block {
@@ -74,7 +75,7 @@ impl Serializer for Block {
         let block = BlockHeader::read(reader)?;
         let mut txs: Vec<Immutable<Transaction>> = Vec::new();
         for _ in 0..block.get_txs_count() {
-            let tx = Transaction::read(reader)?;
+            let tx = Transaction::read_bounded(reader, MAX_TRANSACTION_SIZE)?;
             txs.push(Immutable::Owned(tx));     
         }
 
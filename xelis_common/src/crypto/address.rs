@@ -17,6 +17,19 @@ use core::fmt;
 use log::debug;
 use serde::de::Error as SerdeError;
 use anyhow::Error;
+use thiserror::Error as ThisError;
+
+#[derive(ThisError, Debug)]
+pub enum AddressError {
+    #[error(transparent)]
+    Bech32(#[from] Bech32Error),
+    #[error(transparent)]
+    Reader(#[from] ReaderError),
+    #[error("Invalid network, expected a {} address", if *_0 { "mainnet" } else { "testnet" })]
+    InvalidNetwork(bool),
+    #[error("Integrated address can't be used as a public key")]
+    IntegratedAddress
+}
 
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub enum AddressType {
@@ -146,6 +159,12 @@ impl Address {
 
     // Parse an address from a string (human readable format)
     pub fn from_string(address: &String) -> Result<Self, Error> {
+        Ok(Self::parse(address)?)
+    }
+
+    // Parse an address from a string (human readable format)
+    // The checksum and the network prefix are verified
+    pub fn parse(address: &str) -> Result<Self, AddressError> {
         let (hrp, decoded) = decode(address)?;
         // check that hrp is valid one
         if hrp != PREFIX_ADDRESS && hrp != TESTNET_PREFIX_ADDRESS {
@@ -154,17 +173,6 @@ impl Address {
 
         let bits = convert_bits(&decoded, 5, 8, false)?;
         let addr = Address::decompress(&bits, hrp.as_str())?;
-
-        // now check that the hrp decoded is the one for the network state
-        if (addr.is_mainnet() && hrp != PREFIX_ADDRESS) || (!addr.is_mainnet() && hrp != TESTNET_PREFIX_ADDRESS) {
-            let expected = if addr.is_mainnet() {
-                PREFIX_ADDRESS
-            } else {
-                TESTNET_PREFIX_ADDRESS
-            };
-            return Err(Bech32Error::InvalidPrefix(hrp, expected.to_owned()).into())
-        }
-
         Ok(addr)
     }
 }
@@ -252,9 +260,13 @@ impl Display for Address {
 
 #[cfg(test)]
 mod tests {
-    use crate::crypto::KeyPair;
+    use crate::{
+        api::{DataElement, DataValue},
+        config::{PREFIX_ADDRESS, TESTNET_PREFIX_ADDRESS},
+        crypto::KeyPair
+    };
 
-    use super::{Address, AddressType};
+    use super::{Address, AddressError, AddressType, PublicKey};
 
     #[test]
     fn test_serde() {
@@ -264,4 +276,40 @@ mod tests {
         let addr2: Address = Address::from_string(&v).unwrap();
         assert_eq!(addr, addr2);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_public_key_address() {
+        let (key, _) = KeyPair::new().split();
+        let key = key.compress();
+
+        for mainnet in [true, false] {
+            let address = key.to_address_string(mainnet);
+            assert!(address.starts_with(if mainnet { PREFIX_ADDRESS } else { TESTNET_PREFIX_ADDRESS }));
+            assert_eq!(PublicKey::from_address(&address, mainnet).unwrap(), key);
+
+            // Wrong network
+            assert!(matches!(PublicKey::from_address(&address, !mainnet), Err(AddressError::InvalidNetwork(n)) if n != mainnet));
+        }
+    }
+
+    #[test]
+    fn test_public_key_address_invalid() {
+        let (key, _) = KeyPair::new().split();
+        let address = key.compress().to_address_string(true);
+
+        // Last character changed breaks the checksum
+        let mut corrupted = address.clone();
+        let last = corrupted.pop().unwrap();
+        corrupted.push(if last == 'q' { 'p' } else { 'q' });
+        assert!(matches!(PublicKey::from_address(&corrupted, true), Err(AddressError::Bech32(_))));
+
+        // Unknown prefix
+        let other = address.replacen(PREFIX_ADDRESS, "abc", 1);
+        assert!(matches!(PublicKey::from_address(&other, true), Err(AddressError::Bech32(_))));
+
+        // Integrated address
+        let integrated = Address::new(true, AddressType::Data(DataElement::Value(DataValue::U8(1))), key.compress());
+        let integrated = integrated.to_string();
+        assert!(matches!(PublicKey::from_address(&integrated, true), Err(AddressError::IntegratedAddress)));
+    }
+}
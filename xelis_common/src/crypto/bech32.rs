@@ -153,7 +153,7 @@ pub fn encode(mut hrp: String, data: &[u8]) -> Result<String, Bech32Error> {
     Ok(string)
 }
 
-pub fn decode(bech: &String) -> Result<(String, Vec<u8>), Bech32Error> {
+pub fn decode(bech: &str) -> Result<(String, Vec<u8>), Bech32Error> {
     if bech.to_uppercase() != *bech && bech.to_lowercase() != *bech {
         return Err(Bech32Error::HrpMixCase)
     }
@@ -1,8 +1,8 @@
 use curve25519_dalek::{ristretto::CompressedRistretto, Scalar};
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
-use crate::{api::DataElement, crypto::{Address, AddressType}, serializer::{Reader, ReaderError, Serializer, Writer}};
-use super::{Ciphertext, DecryptHandle, PedersenCommitment, PublicKey};
+use crate::{api::DataElement, crypto::{Address, AddressError, AddressType, Hash}, serializer::{Reader, ReaderError, Serializer, Writer}};
+use super::{Ciphertext, DecryptHandle, PedersenCommitment, PublicKey, Signature};
 
 // Compressed point size in bytes
 pub const RISTRETTO_COMPRESSED_SIZE: usize = 32;
@@ -67,6 +67,12 @@ impl CompressedCommitment {
     pub fn decompress(&self) -> Result<PedersenCommitment, DecompressionError> {
         self.0.decompress().map(PedersenCommitment::from_point).ok_or(DecompressionError)
     }
+
+    // Homomorphic sum: the result commits to the sum of both amounts and openings
+    // Fails if any of the two is not a valid point
+    pub fn add(&self, other: &CompressedCommitment) -> Result<CompressedCommitment, DecompressionError> {
+        Ok((self.decompress()? + other.decompress()?).compress())
+    }
 }
 
 impl SerializableCompressedPoint for CompressedCommitment {
@@ -159,11 +165,39 @@ impl CompressedPublicKey {
         self.0.decompress().map(PublicKey::from_point).ok_or(DecompressionError)
     }
 
+    // Verify a signature over the hash with this key
+    // A key that is not a valid point can't have signed anything
+    pub fn verify_signature(&self, hash: &Hash, signature: &Signature) -> bool {
+        match self.decompress() {
+            Ok(key) => signature.verify(hash.as_bytes(), &key),
+            Err(_) => false
+        }
+    }
+
     // Clone the key to convert it to an address
     pub fn as_address(&self, mainnet: bool) -> Address {
         self.clone().to_address(mainnet)
     }
 
+    // Encode the key as a normal bech32 address for the network
+    pub fn to_address_string(&self, mainnet: bool) -> String {
+        self.as_address(mainnet).to_string()
+    }
+
+    // Parse a key from a normal address of the expected network
+    pub fn from_address(address: &str, mainnet: bool) -> Result<Self, AddressError> {
+        let address = Address::parse(address)?;
+        if address.is_mainnet() != mainnet {
+            return Err(AddressError::InvalidNetwork(mainnet))
+        }
+
+        if !address.is_normal() {
+            return Err(AddressError::IntegratedAddress)
+        }
+
+        Ok(address.to_public_key())
+    }
+
     // Convert it to an address
     pub fn to_address(self, mainnet: bool) -> Address {
         Address::new(mainnet, AddressType::Normal, self)
@@ -238,7 +272,9 @@ impl Serializer for Scalar {
 
 #[cfg(test)]
 mod tests {
+    use crate::crypto::hash;
     use super::*;
+    use super::super::{KeyPair, PedersenOpening};
 
     #[test]
     fn test_compressed_ciphertext_zero() {
@@ -248,4 +284,43 @@ mod tests {
 
         assert_eq!(ciphertext, decompressed);
     }
+
+    #[test]
+    fn test_compressed_commitment_add() {
+        let a = PedersenOpening::generate_new();
+        let b = PedersenOpening::generate_new();
+        let left = PedersenCommitment::new_with_opening(10u64, &a).compress();
+        let right = PedersenCommitment::new_with_opening(32u64, &b).compress();
+
+        let sum = left.add(&right).unwrap();
+        let opening = PedersenOpening::from_scalar(a.as_scalar() + b.as_scalar());
+        assert_eq!(sum, PedersenCommitment::new_with_opening(42u64, &opening).compress());
+        assert_eq!(sum, right.add(&left).unwrap());
+
+        let invalid = CompressedCommitment::new(CompressedRistretto([0xFF; 32]));
+        assert!(left.add(&invalid).is_err());
+        assert!(invalid.add(&left).is_err());
+    }
+
+    #[test]
+    fn test_compressed_public_key_verify_signature() {
+        let keypair = KeyPair::new();
+        let key = keypair.get_public_key().compress();
+        let message = hash(b"message");
+        let signature = keypair.sign(message.as_bytes());
+        assert!(key.verify_signature(&message, &signature));
+
+        // Tampered message
+        let tampered = hash(b"messagf");
+        assert!(!key.verify_signature(&tampered, &signature));
+
+        // Another key
+        let other = KeyPair::new().get_public_key().compress();
+        assert!(!other.verify_signature(&message, &signature));
+
+        // Not a valid point
+        let malformed = CompressedPublicKey::new(CompressedRistretto([0xFF; 32]));
+        assert!(malformed.decompress().is_err());
+        assert!(!malformed.verify_signature(&message, &signature));
+    }
 }
\ No newline at end of file
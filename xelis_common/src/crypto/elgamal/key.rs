@@ -3,7 +3,8 @@ use curve25519_dalek::{
     ristretto::RistrettoPoint,
     Scalar
 };
-use rand::rngs::OsRng;
+use rand::{rngs::OsRng, CryptoRng, RngCore};
+use sha3::{Digest, Sha3_512};
 use zeroize::Zeroize;
 use crate::{
     api::DataElement,
@@ -25,9 +26,14 @@ use super::{
     H
 };
 
+// Domain separator used when deriving a private key from a seed
+const SEED_DOMAIN: &[u8] = b"xelis_keypair_from_seed";
+
 #[derive(Clone)]
 pub struct PublicKey(RistrettoPoint);
 
+// The secret scalar is wiped from memory when dropped
+// Each clone owns its own copy and is wiped the same way
 #[derive(Clone, Zeroize)]
 pub struct PrivateKey(Scalar);
 
@@ -130,10 +136,34 @@ impl PrivateKey {
     }
 }
 
+impl Drop for PrivateKey {
+    fn drop(&mut self) {
+        self.zeroize();
+    }
+}
+
 impl KeyPair {
     // Generate a random new KeyPair
     pub fn new() -> Self {
-        let scalar = Scalar::random(&mut OsRng);
+        Self::generate(&mut OsRng)
+    }
+
+    // Generate a new KeyPair using the given random number generator
+    pub fn generate<R: RngCore + CryptoRng>(rng: &mut R) -> Self {
+        let scalar = Scalar::random(rng);
+        let private_key = PrivateKey::from_scalar(scalar);
+
+        Self::from_private_key(private_key)
+    }
+
+    // Derive a KeyPair deterministically from a 32 bytes seed
+    // The private key is Sha3_512(SEED_DOMAIN || seed) reduced modulo the group order
+    // This derivation must never change: wallets restored from a seed rely on it
+    pub fn from_seed(seed: &[u8; 32]) -> Self {
+        let mut hasher = Sha3_512::new();
+        hasher.update(SEED_DOMAIN);
+        hasher.update(seed);
+        let scalar = Scalar::from_hash(hasher);
         let private_key = PrivateKey::from_scalar(scalar);
 
         Self::from_private_key(private_key)
@@ -179,6 +209,11 @@ impl KeyPair {
         &self.public_key
     }
 
+    // Get the compressed public key of the KeyPair
+    pub fn public(&self) -> CompressedPublicKey {
+        self.public_key.compress()
+    }
+
     // Get the private key of the KeyPair
     pub fn get_private_key(&self) -> &PrivateKey {
         &self.private_key
@@ -199,10 +234,15 @@ impl Serializer for PrivateKey {
         let scalar = Scalar::read(reader)?;
         Ok(PrivateKey::from_scalar(scalar))
     }
+
+    fn size(&self) -> usize {
+        self.0.size()
+    }
 }
 
 #[cfg(test)]
 mod tests {
+    use rand::{rngs::StdRng, SeedableRng};
     use curve25519_dalek::traits::Identity;
 
     use super::*;
@@ -218,6 +258,57 @@ mod tests {
         assert!(signature.verify(message, public_key));
     }
 
+    #[test]
+    fn test_keypair_from_seed() {
+        let seed = [42u8; 32];
+        let keypair = KeyPair::from_seed(&seed);
+        assert_eq!(keypair.public(), KeyPair::from_seed(&seed).public());
+        assert_eq!(keypair.public(), keypair.get_public_key().compress());
+        assert_ne!(keypair.public(), KeyPair::from_seed(&[43u8; 32]).public());
+
+        // Pin the derivation so it can't change silently
+        assert_eq!(
+            hex::encode(KeyPair::from_seed(&[0u8; 32]).public().as_bytes()),
+            "e64d10a3fd6cbb766059c88d8a1d0917e68accce0b63c27abc6c950b157abd7c"
+        );
+
+        let message = b"Hello, world!";
+        let signature = keypair.sign(message);
+        assert!(signature.verify(message, keypair.get_public_key()));
+    }
+
+    #[test]
+    fn test_keypair_generate() {
+        let keypair = KeyPair::generate(&mut StdRng::seed_from_u64(1));
+        assert_eq!(keypair.public(), KeyPair::generate(&mut StdRng::seed_from_u64(1)).public());
+        assert_ne!(keypair.public(), KeyPair::generate(&mut StdRng::seed_from_u64(2)).public());
+    }
+
+    #[test]
+    fn test_private_key_zeroize() {
+        let keypair = KeyPair::new();
+        let mut private_key = keypair.get_private_key().clone();
+        assert_ne!(private_key.as_scalar(), &Scalar::ZERO);
+
+        // Drop runs the same zeroize
+        assert!(std::mem::needs_drop::<PrivateKey>());
+        private_key.zeroize();
+        assert_eq!(private_key.as_scalar(), &Scalar::ZERO);
+
+        // The original key is untouched by zeroizing its clone
+        assert_ne!(keypair.get_private_key().as_scalar(), &Scalar::ZERO);
+    }
+
+    #[test]
+    fn test_opening_zeroize() {
+        let mut opening = PedersenOpening::generate_new();
+        assert_ne!(opening.as_scalar(), Scalar::ZERO);
+
+        assert!(std::mem::needs_drop::<PedersenOpening>());
+        opening.zeroize();
+        assert_eq!(opening.as_scalar(), Scalar::ZERO);
+    }
+
     #[test]
     fn test_encrypt_decrypt() {
         let keypair = KeyPair::new();
@@ -2,9 +2,11 @@ use std::ops::{Add, AddAssign, Sub, SubAssign};
 
 use curve25519_dalek::{traits::MultiscalarMul, RistrettoPoint, Scalar};
 use rand::rngs::OsRng;
+use zeroize::Zeroize;
 use super::{key::PublicKey, CompressedCommitment, CompressedHandle, G, H};
 
-#[derive(Clone, Debug, PartialEq, Eq)]
+// The blinding scalar is wiped from memory when dropped
+#[derive(Clone, Debug, PartialEq, Eq, Zeroize)]
 pub struct PedersenOpening(Scalar);
 
 impl PedersenOpening {
@@ -21,6 +23,12 @@ impl PedersenOpening {
     }
 }
 
+impl Drop for PedersenOpening {
+    fn drop(&mut self) {
+        self.zeroize();
+    }
+}
+
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct PedersenCommitment(RistrettoPoint);
 
@@ -1,7 +1,10 @@
 use curve25519_dalek::{RistrettoPoint, Scalar};
 use serde::{de::Error, Serialize};
 use sha3::{Digest, Sha3_512};
-use crate::serializer::{Reader, ReaderError, Serializer, Writer};
+use crate::{
+    crypto::Hash,
+    serializer::{Reader, ReaderError, Serializer, Writer}
+};
 
 use super::{CompressedPublicKey, PublicKey, H, SCALAR_SIZE};
 
@@ -26,6 +29,24 @@ impl Signature {
     }
 }
 
+// Verify many signatures, each one over its hash with its key
+// A signature only carries its challenge and not its nonce point,
+// so there is no combined equation to check: this falls back to one verification per item
+// Returns the indices of the items that failed, if any
+pub fn verify_signatures_batch(items: &[(CompressedPublicKey, Hash, Signature)]) -> Result<(), Vec<usize>> {
+    let failed: Vec<usize> = items.iter()
+        .enumerate()
+        .filter(|(_, (key, hash, signature))| !key.verify_signature(hash, signature))
+        .map(|(i, _)| i)
+        .collect();
+
+    if failed.is_empty() {
+        Ok(())
+    } else {
+        Err(failed)
+    }
+}
+
 // Create a Scalar from Public Key, Hash of the message, and selected point
 pub fn hash_and_point_to_scalar(key: &CompressedPublicKey, message: &[u8], point: &RistrettoPoint) -> Scalar {
     let mut hasher = Sha3_512::new();
@@ -71,4 +92,40 @@ impl Serializer for Signature {
     fn size(&self) -> usize {
         SIGNATURE_SIZE
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use curve25519_dalek::ristretto::CompressedRistretto;
+    use crate::crypto::hash;
+    use super::*;
+    use super::super::KeyPair;
+
+    fn signed_items(count: usize) -> Vec<(CompressedPublicKey, Hash, Signature)> {
+        (0..count).map(|i| {
+            let keypair = KeyPair::new();
+            let hash = hash(&i.to_be_bytes());
+            let signature = keypair.sign(hash.as_bytes());
+            (keypair.get_public_key().compress(), hash, signature)
+        }).collect()
+    }
+
+    #[test]
+    fn test_verify_signatures_batch() {
+        let items = signed_items(8);
+        assert!(verify_signatures_batch(&items).is_ok());
+        assert!(verify_signatures_batch(&[]).is_ok());
+    }
+
+    #[test]
+    fn test_verify_signatures_batch_failures() {
+        let mut items = signed_items(8);
+        // Signature of another item
+        items[2].2 = items[3].2.clone();
+        assert_eq!(verify_signatures_batch(&items), Err(vec![2]));
+
+        // Invalid key
+        items[5].0 = CompressedPublicKey::new(CompressedRistretto([0xFF; 32]));
+        assert_eq!(verify_signatures_batch(&items), Err(vec![2, 5]));
+    }
+}
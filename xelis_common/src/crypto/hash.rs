@@ -1,10 +1,11 @@
 use crate::serializer::{Writer, Serializer, ReaderError, Reader};
 use std::{
     fmt::{Display, Error, Formatter},
-    convert::TryInto,
     hash::Hasher,
     borrow::Cow,
+    str::FromStr,
 };
+use thiserror::Error as ThisError;
 use serde::de::Error as SerdeError;
 use serde::{Deserialize, Serialize};
 use blake3::hash as blake3_hash;
@@ -20,6 +21,14 @@ pub use xelis_hash::{
 
 pub const HASH_SIZE: usize = 32; // 32 bytes / 256 bits
 
+#[derive(ThisError, Debug, Clone, PartialEq, Eq)]
+pub enum HashParseError {
+    #[error("Invalid hash length: got {} hex characters, expected {}", _0, HASH_SIZE * 2)]
+    InvalidLength(usize),
+    #[error("Invalid hex characters in hash")]
+    InvalidHex
+}
+
 #[derive(Eq, PartialEq, PartialOrd, Ord, Clone, Debug)]
 pub struct Hash([u8; HASH_SIZE]);
 
@@ -47,6 +56,17 @@ impl Hash {
     pub fn to_hex(&self) -> String {
         hex::encode(self.0)
     }
+
+    // Parse a hash from its 64 hex characters
+    pub fn from_hex(hex: &str) -> Result<Self, HashParseError> {
+        if hex.len() != HASH_SIZE * 2 {
+            return Err(HashParseError::InvalidLength(hex.len()))
+        }
+
+        let mut bytes = [0u8; HASH_SIZE];
+        hex::decode_to_slice(hex, &mut bytes).map_err(|_| HashParseError::InvalidHex)?;
+        Ok(Hash::new(bytes))
+    }
 }
 
 pub fn pow_hash(work: &[u8]) -> Result<Hash, XelisHashError> {
@@ -109,13 +129,15 @@ impl<'a> Deserialize<'a> for Hash {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
     where D: serde::Deserializer<'a> {
         let hex = String::deserialize(deserializer)?;
-        if hex.len() != HASH_SIZE * 2 {
-            return Err(SerdeError::custom("Invalid hex length"))
-        }
+        Hash::from_hex(&hex).map_err(SerdeError::custom)
+    }
+}
 
-        let decoded_hex = hex::decode(hex).map_err(SerdeError::custom)?;
-        let bytes: [u8; 32] = decoded_hex.try_into().map_err(|_| SerdeError::custom("Could not transform hex to bytes array for Hash"))?;
-        Ok(Hash::new(bytes))
+impl FromStr for Hash {
+    type Err = HashParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Hash::from_hex(s)
     }
 }
 
@@ -149,4 +171,40 @@ impl<'a> Into<Cow<'a, Hash>> for &'a Hash {
     fn into(self) -> Cow<'a, Hash> {
         Cow::Borrowed(self)
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hash_hex() {
+        let hash = hash(b"xelis");
+        let hex = hash.to_hex();
+        assert_eq!(hex.len(), HASH_SIZE * 2);
+        assert_eq!(hash.to_string(), hex);
+        assert_eq!(Hash::from_hex(&hex).unwrap(), hash);
+        assert_eq!(hex.parse::<Hash>().unwrap(), hash);
+        assert_eq!(hex.to_uppercase().parse::<Hash>().unwrap(), hash);
+
+        let json = serde_json::to_string(&hash).unwrap();
+        assert_eq!(serde_json::from_str::<Hash>(&json).unwrap(), hash);
+    }
+
+    #[test]
+    fn test_hash_hex_invalid() {
+        let hex = Hash::max().to_hex();
+        assert_eq!(Hash::from_hex(&hex[..62]), Err(HashParseError::InvalidLength(62)));
+        assert_eq!("".parse::<Hash>(), Err(HashParseError::InvalidLength(0)));
+        assert_eq!(format!("{}00", hex).parse::<Hash>(), Err(HashParseError::InvalidLength(66)));
+
+        let invalid = format!("zz{}", &hex[2..]);
+        assert_eq!(Hash::from_hex(&invalid), Err(HashParseError::InvalidHex));
+        assert_eq!(
+            HashParseError::InvalidLength(62).to_string(),
+            "Invalid hash length: got 62 hex characters, expected 64"
+        );
+
+        assert!(serde_json::from_str::<Hash>("\"00\"").is_err());
+    }
+}
@@ -9,7 +9,7 @@ use merlin::Transcript;
 use rand::rngs::OsRng;
 use thiserror::Error;
 use std::iter;
-use crate::{serializer::{Reader, ReaderError, Serializer, Writer}, transaction::MAX_TRANSFER_COUNT};
+use crate::{serializer::{Reader, ReaderError, Serializer, Writer}, transaction::MAX_RANGE_PROOF_COMMITMENTS};
 
 use super::{
     elgamal::{
@@ -31,11 +31,21 @@ use zeroize::Zeroize;
 
 // We are using 64 bits for the bulletproofs
 pub const BULLET_PROOF_SIZE: usize = 64;
+// Serialized size of a CiphertextValidityProof: two points and two scalars
+pub const CIPHERTEXT_VALIDITY_PROOF_SIZE: usize = RISTRETTO_COMPRESSED_SIZE * 2 + SCALAR_SIZE * 2;
+
+// Serialized size of a range proof aggregating the given count of commitments,
+// including its u16 length prefix: 4 points and 3 scalars,
+// then the inner product proof with 2 scalars and 2 * log2(n) points,
+// n being the bits proven for all the commitments padded to a power of two
+pub fn range_proof_size(commitments: usize) -> usize {
+    let lg_n = (BULLET_PROOF_SIZE * commitments).next_power_of_two().trailing_zeros() as usize;
+    2 + RISTRETTO_COMPRESSED_SIZE * 4 + SCALAR_SIZE * 5 + RISTRETTO_COMPRESSED_SIZE * 2 * lg_n
+}
 
 lazy_static! {
-    // Bulletproof generators: party size is max transfers * 2 + 1
-    // * 2 in case each transfer use a unique asset + 1 for xelis asset as fee and + 1 to be a power of 2
-    pub static ref BP_GENS: BulletproofGens = BulletproofGens::new(BULLET_PROOF_SIZE, MAX_TRANSFER_COUNT * 2 + 2);
+    // Bulletproof generators: party size is the maximum of commitments proven in a transaction
+    pub static ref BP_GENS: BulletproofGens = BulletproofGens::new(BULLET_PROOF_SIZE, MAX_RANGE_PROOF_COMMITMENTS);
     pub static ref PC_GENS: PedersenGens = PedersenGens::default();
 }
 
@@ -173,6 +183,23 @@ impl CommitmentEqProof {
         }
     }
 
+    // Append the proof to the transcript and extract its challenges (c, w)
+    // It doesn't verify anything, but lets the transcript reach the same state as pre_verify
+    // without the source balance, which is required to check the proof itself
+    pub fn append_to_transcript(&self, transcript: &mut Transcript) -> Result<(Scalar, Scalar), ProofVerificationError> {
+        transcript.equality_proof_domain_separator();
+
+        // include Y_0, Y_1, Y_2 to transcript and extract challenges
+        transcript.validate_and_append_point(b"Y_0", &self.Y_0)?;
+        transcript.validate_and_append_point(b"Y_1", &self.Y_1)?;
+        transcript.validate_and_append_point(b"Y_2", &self.Y_2)?;
+
+        let c = transcript.challenge_scalar(b"c");
+        let w = transcript.challenge_scalar(b"w"); // w used for batch verification
+
+        Ok((c, w))
+    }
+
     pub fn pre_verify(
         &self,
         source_pubkey: &PublicKey,
@@ -181,21 +208,13 @@ impl CommitmentEqProof {
         transcript: &mut Transcript,
         batch_collector: &mut BatchCollector,
     ) -> Result<(), ProofVerificationError> {
-        transcript.equality_proof_domain_separator();
-
         // extract the relevant scalar and Ristretto points from the inputs
         let P_source = source_pubkey.as_point();
         let C_source = source_ciphertext.commitment().as_point();
         let D_source = source_ciphertext.handle().as_point();
         let C_destination = destination_commitment.as_point();
 
-        // include Y_0, Y_1, Y_2 to transcript and extract challenges
-        transcript.validate_and_append_point(b"Y_0", &self.Y_0)?;
-        transcript.validate_and_append_point(b"Y_1", &self.Y_1)?;
-        transcript.validate_and_append_point(b"Y_2", &self.Y_2)?;
-
-        let c = transcript.challenge_scalar(b"c");
-        let w = transcript.challenge_scalar(b"w"); // w used for batch verification
+        let (c, w) = self.append_to_transcript(transcript)?;
         let ww = &w * &w;
 
         let w_negated = -&w;
@@ -353,6 +372,20 @@ impl CiphertextValidityProof {
 
         Ok(())
     }
+
+    // Verify this proof alone, without batching it with others
+    pub fn verify(
+        &self,
+        commitment: &PedersenCommitment,
+        dest_pubkey: &PublicKey,
+        dest_handle: &DecryptHandle,
+        transcript: &mut Transcript,
+    ) -> Result<(), ProofVerificationError> {
+        let mut batch_collector = BatchCollector::default();
+        self.pre_verify(commitment, dest_pubkey, dest_handle, transcript, &mut batch_collector)?;
+        batch_collector.verify()
+            .map_err(|_| ProofVerificationError::CiphertextValidityProof)
+    }
 }
 
 #[allow(non_snake_case)]
@@ -401,7 +434,7 @@ impl Serializer for CiphertextValidityProof {
     }
 
     fn size(&self) -> usize {
-        RISTRETTO_COMPRESSED_SIZE * 2 + SCALAR_SIZE * 2
+        CIPHERTEXT_VALIDITY_PROOF_SIZE
     }
 }
 
@@ -428,8 +461,8 @@ impl Serializer for RangeProof {
             return Err(ReaderError::InvalidSize);
         }
 
-        // Maximum size of a RangeProof is 2 * MAX_TRANSFER_COUNT * RISTRETTO_COMPRESSED_SIZE
-        let max_size_possible = min_size + (MAX_TRANSFER_COUNT * 2).next_power_of_two() * RISTRETTO_COMPRESSED_SIZE;
+        // Maximum size of a RangeProof is MAX_RANGE_PROOF_COMMITMENTS * RISTRETTO_COMPRESSED_SIZE
+        let max_size_possible = min_size + MAX_RANGE_PROOF_COMMITMENTS * RISTRETTO_COMPRESSED_SIZE;
         if len > max_size_possible {
             return Err(ReaderError::InvalidSize);
         }
@@ -437,12 +470,31 @@ impl Serializer for RangeProof {
         let bytes = reader.read_bytes_ref(len)?;
         RangeProof::from_bytes(&bytes).map_err(|_| ReaderError::InvalidValue)
     }
+
+    // The proof doesn't expose its aggregation size, so a standalone proof is serialized to measure it
+    // Transactions know their commitments count and use range_proof_size instead
+    fn size(&self) -> usize {
+        2 + self.to_bytes().len()
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_range_proof_size() {
+        for commitments in [1, 2, 8] {
+            let amounts = vec![42u64; commitments];
+            let blindings: Vec<Scalar> = (0..commitments).map(|_| Scalar::random(&mut OsRng)).collect();
+            let (proof, _) = RangeProof::prove_multiple(&BP_GENS, &PC_GENS, &mut Transcript::new(b"test"), &amounts, &blindings, BULLET_PROOF_SIZE).unwrap();
+
+            assert_eq!(range_proof_size(commitments), proof.to_bytes().len() + 2);
+            assert_eq!(range_proof_size(commitments), Serializer::to_bytes(&proof).len());
+            assert_eq!(proof.size(), Serializer::to_bytes(&proof).len());
+        }
+    }
+
     #[test]
     fn test_commitment_eq_proof() {
         let mut transcript = Transcript::new(b"test");
@@ -512,4 +564,57 @@ mod tests {
         assert!(result.is_ok());
         assert!(batch_collector.verify().is_ok());
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_ciphertext_validity_proof_corrupted() {
+        let keypair = KeyPair::new();
+        let amount = 5u64;
+        let opening = PedersenOpening::generate_new();
+        let commitment = PedersenCommitment::new_with_opening(amount, &opening);
+        let receiver_handle = keypair.get_public_key().decrypt_handle(&opening);
+
+        let proof = CiphertextValidityProof::new(keypair.get_public_key(), amount, &opening, &mut Transcript::new(b"test"));
+        assert!(proof.verify(&commitment, keypair.get_public_key(), &receiver_handle, &mut Transcript::new(b"test")).is_ok());
+
+        // Commitment doesn't match the proven amount
+        let other = PedersenCommitment::new_with_opening(amount + 1, &opening);
+        assert!(proof.verify(&other, keypair.get_public_key(), &receiver_handle, &mut Transcript::new(b"test")).is_err());
+
+        // Corrupt the lowest bit of the last scalar, it stays canonical
+        let mut bytes = proof.to_bytes();
+        let last_scalar = bytes.len() - 32;
+        bytes[last_scalar] ^= 1;
+        let corrupted = CiphertextValidityProof::from_bytes(&bytes).unwrap();
+        assert!(corrupted.verify(&commitment, keypair.get_public_key(), &receiver_handle, &mut Transcript::new(b"test")).is_err());
+    }
+
+    #[test]
+    fn test_range_proof_amounts() {
+        let amounts = [100u64, 5];
+        let openings: Vec<_> = amounts.iter().map(|_| PedersenOpening::generate_new()).collect();
+        let blindings: Vec<_> = openings.iter().map(PedersenOpening::as_scalar).collect();
+        let commitments: Vec<_> = amounts.iter()
+            .zip(&openings)
+            .map(|(amount, opening)| *PedersenCommitment::new_with_opening(*amount, opening).compress().as_point())
+            .collect();
+
+        let (proof, proven) = RangeProof::prove_multiple(&BP_GENS, &PC_GENS, &mut Transcript::new(b"test"), &amounts, &blindings, BULLET_PROOF_SIZE).unwrap();
+        assert_eq!(proven, commitments);
+
+        // Proof survives a serialization round trip
+        let bytes = Serializer::to_bytes(&proof);
+        assert_eq!(Serializer::size(&proof), bytes.len());
+        let proof: RangeProof = Serializer::from_bytes(&bytes).unwrap();
+        assert!(proof.verify_multiple(&BP_GENS, &PC_GENS, &mut Transcript::new(b"test"), &commitments, BULLET_PROOF_SIZE).is_ok());
+
+        // A "negative" amount is a scalar outside of [0, 2^64) and must be rejected
+        let negative = PedersenCommitment::new_with_opening(Scalar::ZERO - Scalar::from(5u64), &openings[1]);
+        let crafted = [commitments[0], *negative.compress().as_point()];
+        assert!(proof.verify_multiple(&BP_GENS, &PC_GENS, &mut Transcript::new(b"test"), &crafted, BULLET_PROOF_SIZE).is_err());
+
+        // Same for an amount just above the u64 range
+        let overflow = PedersenCommitment::new_with_opening(Scalar::from(u64::MAX) + Scalar::ONE, &openings[1]);
+        let crafted = [commitments[0], *overflow.compress().as_point()];
+        assert!(proof.verify_multiple(&BP_GENS, &PC_GENS, &mut Transcript::new(b"test"), &crafted, BULLET_PROOF_SIZE).is_err());
+    }
+}
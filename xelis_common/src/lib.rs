@@ -1,3 +1,6 @@
+// Allow the derive macros to refer to this crate as xelis_common from inside it
+extern crate self as xelis_common;
+
 pub mod crypto;
 pub mod serializer;
 pub mod transaction;
@@ -2,7 +2,6 @@ use std::collections::HashMap;
 
 use thiserror::Error;
 use crate::crypto::Hash;
-use crate::serializer::Serializer;
 
 #[derive(Error, Debug)]
 pub enum ArgError {
@@ -71,7 +70,7 @@ impl ArgType {
             ArgType::Bool => ArgValue::Bool(value.parse().map_err(|_| ArgError::InvalidType)?),
             ArgType::Number => ArgValue::Number(value.parse().map_err(|_| ArgError::InvalidType)?),
             ArgType::String => ArgValue::String(value.to_owned()),
-            ArgType::Hash => ArgValue::Hash(Hash::from_hex(value.to_string()).map_err(|_| ArgError::InvalidType)?),
+            ArgType::Hash => ArgValue::Hash(Hash::from_hex(value).map_err(|_| ArgError::InvalidType)?),
             ArgType::Array(value_type) => {
                 let values = value.split(",");
                 let mut array: Vec<ArgValue> = Vec::new();
@@ -3,7 +3,7 @@ pub mod argument;
 
 use crate::{
     crypto::Hash,
-    serializer::{Serializer, ReaderError},
+    serializer::ReaderError,
 };
 use std::{
     collections::VecDeque,
@@ -643,7 +643,7 @@ impl Prompt {
 
     pub async fn read_hash<S: ToString>(&self, prompt: S) -> Result<Hash, PromptError> {
         let hash_hex = self.read_input(prompt, false).await?;
-        Ok(Hash::from_hex(hash_hex)?)
+        Hash::from_hex(&hash_hex).map_err(|e| PromptError::ParseInputError(e.to_string()))
     }
 
     pub async fn cancel_read_input(&self) -> Result<(), Error> {
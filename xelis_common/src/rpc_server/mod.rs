@@ -9,27 +9,12 @@ pub use rpc_handler::{RPCHandler, Handler};
 pub use rpc_handler::parse_params;
 
 use actix_web::{HttpResponse, web::{self, Data, Payload}, Responder, HttpRequest};
-use serde::{Deserialize, Serialize};
+use serde::Serialize;
 use serde_json::Value;
 
 use self::websocket::{WebSocketServerShared, WebSocketHandler};
 
-pub const JSON_RPC_VERSION: &str = "2.0";
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-#[serde(untagged)]
-pub enum Id {
-    String(String),
-    Number(usize),
-}
-
-#[derive(Clone, Serialize, Deserialize)]
-pub struct RpcRequest {
-    pub jsonrpc: String,
-    pub id: Option<Id>,
-    pub method: String,
-    pub params: Option<Value>
-}
+pub use crate::api::rpc::{Id, RpcRequest, JSON_RPC_VERSION};
 
 #[derive(Serialize)]
 pub struct RpcResponse<'a> {
@@ -271,33 +271,16 @@ impl<T: Serializer> Serializer for Option<T> {
 
 impl<T: Serializer> Serializer for Vec<T> {
     fn read(reader: &mut Reader) -> Result<Self, ReaderError> {
-        let count = reader.read_u16()?;
-        if count > MAX_ITEMS as u16 {
-            warn!("Received {} while maximum is set to {}", count, MAX_ITEMS);
-            return Err(ReaderError::InvalidSize)
-        }
-
-        let mut values = Vec::with_capacity(count as usize);
-        for _ in 0..count {
-            values.push(T::read(reader)?);
-        }
-
-        Ok(values)
+        reader.read_vec(MAX_ITEMS)
     }
 
     fn write(&self, writer: &mut Writer) {
-        writer.write_u16(self.len() as u16);
-        for el in self {
-            el.write(writer);
-        }
+        writer.write_vec(self);
     }
 
     fn size(&self) -> usize {
-        match self.first() {
-            Some(first) => 2 + self.len() * first.size(),
-            // If the vector is empty, we still need to write the size (u16)
-            None => 2
-        }
+        // 2 for the u16 count, items may have different sizes
+        2 + self.iter().map(Serializer::size).sum::<usize>()
     }
 }
 
@@ -336,7 +319,7 @@ impl Serializer for bool {
 impl<K: Serializer + Eq + StdHash, V: Serializer + Eq + StdHash> Serializer for HashMap<K, V> {
     fn read(reader: &mut Reader) -> Result<Self, ReaderError> {
         let size = reader.read_u16()?;
-        let mut map = HashMap::with_capacity(size as usize);
+        let mut map = HashMap::with_capacity((size as usize).min(reader.size()));
         for _ in 0..size {
             let k = K::read(reader)?;
             let v = V::read(reader)?;
@@ -477,7 +460,7 @@ impl<A: Serializer, B: Serializer, C: Serializer> Serializer for (A, B, C) {
 impl<K: Serializer + std::hash::Hash + Eq, V: Serializer> Serializer for IndexMap<K, V> {
     fn read(reader: &mut Reader) -> Result<Self, ReaderError> {
         let size = reader.read_u16()?;
-        let mut map = IndexMap::with_capacity(size as usize);
+        let mut map = IndexMap::with_capacity((size as usize).min(reader.size()));
         for _ in 0..size {
             let k = K::read(reader)?;
             let v = V::read(reader)?;
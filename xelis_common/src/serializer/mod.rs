@@ -2,23 +2,33 @@ mod defaults;
 mod reader;
 mod writer;
 
-pub use reader::{Reader, ReaderError};
+#[cfg(test)]
+mod tests;
+
+pub use reader::{Reader, ReaderError, ReaderErrorAt};
 pub use writer::Writer;
+pub use xelis_derive::Serializer;
 use std::marker::Sized;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use crate::crypto::hash;
+
+// Size of the checksum appended by Serializer::to_bytes_with_checksum
+pub const CHECKSUM_SIZE: usize = 4;
 
 pub trait Serializer {
     fn write(&self, writer: &mut Writer);
 
+    // The buffer is allocated once using size(), so it must be exact
     fn to_bytes(&self) -> Vec<u8> {
-        let mut writer = Writer::new();
+        let size = self.size();
+        let mut writer = Writer::with_capacity(size);
         self.write(&mut writer);
+        debug_assert_eq!(writer.total_write(), size, "size() doesn't match the written bytes");
         writer.bytes()
     }
 
     fn to_hex(&self) -> String {
-        let mut writer = Writer::new();
-        self.write(&mut writer);
-        hex::encode(writer.bytes())
+        hex::encode(self.to_bytes())
     }
 
     fn size(&self) -> usize {
@@ -41,9 +51,61 @@ pub trait Serializer {
         }
     }
 
+    // Encoded using the URL-safe alphabet without padding
+    fn to_base64(&self) -> String {
+        URL_SAFE_NO_PAD.encode(self.to_bytes())
+    }
+
+    // Decode from the URL-safe alphabet without padding, as written by to_base64
+    fn from_base64(base64: &str) -> Result<Self, ReaderError>
+    where Self: Sized {
+        match URL_SAFE_NO_PAD.decode(base64) {
+            Ok(bytes) => Self::from_bytes(&bytes),
+            Err(_) => Err(ReaderError::InvalidBase64)
+        }
+    }
+
     fn from_bytes(bytes: &[u8]) -> Result<Self, ReaderError>
     where Self: Sized {
         let mut reader = Reader::new(bytes);
         Self::read(&mut reader)
     }
+
+    // Serialize the value followed by a checksum of its bytes
+    // The checksum is the first CHECKSUM_SIZE bytes of their hash
+    fn to_bytes_with_checksum(&self) -> Vec<u8> {
+        let mut bytes = self.to_bytes();
+        let checksum = hash(&bytes);
+        bytes.extend(&checksum.as_bytes()[..CHECKSUM_SIZE]);
+        bytes
+    }
+
+    // Read a value written by to_bytes_with_checksum
+    // A corrupted value is rejected with ReaderError::InvalidValue
+    fn from_bytes_with_checksum(bytes: &[u8]) -> Result<Self, ReaderError>
+    where Self: Sized {
+        if bytes.len() < CHECKSUM_SIZE {
+            return Err(ReaderError::InvalidSize)
+        }
+
+        let (bytes, checksum) = bytes.split_at(bytes.len() - CHECKSUM_SIZE);
+        if hash(bytes).as_bytes()[..CHECKSUM_SIZE] != *checksum {
+            return Err(ReaderError::InvalidValue)
+        }
+
+        Self::from_bytes_exact(bytes)
+    }
+
+    // Same as from_bytes but all the bytes must be consumed
+    // Trailing bytes are rejected with ReaderError::InvalidValue
+    fn from_bytes_exact(bytes: &[u8]) -> Result<Self, ReaderError>
+    where Self: Sized {
+        let mut reader = Reader::new(bytes);
+        let value = Self::read(&mut reader)?;
+        if reader.size() != 0 {
+            return Err(ReaderError::InvalidValue)
+        }
+
+        Ok(value)
+    }
 }
\ No newline at end of file
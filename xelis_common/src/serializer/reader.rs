@@ -1,4 +1,9 @@
-use std::array::TryFromSliceError;
+use std::{
+    array::TryFromSliceError,
+    borrow::Cow,
+    io::{self, Read}
+};
+use log::warn;
 use thiserror::Error;
 
 use crate::crypto::Hash;
@@ -13,69 +18,235 @@ pub enum ReaderError {
     InvalidValue,
     #[error("Invalid hex")]
     InvalidHex,
+    #[error("Invalid base64")]
+    InvalidBase64,
     #[error("Error on try into")]
     ErrorTryInto,
     #[error(transparent)]
     TryFromSliceError(#[from] TryFromSliceError),
     #[error(transparent)]
+    Io(#[from] io::Error),
+    #[error(transparent)]
     Any(anyhow::Error)
 }
 
+// Error returned by Reader::read_at along with the offset at which the read failed
+#[derive(Error, Debug)]
+#[error("{kind} at offset {offset}")]
+pub struct ReaderErrorAt {
+    kind: ReaderError,
+    offset: usize
+}
+
+impl ReaderErrorAt {
+    pub fn kind(&self) -> &ReaderError {
+        &self.kind
+    }
+
+    pub fn into_kind(self) -> ReaderError {
+        self.kind
+    }
+
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+}
+
+impl From<ReaderErrorAt> for ReaderError {
+    fn from(error: ReaderErrorAt) -> Self {
+        error.kind
+    }
+}
+
 // Reader help us to read safely from bytes
 // Mostly used when de-serializing an object from Serializer trait 
+// It can also pull the bytes from a stream, see Reader::from_stream
 pub struct Reader<'a> {
-    bytes: &'a[u8], // bytes to read
-    total: usize // total read bytes
+    bytes: Cow<'a, [u8]>, // bytes to read
+    total: usize, // total read bytes
+    dropped: usize, // consumed bytes removed from the buffer, only for a stream
+    limit: Option<usize>, // total read bytes can't go above it, see read_bounded
+    error_offset: Option<usize>, // offset of the last failed read
+    source: Option<&'a mut (dyn Read + Send)> // stream to pull the missing bytes from
 }
 
 impl<'a> Reader<'a> {
     pub fn new(bytes: &'a [u8]) -> Self {
         Reader {
-            bytes,
-            total: 0
+            bytes: Cow::Borrowed(bytes),
+            total: 0,
+            dropped: 0,
+            limit: None,
+            error_offset: None,
+            source: None
+        }
+    }
+
+    // Create a reader pulling its bytes from a stream only when they are needed
+    // Nothing is read ahead, so wrap the source in a BufReader for performance
+    // Consumed bytes are dropped from memory, size() only counts the bytes pulled but not read yet
+    pub fn from_stream(source: &'a mut (dyn Read + Send)) -> Self {
+        Reader {
+            bytes: Cow::Owned(Vec::new()),
+            total: 0,
+            dropped: 0,
+            limit: None,
+            error_offset: None,
+            source: Some(source)
         }
     }
 
+    // Index in the buffer of the next byte to read
+    fn position(&self) -> usize {
+        self.total - self.dropped
+    }
+
+    // Try to have n bytes available, pulling them from the stream if needed
+    // Returns how many bytes are available, which may be less than n
+    fn fill(&mut self, n: usize) -> Result<usize, ReaderError> {
+        let available = self.bytes.len() - self.position();
+        if n <= available {
+            return Ok(available)
+        }
+
+        let Some(source) = self.source.as_mut() else {
+            return Ok(available)
+        };
+
+        // Nothing can borrow the consumed bytes anymore, don't keep them
+        let position = self.total - self.dropped;
+        if position > 0 {
+            self.bytes.to_mut().drain(..position);
+            self.dropped += position;
+        }
+
+        // read_to_end grows the buffer with the bytes received,
+        // so a huge n can't trigger a huge allocation
+        let missing = (n - available) as u64;
+        let read = (&mut **source).take(missing).read_to_end(self.bytes.to_mut())?;
+        Ok(available + read)
+    }
+
+    // Make sure the next n bytes can be read
+    fn ensure(&mut self, n: usize) -> Result<(), ReaderError> {
+        if self.limit.is_some_and(|limit| n > limit - self.total) {
+            return Err(self.error(ReaderError::InvalidSize, self.total))
+        }
+
+        let available = self.fill(n).map_err(|e| self.error(e, self.total))?;
+        if available < n {
+            return Err(self.error(ReaderError::InvalidSize, self.total))
+        }
+
+        Ok(())
+    }
+
+    // Remember where the read failed, see error_offset
+    fn error(&mut self, error: ReaderError, offset: usize) -> ReaderError {
+        self.error_offset = Some(offset);
+        error
+    }
+
     pub fn read<T: Serializer>(&mut self) -> Result<T, ReaderError> {
         T::read(self)
     }
 
+    // Same as read but the error carries the offset at which the read failed
+    // An error not raised by the reader itself points at where it stopped
+    pub fn read_at<T: Serializer>(&mut self) -> Result<T, ReaderErrorAt> {
+        self.error_offset = None;
+        T::read(self).map_err(|kind| ReaderErrorAt {
+            offset: self.error_offset.unwrap_or(self.total),
+            kind
+        })
+    }
+
     pub fn read_bool(&mut self) -> Result<bool, ReaderError> {
         let byte = self.read_u8()?;
         match byte {
             0 => Ok(false),
             1 => Ok(true),
-            _ => Err(ReaderError::InvalidValue)
+            _ => Err(self.error(ReaderError::InvalidValue, self.total - 1))
         }
     }
 
+    // Size is checked against the remaining bytes before any allocation
     pub fn read_bytes<T>(&mut self, n: usize) -> Result<T, ReaderError>
     where T: for<'b> TryFrom<&'b [u8]> {
-        if n > self.size() {
-            return Err(ReaderError::InvalidSize)
-        }
+        self.ensure(n)?;
 
-        let result = match self.bytes[self.total..self.total+n].try_into() {
+        let result = match self.bytes[self.position()..self.position()+n].try_into() {
             Ok(v) => {
                 Ok(v)
             },
             Err(_) => Err(ReaderError::ErrorTryInto)
         };
+        let result = result.map_err(|e| self.error(e, self.total));
 
         self.total += n;
         result
     }
 
     pub fn read_bytes_ref(&mut self, n: usize) -> Result<&[u8], ReaderError> {
-        if n > self.size() {
-            return Err(ReaderError::InvalidSize)
-        }
+        self.ensure(n)?;
 
-        let bytes = &self.bytes[self.total..self.total+n];
+        let bytes = &self.bytes[self.position()..self.position()+n];
         self.total += n;
         Ok(bytes)
     }
 
+    // Read the next n bytes as a slice of the original buffer, nothing is copied
+    // A reader pulling from a stream has no buffer to borrow from, so it returns an error
+    pub fn read_bytes_borrowed(&mut self, n: usize) -> Result<&'a [u8], ReaderError> {
+        let Cow::Borrowed(bytes) = self.bytes else {
+            return Err(self.error(ReaderError::InvalidValue, self.total))
+        };
+        self.ensure(n)?;
+
+        let bytes = &bytes[self.total..self.total+n];
+        self.total += n;
+        Ok(bytes)
+    }
+
+    // Same as read_var_bytes but borrowing the bytes, see read_bytes_borrowed
+    pub fn read_var_bytes_borrowed(&mut self, max: usize) -> Result<&'a [u8], ReaderError> {
+        let len = self.read_u16()? as usize;
+        if len > max {
+            return Err(self.error(ReaderError::InvalidSize, self.total - 2))
+        }
+
+        self.read_bytes_borrowed(len)
+    }
+
+    // Read bytes prefixed by their length as a u16
+    // The length can't be above the max set by the caller
+    pub fn read_var_bytes(&mut self, max: usize) -> Result<Vec<u8>, ReaderError> {
+        let len = self.read_u16()? as usize;
+        if len > max {
+            return Err(self.error(ReaderError::InvalidSize, self.total - 2))
+        }
+
+        self.read_bytes(len)
+    }
+
+    // Read values prefixed by their count as a u16
+    // The count can't be above the max set by the caller
+    pub fn read_vec<T: Serializer>(&mut self, max: usize) -> Result<Vec<T>, ReaderError> {
+        let count = self.read_u16()? as usize;
+        if count > max {
+            warn!("Received {} while maximum is set to {}", count, max);
+            return Err(self.error(ReaderError::InvalidSize, self.total - 2))
+        }
+
+        // Don't trust the count to pre-allocate, each item takes at least a byte
+        let mut values = Vec::with_capacity(count.min(self.size()));
+        for _ in 0..count {
+            values.push(T::read(self)?);
+        }
+
+        Ok(values)
+    }
+
     pub fn read_bytes_32(&mut self) -> Result<[u8; 32], ReaderError> {
         self.read_bytes(32)
     }
@@ -89,10 +260,8 @@ impl<'a> Reader<'a> {
     }
 
     pub fn read_u8(&mut self) -> Result<u8, ReaderError> {
-        if self.size() == 0 {
-            return Err(ReaderError::InvalidSize)
-        }
-        let byte: u8 = self.bytes[self.total];
+        self.ensure(1)?;
+        let byte: u8 = self.bytes[self.position()];
         self.total += 1;
         Ok(byte)
     }
@@ -113,11 +282,37 @@ impl<'a> Reader<'a> {
         Ok(u128::from_be_bytes(self.read_bytes(16)?))
     }
 
+    // Read a LEB128 encoded u64 written by Writer::write_varint
+    // Only the canonical (shortest) encoding is accepted to prevent malleability
+    pub fn read_varint(&mut self) -> Result<u64, ReaderError> {
+        let offset = self.total;
+        let mut value: u64 = 0;
+        let mut shift = 0;
+        loop {
+            let byte = self.read_u8()?;
+            let bits = (byte & 0x7F) as u64;
+            // 10th byte can only hold the last bit of a u64 and must end the encoding
+            if shift == 63 && (byte & 0x80 != 0 || bits > 1) {
+                return Err(self.error(ReaderError::InvalidValue, offset))
+            }
+
+            value |= bits << shift;
+            if byte & 0x80 == 0 {
+                // A trailing zero byte means a shorter encoding exists
+                if byte == 0 && shift != 0 {
+                    return Err(self.error(ReaderError::InvalidValue, offset))
+                }
+                return Ok(value)
+            }
+            shift += 7;
+        }
+    }
+
     pub fn read_string_with_size(&mut self, size: usize) -> Result<String, ReaderError> {
         let bytes: Vec<u8> = self.read_bytes(size)?;
         match String::from_utf8(bytes) {
             Ok(v) => Ok(v),
-            Err(_) => Err(ReaderError::InvalidValue)
+            Err(_) => Err(self.error(ReaderError::InvalidValue, self.total - size))
         }
     }
 
@@ -142,15 +337,54 @@ impl<'a> Reader<'a> {
         Ok(Some(byte))
     }
 
+    // Read a value that can't consume more than max_size bytes
+    // The bound is checked on each read, so nothing is pulled ahead from a stream
+    pub fn read_bounded<T: Serializer>(&mut self, max_size: usize) -> Result<T, ReaderError> {
+        let previous = self.limit;
+        let limit = self.total.saturating_add(max_size);
+        self.limit = Some(previous.map_or(limit, |previous| previous.min(limit)));
+        let result = T::read(self);
+        self.limit = previous;
+        result
+    }
+
+    // Skip the next n bytes
+    pub fn skip(&mut self, n: usize) -> Result<(), ReaderError> {
+        self.ensure(n)?;
+
+        self.total += n;
+        Ok(())
+    }
+
     pub fn total_size(&self) -> usize {
-        self.bytes.len()
+        self.dropped + self.bytes.len()
     }
 
     pub fn size(&self) -> usize {
-        self.bytes.len() - self.total
+        let size = self.bytes.len() - self.position();
+        match self.limit {
+            Some(limit) => size.min(limit - self.total),
+            None => size
+        }
+    }
+
+    // Bytes kept in memory, consumed ones included
+    #[cfg(test)]
+    pub(super) fn buffered(&self) -> usize {
+        self.bytes.len()
+    }
+
+    // Bytes left to read
+    pub fn remaining(&self) -> usize {
+        self.size()
     }
 
     pub fn total_read(&self) -> usize {
         self.total
     }
+
+    // Offset at which the last failed read started, if any
+    pub fn error_offset(&self) -> Option<usize> {
+        self.error_offset
+    }
 }
@@ -0,0 +1,542 @@
+use crate::crypto::Hash;
+
+use super::{Reader, ReaderError, Serializer, Writer, CHECKSUM_SIZE};
+
+fn varint_bytes(value: u64) -> Vec<u8> {
+    let mut writer = Writer::new();
+    writer.write_varint(value);
+    writer.bytes()
+}
+
+#[test]
+fn test_varint_boundaries() {
+    let cases: [(u64, usize); 6] = [
+        (0, 1),
+        (127, 1),
+        (128, 2),
+        (16_383, 2),
+        (16_384, 3),
+        (u64::MAX, 10)
+    ];
+
+    for (value, len) in cases {
+        let bytes = varint_bytes(value);
+        assert_eq!(bytes.len(), len, "value {}", value);
+        assert_eq!(Writer::varint_size(value), len, "value {}", value);
+
+        let mut reader = Reader::new(&bytes);
+        assert_eq!(reader.read_varint().unwrap(), value);
+        assert_eq!(reader.size(), 0);
+    }
+
+    assert_eq!(varint_bytes(0), [0x00]);
+    assert_eq!(varint_bytes(127), [0x7F]);
+    assert_eq!(varint_bytes(128), [0x80, 0x01]);
+
+    // u64::MAX is the only value using the last bit of the 10th byte
+    let mut max = vec![0xFF; 9];
+    max.push(0x01);
+    assert_eq!(varint_bytes(u64::MAX), max);
+    assert_eq!(Reader::new(&max).read_varint().unwrap(), u64::MAX);
+}
+
+#[test]
+fn test_varint_non_canonical() {
+    // 0 encoded on two bytes
+    let mut reader = Reader::new(&[0x80, 0x00]);
+    assert!(matches!(reader.read_varint(), Err(ReaderError::InvalidValue)));
+
+    // 127 padded with a trailing zero byte
+    let mut reader = Reader::new(&[0xFF, 0x80, 0x00]);
+    assert!(matches!(reader.read_varint(), Err(ReaderError::InvalidValue)));
+
+    // Overflowing a u64 on the 10th byte
+    let mut bytes = vec![0xFF; 9];
+    bytes.push(0x02);
+    let mut reader = Reader::new(&bytes);
+    assert!(matches!(reader.read_varint(), Err(ReaderError::InvalidValue)));
+
+    // Continuation bit set on the 10th byte
+    let mut bytes = vec![0xFF; 9];
+    bytes.extend([0x81, 0x00]);
+    let mut reader = Reader::new(&bytes);
+    assert!(matches!(reader.read_varint(), Err(ReaderError::InvalidValue)));
+
+    let mut bytes = vec![0xFF; 9];
+    bytes.extend([0x81, 0x01]);
+    let mut reader = Reader::new(&bytes);
+    assert!(matches!(reader.read_varint(), Err(ReaderError::InvalidValue)));
+
+    // 11 bytes
+    let bytes = vec![0xFF; 11];
+    let mut reader = Reader::new(&bytes);
+    assert!(matches!(reader.read_varint(), Err(ReaderError::InvalidValue)));
+
+    let mut bytes = vec![0x80; 10];
+    bytes.push(0x01);
+    let mut reader = Reader::new(&bytes);
+    assert!(matches!(reader.read_varint(), Err(ReaderError::InvalidValue)));
+
+    // Truncated input
+    let mut reader = Reader::new(&[0x80]);
+    assert!(matches!(reader.read_varint(), Err(ReaderError::InvalidSize)));
+}
+
+#[test]
+fn test_var_bytes() {
+    let max = 64;
+    for len in [0, max] {
+        let bytes = vec![0xAB; len];
+        let mut writer = Writer::new();
+        writer.write_var_bytes(&bytes);
+        assert_eq!(writer.total_write(), 2 + len);
+
+        let data = writer.bytes();
+        let mut reader = Reader::new(&data);
+        assert_eq!(reader.read_var_bytes(max).unwrap(), bytes);
+        assert_eq!(reader.size(), 0);
+    }
+
+    let mut writer = Writer::new();
+    writer.write_var_bytes(&[0xAB; 65]);
+    let data = writer.bytes();
+    let mut reader = Reader::new(&data);
+    assert!(matches!(reader.read_var_bytes(max), Err(ReaderError::InvalidSize)));
+
+    // Length prefix above the available bytes
+    let mut reader = Reader::new(&[0x00, 0x04, 0x01]);
+    assert!(matches!(reader.read_var_bytes(max), Err(ReaderError::InvalidSize)));
+}
+
+#[test]
+fn test_var_bytes_borrowed() {
+    let mut writer = Writer::new();
+    writer.write_var_bytes(b"hello");
+    writer.write_u8(0x01);
+    let data = writer.bytes();
+
+    let mut reader = Reader::new(&data);
+    let bytes = reader.read_var_bytes_borrowed(5).unwrap();
+    assert_eq!(bytes, b"hello");
+    // Points into the original buffer
+    assert_eq!(bytes.as_ptr(), data[2..].as_ptr());
+    assert_eq!(reader.read_u8().unwrap(), 0x01);
+
+    let mut reader = Reader::new(&data);
+    assert!(matches!(reader.read_var_bytes_borrowed(4), Err(ReaderError::InvalidSize)));
+    let mut reader = Reader::new(&data[..4]);
+    assert!(matches!(reader.read_var_bytes_borrowed(5), Err(ReaderError::InvalidSize)));
+
+    // A stream has no buffer to borrow from
+    let mut cursor = std::io::Cursor::new(data.clone());
+    let mut reader = Reader::from_stream(&mut cursor);
+    assert!(matches!(reader.read_var_bytes_borrowed(5), Err(ReaderError::InvalidValue)));
+}
+
+#[test]
+fn test_reader_past_end() {
+    let bytes = [0x01, 0x02, 0x03];
+    let mut reader = Reader::new(&bytes);
+    assert_eq!(reader.remaining(), 3);
+    assert_eq!(reader.total_read(), 0);
+
+    assert_eq!(reader.read_u16().unwrap(), 0x0102);
+    assert_eq!(reader.remaining(), 1);
+    assert_eq!(reader.total_read(), 2);
+
+    // Failed reads don't consume anything
+    assert!(matches!(reader.read_u16(), Err(ReaderError::InvalidSize)));
+    assert!(matches!(reader.read_u32(), Err(ReaderError::InvalidSize)));
+    assert!(matches!(reader.read_u64(), Err(ReaderError::InvalidSize)));
+    assert!(matches!(reader.read_u128(), Err(ReaderError::InvalidSize)));
+    assert!(matches!(reader.read_hash(), Err(ReaderError::InvalidSize)));
+    assert!(matches!(reader.read_bytes_ref(2), Err(ReaderError::InvalidSize)));
+    assert!(matches!(reader.skip(2), Err(ReaderError::InvalidSize)));
+    assert_eq!(reader.remaining(), 1);
+
+    assert_eq!(reader.read_u8().unwrap(), 0x03);
+    assert_eq!(reader.remaining(), 0);
+    assert_eq!(reader.total_read(), 3);
+
+    assert!(matches!(reader.read_u8(), Err(ReaderError::InvalidSize)));
+    assert!(matches!(reader.read_bool(), Err(ReaderError::InvalidSize)));
+    assert!(matches!(reader.read_string(), Err(ReaderError::InvalidSize)));
+    assert!(reader.read_bytes::<Vec<u8>>(1).is_err());
+
+    // Empty reads are still allowed at the end
+    assert!(reader.read_bytes_ref(0).unwrap().is_empty());
+    assert!(reader.read_bounded::<Option<u8>>(10).is_err());
+}
+
+#[test]
+fn test_vec_max_count() {
+    let values: Vec<u64> = (0..5).collect();
+    let mut writer = Writer::new();
+    writer.write_vec(&values);
+    assert_eq!(writer.total_write(), 2 + 5 * 8);
+
+    let data = writer.bytes();
+    let mut reader = Reader::new(&data);
+    assert_eq!(reader.read_vec::<u64>(5).unwrap(), values);
+
+    let mut reader = Reader::new(&data);
+    assert!(matches!(reader.read_vec::<u64>(4), Err(ReaderError::InvalidSize)));
+
+    // Count above the available items
+    let mut reader = Reader::new(&[0x00, 0x02, 0x01]);
+    assert!(matches!(reader.read_vec::<u8>(10), Err(ReaderError::InvalidSize)));
+}
+
+fn option_round_trip<T: Serializer + PartialEq + std::fmt::Debug>(value: Option<T>) {
+    let bytes = value.to_bytes();
+    assert_eq!(bytes.len(), value.size());
+    assert_eq!(bytes[0], value.is_some() as u8);
+    assert_eq!(Option::<T>::from_bytes(&bytes).unwrap(), value);
+}
+
+#[test]
+fn test_option_serializer() {
+    option_round_trip(Some(42u64));
+    option_round_trip::<u64>(None);
+    option_round_trip(Some(String::from("hello")));
+    option_round_trip::<String>(None);
+    option_round_trip(Some(Hash::max()));
+    option_round_trip::<Hash>(None);
+    option_round_trip(Some(vec![1u8, 2, 3]));
+
+    // Presence flag must be a valid bool
+    assert!(matches!(Option::<u8>::from_bytes(&[2, 0]), Err(ReaderError::InvalidValue)));
+    // Missing inner value
+    assert!(matches!(Option::<u64>::from_bytes(&[1, 0]), Err(ReaderError::InvalidSize)));
+}
+
+#[test]
+fn test_byte_array_serializer() {
+    let mut value = [0u8; 32];
+    for (i, byte) in value.iter_mut().enumerate() {
+        *byte = i as u8;
+    }
+
+    let bytes = value.to_bytes();
+    assert_eq!(bytes.len(), 32);
+    assert_eq!(value.size(), 32);
+    assert_eq!(<[u8; 32]>::from_bytes(&bytes).unwrap(), value);
+
+    // One byte short
+    let mut reader = Reader::new(&bytes[..31]);
+    assert!(matches!(<[u8; 32]>::read(&mut reader), Err(ReaderError::InvalidSize)));
+    assert_eq!(reader.remaining(), 31);
+}
+
+#[test]
+fn test_u128_boundaries() {
+    for value in [0u128, 1, u64::MAX as u128 + 1, u128::MAX] {
+        let mut writer = Writer::new();
+        writer.write_u128(&value);
+        assert_eq!(writer.total_write(), 16);
+        assert_eq!(writer.as_bytes(), value.to_be_bytes());
+        assert_eq!(value.size(), 16);
+
+        let data = writer.bytes();
+        let mut reader = Reader::new(&data);
+        assert_eq!(reader.read_u128().unwrap(), value);
+        assert_eq!(u128::from_bytes(&data).unwrap(), value);
+    }
+}
+
+#[test]
+fn test_read_bool_strict() {
+    let mut reader = Reader::new(&[0, 1, 2, 0xFF]);
+    assert!(!reader.read_bool().unwrap());
+    assert!(reader.read_bool().unwrap());
+    assert!(matches!(reader.read_bool(), Err(ReaderError::InvalidValue)));
+    assert!(matches!(reader.read_bool(), Err(ReaderError::InvalidValue)));
+}
+
+#[test]
+fn test_huge_declared_length() {
+    let bytes = [0xFF, 0xFF, 0x01, 0x02];
+
+    let mut reader = Reader::new(&bytes);
+    assert!(matches!(reader.read_bytes::<Vec<u8>>(usize::MAX), Err(ReaderError::InvalidSize)));
+    assert!(matches!(reader.read_bytes_ref(usize::MAX), Err(ReaderError::InvalidSize)));
+    assert_eq!(reader.remaining(), 4);
+
+    // u16::MAX declared while only 2 bytes follow
+    let mut reader = Reader::new(&bytes);
+    assert!(matches!(reader.read_var_bytes(usize::MAX), Err(ReaderError::InvalidSize)));
+
+    let mut reader = Reader::new(&bytes);
+    assert!(matches!(reader.read_vec::<u64>(usize::MAX), Err(ReaderError::InvalidSize)));
+
+    assert!(matches!(std::collections::HashMap::<u8, u8>::from_bytes(&bytes), Err(ReaderError::InvalidSize)));
+}
+
+#[test]
+fn test_from_bytes_exact() {
+    let bytes = 42u64.to_bytes();
+    assert_eq!(u64::from_bytes_exact(&bytes).unwrap(), 42);
+
+    let mut surplus = bytes.clone();
+    surplus.push(0);
+    assert_eq!(u64::from_bytes(&surplus).unwrap(), 42);
+    assert!(matches!(u64::from_bytes_exact(&surplus), Err(ReaderError::InvalidValue)));
+
+    let value = String::from("hello");
+    let bytes = value.to_bytes();
+    assert_eq!(String::from_bytes_exact(&bytes).unwrap(), value);
+
+    let mut surplus = bytes.clone();
+    surplus.extend([1, 2]);
+    assert!(matches!(String::from_bytes_exact(&surplus), Err(ReaderError::InvalidValue)));
+
+    // Errors from the inner read are kept
+    assert!(matches!(u64::from_bytes_exact(&[0; 4]), Err(ReaderError::InvalidSize)));
+}
+
+#[derive(Serializer, Debug, PartialEq)]
+struct Derived {
+    id: u64,
+    name: String,
+    memo: Option<Vec<u8>>,
+    hashes: Vec<Hash>,
+    // Cache, never serialized
+    #[serializer(skip)]
+    cached: Option<Hash>
+}
+
+#[derive(Serializer, Debug, PartialEq)]
+struct DerivedTuple<T>(u8, T);
+
+#[derive(Serializer, Debug, PartialEq)]
+struct DerivedUnit;
+
+#[test]
+fn test_derive_serializer() {
+    let value = Derived {
+        id: 42,
+        name: String::from("xelis"),
+        memo: Some(vec![1, 2, 3]),
+        hashes: vec![Hash::zero(), Hash::max()],
+        cached: Some(Hash::max())
+    };
+
+    let bytes = value.to_bytes();
+    let expected = 8 + (1 + 5) + (1 + 2 + 3) + (2 + 2 * 32);
+    assert_eq!(bytes.len(), expected);
+    assert_eq!(value.size(), expected);
+
+    let read = Derived::from_bytes_exact(&bytes).unwrap();
+    assert!(read.cached.is_none());
+    assert_eq!(read, Derived { cached: None, ..value });
+
+    // Fields are written in their declaration order
+    let mut writer = Writer::new();
+    writer.write_u8(7);
+    writer.write_u64(&9);
+    let tuple = DerivedTuple(7u8, 9u64);
+    assert_eq!(tuple.to_bytes(), writer.bytes());
+    assert_eq!(DerivedTuple::<u64>::from_bytes_exact(&tuple.to_bytes()).unwrap(), tuple);
+
+    assert_eq!(DerivedUnit.size(), 0);
+    assert!(DerivedUnit.to_bytes().is_empty());
+    assert_eq!(DerivedUnit::from_bytes_exact(&[]).unwrap(), DerivedUnit);
+
+    assert!(matches!(Derived::from_bytes(&bytes[..10]), Err(ReaderError::InvalidSize)));
+}
+
+#[test]
+fn test_reader_from_stream() {
+    let mut writer = Writer::new();
+    writer.write_u16(0x0102);
+    writer.write_var_bytes(b"hello");
+    writer.write_u64(&u64::MAX);
+    let bytes = writer.bytes();
+
+    let mut cursor = std::io::Cursor::new(bytes.clone());
+    let mut reader = Reader::from_stream(&mut cursor);
+    // Nothing is pulled before being needed
+    assert_eq!(reader.remaining(), 0);
+    assert_eq!(reader.read_u16().unwrap(), 0x0102);
+    assert_eq!(reader.remaining(), 0);
+    assert_eq!(reader.read_var_bytes(5).unwrap(), b"hello");
+    assert_eq!(reader.read_bytes_ref(8).unwrap(), u64::MAX.to_be_bytes());
+    assert_eq!(reader.total_read(), bytes.len());
+
+    // Huge declared length over a short stream
+    let mut cursor = std::io::Cursor::new(vec![0xFF, 0xFF, 0x01]);
+    let mut reader = Reader::from_stream(&mut cursor);
+    assert!(matches!(reader.read_var_bytes(usize::MAX), Err(ReaderError::InvalidSize)));
+}
+
+// Endless stream of the same byte, handing out a single byte per read
+struct EndlessStream;
+
+impl std::io::Read for EndlessStream {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0)
+        }
+
+        buf[0] = 0xAB;
+        Ok(1)
+    }
+}
+
+#[test]
+fn test_reader_stream_drops_consumed_bytes() {
+    let mut stream = EndlessStream;
+    let mut reader = Reader::from_stream(&mut stream);
+    for _ in 0..1000 {
+        assert_eq!(reader.read_u64().unwrap(), u64::from_be_bytes([0xAB; 8]));
+        assert!(reader.buffered() <= 8);
+    }
+    assert_eq!(reader.total_read(), 8000);
+    assert_eq!(reader.total_size(), 8000);
+
+    // A bounded read stops at the bound instead of waiting for the end of the stream
+    assert!(matches!(reader.read_bounded::<String>(100), Err(ReaderError::InvalidSize)));
+    assert_eq!(reader.read_bounded::<u16>(2).unwrap(), 0xABAB);
+    assert_eq!(reader.total_read(), 8003);
+}
+
+// Sink accepting a limited amount of bytes
+struct LimitedSink {
+    bytes: Vec<u8>,
+    limit: usize
+}
+
+impl std::io::Write for LimitedSink {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        if self.bytes.len() >= self.limit {
+            return Err(std::io::Error::new(std::io::ErrorKind::WriteZero, "sink is full"))
+        }
+
+        let n = buf.len().min(self.limit - self.bytes.len());
+        self.bytes.extend(&buf[..n]);
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn test_writer_to_stream() {
+    let mut buffered = Writer::new();
+    buffered.write_u16(0x0102);
+    buffered.write_string(&String::from("xelis"));
+    buffered.write_varint(300);
+
+    let mut output = Vec::new();
+    let mut writer = Writer::from_stream(&mut output);
+    writer.write_u16(0x0102);
+    writer.write_string(&String::from("xelis"));
+    writer.write_varint(300);
+    assert_eq!(writer.total_write(), buffered.total_write());
+    writer.flush().unwrap();
+    assert_eq!(output, buffered.bytes());
+
+    // Sink errors are reported on flush
+    let mut sink = LimitedSink {
+        bytes: Vec::new(),
+        limit: 4
+    };
+    let mut writer = Writer::from_stream(&mut sink);
+    writer.write_u64(&u64::MAX);
+    writer.write_u8(1);
+    assert!(writer.flush().is_err());
+    // Already reported
+    assert!(writer.flush().is_ok());
+    assert_eq!(sink.bytes.len(), 4);
+}
+
+#[test]
+fn test_reader_error_display() {
+    let slice_error = <[u8; 2]>::try_from(&[0u8; 3][..]).unwrap_err();
+    let errors = [
+        (ReaderError::InvalidSize, "Invalid size"),
+        (ReaderError::InvalidValue, "Invalid value"),
+        (ReaderError::InvalidHex, "Invalid hex"),
+        (ReaderError::InvalidBase64, "Invalid base64"),
+        (ReaderError::ErrorTryInto, "Error on try into"),
+        (ReaderError::TryFromSliceError(slice_error), "could not convert slice to array"),
+        (ReaderError::Io(std::io::Error::new(std::io::ErrorKind::UnexpectedEof, "stream closed")), "stream closed"),
+        (ReaderError::Any(anyhow::anyhow!("custom error")), "custom error")
+    ];
+
+    for (error, message) in errors {
+        assert_eq!(error.to_string(), message);
+
+        // Usable as a std error without any mapping
+        let boxed: Box<dyn std::error::Error> = Box::new(error);
+        assert_eq!(boxed.to_string(), message);
+    }
+
+    let result: anyhow::Result<u64> = (|| Ok(u64::from_bytes(&[0; 2])?))();
+    assert_eq!(result.unwrap_err().to_string(), "Invalid size");
+}
+
+#[test]
+fn test_reader_error_offset() {
+    let mut reader = Reader::new(&[0x00, 0x01, 0x02, 0x03]);
+    reader.read_u16().unwrap();
+    let error = reader.read_at::<u32>().unwrap_err();
+    assert_eq!(error.offset(), 2);
+    assert!(matches!(error.kind(), ReaderError::InvalidSize));
+    assert_eq!(error.to_string(), "Invalid size at offset 2");
+    assert!(matches!(ReaderError::from(error), ReaderError::InvalidSize));
+
+    // Points at the invalid byte
+    let mut reader = Reader::new(&[0x01, 0x00, 0x02]);
+    reader.read_u16().unwrap();
+    assert!(matches!(reader.read_bool(), Err(ReaderError::InvalidValue)));
+    assert_eq!(reader.error_offset(), Some(2));
+
+    // Points at the length prefix
+    let mut reader = Reader::new(&[0x00, 0x00, 0x05, 0x01]);
+    assert_eq!(reader.error_offset(), None);
+    reader.read_u8().unwrap();
+    assert!(matches!(reader.read_var_bytes(4), Err(ReaderError::InvalidSize)));
+    assert_eq!(reader.error_offset(), Some(1));
+}
+
+#[test]
+fn test_checksum() {
+    let value = Derived {
+        id: 7,
+        name: String::from("checksum"),
+        memo: None,
+        hashes: vec![Hash::max()],
+        cached: None
+    };
+
+    let bytes = value.to_bytes_with_checksum();
+    assert_eq!(bytes.len(), value.size() + CHECKSUM_SIZE);
+    assert_eq!(Derived::from_bytes_with_checksum(&bytes).unwrap(), value);
+
+    // Any single flipped bit is detected
+    for i in 0..bytes.len() * 8 {
+        let mut corrupted = bytes.clone();
+        corrupted[i / 8] ^= 1 << (i % 8);
+        assert!(matches!(Derived::from_bytes_with_checksum(&corrupted), Err(ReaderError::InvalidValue)));
+    }
+
+    assert!(matches!(u64::from_bytes_with_checksum(&[0; 3]), Err(ReaderError::InvalidSize)));
+}
+
+#[test]
+fn test_vec_variable_item_size() {
+    let values: Vec<Vec<u8>> = vec![Vec::new(), vec![1, 2, 3], vec![4]];
+    let mut writer = Writer::new();
+    writer.write_vec(&values);
+    let bytes = writer.bytes();
+    // Each item has its own u16 count
+    assert_eq!(bytes.len(), 2 + (2 + 0) + (2 + 3) + (2 + 1));
+    assert_eq!(bytes.len(), values.size());
+
+    let mut reader = Reader::new(&bytes);
+    assert_eq!(reader.read_vec::<Vec<u8>>(3).unwrap(), values);
+    assert_eq!(reader.size(), 0);
+}
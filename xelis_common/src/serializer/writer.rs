@@ -1,50 +1,136 @@
+use std::io::{self, Write};
+
 use crate::crypto::Hash;
 
-pub struct Writer {
-    bytes: Vec<u8>
+use super::Serializer;
+
+// Writer help us to build the bytes of an object
+// Fixed-size integers are written in big endian
+// It can also stream the bytes to a sink, see Writer::from_stream
+pub struct Writer<'a> {
+    bytes: Vec<u8>, // bytes written when there is no sink
+    total: usize, // total written bytes
+    sink: Option<&'a mut (dyn Write + Send)>, // stream receiving the bytes
+    error: Option<io::Error> // first error returned by the sink
 }
 
-impl Writer {
+impl<'a> Writer<'a> {
     pub fn new() -> Self {
         Self {
-            bytes: Vec::new()
+            bytes: Vec::new(),
+            total: 0,
+            sink: None,
+            error: None
+        }
+    }
+
+    // Create a writer able to hold capacity bytes without reallocating
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            bytes: Vec::with_capacity(capacity),
+            total: 0,
+            sink: None,
+            error: None
+        }
+    }
+
+    // Create a writer sending its bytes directly to the sink
+    // Nothing is kept in memory, so as_bytes and bytes are empty
+    // Once the sink fails, next writes are ignored and flush returns the error
+    pub fn from_stream(sink: &'a mut (dyn Write + Send)) -> Self {
+        Self {
+            bytes: Vec::new(),
+            total: 0,
+            sink: Some(sink),
+            error: None
+        }
+    }
+
+    // Flush the sink and return the first error it produced, if any
+    pub fn flush(&mut self) -> io::Result<()> {
+        if let Some(e) = self.error.take() {
+            return Err(e)
+        }
+
+        match self.sink.as_mut() {
+            Some(sink) => sink.flush(),
+            None => Ok(())
         }
     }
 
     pub fn write_bytes(&mut self, bytes: &[u8]) {
-        self.bytes.extend(bytes);
+        self.total += bytes.len();
+        match self.sink.as_mut() {
+            Some(sink) => if self.error.is_none() {
+                if let Err(e) = sink.write_all(bytes) {
+                    self.error = Some(e);
+                }
+            },
+            None => self.bytes.extend(bytes)
+        }
+    }
+
+    // Write the bytes prefixed by their length as a u16
+    // Read back using Reader::read_var_bytes
+    pub fn write_var_bytes(&mut self, bytes: &[u8]) {
+        self.write_u16(bytes.len() as u16);
+        self.write_bytes(bytes);
+    }
+
+    // Write the values prefixed by their count as a u16
+    // Read back using Reader::read_vec
+    pub fn write_vec<T: Serializer>(&mut self, values: &[T]) {
+        self.write_u16(values.len() as u16);
+        for value in values {
+            value.write(self);
+        }
     }
 
     pub fn write_hash(&mut self, hash: &Hash) {
-        self.bytes.extend(hash.as_bytes())
+        self.write_bytes(hash.as_bytes());
     }
 
     pub fn write_bool(&mut self, value: bool) {
-        self.bytes.push(if value { 1 } else { 0 });
+        self.write_u8(if value { 1 } else { 0 });
     }
     pub fn write_u8(&mut self, value: u8) {
-        self.bytes.push(value);
+        self.write_bytes(&[value]);
     }
 
     pub fn write_u16(&mut self, value: u16) {
-        self.bytes.extend(value.to_be_bytes());
+        self.write_bytes(&value.to_be_bytes());
     }
 
     pub fn write_u32(&mut self, value: &u32) {
-        self.bytes.extend(value.to_be_bytes());
+        self.write_bytes(&value.to_be_bytes());
     }
 
     pub fn write_u64(&mut self, value: &u64) {
-        self.bytes.extend(value.to_be_bytes());
+        self.write_bytes(&value.to_be_bytes());
     }
 
     pub fn write_u128(&mut self, value: &u128) {
-        self.bytes.extend(value.to_be_bytes());
+        self.write_bytes(&value.to_be_bytes());
+    }
+
+    // Write a u64 using LEB128: 7 bits per byte, high bit set while more bytes follow
+    // Small values such as nonces or fees only take one or two bytes
+    pub fn write_varint(&mut self, mut value: u64) {
+        while value >= 0x80 {
+            self.write_u8((value as u8) | 0x80);
+            value >>= 7;
+        }
+        self.write_u8(value as u8);
+    }
+
+    // Bytes used by write_varint for this value
+    pub fn varint_size(value: u64) -> usize {
+        (u64::BITS - value.leading_zeros()).max(1).div_ceil(7) as usize
     }
 
     pub fn write_string(&mut self, value: &String) {
-        self.bytes.push(value.len() as u8);
-        self.bytes.extend(value.as_bytes());
+        self.write_u8(value.len() as u8);
+        self.write_bytes(value.as_bytes());
     }
 
     pub fn write_optional_string(&mut self, opt: &Option<String>) {
@@ -53,7 +139,7 @@ impl Writer {
                 self.write_string(v);
             },
             None => {
-                self.bytes.push(0);
+                self.write_u8(0);
             }
         };
     }
@@ -61,16 +147,16 @@ impl Writer {
     pub fn write_optional_non_zero_u8(&mut self, opt: Option<u8>) {
         match opt {
             Some(v) if v != 0 => {
-                self.bytes.push(v);
+                self.write_u8(v);
             },
             _ => {
-                self.bytes.push(0);
+                self.write_u8(0);
             }
         };
     }
 
     pub fn total_write(&self) -> usize {
-        self.bytes.len()
+        self.total
     }
 
     pub fn as_bytes(&self) -> &[u8] {
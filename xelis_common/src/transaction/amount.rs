@@ -0,0 +1,124 @@
+use thiserror::Error;
+
+// Fees and burned amounts are stored in atomic units
+// Those helpers convert them from/to their decimal representation
+// using integer arithmetic only, so no precision is lost
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum AmountError {
+    #[error("Amount is empty")]
+    Empty,
+    #[error("Invalid character '{}' in amount", _0)]
+    InvalidCharacter(char),
+    #[error("Amount has more than {} decimals", _0)]
+    TooManyDecimals(u8),
+    #[error("Amount is too big")]
+    Overflow
+}
+
+// Format an atomic amount using the requested decimals count
+// Trailing zeros of the decimal part are removed: 150000000 with 8 decimals is "1.5"
+pub fn format_amount(atomic: u64, decimals: u8) -> String {
+    let decimals = decimals as usize;
+    if decimals == 0 {
+        return atomic.to_string()
+    }
+
+    // Left pad so there is always at least one digit in the integer part
+    let digits = format!("{:0>1$}", atomic, decimals + 1);
+    let (integer, fraction) = digits.split_at(digits.len() - decimals);
+    let fraction = fraction.trim_end_matches('0');
+    if fraction.is_empty() {
+        integer.to_owned()
+    } else {
+        format!("{}.{}", integer, fraction)
+    }
+}
+
+// Parse a decimal amount into atomic units
+// Amounts are never rounded: decimals that can't be represented are rejected,
+// except trailing zeros such as "1.500000000" with 8 decimals
+pub fn parse_amount(s: &str, decimals: u8) -> Result<u64, AmountError> {
+    let (integer, fraction) = match s.split_once('.') {
+        Some((integer, fraction)) => (integer, fraction),
+        None => (s, "")
+    };
+
+    if integer.is_empty() && fraction.is_empty() {
+        return Err(AmountError::Empty)
+    }
+
+    let fraction = fraction.trim_end_matches('0');
+    if fraction.len() > decimals as usize {
+        // Report the first invalid character rather than the decimals count
+        if let Some(c) = fraction.chars().find(|c| !c.is_ascii_digit()) {
+            return Err(AmountError::InvalidCharacter(c))
+        }
+        return Err(AmountError::TooManyDecimals(decimals))
+    }
+
+    let padding = "0".repeat(decimals as usize - fraction.len());
+    integer.chars()
+        .chain(fraction.chars())
+        .chain(padding.chars())
+        .try_fold(0u64, |value, c| {
+            let digit = c.to_digit(10).ok_or(AmountError::InvalidCharacter(c))?;
+            value.checked_mul(10)
+                .and_then(|value| value.checked_add(digit as u64))
+                .ok_or(AmountError::Overflow)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::{COIN_DECIMALS, COIN_VALUE};
+    use super::*;
+
+    #[test]
+    fn test_format_amount() {
+        assert_eq!(format_amount(0, COIN_DECIMALS), "0");
+        assert_eq!(format_amount(1, COIN_DECIMALS), "0.00000001");
+        assert_eq!(format_amount(COIN_VALUE, COIN_DECIMALS), "1");
+        assert_eq!(format_amount(123 * COIN_VALUE + 45_600_000, COIN_DECIMALS), "123.456");
+        assert_eq!(format_amount(150_000_000, COIN_DECIMALS), "1.5");
+        assert_eq!(format_amount(u64::MAX, COIN_DECIMALS), "184467440737.09551615");
+        assert_eq!(format_amount(u64::MAX, 0), u64::MAX.to_string());
+        assert_eq!(format_amount(5, 30), format!("0.{}5", "0".repeat(29)));
+    }
+
+    #[test]
+    fn test_parse_amount() {
+        assert_eq!(parse_amount("0", COIN_DECIMALS), Ok(0));
+        assert_eq!(parse_amount("1", COIN_DECIMALS), Ok(COIN_VALUE));
+        assert_eq!(parse_amount("1.5", COIN_DECIMALS), Ok(150_000_000));
+        assert_eq!(parse_amount(".5", COIN_DECIMALS), Ok(50_000_000));
+        assert_eq!(parse_amount("2.", COIN_DECIMALS), Ok(2 * COIN_VALUE));
+        assert_eq!(parse_amount("0.00000001", COIN_DECIMALS), Ok(1));
+        assert_eq!(parse_amount("1.500000000000", COIN_DECIMALS), Ok(150_000_000));
+        assert_eq!(parse_amount("42", 0), Ok(42));
+
+        for value in [0, 1, 10, COIN_VALUE, 123_456_789, u64::MAX] {
+            assert_eq!(parse_amount(&format_amount(value, COIN_DECIMALS), COIN_DECIMALS), Ok(value));
+        }
+    }
+
+    #[test]
+    fn test_parse_amount_invalid() {
+        assert_eq!(parse_amount("", COIN_DECIMALS), Err(AmountError::Empty));
+        assert_eq!(parse_amount(".", COIN_DECIMALS), Err(AmountError::Empty));
+        assert_eq!(parse_amount("-1", COIN_DECIMALS), Err(AmountError::InvalidCharacter('-')));
+        assert_eq!(parse_amount("1.2.3", COIN_DECIMALS), Err(AmountError::InvalidCharacter('.')));
+        assert_eq!(parse_amount("1,5", COIN_DECIMALS), Err(AmountError::InvalidCharacter(',')));
+        assert_eq!(parse_amount("0.000000001", COIN_DECIMALS), Err(AmountError::TooManyDecimals(COIN_DECIMALS)));
+        assert_eq!(parse_amount("1.5", 0), Err(AmountError::TooManyDecimals(0)));
+    }
+
+    #[test]
+    fn test_parse_amount_overflow() {
+        assert_eq!(parse_amount("184467440737.09551615", COIN_DECIMALS), Ok(u64::MAX));
+        assert_eq!(parse_amount("184467440737.09551616", COIN_DECIMALS), Err(AmountError::Overflow));
+        assert_eq!(parse_amount("18446744073709551616", 0), Err(AmountError::Overflow));
+        assert_eq!(parse_amount("1", 20), Err(AmountError::Overflow));
+        assert_eq!(parse_amount("0", u8::MAX), Ok(0));
+    }
+}
@@ -4,14 +4,15 @@
 
 use bulletproofs::RangeProof;
 use curve25519_dalek::Scalar;
+use once_cell::sync::OnceCell;
 use serde::{Deserialize, Serialize};
 use std::{
-    collections::HashSet,
+    collections::{HashMap, HashSet},
     iter,
 };
 use crate::{
     account::CiphertextCache,
-    api::DataElement,
+    api::{DataConversionError, DataElement, DataValue},
     config::XELIS_ASSET,
     crypto::{
         elgamal::{
@@ -26,6 +27,7 @@ use crate::{
             SCALAR_SIZE
         },
         proofs::{
+            range_proof_size,
             CiphertextValidityProof,
             CommitmentEqProof,
             ProofGenerationError,
@@ -37,39 +39,51 @@ use crate::{
         Hash,
         ProtocolTranscript,
         HASH_SIZE,
+        Signature,
         SIGNATURE_SIZE
     },
-    serializer::{Reader, ReaderError, Serializer, Writer},
+    serializer::Serializer,
     utils::calculate_tx_fee
 };
 use thiserror::Error;
 use super::{
     extra_data::{ExtraData, PlaintextData},
+    multisig::MultiSigPayload,
     BurnPayload,
     Reference,
     Role,
     SourceCommitment,
     Transaction,
+    TransactionError,
     TransactionType,
     TransferPayload,
-    EXTRA_DATA_LIMIT_SIZE,
-    MAX_TRANSFER_COUNT
+    count_size,
+    max_source_commitments,
+    MAX_RANGE_PROOF_COMMITMENTS,
+    max_transfer_count,
+    validate_fee_asset,
+    EXTRA_DATA_LIMIT_SIZE
 };
 
+// Size added by the encryption of the extra data:
+// 2 represents u16 length of AEADCipher in extra data
+// 2 represents u16 length of UnknownExtraDataFormat
+// We have both length has we move one in the other
+// This mean new ExtraData version has 2 + 2 + 32 (sender) + 32 (receiver) bytes of overhead.
+const ENCRYPTED_EXTRA_DATA_OVERHEAD: usize = 2 + 2 + RISTRETTO_COMPRESSED_SIZE * 2;
+
 #[derive(Error, Debug, Clone)]
 pub enum GenerationError<T> {
     #[error("Error in the state: {0}")]
     State(T),
-    #[error("Empty transfers")]
-    EmptyTransfers,
-    #[error("Max transfer count reached")]
-    MaxTransferCountReached,
     #[error("Sender is receiver")]
     SenderIsReceiver,
-    #[error("Extra data too large")]
-    ExtraDataTooLarge,
+    #[error(transparent)]
+    Transaction(#[from] TransactionError),
     #[error("Encrypted extra data is too large")]
     EncryptedExtraDataTooLarge,
+    #[error("Invalid extra data: {0}")]
+    InvalidExtraData(DataConversionError),
     #[error("Address is not on the same network as us")]
     InvalidNetwork,
     #[error("Extra data was provied with an integrated address")]
@@ -147,11 +161,38 @@ pub struct TransferBuilder {
     pub extra_data: Option<DataElement>,
 }
 
+impl TransferBuilder {
+    // Set a plaintext memo for the recipient of this transfer
+    // It is encrypted for the sender and the recipient when the transaction is built,
+    // so the signature covers the encrypted extra data
+    pub fn with_encrypted_extra_data(mut self, recipient: &CompressedPublicKey, plaintext: &[u8]) -> Result<Self, TransactionError> {
+        if recipient != self.destination.get_public_key() {
+            return Err(TransactionError::ExtraDataRecipientMismatch)
+        }
+
+        let value = DataValue::bytes(plaintext.to_vec())
+            .map_err(|_| TransactionError::ExtraDataTooLarge(plaintext.len()))?;
+        let extra_data = DataElement::Value(value);
+        // Limit is applied on the encrypted size
+        let size = ENCRYPTED_EXTRA_DATA_OVERHEAD + extra_data.size();
+        if size > EXTRA_DATA_LIMIT_SIZE {
+            return Err(TransactionError::ExtraDataTooLarge(size))
+        }
+
+        self.extra_data = Some(extra_data);
+        Ok(self)
+    }
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct TransactionBuilder {
     version: u8,
     source: CompressedPublicKey,
+    #[serde(default)]
+    multisig: Option<MultiSigPayload>,
     data: TransactionTypeBuilder,
+    #[serde(default)]
+    fee_asset: Option<Hash>,
     fee_builder: FeeBuilder
 }
 
@@ -180,10 +221,15 @@ impl TransactionTypeBuilder {
 
     // Get the assets used in the transaction
     pub fn used_assets(&self) -> HashSet<Hash> {
+        self.used_assets_with_fee(&XELIS_ASSET)
+    }
+
+    // Get the assets used in the transaction when fees are paid in fee_asset
+    pub fn used_assets_with_fee(&self, fee_asset: &Hash) -> HashSet<Hash> {
         let mut consumed = HashSet::new();
 
-        // Native asset is always used. (fees)
-        consumed.insert(XELIS_ASSET);
+        // Fee asset is always used
+        consumed.insert(fee_asset.clone());
 
         match &self {
             TransactionTypeBuilder::Transfers(transfers) => {
@@ -216,52 +262,42 @@ impl TransactionTypeBuilder {
     }
 }
 
-// Used to build the final transaction
-// by signing it
-struct TransactionSigner {
-    version: u8,
-    source: CompressedPublicKey,
-    data: TransactionType,
-    fee: u64,
-    nonce: u64,
-    source_commitments: Vec<SourceCommitment>,
-    reference: Reference,
-    range_proof: RangeProof,
-}
-
-impl TransactionSigner {
-    pub fn sign(self, keypair: &KeyPair) -> Transaction {
-        let bytes = self.to_bytes();
-        let signature = keypair.sign(&bytes);
-
-        Transaction {
-            version: self.version,
-            source: self.source,
-            data: self.data,
-            fee: self.fee,
-            nonce: self.nonce,
-            source_commitments: self.source_commitments,
-            range_proof: self.range_proof,
-            reference: self.reference,
-            signature,
-        }
-    }
-}
-
 impl TransactionBuilder {
     pub fn new(version: u8, source: CompressedPublicKey, data: TransactionTypeBuilder, fee_builder: FeeBuilder) -> Self {
         Self {
             version,
             source,
+            multisig: None,
             data,
+            fee_asset: None,
             fee_builder,
         }
     }
 
+    // Pay the fees in another asset than XELIS
+    // It must be one of the assets used by the transaction
+    // Not accepted by the chain yet, so building the transaction is refused
+    pub fn with_fee_asset(mut self, asset: Hash) -> Self {
+        self.fee_asset = Some(asset);
+        self
+    }
+
+    // Get the asset used to pay the fees
+    pub fn get_fee_asset(&self) -> &Hash {
+        self.fee_asset.as_ref().unwrap_or(&XELIS_ASSET)
+    }
+
+    // Require co-signers to approve the transaction
+    // Their signatures must be attached once the transaction is built
+    pub fn with_multisig(mut self, multisig: MultiSigPayload) -> Self {
+        self.multisig = Some(multisig);
+        self
+    }
+
     /// Estimate by hand the bytes size of a final TX
     // Returns bytes size and transfers count
     pub fn estimate_size(&self) -> usize {
-        let assets_used = self.data.used_assets().len();
+        let assets_used = self.data.used_assets_with_fee(self.get_fee_asset()).len();
         // Version byte
         let mut size = 1
         // Source Public Key
@@ -274,8 +310,8 @@ impl TransactionBuilder {
         + 8
         // Reference (hash, topo)
         + HASH_SIZE + 8
-        // Commitments byte length
-        + 1
+        // Commitments count
+        + count_size(self.version)
         // We have one source commitment per asset spent
         // assets * (commitment, asset, proof)
         + assets_used * (RISTRETTO_COMPRESSED_SIZE + HASH_SIZE + (RISTRETTO_COMPRESSED_SIZE * 3 + SCALAR_SIZE * 3))
@@ -283,10 +319,24 @@ impl TransactionBuilder {
         + SIGNATURE_SIZE
         ;
 
+        if self.version >= 1 {
+            // Multisig option byte
+            size += 1;
+            // Fee asset option
+            size += self.fee_asset.size();
+            if let Some(multisig) = &self.multisig {
+                size += multisig.size()
+                // Signatures count byte
+                + 1
+                // Expect only the required signatures
+                + multisig.threshold as usize * (1 + SIGNATURE_SIZE);
+            }
+        }
+
         let transfers_count = match &self.data {
             TransactionTypeBuilder::Transfers(transfers) => {
-                // Transfers count byte
-                size += 1;
+                // Transfers count
+                size += count_size(self.version);
                 for transfer in transfers {
                     size += transfer.asset.size()
                     + transfer.destination.get_public_key().size()
@@ -298,33 +348,20 @@ impl TransactionBuilder {
                     + 1;
 
                     if let Some(extra_data) = transfer.extra_data.as_ref().or(transfer.destination.get_extra_data()) {
-                        // 2 represents u16 length of AEADCipher in extra data
-                        // 2 represents u16 length of UnknownExtraDataFormat
-                        // We have both length has we move one in the other
-                        // This mean new ExtraData version has 2 + 2 + 32 (sender) + 32 (receiver) bytes of overhead.
-                        size += 2 + 2 + (RISTRETTO_COMPRESSED_SIZE * 2) + extra_data.size();
+                        size += ENCRYPTED_EXTRA_DATA_OVERHEAD + extra_data.size();
                     }
                 }
                 transfers.len()
             }
             TransactionTypeBuilder::Burn(payload) => {
                 // Payload size
-                size += payload.size();
+                size += payload.size_with_version(self.version);
                 0
             }
         };
 
         // Range Proof
-        let lg_n = (BULLET_PROOF_SIZE * (transfers_count + assets_used)).next_power_of_two().trailing_zeros() as usize;
-        // Fixed size of the range proof
-        size += RISTRETTO_COMPRESSED_SIZE * 4 + SCALAR_SIZE * 3
-        // u16 bytes length
-        + 2
-        // Inner Product Proof
-        // scalars
-        + SCALAR_SIZE * 2
-        // G_vec len
-        + 2 * RISTRETTO_COMPRESSED_SIZE * lg_n;
+        size += range_proof_size(transfers_count + assets_used);
 
         size
     }
@@ -359,8 +396,8 @@ impl TransactionBuilder {
     }
 
     fn get_new_source_ct(&self, mut ct: Ciphertext, fee: u64, asset: &Hash, transfers: &[TransferWithCommitment]) -> Ciphertext {
-        if asset == &XELIS_ASSET {
-            // Fees are applied to the native blockchain asset only.
+        if asset == self.get_fee_asset() {
+            // Fees are applied to the fee asset only
             ct -= Scalar::from(fee);
         }
 
@@ -386,8 +423,8 @@ impl TransactionBuilder {
     pub fn get_transaction_cost(&self, fee: u64, asset: &Hash) -> u64 {
         let mut cost = 0;
 
-        if *asset == XELIS_ASSET {
-            // Fees are applied to the native blockchain asset only.
+        if asset == self.get_fee_asset() {
+            // Fees are applied to the fee asset only
             cost += fee;
         }
 
@@ -414,33 +451,29 @@ impl TransactionBuilder {
         state: &mut B,
         source_keypair: &KeyPair,
     ) -> Result<Transaction, GenerationError<B::Error>> {
-        // Compute the fees
-        let fee = self.estimate_fees(state)?;
-
-        // Get the nonce
-        let nonce = state.get_nonce().map_err(GenerationError::State)?;
-        state.update_nonce(nonce + 1).map_err(GenerationError::State)?;
-
-        // 0.a Create the commitments
-
-        let used_assets = self.data.used_assets();
-
-        let transfers = if let TransactionTypeBuilder::Transfers(transfers) = &mut self.data {
+        // Validate the transfers before updating anything in the state
+        if let TransactionTypeBuilder::Transfers(transfers) = &mut self.data {
             if transfers.len() == 0 {
-                return Err(GenerationError::EmptyTransfers);
+                return Err(TransactionError::EmptyTransfers.into());
             }
 
-            if transfers.len() > MAX_TRANSFER_COUNT {
-                return Err(GenerationError::MaxTransferCountReached);
+            let max = max_transfer_count(self.version);
+            if transfers.len() > max {
+                return Err(TransactionError::TooManyTransfers(transfers.len(), max).into());
             }
 
             let pk = source_keypair.get_public_key().compress();
             let mut extra_data_size = 0;
+            let mut pairs = HashSet::with_capacity(transfers.len());
             for transfer in transfers.iter_mut() {
                 if *transfer.destination.get_public_key() == pk {
                     return Err(GenerationError::SenderIsReceiver);
                 }
 
+                if !pairs.insert((transfer.asset.clone(), transfer.destination.get_public_key().clone())) {
+                    return Err(TransactionError::DuplicateTransfer.into());
+                }
+
                 if state.is_mainnet() != transfer.destination.is_mainnet() {
                     return Err(GenerationError::InvalidNetwork);
                 }
@@ -455,15 +488,59 @@ impl TransactionBuilder {
                     transfer.extra_data = Some(extra_data);
                 }
 
+                // The limit applies to the encrypted extra data, as checked by Transaction::validate
                 if let Some(extra_data) = &transfer.extra_data {
-                    extra_data_size += extra_data.size();
+                    extra_data.validate().map_err(GenerationError::InvalidExtraData)?;
+                    extra_data_size += ENCRYPTED_EXTRA_DATA_OVERHEAD + extra_data.size();
                 }
             }
 
             if extra_data_size > EXTRA_DATA_LIMIT_SIZE {
-                return Err(GenerationError::ExtraDataTooLarge);
+                return Err(TransactionError::ExtraDataTooLarge(extra_data_size).into());
+            }
+
+            // One source commitment is needed per asset
+            let transfers_count = transfers.len();
+            let assets = self.data.used_assets_with_fee(self.get_fee_asset()).len();
+            let max = max_source_commitments(self.version);
+            if assets > max {
+                return Err(TransactionError::TooManySourceCommitments(assets, max).into());
+            }
+
+            let commitments = assets + transfers_count;
+            if commitments > MAX_RANGE_PROOF_COMMITMENTS {
+                return Err(TransactionError::TooManyRangeProofCommitments(commitments, MAX_RANGE_PROOF_COMMITMENTS).into());
             }
+        }
+
+        if let Some(multisig) = &self.multisig {
+            multisig.validate(self.version, &source_keypair.get_public_key().compress())?;
+        }
+
+        if let Some(asset) = &self.fee_asset {
+            validate_fee_asset(self.version, asset, &self.data.used_assets())?;
+
+            // The chain only credits and checks fees in XELIS for now,
+            // building it would consume a nonce for a transaction never accepted
+            return Err(TransactionError::FeeAssetNotAccepted(asset.clone()).into());
+        }
 
+        if let TransactionTypeBuilder::Burn(payload) = &self.data {
+            payload.validate(self.version)?;
+        }
+
+        // Compute the fees
+        let fee = self.estimate_fees(state)?;
+
+        // Get the nonce
+        let nonce = state.get_nonce().map_err(GenerationError::State)?;
+        state.update_nonce(nonce + 1).map_err(GenerationError::State)?;
+
+        // 0.a Create the commitments
+
+        let used_assets = self.data.used_assets_with_fee(self.get_fee_asset());
+
+        let transfers = if let TransactionTypeBuilder::Transfers(transfers) = &self.data {
             transfers
                 .iter()
                 .map(|transfer| {
@@ -656,44 +733,145 @@ impl TransactionBuilder {
         )
         .map_err(ProofGenerationError::from)?;
 
-        let transaction = TransactionSigner {
+        let mut transaction = Transaction {
             version: self.version,
             source: self.source,
+            multisig: self.multisig,
             data,
             fee,
+            fee_asset: self.fee_asset,
             nonce,
             source_commitments,
-            reference,
             range_proof,
-        }.sign(source_keypair);
+            reference,
+            // Not part of the signed bytes, replaced below
+            signature: Signature::new(Scalar::ZERO, Scalar::ZERO),
+            multisig_signatures: Vec::new(),
+            hash_cache: OnceCell::new(),
+        };
+
+        // Never sign a transaction that would be rejected by the verifier
+        transaction.validate()?;
+
+        let signature = source_keypair.sign(&transaction.signing_bytes());
+        transaction.attach_signature(signature);
 
         Ok(transaction)
     }
 }
 
-impl Serializer for TransactionSigner {
-    fn write(&self, writer: &mut Writer) {
-        self.version.write(writer);
-        self.source.write(writer);
-        self.data.write(writer);
-        self.fee.write(writer);
-        self.nonce.write(writer);
-
-        writer.write_u8(self.source_commitments.len() as u8);
-        for commitment in &self.source_commitments {
-            commitment.write(writer);
+// State used by split_transfers to build its transactions
+// Balances and nonce updates are kept aside and only written to the
+// underlying state once every transaction has been built
+struct StagedAccountState<'a, B: AccountState> {
+    inner: &'a B,
+    balances: HashMap<Hash, (u64, Ciphertext)>,
+    nonce: Option<u64>,
+}
+
+impl<'a, B: AccountState> StagedAccountState<'a, B> {
+    fn new(inner: &'a B) -> Self {
+        Self {
+            inner,
+            balances: HashMap::new(),
+            nonce: None,
         }
+    }
 
-        self.range_proof.write(writer);
-        self.reference.write(writer);
+    // Consume the staged state to release the underlying one
+    fn into_changes(self) -> (HashMap<Hash, (u64, Ciphertext)>, Option<u64>) {
+        (self.balances, self.nonce)
+    }
+}
+
+impl<'a, B: AccountState> FeeHelper for StagedAccountState<'a, B> {
+    type Error = B::Error;
+
+    fn get_fee_multiplier(&self) -> f64 {
+        self.inner.get_fee_multiplier()
     }
 
-    // Should never be called
-    fn read(_: &mut Reader) -> Result<Self, ReaderError> {
-        Err(ReaderError::InvalidValue)
+    fn account_exists(&self, account: &CompressedPublicKey) -> Result<bool, Self::Error> {
+        self.inner.account_exists(account)
     }
 }
 
+impl<'a, B: AccountState> AccountState for StagedAccountState<'a, B> {
+    fn is_mainnet(&self) -> bool {
+        self.inner.is_mainnet()
+    }
+
+    fn get_account_balance(&self, asset: &Hash) -> Result<u64, Self::Error> {
+        match self.balances.get(asset) {
+            Some((balance, _)) => Ok(*balance),
+            None => self.inner.get_account_balance(asset)
+        }
+    }
+
+    fn get_reference(&self) -> Reference {
+        self.inner.get_reference()
+    }
+
+    fn get_account_ciphertext(&self, asset: &Hash) -> Result<CiphertextCache, Self::Error> {
+        match self.balances.get(asset) {
+            Some((_, ciphertext)) => Ok(CiphertextCache::Decompressed(ciphertext.clone())),
+            None => self.inner.get_account_ciphertext(asset)
+        }
+    }
+
+    fn update_account_balance(&mut self, asset: &Hash, new_balance: u64, ciphertext: Ciphertext) -> Result<(), Self::Error> {
+        self.balances.insert(asset.clone(), (new_balance, ciphertext));
+        Ok(())
+    }
+
+    fn get_nonce(&self) -> Result<u64, Self::Error> {
+        match self.nonce {
+            Some(nonce) => Ok(nonce),
+            None => self.inner.get_nonce()
+        }
+    }
+
+    fn update_nonce(&mut self, new_nonce: u64) -> Result<(), Self::Error> {
+        self.nonce = Some(new_nonce);
+        Ok(())
+    }
+}
+
+// Split the transfers in as many transactions as needed to respect the transfers limit
+// Each transaction uses the next nonce and the balances left by the previous one
+// The state is only updated if all the transactions could be built
+pub fn split_transfers<B: AccountState>(
+    version: u8,
+    source_keypair: &KeyPair,
+    transfers: Vec<TransferBuilder>,
+    fee_builder: FeeBuilder,
+    state: &mut B,
+) -> Result<Vec<Transaction>, GenerationError<B::Error>> {
+    if transfers.is_empty() {
+        return Err(TransactionError::EmptyTransfers.into());
+    }
+
+    let source = source_keypair.get_public_key().compress();
+    let mut staged = StagedAccountState::new(state);
+    let transactions = transfers.chunks(max_transfer_count(version))
+        .map(|chunk| {
+            TransactionBuilder::new(version, source.clone(), TransactionTypeBuilder::Transfers(chunk.to_vec()), fee_builder.clone())
+                .build(&mut staged, source_keypair)
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let (balances, nonce) = staged.into_changes();
+    for (asset, (balance, ciphertext)) in balances {
+        state.update_account_balance(&asset, balance, ciphertext).map_err(GenerationError::State)?;
+    }
+
+    if let Some(nonce) = nonce {
+        state.update_nonce(nonce).map_err(GenerationError::State)?;
+    }
+
+    Ok(transactions)
+}
+
 #[cfg(test)]
 mod tests {
     use bulletproofs::RangeProof;
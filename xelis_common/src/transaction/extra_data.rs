@@ -33,7 +33,7 @@ use crate::{
     }
 };
 
-use super::Role;
+use super::{Role, EXTRA_DATA_LIMIT_SIZE};
 
 pub type SharedKey = chacha20poly1305::Key;
 pub type KDF = sha3::Sha3_256;
@@ -288,11 +288,16 @@ impl PlaintextData {
 
 impl Serializer for UnknownExtraDataFormat {
     fn write(&self, writer: &mut Writer) {
-        self.0.write(writer);
+        writer.write_var_bytes(&self.0);
     }
 
     fn read(reader: &mut Reader) -> Result<Self, ReaderError> {
-        Ok(Self(Vec::read(reader)?))
+        Ok(Self(reader.read_var_bytes(EXTRA_DATA_LIMIT_SIZE)?))
+    }
+
+    fn size(&self) -> usize {
+        // 2 for the u16 length prefix
+        2 + self.0.len()
     }
 }
 
@@ -304,6 +309,11 @@ impl Serializer for AEADCipher {
     fn read(reader: &mut Reader) -> Result<Self, ReaderError> {
         Ok(Self(Vec::read(reader)?))
     }
+
+    fn size(&self) -> usize {
+        // 2 for the u16 count of bytes
+        2 + self.0.len()
+    }
 }
 
 impl Serializer for Cipher {
@@ -314,6 +324,11 @@ impl Serializer for Cipher {
     fn read(reader: &mut Reader) -> Result<Self, ReaderError> {
         Ok(Self(Vec::read(reader)?))
     }
+
+    fn size(&self) -> usize {
+        // 2 for the u16 count of bytes
+        2 + self.0.len()
+    }
 }
 
 #[cfg(test)]
@@ -329,6 +344,7 @@ mod tests {
         let bytes = vec![1, 2, 3, 4, 5];
         let data = PlaintextData(bytes.clone());
         let cipher = data.encrypt_in_place_with_aead(&k);
+        assert_eq!(cipher.size(), cipher.to_bytes().len());
         let decrypted = cipher.decrypt_in_place(&k).unwrap();
         assert_eq!(decrypted.0, bytes);
     }
@@ -1,31 +1,152 @@
-use std::fmt;
+use std::{collections::HashSet, fmt, hash::Hasher};
 use crate::{
+    api::DataElement,
+    config::XELIS_ASSET,
     crypto::{
-        elgamal::{CompressedCiphertext, CompressedCommitment, CompressedHandle, CompressedPublicKey},
-        proofs::{CiphertextValidityProof, CommitmentEqProof},
+        elgamal::{CompressedCiphertext, CompressedCommitment, CompressedHandle, CompressedPublicKey, PrivateKey, RISTRETTO_COMPRESSED_SIZE},
+        proofs::{range_proof_size, CiphertextValidityProof, CommitmentEqProof, ProofVerificationError, CIPHERTEXT_VALIDITY_PROOF_SIZE},
+        hash,
         Hash,
         Hashable,
         Signature,
+        HASH_SIZE,
     },
     serializer::{Reader, ReaderError, Serializer, Writer}
 };
 use bulletproofs::RangeProof;
+use indexmap::IndexMap;
+use curve25519_dalek::ristretto::CompressedRistretto;
 use log::debug;
+use once_cell::sync::OnceCell;
 use serde::{Deserialize, Serialize};
-use self::extra_data::UnknownExtraDataFormat;
+use thiserror::Error;
+use self::{
+    extra_data::{CipherFormatError, UnknownExtraDataFormat},
+    multisig::{MultiSigPayload, SignatureId}
+};
 
 pub mod builder;
 pub mod verify;
 pub mod extra_data;
+pub mod multisig;
+pub mod summary;
+pub mod amount;
+pub mod payment_uri;
 
 #[cfg(test)]
 mod tests;
 
 // Maximum total size of payload across all transfers per transaction
 pub const EXTRA_DATA_LIMIT_SIZE: usize = 1024;
-pub const MAX_TRANSFER_COUNT: usize = 255;
+// Maximum transfers in a version 0 transaction, its count is written as a u8
+pub const MAX_TRANSFER_COUNT_V0: usize = u8::MAX as usize;
+// Maximum transfers per transaction since version 1, its count is written as a u16
+// Still bounded as the bulletproofs generators and the verification cost grow with it
+pub const MAX_TRANSFER_COUNT: usize = 511;
+// Maximum commitments proven by the aggregated range proof of a transaction:
+// one per source commitment and one per transfer
+// The bulletproofs generators are sized for it, so it must stay a power of two
+pub const MAX_RANGE_PROOF_COMMITMENTS: usize = 512;
+// Latest transaction version supported
+// Version 1 allows an extra data on burns, co-signers on the source,
+// fees paid in another asset and up to MAX_TRANSFER_COUNT transfers
+pub const MAX_TX_VERSION: u8 = 1;
+
+// Fee asset rules that don't depend on the chain state, shared by Transaction::validate and the builder
+// It must be one of the assets transferred or burned
+// XELIS must be represented as None to have a single encoding
+fn validate_fee_asset(version: u8, asset: &Hash, assets: &HashSet<Hash>) -> Result<(), TransactionError> {
+    if version == 0 {
+        return Err(TransactionError::FeeAssetNotSupported(version))
+    }
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
+    if *asset == XELIS_ASSET || !assets.contains(asset) {
+        return Err(TransactionError::InvalidFeeAsset(asset.clone()))
+    }
+
+    Ok(())
+}
+
+// Maximum transfers allowed in a transaction
+pub const fn max_transfer_count(version: u8) -> usize {
+    if version == 0 {
+        MAX_TRANSFER_COUNT_V0
+    } else {
+        MAX_TRANSFER_COUNT
+    }
+}
+
+// Maximum source commitments allowed in a transaction
+// We have one per asset used, including the fee asset
+pub const fn max_source_commitments(version: u8) -> usize {
+    if version == 0 {
+        MAX_TRANSFER_COUNT_V0
+    } else {
+        MAX_TRANSFER_COUNT + 1
+    }
+}
+
+// Weights used to estimate the verification cost of a transaction
+// Fixed cost of a transaction: signature and range proof setup
+pub const BASE_WEIGHT: u64 = 1_000;
+// Cost per byte of the transaction
+pub const BYTE_WEIGHT: u64 = 1;
+// Cost per transfer: commitment, two handles and the ct validity proof
+pub const TRANSFER_WEIGHT: u64 = 5_000;
+// Cost per source commitment and its equality proof
+pub const SOURCE_COMMITMENT_WEIGHT: u64 = 3_000;
+// Cost of a burn, only a public amount to check
+pub const BURN_WEIGHT: u64 = 100;
+// Cost per co-signer signature
+pub const MULTISIG_SIGNATURE_WEIGHT: u64 = 1_000;
+
+// Errors returned when a transaction doesn't respect the protocol rules
+// These checks don't require any chain state
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum TransactionError {
+    #[error("Unsupported transaction version: {}", _0)]
+    UnsupportedVersion(u8),
+    #[error("Burn amount cannot be zero")]
+    ZeroBurn,
+    #[error("Extra data on burn is not supported in version {}", _0)]
+    BurnExtraDataNotSupported(u8),
+    #[error("Transaction has no transfers")]
+    EmptyTransfers,
+    #[error("Too many transfers: {} while maximum is {}", _0, _1)]
+    TooManyTransfers(usize, usize),
+    #[error("Multiple transfers to the same destination for the same asset")]
+    DuplicateTransfer,
+    #[error("Too many source commitments: {} while maximum is {}", _0, _1)]
+    TooManySourceCommitments(usize, usize),
+    #[error("Too many range proof commitments: {} while maximum is {}", _0, _1)]
+    TooManyRangeProofCommitments(usize, usize),
+    #[error("Extra data is too large: {} bytes while maximum is {}", _0, EXTRA_DATA_LIMIT_SIZE)]
+    ExtraDataTooLarge(usize),
+    #[error("Extra data recipient is not the transfer destination")]
+    ExtraDataRecipientMismatch,
+    #[error("Multisig is not supported in version {}", _0)]
+    MultiSigNotSupported(u8),
+    #[error("Invalid multisig configuration")]
+    InvalidMultiSig,
+    #[error("Too many multisig signatures: {} while maximum is {}", _0, _1)]
+    TooManyMultiSigSignatures(usize, usize),
+    #[error("Fee is too low: {} while minimum is {}", _0, _1)]
+    FeeTooLow(u64, u64),
+    #[error("Fee asset is not supported in version {}", _0)]
+    FeeAssetNotSupported(u8),
+    #[error("Fee asset {} is not used by the transaction", _0)]
+    InvalidFeeAsset(Hash),
+    #[error("Fee asset {} is not accepted", _0)]
+    FeeAssetNotAccepted(Hash),
+    #[error("Invalid transaction signature")]
+    BadSignature,
+    #[error("Sender cannot be the receiver")]
+    SenderIsReceiver,
+    #[error("Proof verification error: {0}")]
+    Proof(#[from] ProofVerificationError),
+}
+
+#[derive(Serialize, Deserialize, Serializer, Clone, Debug)]
 pub struct Reference {
     pub hash: Hash,
     pub topoheight: u64,
@@ -48,13 +169,24 @@ pub enum Role {
     Receiver,
 }
 
-#[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
+#[derive(serde::Serialize, serde::Deserialize, Serializer, Clone, Debug)]
 pub struct SourceCommitment {
     commitment: CompressedCommitment,
     proof: CommitmentEqProof,
     asset: Hash,
 }
 
+impl SourceCommitment {
+    // Create a new source commitment
+    pub fn new(commitment: CompressedCommitment, proof: CommitmentEqProof, asset: Hash) -> Self {
+        Self {
+            commitment,
+            proof,
+            asset
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct TransferPayload {
     asset: Hash,
@@ -73,7 +205,11 @@ pub struct TransferPayload {
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct BurnPayload {
     pub asset: Hash,
-    pub amount: u64
+    pub amount: u64,
+    // Public memo attached to the burn
+    // Only available since version 1
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub extra_data: Option<Vec<u8>>
 }
 
 // this enum represent all types of transaction available on XELIS Network
@@ -91,10 +227,18 @@ pub struct Transaction {
     version: u8,
     // Source of the transaction
     source: CompressedPublicKey,
+    /// Co-signers that must approve the transaction
+    /// Only available since version 1
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    multisig: Option<MultiSigPayload>,
     /// Type of the transaction
     data: TransactionType,
-    /// Fees in XELIS
+    /// Fees paid in the fee asset
     fee: u64,
+    /// Asset used to pay the fees, None means XELIS
+    /// Only available since version 1
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    fee_asset: Option<Hash>,
     /// nonce must be equal to the one on chain account
     /// used to prevent replay attacks and have ordered transactions
     nonce: u64,
@@ -106,6 +250,13 @@ pub struct Transaction {
     reference: Reference,
     /// The signature of the source key
     signature: Signature,
+    /// The signatures of the co-signers
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    multisig_signatures: Vec<SignatureId>,
+    /// Cached hash of the transaction, computed on first use
+    /// Must be reset by any function mutating the transaction
+    #[serde(skip)]
+    hash_cache: OnceCell<Hash>,
 }
 
 impl TransferPayload {
@@ -137,6 +288,22 @@ impl TransferPayload {
         &self.extra_data
     }
 
+    // Decrypt the extra data using the private key of the sender or the receiver
+    pub fn decrypt_extra_data(&self, private_key: &PrivateKey, role: Role) -> Result<Option<DataElement>, CipherFormatError> {
+        let cipher = match &self.extra_data {
+            Some(cipher) => cipher,
+            None => return Ok(None)
+        };
+
+        // Old format is using the amount handle of the party
+        let handle = match role {
+            Role::Sender => &self.sender_handle,
+            Role::Receiver => &self.receiver_handle
+        }.decompress().map_err(|_| CipherFormatError)?;
+
+        cipher.decrypt(private_key, &handle, role).map(Some)
+    }
+
     // Get the ciphertext commitment
     pub fn get_commitment(&self) -> &CompressedCommitment {
         &self.commitment
@@ -173,17 +340,23 @@ impl TransferPayload {
 }
 
 impl Transaction {
-    pub fn new(source: CompressedPublicKey, data: TransactionType, fee: u64, nonce: u64, source_commitments: Vec<SourceCommitment>, range_proof: RangeProof, reference: Reference, signature: Signature) -> Self {
+    // The range proof must aggregate the source commitments then the transfers,
+    // its serialized size is derived from their count
+    pub fn new(version: u8, source: CompressedPublicKey, multisig: Option<MultiSigPayload>, data: TransactionType, fee: u64, fee_asset: Option<Hash>, nonce: u64, source_commitments: Vec<SourceCommitment>, range_proof: RangeProof, reference: Reference, signature: Signature, multisig_signatures: Vec<SignatureId>) -> Self {
         Transaction {
-            version: 0,
+            version,
             source,
+            multisig,
             data,
             fee,
+            fee_asset,
             nonce,
             source_commitments,
             range_proof,
             reference,
-            signature
+            signature,
+            multisig_signatures,
+            hash_cache: OnceCell::new()
         }
     }
 
@@ -197,6 +370,11 @@ impl Transaction {
         &self.source
     }
 
+    // Get the co-signers configuration if any
+    pub fn get_multisig(&self) -> Option<&MultiSigPayload> {
+        self.multisig.as_ref()
+    }
+
     // Get the transaction type
     pub fn get_data(&self) -> &TransactionType {
         &self.data
@@ -207,6 +385,11 @@ impl Transaction {
         self.fee
     }
 
+    // Get the asset used to pay the fees
+    pub fn get_fee_asset(&self) -> &Hash {
+        self.fee_asset.as_ref().unwrap_or(&XELIS_ASSET)
+    }
+
     // Get the nonce used
     pub fn get_nonce(&self) -> u64 {
         self.nonce
@@ -227,37 +410,206 @@ impl Transaction {
         &self.signature
     }
 
+    // Get the signatures of the co-signers
+    pub fn get_multisig_signatures(&self) -> &Vec<SignatureId> {
+        &self.multisig_signatures
+    }
+
     // Get the block reference to determine which block the transaction is built
     pub fn get_reference(&self) -> &Reference {
         &self.reference
     }
 
-    pub fn consume(self) -> (CompressedPublicKey, TransactionType) {
-        (self.source, self.data)
+    /// Get the bytes signed by the source key.
+    /// This is the serialized transaction without its signature.
+    /// This layout must remain stable across versions: any change would
+    /// make previously signed transactions unverifiable.
+    pub fn signing_bytes(&self) -> Vec<u8> {
+        let mut writer = Writer::new();
+        self.write_unsigned(&mut writer);
+        writer.bytes()
     }
-}
 
-impl Serializer for SourceCommitment {
-    fn write(&self, writer: &mut Writer) {
-        self.commitment.write(writer);
-        self.proof.write(writer);
-        self.asset.write(writer);
+    // Set the signature of the transaction
+    // Used when the signature is computed externally (offline signing, hardware wallet)
+    pub fn attach_signature(&mut self, signature: Signature) {
+        self.signature = signature;
+        self.hash_cache = OnceCell::new();
     }
 
-    fn read(reader: &mut Reader) -> Result<SourceCommitment, ReaderError> {
-        let commitment = CompressedCommitment::read(reader)?;
-        let proof = CommitmentEqProof::read(reader)?;
-        let asset = Hash::read(reader)?;
+    // Add the signature of a co-signer over the signing bytes
+    pub fn attach_multisig_signature(&mut self, signature: SignatureId) {
+        self.multisig_signatures.push(signature);
+        self.hash_cache = OnceCell::new();
+    }
 
-        Ok(SourceCommitment {
-            commitment,
-            proof,
-            asset
-        })
+    /// Read a transaction that can't use more than max_size bytes.
+    /// Reading stops with `ReaderError::InvalidSize` as soon as the bound is reached.
+    pub fn read_bounded(reader: &mut Reader, max_size: usize) -> Result<Transaction, ReaderError> {
+        reader.read_bounded(max_size)
     }
 
-    fn size(&self) -> usize {
-        self.commitment.size() + self.proof.size() + self.asset.size()
+    /// Decode a transaction from its hex representation.
+    /// Odd-length or non-hex input returns `ReaderError::InvalidHex`,
+    /// and like `Serializer::from_bytes_exact`, trailing bytes are rejected.
+    pub fn from_hex(hex: &str) -> Result<Transaction, ReaderError> {
+        let bytes = hex::decode(hex).map_err(|e| {
+            debug!("invalid transaction hex: {}", e);
+            ReaderError::InvalidHex
+        })?;
+
+        Transaction::from_bytes_exact(&bytes)
+    }
+
+    // Get the transaction hash, it is only computed once
+    pub fn hash(&self) -> &Hash {
+        self.hash_cache.get_or_init(|| hash(&self.to_bytes()))
+    }
+
+    /// Verify the rules of the transaction that don't depend on the chain state.
+    /// Counts are written using a single byte, so this must be called
+    /// on a manually created transaction before serializing it,
+    /// otherwise its lengths would be silently truncated.
+    pub fn validate(&self) -> Result<(), TransactionError> {
+        if self.version > MAX_TX_VERSION {
+            return Err(TransactionError::UnsupportedVersion(self.version))
+        }
+
+        match &self.multisig {
+            Some(multisig) => {
+                multisig.validate(self.version, &self.source)?;
+
+                let count = multisig.participants.len();
+                if self.multisig_signatures.len() > count {
+                    return Err(TransactionError::TooManyMultiSigSignatures(self.multisig_signatures.len(), count))
+                }
+            },
+            None => if !self.multisig_signatures.is_empty() {
+                return Err(TransactionError::TooManyMultiSigSignatures(self.multisig_signatures.len(), 0))
+            }
+        };
+
+        if let Some(asset) = &self.fee_asset {
+            validate_fee_asset(self.version, asset, &self.referenced_assets())?;
+        }
+
+        if let TransactionType::Burn(payload) = &self.data {
+            payload.validate(self.version)?;
+        }
+
+        if let TransactionType::Transfers(transfers) = &self.data {
+            if transfers.is_empty() {
+                return Err(TransactionError::EmptyTransfers)
+            }
+
+            let max = max_transfer_count(self.version);
+            if transfers.len() > max {
+                return Err(TransactionError::TooManyTransfers(transfers.len(), max))
+            }
+
+            let extra_data_size = transfers.iter()
+                .filter_map(|transfer| transfer.extra_data.as_ref())
+                .map(|extra_data| extra_data.size())
+                .sum::<usize>();
+
+            if extra_data_size > EXTRA_DATA_LIMIT_SIZE {
+                return Err(TransactionError::ExtraDataTooLarge(extra_data_size))
+            }
+
+            if self.has_duplicate_transfers() {
+                return Err(TransactionError::DuplicateTransfer)
+            }
+        }
+
+        let max = max_source_commitments(self.version);
+        if self.source_commitments.len() > max {
+            return Err(TransactionError::TooManySourceCommitments(self.source_commitments.len(), max))
+        }
+
+        let commitments = self.source_commitments.len() + self.data.transfer_count();
+        if commitments > MAX_RANGE_PROOF_COMMITMENTS {
+            return Err(TransactionError::TooManyRangeProofCommitments(commitments, MAX_RANGE_PROOF_COMMITMENTS))
+        }
+
+        Ok(())
+    }
+
+    /// Check if two transfers are sending the same asset to the same destination.
+    /// They should be merged into a single transfer instead.
+    pub fn has_duplicate_transfers(&self) -> bool {
+        let mut pairs = HashSet::with_capacity(self.data.transfer_count());
+        self.data.transfers().any(|transfer| !pairs.insert((&transfer.asset, &transfer.destination)))
+    }
+
+    /// Get all the assets used by this transaction
+    pub fn referenced_assets(&self) -> HashSet<Hash> {
+        let mut assets: HashSet<Hash> = self.data.transfers()
+            .map(|transfer| transfer.asset.clone())
+            .collect();
+
+        if let TransactionType::Burn(payload) = &self.data {
+            assets.insert(payload.asset.clone());
+        }
+
+        assets
+    }
+
+    /// Minimum fee required to pay for the bytes size of this transaction
+    pub fn minimum_fee(&self, fee_per_byte: u64) -> u64 {
+        (self.size() as u64).saturating_mul(fee_per_byte)
+    }
+
+    /// Verify that the fee paid covers the bytes size of this transaction
+    pub fn validate_fee(&self, fee_per_byte: u64) -> Result<(), TransactionError> {
+        let minimum = self.minimum_fee(fee_per_byte);
+        if self.fee < minimum {
+            return Err(TransactionError::FeeTooLow(self.fee, minimum))
+        }
+
+        Ok(())
+    }
+
+    /// Estimated cost to verify this transaction.
+    /// Transfers are much more expensive than their size suggests
+    /// because of their proofs, while a burn is cheap.
+    pub fn weight(&self) -> u64 {
+        let data_weight = match &self.data {
+            TransactionType::Transfers(transfers) => transfers.len() as u64 * TRANSFER_WEIGHT,
+            TransactionType::Burn(_) => BURN_WEIGHT
+        };
+
+        BASE_WEIGHT
+        + self.size() as u64 * BYTE_WEIGHT
+        + data_weight
+        + self.source_commitments.len() as u64 * SOURCE_COMMITMENT_WEIGHT
+        + self.multisig_signatures.len() as u64 * MULTISIG_SIGNATURE_WEIGHT
+    }
+
+    pub fn consume(self) -> (CompressedPublicKey, TransactionType) {
+        (self.source, self.data)
+    }
+
+    // Write all the fields except the signature
+    fn write_unsigned(&self, writer: &mut Writer) {
+        self.version.write(writer);
+        self.source.write(writer);
+        if self.version >= 1 {
+            self.multisig.write(writer);
+        }
+        self.data.write_with_version(writer, self.version);
+        self.fee.write(writer);
+        if self.version >= 1 {
+            self.fee_asset.write(writer);
+        }
+        self.nonce.write(writer);
+
+        write_count(writer, self.source_commitments.len(), self.version);
+        for commitment in &self.source_commitments {
+            commitment.write(writer);
+        }
+
+        self.range_proof.write(writer);
+        self.reference.write(writer);
     }
 }
 
@@ -304,38 +656,260 @@ impl Serializer for TransferPayload {
     }
 }
 
-impl Serializer for BurnPayload {
-    fn write(&self, writer: &mut Writer) {
+// Borrowed view of a serialized TransferPayload
+// Every field points into the original buffer, so scanning transfers doesn't copy anything
+// It is validated the same way as TransferPayload::read
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TransferPayloadRef<'a> {
+    asset: &'a [u8; HASH_SIZE],
+    destination: &'a [u8; RISTRETTO_COMPRESSED_SIZE],
+    extra_data: Option<&'a [u8]>,
+    commitment: &'a [u8; RISTRETTO_COMPRESSED_SIZE],
+    sender_handle: &'a [u8; RISTRETTO_COMPRESSED_SIZE],
+    receiver_handle: &'a [u8; RISTRETTO_COMPRESSED_SIZE],
+    ct_validity_proof: &'a [u8; CIPHERTEXT_VALIDITY_PROOF_SIZE],
+}
+
+// Borrow the next N bytes of the reader as an array
+fn read_array_borrowed<'a, const N: usize>(reader: &mut Reader<'a>) -> Result<&'a [u8; N], ReaderError> {
+    let bytes = reader.read_bytes_borrowed(N)?;
+    Ok(bytes.try_into()?)
+}
+
+impl<'a> TransferPayloadRef<'a> {
+    // Read a transfer without copying it
+    // Only a reader created with Reader::new can be borrowed, a stream returns an error
+    pub fn read(reader: &mut Reader<'a>) -> Result<Self, ReaderError> {
+        let asset = read_array_borrowed(reader)?;
+        let destination = read_array_borrowed(reader)?;
+        let extra_data = if reader.read_bool()? {
+            Some(reader.read_var_bytes_borrowed(EXTRA_DATA_LIMIT_SIZE)?)
+        } else {
+            None
+        };
+
+        let commitment = read_array_borrowed(reader)?;
+        let sender_handle = read_array_borrowed(reader)?;
+        let receiver_handle = read_array_borrowed(reader)?;
+
+        // Parse the proof to reject non canonical scalars, but only keep its bytes
+        let ct_validity_proof = read_array_borrowed(reader)?;
+        CiphertextValidityProof::from_bytes(ct_validity_proof)?;
+
+        Ok(Self {
+            asset,
+            destination,
+            extra_data,
+            commitment,
+            sender_handle,
+            receiver_handle,
+            ct_validity_proof
+        })
+    }
+
+    // Get the asset hash bytes spent in this transfer
+    pub fn get_asset(&self) -> &'a [u8; HASH_SIZE] {
+        self.asset
+    }
+
+    // Get the compressed destination key bytes
+    pub fn get_destination(&self) -> &'a [u8; RISTRETTO_COMPRESSED_SIZE] {
+        self.destination
+    }
+
+    // Get the extra data if any, without its length prefix
+    pub fn get_extra_data(&self) -> Option<&'a [u8]> {
+        self.extra_data
+    }
+
+    // Get the compressed ciphertext commitment bytes
+    pub fn get_commitment(&self) -> &'a [u8; RISTRETTO_COMPRESSED_SIZE] {
+        self.commitment
+    }
+
+    // Get the compressed decrypt handle bytes for sender
+    pub fn get_sender_handle(&self) -> &'a [u8; RISTRETTO_COMPRESSED_SIZE] {
+        self.sender_handle
+    }
+
+    // Get the compressed decrypt handle bytes for receiver
+    pub fn get_receiver_handle(&self) -> &'a [u8; RISTRETTO_COMPRESSED_SIZE] {
+        self.receiver_handle
+    }
+
+    // Get the serialized validity proof
+    pub fn get_proof(&self) -> &'a [u8; CIPHERTEXT_VALIDITY_PROOF_SIZE] {
+        self.ct_validity_proof
+    }
+
+    // Serialized size of the transfer
+    pub fn size(&self) -> usize {
+        // 1 for the extra data option flag, 2 for its length prefix
+        HASH_SIZE
+        + RISTRETTO_COMPRESSED_SIZE * 4
+        + 1 + self.extra_data.map_or(0, |data| 2 + data.len())
+        + CIPHERTEXT_VALIDITY_PROOF_SIZE
+    }
+
+    // Copy the view into an owned transfer
+    pub fn to_payload(&self) -> Result<TransferPayload, ReaderError> {
+        Ok(TransferPayload {
+            asset: Hash::new(*self.asset),
+            destination: CompressedPublicKey::new(CompressedRistretto(*self.destination)),
+            extra_data: self.extra_data.map(|data| UnknownExtraDataFormat(data.to_vec())),
+            commitment: CompressedCommitment::new(CompressedRistretto(*self.commitment)),
+            sender_handle: CompressedHandle::new(CompressedRistretto(*self.sender_handle)),
+            receiver_handle: CompressedHandle::new(CompressedRistretto(*self.receiver_handle)),
+            ct_validity_proof: CiphertextValidityProof::from_bytes(self.ct_validity_proof)?
+        })
+    }
+}
+
+// Transfers and source commitments counts are written
+// as a u8 in version 0 and as a u16 since version 1
+// Counts are bounded by MAX_TRANSFER_COUNT_V0 / MAX_TRANSFER_COUNT in validate,
+// anything above would be truncated and corrupt the encoding
+fn write_count(writer: &mut Writer, count: usize, version: u8) {
+    if version == 0 {
+        let count = u8::try_from(count)
+            .unwrap_or_else(|_| panic!("Transaction count {} does not fit in a u8, version 0 allows up to MAX_TRANSFER_COUNT_V0 ({})", count, MAX_TRANSFER_COUNT_V0));
+        writer.write_u8(count);
+    } else {
+        let count = u16::try_from(count)
+            .unwrap_or_else(|_| panic!("Transaction count {} does not fit in a u16, version 1 allows up to MAX_TRANSFER_COUNT ({})", count, MAX_TRANSFER_COUNT));
+        writer.write_u16(count);
+    }
+}
+
+fn read_count(reader: &mut Reader, version: u8) -> Result<usize, ReaderError> {
+    Ok(if version == 0 {
+        reader.read_u8()? as usize
+    } else {
+        reader.read_u16()? as usize
+    })
+}
+
+const fn count_size(version: u8) -> usize {
+    if version == 0 {
+        1
+    } else {
+        2
+    }
+}
+
+// The burn layout depends on the transaction version
+// Version 0 has no extra data field
+impl BurnPayload {
+    // Check the payload against the rules of the transaction version
+    pub fn validate(&self, version: u8) -> Result<(), TransactionError> {
+        if self.amount == 0 {
+            return Err(TransactionError::ZeroBurn)
+        }
+
+        if let Some(extra_data) = self.extra_data.as_ref() {
+            // It would be dropped silently when writing it
+            if version == 0 {
+                return Err(TransactionError::BurnExtraDataNotSupported(version))
+            }
+
+            if extra_data.len() > EXTRA_DATA_LIMIT_SIZE {
+                return Err(TransactionError::ExtraDataTooLarge(extra_data.len()))
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn write_with_version(&self, writer: &mut Writer, version: u8) {
         self.asset.write(writer);
         self.amount.write(writer);
+        if version >= 1 {
+            writer.write_bool(self.extra_data.is_some());
+            if let Some(extra_data) = &self.extra_data {
+                writer.write_var_bytes(extra_data);
+            }
+        }
     }
 
-    fn read(reader: &mut Reader) -> Result<BurnPayload, ReaderError> {
+    pub fn read_with_version(reader: &mut Reader, version: u8) -> Result<BurnPayload, ReaderError> {
         let asset = Hash::read(reader)?;
         let amount = reader.read_u64()?;
+        // Burning nothing is only a way to spam the network
+        if amount == 0 {
+            return Err(ReaderError::InvalidValue)
+        }
+
+        let extra_data = if version >= 1 {
+            if reader.read_bool()? {
+                Some(reader.read_var_bytes(EXTRA_DATA_LIMIT_SIZE)?)
+            } else {
+                None
+            }
+        } else {
+            None
+        };
+
         Ok(BurnPayload {
             asset,
-            amount
+            amount,
+            extra_data
         })
     }
 
-    fn size(&self) -> usize {
-        self.asset.size() + self.amount.size()
+    pub fn size_with_version(&self, version: u8) -> usize {
+        let mut size = self.asset.size() + self.amount.size();
+        if version >= 1 {
+            size += self.extra_data.size();
+        }
+        size
     }
 }
 
-impl Serializer for TransactionType {
-    fn write(&self, writer: &mut Writer) {
+impl TransactionType {
+    // Iterate over the transfers, a burn has none
+    pub fn transfers(&self) -> impl Iterator<Item = &TransferPayload> {
+        let transfers: &[TransferPayload] = match self {
+            TransactionType::Transfers(transfers) => transfers,
+            TransactionType::Burn(_) => &[]
+        };
+        transfers.iter()
+    }
+
+    // Group the transfers by asset, in the order each asset first appears
+    // A burn has no transfers, so its map is empty
+    pub fn transfers_by_asset(&self) -> IndexMap<Hash, Vec<&TransferPayload>> {
+        let mut assets: IndexMap<Hash, Vec<&TransferPayload>> = IndexMap::new();
+        for transfer in self.transfers() {
+            assets.entry(transfer.asset.clone())
+                .or_default()
+                .push(transfer);
+        }
+        assets
+    }
+
+    // Count of transfers, zero for a burn
+    pub fn transfer_count(&self) -> usize {
+        match self {
+            TransactionType::Transfers(transfers) => transfers.len(),
+            TransactionType::Burn(_) => 0
+        }
+    }
+
+    pub fn write_with_version(&self, writer: &mut Writer, version: u8) {
         match self {
             TransactionType::Burn(payload) => {
                 writer.write_u8(0);
-                payload.write(writer);
+                payload.write_with_version(writer, version);
+            }
+            // Fast path for the most common case, same bytes as the loop below
+            TransactionType::Transfers(txs) if txs.len() == 1 => {
+                writer.write_u8(1);
+                write_count(writer, 1, version);
+                txs[0].write(writer);
             }
             TransactionType::Transfers(txs) => {
                 writer.write_u8(1);
-                // max 255 txs per transaction
-                let len: u8 = txs.len() as u8;
-                writer.write_u8(len);
+                write_count(writer, txs.len(), version);
                 for tx in txs {
                     tx.write(writer);
                 }
@@ -343,19 +917,25 @@ impl Serializer for TransactionType {
         };
     }
 
-    fn read(reader: &mut Reader) -> Result<TransactionType, ReaderError> {
+    pub fn read_with_version(reader: &mut Reader, version: u8) -> Result<TransactionType, ReaderError> {
         Ok(match reader.read_u8()? {
             0 => {
-                let payload = BurnPayload::read(reader)?;
+                let payload = BurnPayload::read_with_version(reader, version)?;
                 TransactionType::Burn(payload)
             },
             1 => {
-                let txs_count = reader.read_u8()?;
-                if txs_count == 0 || txs_count > MAX_TRANSFER_COUNT as u8 {
+                let txs_count = read_count(reader, version)?;
+                if txs_count == 0 || txs_count > max_transfer_count(version) {
                     return Err(ReaderError::InvalidSize)
                 }
 
-                let mut txs = Vec::with_capacity(txs_count as usize);
+                // Fast path for the most common case, no loop over the transfers
+                if txs_count == 1 {
+                    let tx = TransferPayload::read(reader)?;
+                    return Ok(TransactionType::Transfers(vec![tx]))
+                }
+
+                let mut txs = Vec::with_capacity(txs_count);
                 for _ in 0..txs_count {
                     txs.push(TransferPayload::read(reader)?);
                 }
@@ -367,14 +947,14 @@ impl Serializer for TransactionType {
         })
     }
 
-    fn size(&self) -> usize {
+    pub fn size_with_version(&self, version: u8) -> usize {
         match self {
             TransactionType::Burn(payload) => {
-                1 + payload.size()
+                1 + payload.size_with_version(version)
             },
             TransactionType::Transfers(txs) => {
-                // 1 byte for variant, 1 byte for count of transfers
-                let mut size = 1 + 1;
+                // 1 byte for variant and the count of transfers
+                let mut size = 1 + count_size(version);
                 for tx in txs {
                     size += tx.size();
                 }
@@ -386,102 +966,160 @@ impl Serializer for TransactionType {
 
 impl Serializer for Transaction {
     fn write(&self, writer: &mut Writer) {
-        self.version.write(writer);
-        self.source.write(writer);
-        self.data.write(writer);
-        self.fee.write(writer);
-        self.nonce.write(writer);
-
-        writer.write_u8(self.source_commitments.len() as u8);
-        for commitment in &self.source_commitments {
-            commitment.write(writer);
-        }
-
-        self.range_proof.write(writer);
-        self.reference.write(writer);
+        self.write_unsigned(writer);
         self.signature.write(writer);
+        if self.multisig.is_some() {
+            writer.write_u8(self.multisig_signatures.len() as u8);
+            for signature in &self.multisig_signatures {
+                signature.write(writer);
+            }
+        }
     }
 
     fn read(reader: &mut Reader) -> Result<Transaction, ReaderError> {
         let version = reader.read_u8()?;
-        // At this moment we only support version 0, so we check it here directly
-        if version != 0 {
-            debug!("Expected version 0 got version {version}");
+        if version > MAX_TX_VERSION {
+            debug!("Expected version up to {MAX_TX_VERSION} got version {version}");
             return Err(ReaderError::InvalidValue)
         }
 
         let source = CompressedPublicKey::read(reader)?;
-        let data = TransactionType::read(reader)?;
+        let multisig = if version >= 1 {
+            Option::<MultiSigPayload>::read(reader)?
+        } else {
+            None
+        };
+        let data = TransactionType::read_with_version(reader, version)?;
         let fee = reader.read_u64()?;
+        let fee_asset = if version >= 1 {
+            Option::<Hash>::read(reader)?
+        } else {
+            None
+        };
         let nonce = reader.read_u64()?;
 
-        let commitments_len = reader.read_u8()?;
-        if commitments_len == 0 || commitments_len > MAX_TRANSFER_COUNT as u8 {
+        let commitments_len = read_count(reader, version)?;
+        if commitments_len == 0 || commitments_len > max_source_commitments(version) {
             return Err(ReaderError::InvalidSize)
         }
 
-        let mut source_commitments = Vec::with_capacity(commitments_len as usize);
+        let mut source_commitments = Vec::with_capacity(commitments_len);
         for _ in 0..commitments_len {
             source_commitments.push(SourceCommitment::read(reader)?);
         }
 
+        let start = reader.total_read();
         let range_proof = RangeProof::read(reader)?;
+        // Its size is fixed by the commitments it proves, size() relies on it
+        if reader.total_read() - start != range_proof_size(commitments_len + data.transfer_count()) {
+            return Err(ReaderError::InvalidSize)
+        }
+
         let reference = Reference::read(reader)?;
         let signature = Signature::read(reader)?;
 
+        let mut multisig_signatures = Vec::new();
+        if let Some(multisig) = multisig.as_ref() {
+            let count = reader.read_u8()?;
+            if count as usize > multisig.participants.len() {
+                return Err(ReaderError::InvalidSize)
+            }
+
+            multisig_signatures.reserve(count as usize);
+            for _ in 0..count {
+                let signature = SignatureId::read(reader)?;
+                if signature.id as usize >= multisig.participants.len() {
+                    return Err(ReaderError::InvalidValue)
+                }
+                multisig_signatures.push(signature);
+            }
+        }
+
         Ok(Transaction {
             version,
             source,
+            multisig,
             data,
             fee,
+            fee_asset,
             nonce,
             source_commitments,
             range_proof,
             reference,
             signature,
+            multisig_signatures,
+            hash_cache: OnceCell::new(),
         })
     }
 
     fn size(&self) -> usize {
         // Version byte
-        1
+        let mut size = 1
         + self.source.size()
-        + self.data.size()
+        + self.data.size_with_version(self.version)
         + self.fee.size()
         + self.nonce.size()
-        // Commitments length byte
-        + 1
+        // Commitments count
+        + count_size(self.version)
         + self.source_commitments.iter().map(|c| c.size()).sum::<usize>()
-        + self.range_proof.size()
+        + range_proof_size(self.source_commitments.len() + self.data.transfer_count())
         + self.reference.size()
-        + self.signature.size()
+        + self.signature.size();
+
+        if self.version >= 1 {
+            size += self.multisig.size() + self.fee_asset.size();
+        }
+
+        if self.multisig.is_some() {
+            // Signatures count byte
+            size += 1 + self.multisig_signatures.iter().map(|s| s.size()).sum::<usize>();
+        }
+
+        size
     }
 }
 
-impl Hashable for Transaction {}
+// Read a transaction from the whole buffer, trailing bytes are rejected
+impl TryFrom<&[u8]> for Transaction {
+    type Error = ReaderError;
 
-impl AsRef<Transaction> for Transaction {
-    fn as_ref(&self) -> &Transaction {
-        self
+    fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+        Transaction::from_bytes_exact(bytes)
     }
 }
 
-impl Serializer for Reference {
-    fn write(&self, writer: &mut Writer) {
-        self.hash.write(writer);
-        writer.write_u64(&self.topoheight);
+impl TryFrom<Vec<u8>> for Transaction {
+    type Error = ReaderError;
+
+    fn try_from(bytes: Vec<u8>) -> Result<Self, Self::Error> {
+        Transaction::try_from(bytes.as_slice())
     }
+}
 
-    fn read(reader: &mut Reader) -> Result<Reference, ReaderError> {
-        let hash = Hash::read(reader)?;
-        let topoheight = reader.read_u64()?;
-        Ok(Reference {
-            hash,
-            topoheight
-        })
+impl Hashable for Transaction {
+    fn hash(&self) -> Hash {
+        Transaction::hash(self).clone()
     }
+}
 
-    fn size(&self) -> usize {
-        self.hash.size() + self.topoheight.size()
+// Two transactions are equal when their content is, the cached hash is ignored
+// The content hash covers every serialized field, so it is used for both comparison and hashing
+impl PartialEq for Transaction {
+    fn eq(&self, other: &Self) -> bool {
+        Transaction::hash(self) == Transaction::hash(other)
+    }
+}
+
+impl Eq for Transaction {}
+
+impl std::hash::Hash for Transaction {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        std::hash::Hash::hash(Transaction::hash(self), state);
+    }
+}
+
+impl AsRef<Transaction> for Transaction {
+    fn as_ref(&self) -> &Transaction {
+        self
     }
 }
\ No newline at end of file
@@ -0,0 +1,114 @@
+use serde::{Deserialize, Serialize};
+use crate::{
+    crypto::{elgamal::CompressedPublicKey, Signature},
+    serializer::{Reader, ReaderError, Serializer, Writer}
+};
+use super::TransactionError;
+
+// Co-signers that must approve a transaction in addition to its source
+// It is part of the signed bytes of the transaction
+// Once a transaction carrying it is applied, it is registered for the source account
+// and every following transaction of that account must provide the same one
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct MultiSigPayload {
+    // Minimum count of co-signer signatures required
+    pub threshold: u8,
+    // Keys allowed to co-sign the transaction
+    pub participants: Vec<CompressedPublicKey>
+}
+
+// Signature of a co-signer
+// id is the index of the co-signer in the participants list
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SignatureId {
+    pub id: u8,
+    pub signature: Signature
+}
+
+impl MultiSigPayload {
+    pub fn new(threshold: u8, participants: Vec<CompressedPublicKey>) -> Self {
+        Self {
+            threshold,
+            participants
+        }
+    }
+
+    // Rules that don't depend on the chain state, shared by Transaction::validate and the builder
+    // The source can't be one of its own co-signers
+    pub fn validate(&self, version: u8, source: &CompressedPublicKey) -> Result<(), TransactionError> {
+        if version == 0 {
+            return Err(TransactionError::MultiSigNotSupported(version))
+        }
+
+        let count = self.participants.len();
+        if self.threshold == 0
+            || count > u8::MAX as usize
+            || self.threshold as usize > count
+            || self.participants.contains(source)
+            || self.has_duplicate_participants() {
+            return Err(TransactionError::InvalidMultiSig)
+        }
+
+        Ok(())
+    }
+
+    // Check if the participants list contains the same key twice
+    pub fn has_duplicate_participants(&self) -> bool {
+        self.participants.iter()
+            .enumerate()
+            .any(|(i, key)| self.participants[i + 1..].contains(key))
+    }
+}
+
+impl Serializer for MultiSigPayload {
+    fn write(&self, writer: &mut Writer) {
+        writer.write_u8(self.threshold);
+        writer.write_u8(self.participants.len() as u8);
+        for participant in &self.participants {
+            participant.write(writer);
+        }
+    }
+
+    fn read(reader: &mut Reader) -> Result<Self, ReaderError> {
+        let threshold = reader.read_u8()?;
+        let count = reader.read_u8()?;
+        if threshold == 0 || threshold > count {
+            return Err(ReaderError::InvalidValue)
+        }
+
+        let mut participants = Vec::with_capacity(count as usize);
+        for _ in 0..count {
+            participants.push(CompressedPublicKey::read(reader)?);
+        }
+
+        Ok(Self {
+            threshold,
+            participants
+        })
+    }
+
+    fn size(&self) -> usize {
+        // threshold byte and participants count byte
+        1 + 1 + self.participants.iter().map(|p| p.size()).sum::<usize>()
+    }
+}
+
+impl Serializer for SignatureId {
+    fn write(&self, writer: &mut Writer) {
+        writer.write_u8(self.id);
+        self.signature.write(writer);
+    }
+
+    fn read(reader: &mut Reader) -> Result<Self, ReaderError> {
+        let id = reader.read_u8()?;
+        let signature = Signature::read(reader)?;
+        Ok(Self {
+            id,
+            signature
+        })
+    }
+
+    fn size(&self) -> usize {
+        1 + self.signature.size()
+    }
+}
@@ -0,0 +1,538 @@
+use std::{collections::HashSet, fmt};
+use indexmap::IndexMap;
+use thiserror::Error;
+use crate::{
+    config::{COIN_DECIMALS, MAXIMUM_SUPPLY},
+    crypto::{
+        elgamal::CompressedPublicKey,
+        AddressError,
+        Hash,
+        HashParseError
+    },
+    serializer::{Reader, ReaderError, Serializer, Writer}
+};
+use super::amount::{format_amount, parse_amount, AmountError};
+
+// Scheme of a payment URI: xel:ADDRESS?amount=1.5&asset=HASH
+pub const PAYMENT_URI_SCHEME: &str = "xel";
+
+// Flags of the binary payload, all other bits must be unset
+const FLAG_MAINNET: u8 = 1;
+const FLAG_AMOUNT: u8 = 1 << 1;
+const FLAG_ASSET: u8 = 1 << 2;
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum PaymentUriError {
+    #[error("Invalid scheme, expected '{}:'", PAYMENT_URI_SCHEME)]
+    InvalidScheme,
+    #[error("Invalid address: {}", _0)]
+    Address(String),
+    #[error("Invalid amount: {}", _0)]
+    Amount(#[from] AmountError),
+    #[error("Amount {} must be above zero and up to the maximum supply", _0)]
+    AmountOutOfRange(u64),
+    #[error("Invalid asset: {}", _0)]
+    Asset(#[from] HashParseError),
+    #[error("Invalid parameter '{}'", _0)]
+    InvalidParam(String),
+    #[error("Parameter '{}' is present more than once", _0)]
+    DuplicateParam(String),
+    #[error("Parameter '{}' is reserved", _0)]
+    ReservedParam(String),
+    #[error("Invalid percent encoding in '{}'", _0)]
+    InvalidEncoding(String)
+}
+
+impl From<AddressError> for PaymentUriError {
+    fn from(err: AddressError) -> Self {
+        Self::Address(err.to_string())
+    }
+}
+
+// Payment request that can be shared as a link or scanned by a wallet
+// The amount is written in coins using COIN_DECIMALS, and no asset means XELIS
+// Keys and values are percent-encoded in the text form
+// Unknown parameters are kept as is, in their original order, so a wallet
+// forwarding the URI doesn't drop information it doesn't understand
+// Fields are only set through the constructors so an invalid amount can't be shared
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PaymentUri {
+    mainnet: bool,
+    destination: CompressedPublicKey,
+    amount: Option<u64>,
+    asset: Option<Hash>,
+    params: IndexMap<String, String>
+}
+
+// Parameters handled by PaymentUri itself
+const RESERVED_PARAMS: [&str; 2] = ["amount", "asset"];
+
+// Percent-encode everything except the unreserved characters of RFC 3986
+fn encode_component(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => encoded.push(byte as char),
+            _ => encoded.push_str(&format!("%{:02X}", byte))
+        }
+    }
+    encoded
+}
+
+// Decode a percent-encoded component, '+' is kept as is
+fn decode_component(value: &str) -> Result<String, PaymentUriError> {
+    let invalid = || PaymentUriError::InvalidEncoding(value.to_owned());
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = value.get(i + 1..i + 3).ok_or_else(invalid)?;
+            decoded.push(u8::from_str_radix(hex, 16).map_err(|_| invalid())?);
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8(decoded).map_err(|_| invalid())
+}
+
+// Requesting nothing or more than what can exist is refused
+fn check_amount(amount: u64) -> Result<u64, PaymentUriError> {
+    if amount == 0 || amount > MAXIMUM_SUPPLY {
+        return Err(PaymentUriError::AmountOutOfRange(amount))
+    }
+    Ok(amount)
+}
+
+impl PaymentUri {
+    // Create a payment URI without any amount or asset
+    pub fn new(mainnet: bool, destination: CompressedPublicKey) -> Self {
+        Self {
+            mainnet,
+            destination,
+            amount: None,
+            asset: None,
+            params: IndexMap::new()
+        }
+    }
+
+    // Request a specific amount in atomic units
+    pub fn with_amount(mut self, amount: u64) -> Result<Self, PaymentUriError> {
+        self.amount = Some(check_amount(amount)?);
+        Ok(self)
+    }
+
+    // Request an asset other than XELIS
+    pub fn with_asset(mut self, asset: Hash) -> Self {
+        self.asset = Some(asset);
+        self
+    }
+
+    // Add a parameter that is not known by this version
+    // Reserved parameters must be set using their own constructor
+    pub fn with_param(mut self, key: String, value: String) -> Result<Self, PaymentUriError> {
+        if key.is_empty() {
+            return Err(PaymentUriError::InvalidParam(key))
+        }
+
+        if RESERVED_PARAMS.contains(&key.as_str()) {
+            return Err(PaymentUriError::ReservedParam(key))
+        }
+
+        self.params.insert(key, value);
+        Ok(self)
+    }
+
+    pub fn is_mainnet(&self) -> bool {
+        self.mainnet
+    }
+
+    pub fn get_destination(&self) -> &CompressedPublicKey {
+        &self.destination
+    }
+
+    pub fn get_amount(&self) -> Option<u64> {
+        self.amount
+    }
+
+    pub fn get_asset(&self) -> Option<&Hash> {
+        self.asset.as_ref()
+    }
+
+    pub fn get_params(&self) -> &IndexMap<String, String> {
+        &self.params
+    }
+
+    // Parse a payment URI, its address must be on the expected network
+    pub fn parse(uri: &str, mainnet: bool) -> Result<Self, PaymentUriError> {
+        let rest = uri.strip_prefix(PAYMENT_URI_SCHEME)
+            .and_then(|rest| rest.strip_prefix(':'))
+            .ok_or(PaymentUriError::InvalidScheme)?;
+
+        let (address, query) = match rest.split_once('?') {
+            Some((address, query)) => (address, query),
+            None => (rest, "")
+        };
+
+        let destination = CompressedPublicKey::from_address(address, mainnet)?;
+        let mut payment = Self::new(mainnet, destination);
+        let mut seen = HashSet::new();
+        for param in query.split('&').filter(|param| !param.is_empty()) {
+            let (key, value) = param.split_once('=')
+                .ok_or_else(|| PaymentUriError::InvalidParam(param.to_owned()))?;
+
+            if key.is_empty() {
+                return Err(PaymentUriError::InvalidParam(param.to_owned()))
+            }
+
+            let key = decode_component(key)?;
+            let value = decode_component(value)?;
+            if !seen.insert(key.clone()) {
+                return Err(PaymentUriError::DuplicateParam(key))
+            }
+
+            match key.as_str() {
+                "amount" => payment.amount = Some(check_amount(parse_amount(&value, COIN_DECIMALS)?)?),
+                "asset" => payment.asset = Some(Hash::from_hex(&value)?),
+                _ => {
+                    payment.params.insert(key, value);
+                }
+            }
+        }
+
+        Ok(payment)
+    }
+
+    // Encode the payment request as a compact binary payload for QR codes
+    // The address is stored as its raw 32 bytes instead of bech32
+    pub fn to_qr_payload(&self) -> Vec<u8> {
+        self.to_bytes()
+    }
+
+    // Decode a payload built by to_qr_payload, no trailing bytes are allowed
+    pub fn from_qr_payload(bytes: &[u8]) -> Result<Self, ReaderError> {
+        Self::from_bytes_exact(bytes)
+    }
+}
+
+// Write a string prefixed by its length as a varint
+fn write_param(writer: &mut Writer, value: &str) {
+    writer.write_varint(value.len() as u64);
+    writer.write_bytes(value.as_bytes());
+}
+
+fn read_param(reader: &mut Reader) -> Result<String, ReaderError> {
+    let len = reader.read_varint()?;
+    if len > reader.size() as u64 {
+        return Err(ReaderError::InvalidSize)
+    }
+    reader.read_string_with_size(len as usize)
+}
+
+// Binary layout: flags, destination, amount as varint, asset, params count as varint and params
+impl Serializer for PaymentUri {
+    fn write(&self, writer: &mut Writer) {
+        let mut flags = 0;
+        if self.mainnet {
+            flags |= FLAG_MAINNET;
+        }
+        if self.amount.is_some() {
+            flags |= FLAG_AMOUNT;
+        }
+        if self.asset.is_some() {
+            flags |= FLAG_ASSET;
+        }
+
+        writer.write_u8(flags);
+        self.destination.write(writer);
+        if let Some(amount) = self.amount {
+            writer.write_varint(amount);
+        }
+        if let Some(asset) = &self.asset {
+            writer.write_hash(asset);
+        }
+
+        writer.write_varint(self.params.len() as u64);
+        for (key, value) in &self.params {
+            write_param(writer, key);
+            write_param(writer, value);
+        }
+    }
+
+    fn size(&self) -> usize {
+        let param_size = |value: &str| Writer::varint_size(value.len() as u64) + value.len();
+
+        // 1 for the flags
+        1 + self.destination.size()
+        + self.amount.map_or(0, Writer::varint_size)
+        + self.asset.as_ref().map_or(0, Serializer::size)
+        + Writer::varint_size(self.params.len() as u64)
+        + self.params.iter().map(|(key, value)| param_size(key) + param_size(value)).sum::<usize>()
+    }
+
+    fn read(reader: &mut Reader) -> Result<Self, ReaderError> {
+        let flags = reader.read_u8()?;
+        if flags & !(FLAG_MAINNET | FLAG_AMOUNT | FLAG_ASSET) != 0 {
+            return Err(ReaderError::InvalidValue)
+        }
+
+        // Address must be a valid point
+        let destination = CompressedPublicKey::read(reader)?;
+        if destination.decompress().is_err() {
+            return Err(ReaderError::InvalidValue)
+        }
+
+        let amount = if flags & FLAG_AMOUNT != 0 {
+            let amount = reader.read_varint()?;
+            Some(check_amount(amount).map_err(|_| ReaderError::InvalidValue)?)
+        } else {
+            None
+        };
+
+        let asset = if flags & FLAG_ASSET != 0 {
+            Some(reader.read_hash()?)
+        } else {
+            None
+        };
+
+        // Each param takes at least two bytes
+        let count = reader.read_varint()?;
+        if count > reader.size() as u64 / 2 {
+            return Err(ReaderError::InvalidSize)
+        }
+
+        let mut params = IndexMap::with_capacity(count as usize);
+        for _ in 0..count {
+            let key = read_param(reader)?;
+            // Known params must use their own fields
+            if key.is_empty() || RESERVED_PARAMS.contains(&key.as_str()) {
+                return Err(ReaderError::InvalidValue)
+            }
+
+            let value = read_param(reader)?;
+            if params.insert(key, value).is_some() {
+                return Err(ReaderError::InvalidValue)
+            }
+        }
+
+        Ok(Self {
+            mainnet: flags & FLAG_MAINNET != 0,
+            destination,
+            amount,
+            asset,
+            params
+        })
+    }
+}
+
+impl fmt::Display for PaymentUri {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", PAYMENT_URI_SCHEME, self.destination.to_address_string(self.mainnet))?;
+
+        let known = self.amount.map(|amount| ("amount", format_amount(amount, COIN_DECIMALS)))
+            .into_iter()
+            .chain(self.asset.as_ref().map(|asset| ("asset", asset.to_hex())));
+        let unknown = self.params.iter().map(|(key, value)| (key.as_str(), value.clone()));
+
+        for (i, (key, value)) in known.chain(unknown).enumerate() {
+            let separator = if i == 0 { '?' } else { '&' };
+            write!(f, "{}{}={}", separator, encode_component(key), encode_component(&value))?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        config::COIN_VALUE,
+        crypto::KeyPair
+    };
+    use super::*;
+
+    #[test]
+    fn test_payment_uri_full() {
+        let key = KeyPair::new().get_public_key().compress();
+        let address = key.to_address_string(true);
+        let asset = Hash::max();
+        let uri = format!("xel:{}?amount=1.5&asset={}&label=coffee&memo=", address, asset);
+
+        let payment = PaymentUri::parse(&uri, true).unwrap();
+        assert_eq!(payment.destination, key);
+        assert_eq!(payment.amount, Some(COIN_VALUE + COIN_VALUE / 2));
+        assert_eq!(payment.asset, Some(asset));
+        assert_eq!(payment.params.get("label").map(String::as_str), Some("coffee"));
+        assert_eq!(payment.params.get("memo").map(String::as_str), Some(""));
+
+        // Unknown parameters are preserved through a round trip
+        assert_eq!(payment.to_string(), uri);
+        assert_eq!(PaymentUri::parse(&payment.to_string(), true).unwrap(), payment);
+    }
+
+    #[test]
+    fn test_payment_uri_address_only() {
+        let key = KeyPair::new().get_public_key().compress();
+        let uri = format!("xel:{}", key.to_address_string(false));
+
+        let payment = PaymentUri::parse(&uri, false).unwrap();
+        assert_eq!(payment, PaymentUri::new(false, key));
+        assert_eq!(payment.to_string(), uri);
+
+        // An empty query is accepted
+        assert_eq!(PaymentUri::parse(&format!("{}?", uri), false).unwrap(), payment);
+    }
+
+    #[test]
+    fn test_payment_uri_qr_payload() {
+        let key = KeyPair::new().get_public_key().compress();
+        let uri = format!("xel:{}?amount=1.5&asset={}&label=coffee", key.to_address_string(true), Hash::max());
+        let payment = PaymentUri::parse(&uri, true).unwrap();
+
+        let payload = payment.to_qr_payload();
+        assert!(payload.len() < uri.len());
+        assert_eq!(PaymentUri::from_qr_payload(&payload).unwrap(), payment);
+
+        let payment = PaymentUri::new(false, key);
+        let payload = payment.to_qr_payload();
+        // flags, key and params count
+        assert_eq!(payload.len(), 1 + 32 + 1);
+        assert_eq!(PaymentUri::from_qr_payload(&payload).unwrap(), payment);
+    }
+
+    #[test]
+    fn test_payment_uri_qr_payload_malformed() {
+        let key = KeyPair::new().get_public_key().compress();
+        let payment = PaymentUri::new(true, key.clone()).with_amount(COIN_VALUE).unwrap();
+        let payload = payment.to_qr_payload();
+        assert!(PaymentUri::from_qr_payload(&payload).is_ok());
+
+        let decode = PaymentUri::from_qr_payload;
+
+        // Truncated and trailing bytes
+        assert!(decode(&payload[..payload.len() - 1]).is_err());
+        assert!(matches!(decode(&[payload.as_slice(), &[0]].concat()), Err(ReaderError::InvalidValue)));
+
+        // Unknown flag
+        let mut invalid = payload.clone();
+        invalid[0] |= 1 << 3;
+        assert!(matches!(decode(&invalid), Err(ReaderError::InvalidValue)));
+
+        // Address that is not a valid point
+        let mut invalid = payload.clone();
+        invalid[1..33].copy_from_slice(&[0xff; 32]);
+        assert!(matches!(decode(&invalid), Err(ReaderError::InvalidValue)));
+
+        // Zero amount and amount above the maximum supply
+        for amount in [0, MAXIMUM_SUPPLY + 1] {
+            let mut writer = Writer::new();
+            writer.write_u8(FLAG_MAINNET | FLAG_AMOUNT);
+            key.write(&mut writer);
+            writer.write_varint(amount);
+            writer.write_varint(0);
+            assert!(matches!(decode(writer.as_bytes()), Err(ReaderError::InvalidValue)));
+        }
+
+        // Known param hidden in the unknown params
+        let mut writer = Writer::new();
+        writer.write_u8(FLAG_MAINNET);
+        key.write(&mut writer);
+        writer.write_varint(1);
+        write_param(&mut writer, "amount");
+        write_param(&mut writer, "1");
+        assert!(matches!(decode(writer.as_bytes()), Err(ReaderError::InvalidValue)));
+
+        // Huge declared params count
+        let mut writer = Writer::new();
+        writer.write_u8(FLAG_MAINNET);
+        key.write(&mut writer);
+        writer.write_varint(u64::MAX);
+        assert!(matches!(decode(writer.as_bytes()), Err(ReaderError::InvalidSize)));
+    }
+
+    #[test]
+    fn test_payment_uri_malformed() {
+        let key = KeyPair::new().get_public_key().compress();
+        let address = key.to_address_string(true);
+
+        assert_eq!(PaymentUri::parse(&address, true), Err(PaymentUriError::InvalidScheme));
+        assert_eq!(PaymentUri::parse(&format!("bitcoin:{}", address), true), Err(PaymentUriError::InvalidScheme));
+        assert!(matches!(PaymentUri::parse("xel:", true), Err(PaymentUriError::Address(_))));
+        assert!(matches!(PaymentUri::parse("xel:notanaddress", true), Err(PaymentUriError::Address(_))));
+        // Testnet wallet must not accept a mainnet request
+        assert!(matches!(PaymentUri::parse(&format!("xel:{}", address), false), Err(PaymentUriError::Address(_))));
+
+        let with_query = |query: &str| PaymentUri::parse(&format!("xel:{}?{}", address, query), true);
+        assert_eq!(with_query("amount=-1"), Err(PaymentUriError::Amount(AmountError::InvalidCharacter('-'))));
+        assert_eq!(with_query("amount=0.000000001"), Err(PaymentUriError::Amount(AmountError::TooManyDecimals(COIN_DECIMALS))));
+        assert_eq!(with_query("asset=00"), Err(PaymentUriError::Asset(HashParseError::InvalidLength(2))));
+        assert_eq!(with_query("amount"), Err(PaymentUriError::InvalidParam("amount".to_owned())));
+        assert_eq!(with_query("=1"), Err(PaymentUriError::InvalidParam("=1".to_owned())));
+        assert_eq!(with_query("amount=1&amount=2"), Err(PaymentUriError::DuplicateParam("amount".to_owned())));
+        assert_eq!(with_query("amount=0"), Err(PaymentUriError::AmountOutOfRange(0)));
+        let above_supply = format_amount(MAXIMUM_SUPPLY + 1, COIN_DECIMALS);
+        assert_eq!(with_query(&format!("amount={}", above_supply)), Err(PaymentUriError::AmountOutOfRange(MAXIMUM_SUPPLY + 1)));
+    }
+
+    #[test]
+    fn test_payment_uri_percent_encoding() {
+        let key = KeyPair::new().get_public_key().compress();
+        let payment = PaymentUri::new(true, key.clone())
+            .with_param("note & more".to_owned(), "a=b&c?d%e ü".to_owned()).unwrap();
+
+        let uri = payment.to_string();
+        assert_eq!(uri, format!("xel:{}?note%20%26%20more=a%3Db%26c%3Fd%25e%20%C3%BC", key.to_address_string(true)));
+        assert_eq!(PaymentUri::parse(&uri, true).unwrap(), payment);
+
+        let address = key.to_address_string(true);
+        let with_query = |query: &str| PaymentUri::parse(&format!("xel:{}?{}", address, query), true);
+        // Known params can be encoded too
+        assert_eq!(with_query("%61mount=1").unwrap().get_amount(), Some(COIN_VALUE));
+        assert_eq!(with_query("amount=1&%61mount=2"), Err(PaymentUriError::DuplicateParam("amount".to_owned())));
+        assert_eq!(with_query("memo=%2"), Err(PaymentUriError::InvalidEncoding("%2".to_owned())));
+        assert_eq!(with_query("memo=%zz"), Err(PaymentUriError::InvalidEncoding("%zz".to_owned())));
+        assert_eq!(with_query("memo=%ff"), Err(PaymentUriError::InvalidEncoding("%ff".to_owned())));
+    }
+
+    #[test]
+    fn test_payment_uri_reserved_params() {
+        let key = KeyPair::new().get_public_key().compress();
+        let payment = PaymentUri::new(true, key);
+        assert_eq!(payment.clone().with_param("amount".to_owned(), "1".to_owned()), Err(PaymentUriError::ReservedParam("amount".to_owned())));
+        assert_eq!(payment.clone().with_param("asset".to_owned(), "00".to_owned()), Err(PaymentUriError::ReservedParam("asset".to_owned())));
+        assert_eq!(payment.with_param(String::new(), "1".to_owned()), Err(PaymentUriError::InvalidParam(String::new())));
+    }
+
+    #[test]
+    fn test_payment_uri_amount_range() {
+        let key = KeyPair::new().get_public_key().compress();
+        let payment = PaymentUri::new(true, key);
+        assert_eq!(payment.clone().with_amount(0), Err(PaymentUriError::AmountOutOfRange(0)));
+        assert_eq!(payment.clone().with_amount(MAXIMUM_SUPPLY + 1), Err(PaymentUriError::AmountOutOfRange(MAXIMUM_SUPPLY + 1)));
+
+        let payment = payment.with_amount(MAXIMUM_SUPPLY).unwrap();
+        assert_eq!(payment.get_amount(), Some(MAXIMUM_SUPPLY));
+    }
+
+    #[test]
+    fn test_payment_uri_qr_round_trip() {
+        let key = KeyPair::new().get_public_key().compress();
+        for amount in [1, COIN_VALUE + COIN_VALUE / 2, MAXIMUM_SUPPLY] {
+            let payment = PaymentUri::new(false, key.clone())
+                .with_amount(amount).unwrap()
+                .with_asset(Hash::max())
+                .with_param("label".to_owned(), "coffee".to_owned()).unwrap();
+
+            // Text URI to QR payload and back to the same text URI
+            let uri = payment.to_string();
+            let parsed = PaymentUri::parse(&uri, false).unwrap();
+            assert_eq!(parsed, payment);
+
+            let decoded = PaymentUri::from_qr_payload(&parsed.to_qr_payload()).unwrap();
+            assert_eq!(decoded, payment);
+            assert_eq!(decoded.to_string(), uri);
+        }
+    }
+}
@@ -0,0 +1,60 @@
+use serde::{Deserialize, Serialize};
+use crate::crypto::{elgamal::CompressedPublicKey, Hash};
+use super::{Transaction, TransactionType};
+
+// Public part of a transfer
+// Its amount is encrypted and can't be displayed
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct TransferSummary {
+    pub asset: Hash,
+    pub destination: CompressedPublicKey
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TransactionTypeSummary {
+    Transfers(Vec<TransferSummary>),
+    Burn {
+        asset: Hash,
+        amount: u64
+    }
+}
+
+// Metadata of a transaction that can be displayed by explorers
+// without any commitment or proof
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct TransactionSummary {
+    pub version: u8,
+    pub source: CompressedPublicKey,
+    pub fee: u64,
+    pub nonce: u64,
+    pub data: TransactionTypeSummary
+}
+
+impl Transaction {
+    // Build the public summary of this transaction
+    pub fn summary(&self) -> TransactionSummary {
+        let data = match &self.data {
+            TransactionType::Transfers(transfers) => TransactionTypeSummary::Transfers(
+                transfers.iter()
+                    .map(|transfer| TransferSummary {
+                        asset: transfer.asset.clone(),
+                        destination: transfer.destination.clone()
+                    })
+                    .collect()
+            ),
+            TransactionType::Burn(payload) => TransactionTypeSummary::Burn {
+                asset: payload.asset.clone(),
+                amount: payload.amount
+            }
+        };
+
+        TransactionSummary {
+            version: self.version,
+            source: self.source.clone(),
+            fee: self.fee,
+            nonce: self.nonce,
+            data
+        }
+    }
+}
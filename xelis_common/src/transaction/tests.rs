@@ -1,37 +1,69 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use async_trait::async_trait;
+use bulletproofs::RangeProof;
+use curve25519_dalek::{ristretto::CompressedRistretto, Scalar};
+use once_cell::sync::Lazy;
+use proptest::prelude::*;
 use crate::{
     account::CiphertextCache,
-    api::{DataElement, DataValue},
-    config::{COIN_VALUE, XELIS_ASSET},
+    api::{DataConversionError, DataElement, DataValue, MAX_ELEMENT_ITEMS},
+    config::{COIN_VALUE, MAX_TRANSACTION_SIZE, XELIS_ASSET},
     crypto::{
-        elgamal::{Ciphertext, PedersenOpening},
+        elgamal::{Ciphertext, CompressedCommitment, CompressedHandle, CompressedPublicKey, PedersenOpening, G},
+        hash,
+        proofs::{CiphertextValidityProof, CommitmentEqProof, ProofGenerationError, ProofVerificationError, BULLET_PROOF_SIZE},
         Address,
         Hash,
+        HASH_SIZE,
+        Hashable,
         KeyPair,
-        PublicKey
+        PublicKey,
+        Signature,
+        SIGNATURE_SIZE
     },
-    serializer::Serializer,
-    transaction::{TransactionType, MAX_TRANSFER_COUNT}
+    serializer::{Reader, ReaderError, Serializer, Writer},
+    transaction::{
+        TransactionType,
+        BYTE_WEIGHT,
+        BURN_WEIGHT,
+        EXTRA_DATA_LIMIT_SIZE,
+        MAX_RANGE_PROOF_COMMITMENTS,
+        MAX_TRANSFER_COUNT,
+        MAX_TRANSFER_COUNT_V0,
+        MAX_TX_VERSION,
+        TRANSFER_WEIGHT
+    }
 };
 use super::{
     extra_data::{
         derive_shared_key_from_opening,
-        PlaintextData
+        PlaintextData,
+        UnknownExtraDataFormat
     },
+    multisig::{MultiSigPayload, SignatureId},
+    summary::{TransactionSummary, TransactionTypeSummary, TransferSummary},
     builder::{
         AccountState,
         FeeBuilder,
         FeeHelper,
+        GenerationError,
         TransactionBuilder,
         TransactionTypeBuilder,
-        TransferBuilder
+        TransferBuilder,
+        split_transfers
     },
-    verify::BlockchainVerificationState,
+    verify::{build_verification_pool, verify_transactions_parallel, verify_transactions_parallel_in, BlockchainVerificationState, VerificationError},
     BurnPayload,
     Reference,
     Role,
-    Transaction
+    SourceCommitment,
+    Transaction,
+    TransactionError,
+    TransferPayload,
+    TransferPayloadRef,
+    max_source_commitments,
+    max_transfer_count,
+    write_count
 };
 
 struct AccountChainState {
@@ -41,127 +73,1718 @@ struct AccountChainState {
 
 struct ChainState {
     accounts: HashMap<PublicKey, AccountChainState>,
+    multisig: HashMap<PublicKey, MultiSigPayload>,
+}
+
+#[derive(Clone)]
+struct Balance {
+    ciphertext: CiphertextCache,
+    balance: u64,
+}
+
+#[derive(Clone)]
+struct Account {
+    balances: HashMap<Hash, Balance>,
+    keypair: KeyPair,
+    nonce: u64,
+}
+
+impl Account {
+    fn new() -> Self {
+        Self {
+            balances: HashMap::new(),
+            keypair: KeyPair::new(),
+            nonce: 0,
+        }
+    }
+
+    fn set_balance(&mut self, asset: Hash, balance: u64) {
+        let ciphertext = self.keypair.get_public_key().encrypt(balance);
+        self.balances.insert(asset, Balance {
+            balance,
+            ciphertext: CiphertextCache::Decompressed(ciphertext),
+        });
+    }
+
+    fn address(&self) -> Address {
+        self.keypair.get_public_key().to_address(false)
+    }
+}
+
+// Chain state holding the balances and nonce of each account
+fn chain_state_for(accounts: &[&Account]) -> ChainState {
+    let mut chain = ChainState {
+        accounts: HashMap::new(),
+        multisig: HashMap::new(),
+    };
+
+    for account in accounts {
+        let balances = account.balances.iter()
+            .map(|(asset, balance)| (asset.clone(), balance.ciphertext.clone().take_ciphertext().unwrap()))
+            .collect();
+
+        chain.accounts.insert(account.keypair.get_public_key().compress(), AccountChainState {
+            balances,
+            nonce: account.nonce,
+        });
+    }
+
+    chain
+}
+
+struct AccountStateImpl {
+    balances: HashMap<Hash, Balance>,
+    reference: Reference,
+    nonce: u64,
+}
+
+fn create_tx_for(account: Account, destination: Address, amount: u64, extra_data: Option<DataElement>) -> Transaction {
+    let mut state = AccountStateImpl {
+        balances: account.balances,
+        nonce: account.nonce,
+        reference: Reference {
+            topoheight: 0,
+            hash: Hash::zero(),
+        },
+    };
+
+    let data = TransactionTypeBuilder::Transfers(vec![TransferBuilder {
+        amount,
+        destination,
+        asset: XELIS_ASSET,
+        extra_data,
+    }]);
+
+
+    let builder = TransactionBuilder::new(0, account.keypair.get_public_key().compress(), data, FeeBuilder::Multiplier(1f64));
+    let estimated_size = builder.estimate_size();
+    let tx = builder.build(&mut state, &account.keypair).unwrap();
+    assert!(estimated_size == tx.size(), "expected {} bytes got {} bytes", tx.size(), estimated_size);
+    assert!(tx.to_bytes().len() == estimated_size);
+
+    tx
+}
+
+fn build_tx_with(account: &Account, data: TransactionTypeBuilder) -> (Result<Transaction, GenerationError<()>>, AccountStateImpl) {
+    let mut state = AccountStateImpl {
+        balances: account.balances.clone(),
+        nonce: account.nonce,
+        reference: Reference {
+            topoheight: 0,
+            hash: Hash::zero(),
+        },
+    };
+
+    let builder = TransactionBuilder::new(0, account.keypair.get_public_key().compress(), data, FeeBuilder::Multiplier(1f64));
+    let result = builder.build(&mut state, &account.keypair);
+    (result, state)
+}
+
+#[test]
+fn test_encrypt_decrypt() {
+    let r = PedersenOpening::generate_new();
+    let key = derive_shared_key_from_opening(&r);
+    let message = "Hello, World!".as_bytes().to_vec();
+
+    let plaintext = PlaintextData(message.clone());
+    let cipher = plaintext.encrypt_in_place_with_aead(&key);
+    let decrypted = cipher.decrypt_in_place(&key).unwrap();
+
+    assert_eq!(decrypted.0, message);
+}
+
+#[test]
+fn test_encrypt_decrypt_two_parties() {
+    let mut alice = Account::new();
+    alice.balances.insert(XELIS_ASSET, Balance {
+        balance: 100 * COIN_VALUE,
+        ciphertext: CiphertextCache::Decompressed(alice.keypair.get_public_key().encrypt(100 * COIN_VALUE)),
+    });
+
+    let bob = Account::new();
+
+    let payload = DataElement::Value(DataValue::String("Hello, World!".to_string()));
+    let tx = create_tx_for(alice.clone(), bob.address(), 50, Some(payload.clone()));
+    let TransactionType::Transfers(transfers) = tx.get_data() else {
+        unreachable!()
+    };
+
+    let transfer = &transfers[0];
+    let cipher = transfer.extra_data.clone().unwrap();
+    // Verify the extra data from alice (sender)
+    {
+        let decrypted = cipher.decrypt_v2(&alice.keypair.get_private_key(), Role::Sender).unwrap();
+        assert_eq!(decrypted, payload);
+    }
+
+    // Verify the extra data from bob (receiver)
+    {
+        let decrypted = cipher.decrypt_v2(&bob.keypair.get_private_key(), Role::Receiver).unwrap();
+        assert_eq!(decrypted, payload);
+    }
+
+    // Verify the extra data from alice (sender) with the wrong key
+    {
+        let decrypted = cipher.decrypt_v2(&bob.keypair.get_private_key(), Role::Sender);
+        assert!(decrypted.is_err());
+    }
+}
+
+#[test]
+fn test_transfer_encrypted_extra_data() {
+    let mut alice = Account::new();
+    let bob = Account::new();
+    let charlie = Account::new();
+    alice.set_balance(XELIS_ASSET, 100 * COIN_VALUE);
+
+    let tx = create_tx_for(alice.clone(), bob.address(), 50, None);
+    let transfer = tx.get_data().transfers().next().unwrap();
+    assert!(transfer.decrypt_extra_data(&bob.keypair.get_private_key(), Role::Receiver).unwrap().is_none());
+
+    let bob_key = bob.keypair.get_public_key().compress();
+    let builder = TransferBuilder {
+        amount: 50,
+        destination: bob.address(),
+        asset: XELIS_ASSET,
+        extra_data: None,
+    };
+
+    // Memo is encrypted while building, so it is covered by the signature
+    let memo = b"invoice #42";
+    let transfer = builder.clone().with_encrypted_extra_data(&bob_key, memo).unwrap();
+    let (result, _) = build_tx_with(&alice, TransactionTypeBuilder::Transfers(vec![transfer]));
+    let tx = result.unwrap();
+    assert!(tx.verify_signature());
+
+    let transfer = tx.get_data().transfers().next().unwrap();
+    let expected = DataElement::Value(DataValue::Bytes(memo.to_vec()));
+    assert_eq!(transfer.decrypt_extra_data(&bob.keypair.get_private_key(), Role::Receiver).unwrap(), Some(expected.clone()));
+    assert_eq!(transfer.decrypt_extra_data(&alice.keypair.get_private_key(), Role::Sender).unwrap(), Some(expected));
+    // Wrong key
+    assert!(transfer.decrypt_extra_data(&charlie.keypair.get_private_key(), Role::Receiver).is_err());
+
+    // Recipient must be the destination of the transfer
+    let charlie_key = charlie.keypair.get_public_key().compress();
+    assert!(matches!(builder.clone().with_encrypted_extra_data(&charlie_key, memo), Err(TransactionError::ExtraDataRecipientMismatch)));
+
+    // Limit is applied on the encrypted size
+    let memo = vec![0; EXTRA_DATA_LIMIT_SIZE - 8];
+    assert!(DataElement::Value(DataValue::Bytes(memo.clone())).size() <= EXTRA_DATA_LIMIT_SIZE);
+    assert!(matches!(builder.with_encrypted_extra_data(&bob_key, &memo), Err(TransactionError::ExtraDataTooLarge(_))));
+}
+
+#[test]
+fn test_tx_signature() {
+    let mut alice = Account::new();
+    let bob = Account::new();
+
+    alice.set_balance(XELIS_ASSET, 100 * COIN_VALUE);
+
+    let tx = create_tx_for(alice, bob.address(), 50, None);
+    assert!(tx.verify_signature());
+
+    // Deserialized TX must still have a valid signature
+    let tx = Transaction::from_bytes(&tx.to_bytes()).unwrap();
+    assert!(tx.verify_signature());
+
+    // Tamper the fee byte, signature must be invalid
+    let mut bytes = tx.to_bytes();
+    let fee_offset = 1 + tx.get_source().size() + tx.get_data().size_with_version(tx.get_version());
+    bytes[fee_offset + 7] ^= 0x01;
+
+    let tampered = Transaction::from_bytes(&bytes).unwrap();
+    assert_eq!(tampered.get_fee(), tx.get_fee() ^ 0x01);
+    assert!(!tampered.verify_signature());
+    assert_eq!(tampered.check_signatures(), Err(TransactionError::BadSignature));
+    assert_eq!(tx.check_signatures(), Ok(()));
+}
+
+#[test]
+fn test_tx_validate_too_many_transfers() {
+    let mut alice = Account::new();
+    let bob = Account::new();
+
+    alice.set_balance(XELIS_ASSET, 100 * COIN_VALUE);
+
+    let mut tx = create_tx_for(alice, bob.address(), 50, None);
+    assert!(tx.validate().is_ok());
+
+    // Its count is written as a single byte, it must be rejected instead of truncated
+    if let TransactionType::Transfers(transfers) = &mut tx.data {
+        let transfer = transfers[0].clone();
+        transfers.resize(MAX_TRANSFER_COUNT_V0 + 1, transfer);
+    }
+
+    assert_eq!(tx.validate(), Err(TransactionError::TooManyTransfers(MAX_TRANSFER_COUNT_V0 + 1, MAX_TRANSFER_COUNT_V0)));
+}
+
+#[test]
+fn test_tx_validate_too_many_range_proof_commitments() {
+    let mut alice = Account::new();
+    let bob = Account::new();
+
+    alice.set_balance(XELIS_ASSET, 100 * COIN_VALUE);
+
+    let mut tx = create_tx_for(alice, bob.address(), 50, None);
+    tx.version = 1;
+
+    // Maximum transfers with the single XELIS source commitment fill the bulletproofs generators
+    if let TransactionType::Transfers(transfers) = &mut tx.data {
+        let transfer = transfers[0].clone();
+        transfers.resize_with(MAX_TRANSFER_COUNT, || TransferPayload {
+            destination: KeyPair::new().get_public_key().compress(),
+            ..transfer.clone()
+        });
+    }
+    assert_eq!(tx.source_commitments.len() + MAX_TRANSFER_COUNT, MAX_RANGE_PROOF_COMMITMENTS);
+    assert!(tx.validate().is_ok());
+
+    // One more source commitment can't be proven anymore
+    let commitment = tx.source_commitments[0].clone();
+    tx.source_commitments.push(commitment);
+    assert_eq!(tx.validate(), Err(TransactionError::TooManyRangeProofCommitments(MAX_RANGE_PROOF_COMMITMENTS + 1, MAX_RANGE_PROOF_COMMITMENTS)));
+}
+
+#[test]
+fn test_tx_signing_bytes() {
+    let mut alice = Account::new();
+    let bob = Account::new();
+
+    alice.set_balance(XELIS_ASSET, 100 * COIN_VALUE);
+
+    let keypair = alice.keypair.clone();
+    let mut tx = create_tx_for(alice, bob.address(), 50, None);
+
+    // Signing bytes are the serialized TX without the trailing signature
+    let bytes = tx.to_bytes();
+    let signing_bytes = tx.signing_bytes();
+    assert_eq!(signing_bytes.len(), bytes.len() - SIGNATURE_SIZE);
+    assert_eq!(signing_bytes[..], bytes[..bytes.len() - SIGNATURE_SIZE]);
+
+    // Layout starts with the version then the source key
+    assert_eq!(signing_bytes[0], tx.get_version());
+    assert_eq!(signing_bytes[1..33], tx.get_source().as_bytes()[..]);
+
+    // Signature from another key is rejected
+    tx.attach_signature(bob.keypair.sign(&signing_bytes));
+    assert!(!tx.verify_signature());
+
+    // Signature computed externally can be installed
+    tx.attach_signature(keypair.sign(&signing_bytes));
+    assert!(tx.verify_signature());
+}
+
+#[test]
+fn test_builder_empty_transfers() {
+    let mut alice = Account::new();
+    alice.set_balance(XELIS_ASSET, 100 * COIN_VALUE);
+
+    let (result, state) = build_tx_with(&alice, TransactionTypeBuilder::Transfers(Vec::new()));
+    assert!(matches!(result, Err(GenerationError::Transaction(TransactionError::EmptyTransfers))));
+    // Nonce must not be consumed by a rejected TX
+    assert_eq!(state.nonce, alice.nonce);
+}
+
+#[test]
+fn test_builder_too_many_transfers() {
+    let mut alice = Account::new();
+    let bob = Account::new();
+    alice.set_balance(XELIS_ASSET, 100 * COIN_VALUE);
+
+    let transfers = (0..MAX_TRANSFER_COUNT_V0 + 1).map(|_| TransferBuilder {
+        amount: 1,
+        destination: bob.address(),
+        asset: XELIS_ASSET,
+        extra_data: None,
+    }).collect();
+
+    let (result, state) = build_tx_with(&alice, TransactionTypeBuilder::Transfers(transfers));
+    assert!(matches!(result, Err(GenerationError::Transaction(TransactionError::TooManyTransfers(_, MAX_TRANSFER_COUNT_V0)))));
+    assert_eq!(state.nonce, alice.nonce);
+}
+
+#[test]
+fn test_transfers_count_serialization() {
+    let mut alice = Account::new();
+    let bob = Account::new();
+    alice.set_balance(XELIS_ASSET, 100 * COIN_VALUE);
+
+    let tx = create_tx_for(alice, bob.address(), 50, None);
+    let TransactionType::Transfers(transfers) = tx.get_data() else {
+        panic!("expected transfers")
+    };
+    let transfer = transfers[0].clone();
+
+    let round_trip = |count: usize, version: u8| {
+        let data = TransactionType::Transfers(vec![transfer.clone(); count]);
+        let mut writer = Writer::new();
+        data.write_with_version(&mut writer, version);
+        let bytes = writer.bytes();
+        assert_eq!(bytes.len(), data.size_with_version(version));
+
+        let mut reader = Reader::new(&bytes);
+        TransactionType::read_with_version(&mut reader, version)
+            .map(|data| match data {
+                TransactionType::Transfers(transfers) => transfers.len(),
+                TransactionType::Burn(_) => 0
+            })
+    };
+
+    assert_eq!(round_trip(MAX_TRANSFER_COUNT_V0, 0).unwrap(), MAX_TRANSFER_COUNT_V0);
+    assert_eq!(round_trip(MAX_TRANSFER_COUNT_V0, 1).unwrap(), MAX_TRANSFER_COUNT_V0);
+    assert_eq!(round_trip(MAX_TRANSFER_COUNT_V0 + 1, 1).unwrap(), MAX_TRANSFER_COUNT_V0 + 1);
+    assert_eq!(round_trip(MAX_TRANSFER_COUNT, 1).unwrap(), MAX_TRANSFER_COUNT);
+    assert!(round_trip(MAX_TRANSFER_COUNT + 1, 1).is_err());
+}
+
+#[test]
+fn test_single_transfer_fast_path() {
+    let mut alice = Account::new();
+    let bob = Account::new();
+    alice.set_balance(XELIS_ASSET, 100 * COIN_VALUE);
+
+    let tx = create_tx_for(alice, bob.address(), 50, None);
+    let transfer = tx.get_data().transfers().next().unwrap().clone();
+    let data = TransactionType::Transfers(vec![transfer.clone()]);
+
+    for version in 0..=MAX_TX_VERSION {
+        // Generic path: variant, count then each transfer
+        let mut writer = Writer::new();
+        writer.write_u8(1);
+        if version == 0 {
+            writer.write_u8(1);
+        } else {
+            writer.write_u16(1);
+        }
+        transfer.write(&mut writer);
+        let expected = writer.bytes();
+
+        let mut writer = Writer::new();
+        data.write_with_version(&mut writer, version);
+        let bytes = writer.bytes();
+        assert_eq!(bytes, expected);
+        assert_eq!(bytes.len(), data.size_with_version(version));
+
+        let mut reader = Reader::new(&bytes);
+        let TransactionType::Transfers(transfers) = TransactionType::read_with_version(&mut reader, version).unwrap() else {
+            panic!("expected transfers")
+        };
+        assert_eq!(reader.remaining(), 0);
+        assert_eq!(transfers.len(), 1);
+        assert_eq!(transfers[0].to_bytes(), transfer.to_bytes());
+
+        // Same checks as the generic path: a truncated transfer is rejected
+        let mut reader = Reader::new(&bytes[..bytes.len() - 1]);
+        assert!(TransactionType::read_with_version(&mut reader, version).is_err());
+    }
+
+    // Two transfers still use the generic path and produce the same layout
+    let data = TransactionType::Transfers(vec![transfer.clone(); 2]);
+    let mut writer = Writer::new();
+    data.write_with_version(&mut writer, 1);
+    let bytes = writer.bytes();
+    let single = transfer.to_bytes();
+    assert_eq!(&bytes[..3], &[1, 0, 2]);
+    assert_eq!(&bytes[3..], [single.clone(), single].concat());
+}
+
+#[test]
+fn test_transfers_large_batch_round_trip() {
+    let mut alice = Account::new();
+    let bob = Account::new();
+    alice.set_balance(XELIS_ASSET, 100 * COIN_VALUE);
+
+    let mut tx = create_tx_for(alice, bob.address(), 50, None);
+    // Above the version 0 limit, only version 1 can encode the count
+    tx.version = 1;
+    let TransactionType::Transfers(transfers) = &mut tx.data else {
+        panic!("expected transfers")
+    };
+    let transfer = transfers[0].clone();
+    transfers.resize(MAX_TRANSFER_COUNT, transfer.clone());
+    tx.range_proof = fixture_range_proof(tx.source_commitments.len() + MAX_TRANSFER_COUNT);
+
+    // Same bytes as the count followed by each transfer
+    let mut writer = Writer::new();
+    writer.write_u8(1);
+    writer.write_u16(MAX_TRANSFER_COUNT as u16);
+    for _ in 0..MAX_TRANSFER_COUNT {
+        transfer.write(&mut writer);
+    }
+    let expected = writer.bytes();
+
+    let mut writer = Writer::new();
+    tx.get_data().write_with_version(&mut writer, tx.get_version());
+    assert_eq!(writer.bytes(), expected);
+
+    let bytes = tx.to_bytes();
+    let decoded = Transaction::from_bytes(&bytes).unwrap();
+    assert_eq!(decoded.get_data().transfers().count(), MAX_TRANSFER_COUNT);
+    assert_eq!(decoded.to_bytes(), bytes);
+    assert_eq!(decoded.hash(), tx.hash());
 }
 
-#[derive(Clone)]
-struct Balance {
-    ciphertext: CiphertextCache,
-    balance: u64,
-}
+// Check that every field of the borrowed view matches the owned transfer
+fn assert_transfer_ref_eq(view: &TransferPayloadRef, transfer: &TransferPayload) {
+    assert_eq!(view.get_asset(), transfer.get_asset().as_bytes());
+    assert_eq!(view.get_destination(), transfer.get_destination().as_bytes());
+    assert_eq!(view.get_extra_data(), transfer.get_extra_data().as_ref().map(|data| data.0.as_slice()));
+    assert_eq!(view.get_commitment(), transfer.get_commitment().as_bytes());
+    assert_eq!(view.get_sender_handle(), transfer.get_sender_handle().as_bytes());
+    assert_eq!(view.get_receiver_handle(), transfer.get_receiver_handle().as_bytes());
+    assert_eq!(view.get_proof().as_slice(), transfer.get_proof().to_bytes());
+    assert_eq!(view.size(), transfer.size());
+    assert_eq!(view.to_payload().unwrap().to_bytes(), transfer.to_bytes());
+}
+
+#[test]
+fn test_transfer_payload_ref() {
+    let mut alice = Account::new();
+    let bob = Account::new();
+    alice.set_balance(XELIS_ASSET, 100 * COIN_VALUE);
+
+    let memo = DataElement::Value(DataValue::String("memo".to_string()));
+    let with_extra_data = create_tx_for(alice.clone(), bob.address(), 1, Some(memo));
+    let without_extra_data = create_tx_for(alice, bob.address(), 2, None);
+    let transfers: Vec<TransferPayload> = with_extra_data.get_data().transfers()
+        .chain(without_extra_data.get_data().transfers())
+        .cloned()
+        .collect();
+
+    let mut writer = Writer::new();
+    for transfer in &transfers {
+        transfer.write(&mut writer);
+    }
+    let bytes = writer.bytes();
+
+    // Transfers are read one after the other like the owned ones
+    let mut reader = Reader::new(&bytes);
+    let mut owned_reader = Reader::new(&bytes);
+    for transfer in &transfers {
+        let view = TransferPayloadRef::read(&mut reader).unwrap();
+        let owned = TransferPayload::read(&mut owned_reader).unwrap();
+        assert_eq!(reader.total_read(), owned_reader.total_read());
+        assert_transfer_ref_eq(&view, transfer);
+        assert_transfer_ref_eq(&view, &owned);
+    }
+    assert_eq!(reader.remaining(), 0);
+
+    // Extra data is not copied
+    let view = TransferPayloadRef::read(&mut Reader::new(&bytes)).unwrap();
+    let extra_data = view.get_extra_data().unwrap();
+    assert!(bytes.as_ptr_range().contains(&extra_data.as_ptr()));
+
+    // A stream has no buffer to borrow from
+    let mut cursor = std::io::Cursor::new(bytes.clone());
+    assert!(TransferPayloadRef::read(&mut Reader::from_stream(&mut cursor)).is_err());
+
+    // Rejected like the owned parse
+    let transfer = &transfers[1];
+    let mut too_large = transfer.clone();
+    too_large.extra_data = Some(UnknownExtraDataFormat(vec![0; EXTRA_DATA_LIMIT_SIZE + 1]));
+    let mut non_canonical = transfer.to_bytes();
+    let len = non_canonical.len();
+    non_canonical[len - 32..].copy_from_slice(&[0xFF; 32]);
+    for bytes in [too_large.to_bytes(), non_canonical, transfer.to_bytes()[..100].to_vec()] {
+        assert!(TransferPayload::from_bytes(&bytes).is_err());
+        assert!(TransferPayloadRef::read(&mut Reader::new(&bytes)).is_err());
+    }
+}
+
+#[test]
+fn test_transaction_try_from_bytes() {
+    let mut alice = Account::new();
+    let bob = Account::new();
+    alice.set_balance(XELIS_ASSET, 100 * COIN_VALUE);
+
+    let tx = create_tx_for(alice, bob.address(), 50, None);
+    let bytes = tx.to_bytes();
+
+    let read = Transaction::try_from(bytes.as_slice()).unwrap();
+    assert_eq!(read.hash(), tx.hash());
+    let read = Transaction::try_from(bytes.clone()).unwrap();
+    assert_eq!(read.hash(), tx.hash());
+
+    // Truncated input
+    for len in [0, 1, bytes.len() / 2, bytes.len() - 1] {
+        assert!(matches!(Transaction::try_from(&bytes[..len]), Err(ReaderError::InvalidSize)));
+        assert!(Transaction::try_from(bytes[..len].to_vec()).is_err());
+    }
+
+    // Trailing bytes
+    let mut extended = bytes;
+    extended.push(0);
+    assert!(matches!(Transaction::try_from(extended.as_slice()), Err(ReaderError::InvalidValue)));
+    assert!(Transaction::try_from(extended).is_err());
+}
+
+// The hash cache is interior mutable, but it only memoizes the hash of the
+// immutable content, so the key used by the set never changes
+#[allow(clippy::mutable_key_type)]
+#[test]
+fn test_transaction_eq_hash() {
+    let mut alice = Account::new();
+    let bob = Account::new();
+    alice.set_balance(XELIS_ASSET, 100 * COIN_VALUE);
+
+    let tx = create_tx_for(alice.clone(), bob.address(), 50, None);
+    // Fill the hash cache of one side only
+    tx.hash();
+    let read = Transaction::from_bytes(&tx.to_bytes()).unwrap();
+    assert_eq!(tx, read);
+    assert_eq!(tx, tx.clone());
+
+    let other = create_tx_for(alice, bob.address(), 50, None);
+    assert_ne!(tx, other);
+
+    let mut set = HashSet::new();
+    assert!(set.insert(tx.clone()));
+    assert!(!set.insert(read));
+    assert_eq!(set.len(), 1);
+    assert!(set.insert(other));
+    assert_eq!(set.len(), 2);
+    assert!(set.contains(&tx));
+}
+
+#[test]
+fn test_transfers_by_asset() {
+    let mut alice = Account::new();
+    let bob = Account::new();
+    alice.set_balance(XELIS_ASSET, 100 * COIN_VALUE);
+
+    let tx = create_tx_for(alice, bob.address(), 50, None);
+    let transfer = tx.get_data().transfers().next().unwrap().clone();
+    let other_asset = Hash::new([1; 32]);
+    let with_asset = |asset: &Hash, id: u8| {
+        let mut transfer = transfer.clone();
+        transfer.asset = asset.clone();
+        transfer.extra_data = Some(UnknownExtraDataFormat(vec![id]));
+        transfer
+    };
+
+    // Assets are kept in the order they first appear
+    let data = TransactionType::Transfers(vec![
+        with_asset(&other_asset, 0),
+        with_asset(&XELIS_ASSET, 1),
+        with_asset(&other_asset, 2),
+        with_asset(&XELIS_ASSET, 3),
+        with_asset(&other_asset, 4),
+    ]);
+
+    let grouped = data.transfers_by_asset();
+    assert_eq!(grouped.keys().collect::<Vec<_>>(), vec![&other_asset, &XELIS_ASSET]);
+    let ids = |asset: &Hash| grouped[asset].iter()
+        .map(|transfer| transfer.get_extra_data().as_ref().unwrap().0[0])
+        .collect::<Vec<_>>();
+    assert_eq!(ids(&other_asset), vec![0, 2, 4]);
+    assert_eq!(ids(&XELIS_ASSET), vec![1, 3]);
+
+    let burn = TransactionType::Burn(BurnPayload {
+        asset: XELIS_ASSET,
+        amount: 1,
+        extra_data: None
+    });
+    assert!(burn.transfers_by_asset().is_empty());
+}
+
+#[test]
+fn test_builder_extra_data_too_large() {
+    let mut alice = Account::new();
+    let bob = Account::new();
+    alice.set_balance(XELIS_ASSET, 100 * COIN_VALUE);
+
+    // Strings are limited to 255 bytes, use an array to go above the limit
+    let value = DataElement::Value(DataValue::String("a".repeat(255)));
+    let extra_data = DataElement::Array(vec![value; EXTRA_DATA_LIMIT_SIZE / 255 + 1]);
+    assert!(extra_data.size() > EXTRA_DATA_LIMIT_SIZE);
+
+    let transfers = vec![TransferBuilder {
+        amount: 1,
+        destination: bob.address(),
+        asset: XELIS_ASSET,
+        extra_data: Some(extra_data),
+    }];
+
+    let (result, state) = build_tx_with(&alice, TransactionTypeBuilder::Transfers(transfers));
+    assert!(matches!(result, Err(GenerationError::Transaction(TransactionError::ExtraDataTooLarge(_)))));
+    assert_eq!(state.nonce, alice.nonce);
+}
+
+#[test]
+fn test_builder_extra_data_encrypted_limit() {
+    let mut alice = Account::new();
+    let bob = Account::new();
+    alice.set_balance(XELIS_ASSET, 100 * COIN_VALUE);
+
+    // Fits in the limit before being encrypted but not after
+    let base = DataElement::Value(DataValue::Bytes(Vec::new())).size();
+    let extra_data = DataElement::Value(DataValue::Bytes(vec![0; EXTRA_DATA_LIMIT_SIZE - base]));
+    assert_eq!(extra_data.size(), EXTRA_DATA_LIMIT_SIZE);
+
+    let transfers = vec![TransferBuilder {
+        amount: 1,
+        destination: bob.address(),
+        asset: XELIS_ASSET,
+        extra_data: Some(extra_data),
+    }];
+
+    let (result, state) = build_tx_with(&alice, TransactionTypeBuilder::Transfers(transfers));
+    assert!(matches!(result, Err(GenerationError::Transaction(TransactionError::ExtraDataTooLarge(_)))));
+    assert_eq!(state.nonce, alice.nonce);
+}
+
+#[test]
+fn test_builder_invalid_extra_data() {
+    let mut alice = Account::new();
+    let bob = Account::new();
+    alice.set_balance(XELIS_ASSET, 100 * COIN_VALUE);
+
+    // Small enough, but the count of items can't be written on a byte
+    let extra_data = DataElement::Array(vec![DataElement::Value(DataValue::Bool(true)); MAX_ELEMENT_ITEMS + 1]);
+    assert!(extra_data.size() <= EXTRA_DATA_LIMIT_SIZE);
+
+    let transfers = vec![TransferBuilder {
+        amount: 1,
+        destination: bob.address(),
+        asset: XELIS_ASSET,
+        extra_data: Some(extra_data),
+    }];
+
+    let (result, state) = build_tx_with(&alice, TransactionTypeBuilder::Transfers(transfers));
+    assert!(matches!(result, Err(GenerationError::InvalidExtraData(DataConversionError::TooManyItems(_)))));
+    assert_eq!(state.nonce, alice.nonce);
+}
+
+#[test]
+fn test_builder_valid_transfers() {
+    let mut alice = Account::new();
+    let bob = Account::new();
+    alice.set_balance(XELIS_ASSET, 100 * COIN_VALUE);
+
+    let transfers = vec![TransferBuilder {
+        amount: 1,
+        destination: bob.address(),
+        asset: XELIS_ASSET,
+        extra_data: Some(DataElement::Value(DataValue::String("Hello, World!".to_string()))),
+    }];
+
+    let (result, state) = build_tx_with(&alice, TransactionTypeBuilder::Transfers(transfers));
+    let tx = result.unwrap();
+    assert_eq!(tx.get_nonce(), alice.nonce);
+    assert_eq!(state.nonce, alice.nonce + 1);
+    assert!(tx.verify_signature());
+}
+
+#[test]
+fn test_duplicate_transfers() {
+    let mut alice = Account::new();
+    let bob = Account::new();
+    let charlie = Account::new();
+    let asset = Hash::max();
+
+    alice.set_balance(XELIS_ASSET, 100 * COIN_VALUE);
+    alice.set_balance(asset.clone(), 100 * COIN_VALUE);
+
+    let transfer = |destination: &Account, asset: Hash| TransferBuilder {
+        amount: 1,
+        destination: destination.address(),
+        asset,
+        extra_data: None,
+    };
+
+    // Same asset to the same destination twice
+    let transfers = vec![transfer(&bob, XELIS_ASSET), transfer(&bob, XELIS_ASSET)];
+    let (result, state) = build_tx_with(&alice, TransactionTypeBuilder::Transfers(transfers));
+    assert!(matches!(result, Err(GenerationError::Transaction(TransactionError::DuplicateTransfer))));
+    assert_eq!(state.nonce, alice.nonce);
+
+    // Same destination but a different asset
+    let transfers = vec![transfer(&bob, XELIS_ASSET), transfer(&bob, asset)];
+    let (result, _) = build_tx_with(&alice, TransactionTypeBuilder::Transfers(transfers));
+    let mut tx = result.unwrap();
+    assert!(!tx.has_duplicate_transfers());
+
+    // Clean set
+    let transfers = vec![transfer(&bob, XELIS_ASSET), transfer(&charlie, XELIS_ASSET)];
+    let (result, _) = build_tx_with(&alice, TransactionTypeBuilder::Transfers(transfers));
+    assert!(!result.unwrap().has_duplicate_transfers());
+
+    // Duplicate a transfer manually, it must be caught by the validation
+    if let TransactionType::Transfers(transfers) = &mut tx.data {
+        let transfer = transfers[0].clone();
+        transfers.push(transfer);
+    }
+    assert!(tx.has_duplicate_transfers());
+    assert_eq!(tx.validate(), Err(TransactionError::DuplicateTransfer));
+}
+
+#[test]
+fn test_referenced_assets() {
+    let mut alice = Account::new();
+    let bob = Account::new();
+    let charlie = Account::new();
+    let asset = Hash::max();
+
+    alice.set_balance(XELIS_ASSET, 100 * COIN_VALUE);
+    alice.set_balance(asset.clone(), 100 * COIN_VALUE);
+
+    let transfers = vec![
+        TransferBuilder {
+            amount: 1,
+            destination: bob.address(),
+            asset: XELIS_ASSET,
+            extra_data: None,
+        },
+        TransferBuilder {
+            amount: 1,
+            destination: bob.address(),
+            asset: asset.clone(),
+            extra_data: None,
+        },
+        TransferBuilder {
+            amount: 1,
+            destination: charlie.address(),
+            asset: asset.clone(),
+            extra_data: None,
+        }
+    ];
+    let (result, _) = build_tx_with(&alice, TransactionTypeBuilder::Transfers(transfers));
+    let assets = result.unwrap().referenced_assets();
+    assert_eq!(assets.len(), 2);
+    assert!(assets.contains(&XELIS_ASSET));
+    assert!(assets.contains(&asset));
+
+    let burn = BurnPayload {
+        amount: 50,
+        asset: asset.clone(),
+        extra_data: None,
+    };
+    let (result, _) = build_tx_with(&alice, TransactionTypeBuilder::Burn(burn));
+    let assets = result.unwrap().referenced_assets();
+    assert_eq!(assets.len(), 1);
+    assert!(assets.contains(&asset));
+}
+
+#[test]
+fn test_burn_extra_data_serialization() {
+    let payload = BurnPayload {
+        amount: 50,
+        asset: XELIS_ASSET,
+        extra_data: Some(b"proof of burn".to_vec()),
+    };
+
+    // Version 0 has no extra data field
+    let mut writer = Writer::new();
+    payload.write_with_version(&mut writer, 0);
+    let bytes = writer.bytes();
+    assert_eq!(bytes.len(), payload.size_with_version(0));
+    assert_eq!(bytes.len(), XELIS_ASSET.size() + 8);
+
+    let mut reader = Reader::new(&bytes);
+    let read = BurnPayload::read_with_version(&mut reader, 0).unwrap();
+    assert_eq!(read.amount, payload.amount);
+    assert!(read.extra_data.is_none());
+
+    // Version 1
+    let mut writer = Writer::new();
+    payload.write_with_version(&mut writer, 1);
+    let bytes = writer.bytes();
+    assert_eq!(bytes.len(), payload.size_with_version(1));
+
+    let mut reader = Reader::new(&bytes);
+    let read = BurnPayload::read_with_version(&mut reader, 1).unwrap();
+    assert_eq!(read.asset, payload.asset);
+    assert_eq!(read.amount, payload.amount);
+    assert_eq!(read.extra_data, payload.extra_data);
+
+    // Too large extra data is rejected
+    let payload = BurnPayload {
+        extra_data: Some(vec![0; EXTRA_DATA_LIMIT_SIZE + 1]),
+        ..payload
+    };
+    let mut writer = Writer::new();
+    payload.write_with_version(&mut writer, 1);
+    let bytes = writer.bytes();
+    let mut reader = Reader::new(&bytes);
+    assert!(BurnPayload::read_with_version(&mut reader, 1).is_err());
+}
+
+#[test]
+fn test_burn_hex_base64() {
+    // A burn is only serialized as part of a transaction, its layout depends on the version
+    let mut alice = Account::new();
+    alice.set_balance(XELIS_ASSET, 100 * COIN_VALUE);
+    let (result, _) = build_tx_with(&alice, TransactionTypeBuilder::Burn(BurnPayload {
+        amount: 50,
+        asset: XELIS_ASSET,
+        extra_data: None,
+    }));
+    let tx = result.unwrap();
+
+    let hex = tx.to_hex();
+    let read = Transaction::from_hex(&hex).unwrap();
+    assert_eq!(read.to_bytes(), tx.to_bytes());
+
+    let base64 = tx.to_base64();
+    assert!(!base64.contains(['+', '/', '=']));
+    let read = Transaction::from_base64(&base64).unwrap();
+    assert_eq!(read.to_bytes(), tx.to_bytes());
+    match read.get_data() {
+        TransactionType::Burn(payload) => {
+            assert_eq!(payload.asset, XELIS_ASSET);
+            assert_eq!(payload.amount, 50);
+            assert!(payload.extra_data.is_none());
+        },
+        _ => panic!("expected a burn")
+    }
+
+    // Standard alphabet and padding are rejected
+    assert!(matches!(Transaction::from_base64(&format!("{}=", base64)), Err(ReaderError::InvalidBase64)));
+    assert!(matches!(Transaction::from_base64("/+"), Err(ReaderError::InvalidBase64)));
+    assert!(matches!(Transaction::from_hex("zz"), Err(ReaderError::InvalidHex)));
+}
+
+#[test]
+fn test_burn_zero_amount() {
+    let payload = BurnPayload {
+        amount: 1,
+        asset: XELIS_ASSET,
+        extra_data: None,
+    };
+
+    let mut writer = Writer::new();
+    payload.write_with_version(&mut writer, 0);
+    let bytes = writer.bytes();
+    let mut reader = Reader::new(&bytes);
+    assert_eq!(BurnPayload::read_with_version(&mut reader, 0).unwrap().amount, 1);
+
+    let payload = BurnPayload {
+        amount: 0,
+        ..payload
+    };
+    let mut writer = Writer::new();
+    payload.write_with_version(&mut writer, 0);
+    let bytes = writer.bytes();
+    let mut reader = Reader::new(&bytes);
+    assert!(matches!(BurnPayload::read_with_version(&mut reader, 0), Err(ReaderError::InvalidValue)));
+
+    // Builder must refuse it too
+    let mut alice = Account::new();
+    alice.set_balance(XELIS_ASSET, 100 * COIN_VALUE);
+    let (result, state) = build_tx_with(&alice, TransactionTypeBuilder::Burn(payload));
+    assert!(matches!(result, Err(GenerationError::Transaction(TransactionError::ZeroBurn))));
+    assert_eq!(state.nonce, alice.nonce);
+}
+
+#[test]
+fn test_burn_extra_data_tx() {
+    let mut alice = Account::new();
+    alice.set_balance(XELIS_ASSET, 100 * COIN_VALUE);
+
+    let payload = BurnPayload {
+        amount: 50,
+        asset: XELIS_ASSET,
+        extra_data: Some(b"proof of burn".to_vec()),
+    };
+
+    // Not supported in version 0
+    let (result, _) = build_tx_with(&alice, TransactionTypeBuilder::Burn(payload.clone()));
+    assert!(matches!(result, Err(GenerationError::Transaction(TransactionError::BurnExtraDataNotSupported(0)))));
+
+    let mut state = AccountStateImpl {
+        balances: alice.balances.clone(),
+        nonce: alice.nonce,
+        reference: Reference {
+            topoheight: 0,
+            hash: Hash::zero(),
+        },
+    };
+    let builder = TransactionBuilder::new(1, alice.keypair.get_public_key().compress(), TransactionTypeBuilder::Burn(payload), FeeBuilder::Multiplier(1f64));
+    let estimated_size = builder.estimate_size();
+    let tx = builder.build(&mut state, &alice.keypair).unwrap();
+    assert_eq!(tx.size(), estimated_size);
+    assert!(tx.verify_signature());
+
+    let bytes = tx.to_bytes();
+    assert_eq!(bytes.len(), tx.size());
+
+    let tx = Transaction::from_bytes(&bytes).unwrap();
+    assert_eq!(tx.get_version(), 1);
+    assert!(tx.verify_signature());
+    match tx.get_data() {
+        TransactionType::Burn(payload) => assert_eq!(payload.extra_data.as_deref(), Some(&b"proof of burn"[..])),
+        _ => panic!("expected a burn")
+    };
+}
+
+#[test]
+fn test_tx_v0_serialization() {
+    let mut alice = Account::new();
+    let bob = Account::new();
+    alice.set_balance(XELIS_ASSET, 100 * COIN_VALUE);
+
+    let tx = create_tx_for(alice, bob.address(), 50, None);
+    assert_eq!(tx.get_version(), 0);
+
+    let bytes = tx.to_bytes();
+    let read = Transaction::from_bytes(&bytes).unwrap();
+    assert!(read.get_multisig().is_none());
+    assert!(read.get_multisig_signatures().is_empty());
+    assert_eq!(read.to_bytes(), bytes);
+    assert!(read.verify_signature());
+}
+
+#[test]
+fn test_tx_reference() {
+    let mut alice = Account::new();
+    let bob = Account::new();
+    alice.set_balance(XELIS_ASSET, 100 * COIN_VALUE);
+
+    let reference = Reference {
+        topoheight: 42,
+        hash: Hash::new([7; 32]),
+    };
+
+    for version in [0, 1] {
+        let mut state = AccountStateImpl {
+            balances: alice.balances.clone(),
+            nonce: alice.nonce,
+            reference: reference.clone(),
+        };
+
+        let data = TransactionTypeBuilder::Transfers(vec![TransferBuilder {
+            amount: 1,
+            destination: bob.address(),
+            asset: XELIS_ASSET,
+            extra_data: None,
+        }]);
+        let tx = TransactionBuilder::new(version, alice.keypair.get_public_key().compress(), data, FeeBuilder::Multiplier(1f64))
+            .build(&mut state, &alice.keypair)
+            .unwrap();
+
+        let read = Transaction::from_bytes(&tx.to_bytes()).unwrap();
+        assert_eq!(read.get_reference().hash, reference.hash);
+        assert_eq!(read.get_reference().topoheight, reference.topoheight);
+        assert!(read.verify_signature());
+    }
+}
+
+#[test]
+fn test_tx_v1_multisig() {
+    let mut alice = Account::new();
+    let bob = Account::new();
+    let charlie = Account::new();
+    let dave = Account::new();
+    alice.set_balance(XELIS_ASSET, 100 * COIN_VALUE);
+
+    let multisig = MultiSigPayload::new(2, vec![
+        bob.keypair.get_public_key().compress(),
+        charlie.keypair.get_public_key().compress(),
+        dave.keypair.get_public_key().compress(),
+    ]);
+
+    let mut state = AccountStateImpl {
+        balances: alice.balances.clone(),
+        nonce: alice.nonce,
+        reference: Reference {
+            topoheight: 0,
+            hash: Hash::zero(),
+        },
+    };
+
+    let transfers = vec![TransferBuilder {
+        amount: 1,
+        destination: bob.address(),
+        asset: XELIS_ASSET,
+        extra_data: None,
+    }];
+
+    // Not available in version 0
+    let builder = TransactionBuilder::new(0, alice.keypair.get_public_key().compress(), TransactionTypeBuilder::Transfers(transfers.clone()), FeeBuilder::Multiplier(1f64))
+        .with_multisig(multisig.clone());
+    assert!(matches!(builder.build(&mut state, &alice.keypair), Err(GenerationError::Transaction(TransactionError::MultiSigNotSupported(0)))));
+
+    let builder = TransactionBuilder::new(1, alice.keypair.get_public_key().compress(), TransactionTypeBuilder::Transfers(transfers), FeeBuilder::Multiplier(1f64))
+        .with_multisig(multisig);
+    let estimated_size = builder.estimate_size();
+    let mut tx = builder.build(&mut state, &alice.keypair).unwrap();
+    assert!(tx.verify_signature());
+    // No co-signer yet
+    assert!(!tx.verify_multisig_signatures());
+
+    let bytes = tx.signing_bytes();
+    tx.attach_multisig_signature(SignatureId { id: 0, signature: bob.keypair.sign(&bytes) });
+    // Below the threshold
+    assert!(!tx.verify_multisig_signatures());
+
+    tx.attach_multisig_signature(SignatureId { id: 2, signature: dave.keypair.sign(&bytes) });
+    assert!(tx.verify_multisig_signatures());
+    assert!(tx.validate().is_ok());
+    assert_eq!(tx.size(), estimated_size);
+
+    let bytes = tx.to_bytes();
+    assert_eq!(bytes.len(), tx.size());
+    let read = Transaction::from_bytes(&bytes).unwrap();
+    assert_eq!(read.get_version(), 1);
+    assert_eq!(read.get_multisig().map(|m| m.threshold), Some(2));
+    assert_eq!(read.get_multisig_signatures().len(), 2);
+    assert!(read.verify_signature());
+    assert!(read.verify_multisig_signatures());
+    assert_eq!(read.to_bytes(), bytes);
+
+    // Wrong co-signer for the id
+    let mut invalid = read.clone();
+    invalid.multisig_signatures[1].id = 1;
+    assert!(!invalid.verify_multisig_signatures());
+
+    // Same co-signer twice
+    let mut invalid = read;
+    invalid.multisig_signatures[1] = invalid.multisig_signatures[0].clone();
+    assert!(!invalid.verify_multisig_signatures());
+}
+
+// Build a version 1 transfer of 1 atomic unit, with the co-signers signatures attached
+fn build_multisig_tx(state: &mut AccountStateImpl, sender: &Account, destination: &Account, multisig: Option<MultiSigPayload>, cosigners: &[(u8, &Account)]) -> Transaction {
+    let transfers = vec![TransferBuilder {
+        amount: 1,
+        destination: destination.address(),
+        asset: XELIS_ASSET,
+        extra_data: None,
+    }];
+
+    let mut builder = TransactionBuilder::new(1, sender.keypair.get_public_key().compress(), TransactionTypeBuilder::Transfers(transfers), FeeBuilder::Multiplier(1f64));
+    if let Some(multisig) = multisig {
+        builder = builder.with_multisig(multisig);
+    }
+
+    let mut tx = builder.build(state, &sender.keypair).unwrap();
+    let bytes = tx.signing_bytes();
+    for (id, cosigner) in cosigners {
+        tx.attach_multisig_signature(SignatureId { id: *id, signature: cosigner.keypair.sign(&bytes) });
+    }
+    tx
+}
+
+#[tokio::test]
+async fn test_tx_multisig_registered() {
+    let mut alice = Account::new();
+    let mut bob = Account::new();
+    let charlie = Account::new();
+    alice.set_balance(XELIS_ASSET, 100 * COIN_VALUE);
+    bob.set_balance(XELIS_ASSET, 0);
+
+    let alice_key = alice.keypair.get_public_key().compress();
+    let multisig = MultiSigPayload::new(1, vec![
+        bob.keypair.get_public_key().compress(),
+        charlie.keypair.get_public_key().compress(),
+    ]);
+
+    let mut chain = chain_state_for(&[&alice, &bob]);
+    let mut state = AccountStateImpl {
+        balances: alice.balances.clone(),
+        nonce: alice.nonce,
+        reference: Reference {
+            topoheight: 0,
+            hash: Hash::zero(),
+        },
+    };
+
+    // First multisig of the account registers it
+    let tx = build_multisig_tx(&mut state, &alice, &bob, Some(multisig.clone()), &[(0, &bob)]);
+    tx.verify(&mut chain).await.unwrap();
+    assert_eq!(chain.multisig.get(&alice_key), Some(&multisig));
+
+    let snapshot = |state: &AccountStateImpl| AccountStateImpl {
+        balances: state.balances.clone(),
+        nonce: state.nonce,
+        reference: state.reference.clone(),
+    };
+
+    // Without any multisig, only alice signature is present
+    let tx = build_multisig_tx(&mut snapshot(&state), &alice, &bob, None, &[]);
+    assert!(matches!(tx.verify(&mut chain).await, Err(VerificationError::MultiSigMismatch)));
+    chain.accounts.get_mut(&alice_key).unwrap().nonce = 1;
+
+    // Co-signers chosen by the sender are not the registered ones
+    let other = MultiSigPayload::new(1, vec![charlie.keypair.get_public_key().compress()]);
+    let tx = build_multisig_tx(&mut snapshot(&state), &alice, &bob, Some(other), &[(0, &charlie)]);
+    assert!(matches!(tx.verify(&mut chain).await, Err(VerificationError::MultiSigMismatch)));
+    chain.accounts.get_mut(&alice_key).unwrap().nonce = 1;
+
+    // Registered multisig without enough signatures
+    let tx = build_multisig_tx(&mut snapshot(&state), &alice, &bob, Some(multisig.clone()), &[]);
+    assert!(matches!(tx.verify(&mut chain).await, Err(VerificationError::InvalidSignature)));
+    chain.accounts.get_mut(&alice_key).unwrap().nonce = 1;
+
+    let tx = build_multisig_tx(&mut state, &alice, &bob, Some(multisig.clone()), &[(1, &charlie)]);
+    tx.verify(&mut chain).await.unwrap();
+    assert_eq!(chain.multisig.get(&alice_key), Some(&multisig));
+}
+
+#[test]
+fn test_tx_minimum_fee() {
+    let mut alice = Account::new();
+    let bob = Account::new();
+    alice.set_balance(XELIS_ASSET, 100 * COIN_VALUE);
+
+    let mut tx = create_tx_for(alice, bob.address(), 50, None);
+    let fee_per_byte = 10;
+    let minimum = tx.minimum_fee(fee_per_byte);
+    assert_eq!(minimum, tx.size() as u64 * fee_per_byte);
+
+    tx.fee = minimum - 1;
+    assert_eq!(tx.validate_fee(fee_per_byte), Err(TransactionError::FeeTooLow(minimum - 1, minimum)));
+
+    tx.fee = minimum;
+    assert!(tx.validate_fee(fee_per_byte).is_ok());
+
+    tx.fee = minimum + 1;
+    assert!(tx.validate_fee(fee_per_byte).is_ok());
+}
+
+#[test]
+fn test_tx_hash_cache() {
+    let mut alice = Account::new();
+    let bob = Account::new();
+    alice.set_balance(XELIS_ASSET, 100 * COIN_VALUE);
+
+    let mut tx = create_tx_for(alice.clone(), bob.address(), 50, None);
+    let expected = hash(&tx.to_bytes());
+    assert_eq!(*tx.hash(), expected);
+    // Same reference is returned
+    assert!(std::ptr::eq(tx.hash(), tx.hash()));
+    assert_eq!(Hashable::hash(&tx), expected);
+
+    // Not part of the serialized transaction
+    let read = Transaction::from_bytes(&tx.to_bytes()).unwrap();
+    assert_eq!(*read.hash(), expected);
+
+    // Updating the signature must invalidate the cache
+    tx.attach_signature(alice.keypair.sign(&tx.signing_bytes()));
+    assert_ne!(*tx.hash(), expected);
+    assert_eq!(*tx.hash(), hash(&tx.to_bytes()));
+}
+
+#[test]
+fn test_tx_read_bounded() {
+    let mut alice = Account::new();
+    let bob = Account::new();
+    alice.set_balance(XELIS_ASSET, 100 * COIN_VALUE);
+
+    let tx = create_tx_for(alice, bob.address(), 50, None);
+    let size = tx.size();
+
+    // Two transactions in the same stream
+    let mut bytes = tx.to_bytes();
+    bytes.extend(tx.to_bytes());
+
+    let mut reader = Reader::new(&bytes);
+    assert!(Transaction::read_bounded(&mut reader, MAX_TRANSACTION_SIZE).is_ok());
+    assert_eq!(reader.total_read(), size);
+    assert!(Transaction::read_bounded(&mut reader, size).is_ok());
+    assert_eq!(reader.size(), 0);
+
+    // Stream exceeding the bound
+    let mut reader = Reader::new(&bytes);
+    assert!(matches!(Transaction::read_bounded(&mut reader, size - 1), Err(ReaderError::InvalidSize)));
+}
+
+// Stream failing with an I/O error once its bytes are consumed
+struct FailingStream {
+    bytes: std::io::Cursor<Vec<u8>>
+}
+
+impl std::io::Read for FailingStream {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match self.bytes.read(buf)? {
+            0 => Err(std::io::Error::new(std::io::ErrorKind::ConnectionReset, "stream closed")),
+            n => Ok(n)
+        }
+    }
+}
+
+#[test]
+fn test_tx_truncated_offset() {
+    let mut alice = Account::new();
+    let bob = Account::new();
+    alice.set_balance(XELIS_ASSET, 100 * COIN_VALUE);
+
+    let tx = create_tx_for(alice, bob.address(), 50, None);
+    let bytes = tx.to_bytes();
+    let signature_offset = bytes.len() - SIGNATURE_SIZE;
+
+    // Missing signature
+    let error = Reader::new(&bytes[..signature_offset]).read_at::<Transaction>().unwrap_err();
+    assert_eq!(error.offset(), signature_offset);
+    assert!(matches!(error.kind(), ReaderError::InvalidSize));
+
+    // Second half of the signature is missing
+    let error = Reader::new(&bytes[..bytes.len() - 10]).read_at::<Transaction>().unwrap_err();
+    assert_eq!(error.offset(), signature_offset + SIGNATURE_SIZE / 2);
+
+    // Offsets from a bounded read are relative to the whole input
+    let mut input = vec![0xFF; 3];
+    input.extend(&bytes[..signature_offset]);
+    let mut reader = Reader::new(&input);
+    reader.skip(3).unwrap();
+    assert!(matches!(Transaction::read_bounded(&mut reader, MAX_TRANSACTION_SIZE), Err(ReaderError::InvalidSize)));
+    assert_eq!(reader.error_offset(), Some(3 + signature_offset));
+}
+
+#[test]
+fn test_tx_read_from_stream() {
+    use std::io::{BufReader, Cursor};
+
+    let mut alice = Account::new();
+    let bob = Account::new();
+    alice.set_balance(XELIS_ASSET, 100 * COIN_VALUE);
+
+    let tx = create_tx_for(alice, bob.address(), 50, None);
+    let bytes = tx.to_bytes();
+
+    // Two transactions back to back in the same stream
+    let mut cursor = BufReader::new(Cursor::new([bytes.clone(), bytes.clone()].concat()));
+    let mut reader = Reader::from_stream(&mut cursor);
+    for _ in 0..2 {
+        let read = Transaction::read(&mut reader).unwrap();
+        assert_eq!(read.hash(), tx.hash());
+    }
+    assert_eq!(reader.total_read(), bytes.len() * 2);
+    assert!(matches!(reader.read_u8(), Err(ReaderError::InvalidSize)));
+
+    // Bounded reads pull the bytes they need
+    let mut cursor = Cursor::new(bytes.clone());
+    let mut reader = Reader::from_stream(&mut cursor);
+    assert_eq!(Transaction::read_bounded(&mut reader, MAX_TRANSACTION_SIZE).unwrap().hash(), tx.hash());
+    assert_eq!(reader.total_read(), bytes.len());
+
+    // Stream ending mid-parse
+    let mut cursor = Cursor::new(bytes[..bytes.len() / 2].to_vec());
+    let mut reader = Reader::from_stream(&mut cursor);
+    assert!(matches!(Transaction::read(&mut reader), Err(ReaderError::InvalidSize)));
+
+    // Stream failing mid-parse
+    let mut stream = FailingStream {
+        bytes: Cursor::new(bytes[..bytes.len() / 2].to_vec())
+    };
+    let mut reader = Reader::from_stream(&mut stream);
+    assert!(matches!(Transaction::read(&mut reader), Err(ReaderError::Io(_))));
+}
+
+// Endless stream repeating its bytes, handing out at most 3 bytes per read
+struct EndlessStream {
+    bytes: Vec<u8>,
+    served: usize
+}
+
+impl std::io::Read for EndlessStream {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = buf.len().min(3);
+        for byte in &mut buf[..n] {
+            *byte = self.bytes[self.served % self.bytes.len()];
+            self.served += 1;
+        }
+        Ok(n)
+    }
+}
+
+#[test]
+fn test_tx_read_bounded_from_endless_stream() {
+    let mut alice = Account::new();
+    let bob = Account::new();
+    alice.set_balance(XELIS_ASSET, 100 * COIN_VALUE);
+
+    let tx = create_tx_for(alice, bob.address(), 50, None);
+    let bytes = tx.to_bytes();
+
+    // The bound must not wait for MAX_TRANSACTION_SIZE bytes nor the end of the stream
+    let mut stream = EndlessStream {
+        bytes: bytes.clone(),
+        served: 0
+    };
+    let mut reader = Reader::from_stream(&mut stream);
+    for _ in 0..100 {
+        let read = Transaction::read_bounded(&mut reader, MAX_TRANSACTION_SIZE).unwrap();
+        assert_eq!(read.hash(), tx.hash());
+    }
+    assert_eq!(reader.total_read(), bytes.len() * 100);
+    drop(reader);
+    // Nothing was pulled ahead
+    assert_eq!(stream.served, bytes.len() * 100);
+
+    // The bound is still enforced while streaming
+    let mut stream = EndlessStream {
+        bytes: bytes.clone(),
+        served: 0
+    };
+    let mut reader = Reader::from_stream(&mut stream);
+    assert!(matches!(Transaction::read_bounded(&mut reader, bytes.len() - 1), Err(ReaderError::InvalidSize)));
+}
+
+#[test]
+fn test_tx_write_to_stream() {
+    let mut alice = Account::new();
+    let bob = Account::new();
+    alice.set_balance(XELIS_ASSET, 100 * COIN_VALUE);
+
+    let tx = create_tx_for(alice, bob.address(), 50, None);
+
+    let mut output = Vec::new();
+    let mut writer = Writer::from_stream(&mut output);
+    tx.write(&mut writer);
+    assert_eq!(writer.total_write(), tx.size());
+    assert!(writer.as_bytes().is_empty());
+    writer.flush().unwrap();
+
+    assert_eq!(output, tx.to_bytes());
+}
+
+#[test]
+fn test_tx_from_bytes_exact() {
+    let mut alice = Account::new();
+    let bob = Account::new();
+    alice.set_balance(XELIS_ASSET, 100 * COIN_VALUE);
+
+    let tx = create_tx_for(alice, bob.address(), 50, None);
+    let mut bytes = tx.to_bytes();
+    assert!(Transaction::from_bytes_exact(&bytes).is_ok());
+
+    bytes.push(0);
+    assert!(matches!(Transaction::from_bytes_exact(&bytes), Err(ReaderError::InvalidValue)));
+    // Still accepted by the lenient version
+    assert!(Transaction::from_bytes(&bytes).is_ok());
+}
+
+#[test]
+fn test_tx_hex() {
+    let mut alice = Account::new();
+    let bob = Account::new();
+    alice.set_balance(XELIS_ASSET, 100 * COIN_VALUE);
+
+    let transfer = create_tx_for(alice.clone(), bob.address(), 50, None);
+    let hex = transfer.to_hex();
+    let decoded = Transaction::from_hex(&hex).unwrap();
+    assert_eq!(decoded.hash(), transfer.hash());
+
+    let burn = BurnPayload {
+        amount: 50,
+        asset: XELIS_ASSET,
+        extra_data: None,
+    };
+    let (result, _) = build_tx_with(&alice, TransactionTypeBuilder::Burn(burn));
+    let burn = result.unwrap();
+    let decoded = Transaction::from_hex(&burn.to_hex()).unwrap();
+    assert_eq!(decoded.hash(), burn.hash());
+
+    // Odd length
+    assert!(matches!(Transaction::from_hex(&hex[1..]), Err(ReaderError::InvalidHex)));
+    // Not hex
+    assert!(matches!(Transaction::from_hex(&format!("zz{}", &hex[2..])), Err(ReaderError::InvalidHex)));
+    // Trailing byte
+    assert!(Transaction::from_hex(&format!("{}00", hex)).is_err());
+}
+
+#[test]
+fn test_tx_summary() {
+    let mut alice = Account::new();
+    let bob = Account::new();
+    let charlie = Account::new();
+    let asset = Hash::max();
+    alice.set_balance(XELIS_ASSET, 100 * COIN_VALUE);
+    alice.set_balance(asset.clone(), 100 * COIN_VALUE);
+
+    let transfers = vec![
+        TransferBuilder {
+            amount: 1,
+            destination: bob.address(),
+            asset: XELIS_ASSET,
+            extra_data: None,
+        },
+        TransferBuilder {
+            amount: 2,
+            destination: charlie.address(),
+            asset: asset.clone(),
+            extra_data: None,
+        }
+    ];
+    let (result, _) = build_tx_with(&alice, TransactionTypeBuilder::Transfers(transfers));
+    let tx = result.unwrap();
+
+    let expected = TransactionSummary {
+        version: 0,
+        source: alice.keypair.get_public_key().compress(),
+        fee: tx.get_fee(),
+        nonce: alice.nonce,
+        data: TransactionTypeSummary::Transfers(vec![
+            TransferSummary {
+                asset: XELIS_ASSET,
+                destination: bob.keypair.get_public_key().compress(),
+            },
+            TransferSummary {
+                asset: asset.clone(),
+                destination: charlie.keypair.get_public_key().compress(),
+            }
+        ]),
+    };
+    assert_eq!(tx.summary(), expected);
+
+    let burn = BurnPayload {
+        amount: 50,
+        asset: asset.clone(),
+        extra_data: None,
+    };
+    let (result, _) = build_tx_with(&alice, TransactionTypeBuilder::Burn(burn));
+    let summary = result.unwrap().summary();
+    assert_eq!(summary.data, TransactionTypeSummary::Burn { asset, amount: 50 });
+
+    // Can be exposed through the API
+    let json = serde_json::to_string(&summary).unwrap();
+    assert_eq!(serde_json::from_str::<TransactionSummary>(&json).unwrap(), summary);
+}
+
+#[test]
+fn test_transaction_type_transfers() {
+    let mut alice = Account::new();
+    alice.set_balance(XELIS_ASSET, 100 * COIN_VALUE);
+
+    let receivers = (0..3).map(|_| Account::new()).collect::<Vec<_>>();
+    let transfers = receivers.iter().map(|receiver| TransferBuilder {
+        amount: 1,
+        destination: receiver.address(),
+        asset: XELIS_ASSET,
+        extra_data: None,
+    }).collect();
+    let (result, _) = build_tx_with(&alice, TransactionTypeBuilder::Transfers(transfers));
+    let tx = result.unwrap();
+
+    assert_eq!(tx.get_data().transfer_count(), 3);
+    let destinations = tx.get_data().transfers().map(|transfer| transfer.get_destination().clone()).collect::<Vec<_>>();
+    let expected = receivers.iter().map(|receiver| receiver.keypair.get_public_key().compress()).collect::<Vec<_>>();
+    assert_eq!(destinations, expected);
+
+    let burn = TransactionType::Burn(BurnPayload {
+        amount: 50,
+        asset: XELIS_ASSET,
+        extra_data: None,
+    });
+    assert_eq!(burn.transfer_count(), 0);
+    assert_eq!(burn.transfers().count(), 0);
+}
+
+#[test]
+fn test_tx_weight() {
+    let mut alice = Account::new();
+    alice.set_balance(XELIS_ASSET, 100 * COIN_VALUE);
+
+    let transfers = (0..10).map(|_| TransferBuilder {
+        amount: 1,
+        destination: Account::new().address(),
+        asset: XELIS_ASSET,
+        extra_data: None,
+    }).collect();
+    let (result, _) = build_tx_with(&alice, TransactionTypeBuilder::Transfers(transfers));
+    let transfers_tx = result.unwrap();
+
+    let burn = BurnPayload {
+        amount: 50,
+        asset: XELIS_ASSET,
+        extra_data: None,
+    };
+    let (result, _) = build_tx_with(&alice, TransactionTypeBuilder::Burn(burn));
+    let burn_tx = result.unwrap();
+
+    assert!(transfers_tx.weight() > burn_tx.weight());
+    // Weight grows faster than the size because of the proofs
+    assert!(transfers_tx.weight() - burn_tx.weight() >= 10 * TRANSFER_WEIGHT);
+    assert!(burn_tx.weight() >= burn_tx.size() as u64 * BYTE_WEIGHT + BURN_WEIGHT);
+}
+
+#[tokio::test]
+async fn test_tx_fee_asset() {
+    let mut alice = Account::new();
+    let mut bob = Account::new();
+    let asset = Hash::max();
+
+    alice.set_balance(XELIS_ASSET, 100 * COIN_VALUE);
+    alice.set_balance(asset.clone(), 100 * COIN_VALUE);
+    bob.set_balance(asset.clone(), 0);
+
+    let mut state = AccountStateImpl {
+        balances: alice.balances.clone(),
+        nonce: alice.nonce,
+        reference: Reference {
+            topoheight: 0,
+            hash: Hash::zero(),
+        },
+    };
+
+    let transfers = vec![TransferBuilder {
+        amount: 50,
+        destination: bob.address(),
+        asset: asset.clone(),
+        extra_data: None,
+    }];
+
+    // Not available in version 0
+    let builder = TransactionBuilder::new(0, alice.keypair.get_public_key().compress(), TransactionTypeBuilder::Transfers(transfers.clone()), FeeBuilder::Multiplier(1f64))
+        .with_fee_asset(asset.clone());
+    assert!(matches!(builder.build(&mut state, &alice.keypair), Err(GenerationError::Transaction(TransactionError::FeeAssetNotSupported(0)))));
+
+    // Must be one of the assets transferred
+    let builder = TransactionBuilder::new(1, alice.keypair.get_public_key().compress(), TransactionTypeBuilder::Transfers(transfers.clone()), FeeBuilder::Multiplier(1f64))
+        .with_fee_asset(Hash::new([1; 32]));
+    assert!(matches!(builder.build(&mut state, &alice.keypair), Err(GenerationError::Transaction(TransactionError::InvalidFeeAsset(_)))));
+
+    // Refused until the chain accepts it, without consuming a nonce
+    let builder = TransactionBuilder::new(1, alice.keypair.get_public_key().compress(), TransactionTypeBuilder::Transfers(transfers.clone()), FeeBuilder::Multiplier(1f64))
+        .with_fee_asset(asset.clone());
+    assert!(matches!(builder.build(&mut state, &alice.keypair), Err(GenerationError::Transaction(TransactionError::FeeAssetNotAccepted(ref fee_asset))) if *fee_asset == asset));
+    assert_eq!(state.nonce, alice.nonce);
+
+    // Same transaction paying its fees in the asset, as another wallet could create it
+    let builder = TransactionBuilder::new(1, alice.keypair.get_public_key().compress(), TransactionTypeBuilder::Transfers(transfers), FeeBuilder::Multiplier(1f64));
+    let estimated_size = builder.estimate_size();
+    let built = builder.build(&mut state, &alice.keypair).unwrap();
+    let mut tx = Transaction::new(
+        built.get_version(),
+        built.get_source().clone(),
+        None,
+        built.get_data().clone(),
+        built.get_fee(),
+        Some(asset.clone()),
+        built.get_nonce(),
+        built.get_source_commitments().clone(),
+        built.get_range_proof().clone(),
+        built.get_reference().clone(),
+        built.get_signature().clone(),
+        Vec::new()
+    );
+    tx.attach_signature(alice.keypair.sign(&tx.signing_bytes()));
+    // Only the fee asset hash is added to the estimated size
+    assert_eq!(estimated_size + HASH_SIZE, tx.size());
+    assert_eq!(*tx.get_fee_asset(), asset);
+    assert!(tx.verify_signature());
+    assert!(tx.validate().is_ok());
+
+    let decoded = Transaction::from_bytes(&tx.to_bytes()).unwrap();
+    assert_eq!(*decoded.get_fee_asset(), asset);
+    assert_eq!(decoded.hash(), tx.hash());
+
+    // Fee asset is part of the signed bytes
+    let with_fee_asset = |fee_asset: Hash| Transaction::new(
+        tx.get_version(),
+        tx.get_source().clone(),
+        None,
+        tx.get_data().clone(),
+        tx.get_fee(),
+        Some(fee_asset),
+        tx.get_nonce(),
+        tx.get_source_commitments().clone(),
+        tx.get_range_proof().clone(),
+        tx.get_reference().clone(),
+        tx.get_signature().clone(),
+        Vec::new()
+    );
+
+    let tampered = with_fee_asset(Hash::new([1; 32]));
+    assert!(!tampered.verify_signature());
+    assert_eq!(tampered.validate(), Err(TransactionError::InvalidFeeAsset(Hash::new([1; 32]))));
 
-#[derive(Clone)]
-struct Account {
-    balances: HashMap<Hash, Balance>,
-    keypair: KeyPair,
-    nonce: u64,
-}
+    // XELIS must be encoded as None
+    let tampered = with_fee_asset(XELIS_ASSET);
+    assert_eq!(tampered.validate(), Err(TransactionError::InvalidFeeAsset(XELIS_ASSET)));
 
-impl Account {
-    fn new() -> Self {
-        Self {
-            balances: HashMap::new(),
-            keypair: KeyPair::new(),
-            nonce: 0,
-        }
-    }
+    let mut chain = ChainState {
+        accounts: HashMap::new(),
+        multisig: HashMap::new(),
+    };
 
-    fn set_balance(&mut self, asset: Hash, balance: u64) {
-        let ciphertext = self.keypair.get_public_key().encrypt(balance);
-        self.balances.insert(asset, Balance {
-            balance,
-            ciphertext: CiphertextCache::Decompressed(ciphertext),
+    for account in [&alice, &bob] {
+        let mut balances = HashMap::new();
+        for (asset, balance) in &account.balances {
+            balances.insert(asset.clone(), balance.ciphertext.clone().take_ciphertext().unwrap());
+        }
+        chain.accounts.insert(account.keypair.get_public_key().compress(), AccountChainState {
+            balances,
+            nonce: account.nonce,
         });
     }
 
-    fn address(&self) -> Address {
-        self.keypair.get_public_key().to_address(false)
-    }
+    // The chain only credits and checks fees in XELIS, so any other fee asset is refused
+    assert!(matches!(tx.verify(&mut chain).await, Err(VerificationError::Transaction(TransactionError::FeeAssetNotAccepted(fee_asset))) if fee_asset == asset));
+    assert_eq!(tx.verify_stateless(), Err(TransactionError::FeeAssetNotAccepted(asset)));
 }
 
-struct AccountStateImpl {
-    balances: HashMap<Hash, Balance>,
-    reference: Reference,
-    nonce: u64,
-}
+#[test]
+fn test_split_transfers() {
+    let mut alice = Account::new();
+    alice.set_balance(XELIS_ASSET, 100 * COIN_VALUE);
 
-fn create_tx_for(account: Account, destination: Address, amount: u64, extra_data: Option<DataElement>) -> Transaction {
     let mut state = AccountStateImpl {
-        balances: account.balances,
-        nonce: account.nonce,
+        balances: alice.balances.clone(),
+        nonce: 5,
         reference: Reference {
             topoheight: 0,
             hash: Hash::zero(),
         },
     };
 
-    let data = TransactionTypeBuilder::Transfers(vec![TransferBuilder {
-        amount,
-        destination,
+    let transfers: Vec<_> = (0..600).map(|_| TransferBuilder {
+        amount: 1,
+        destination: Account::new().address(),
         asset: XELIS_ASSET,
-        extra_data,
-    }]);
-
-
-    let builder = TransactionBuilder::new(0, account.keypair.get_public_key().compress(), data, FeeBuilder::Multiplier(1f64));
-    let estimated_size = builder.estimate_size();
-    let tx = builder.build(&mut state, &account.keypair).unwrap();
-    assert!(estimated_size == tx.size(), "expected {} bytes got {} bytes", tx.size(), estimated_size);
-    assert!(tx.to_bytes().len() == estimated_size);
+        extra_data: None,
+    }).collect();
 
-    tx
-}
-
-#[test]
-fn test_encrypt_decrypt() {
-    let r = PedersenOpening::generate_new();
-    let key = derive_shared_key_from_opening(&r);
-    let message = "Hello, World!".as_bytes().to_vec();
+    let txs = split_transfers(0, &alice.keypair, transfers, FeeBuilder::Multiplier(1f64), &mut state).unwrap();
+    assert_eq!(txs.len(), 3);
+    assert_eq!(txs.iter().map(|tx| tx.get_data().transfer_count()).collect::<Vec<_>>(), vec![MAX_TRANSFER_COUNT_V0, MAX_TRANSFER_COUNT_V0, 600 - 2 * MAX_TRANSFER_COUNT_V0]);
+    assert_eq!(txs.iter().map(|tx| tx.get_nonce()).collect::<Vec<_>>(), vec![5, 6, 7]);
+    assert_eq!(state.nonce, 8);
+    for tx in &txs {
+        assert!(tx.validate().is_ok());
+        assert!(tx.verify_signature());
+    }
 
-    let plaintext = PlaintextData(message.clone());
-    let cipher = plaintext.encrypt_in_place_with_aead(&key);
-    let decrypted = cipher.decrypt_in_place(&key).unwrap();
+    let fees: u64 = txs.iter().map(|tx| tx.get_fee()).sum();
+    assert_eq!(state.get_account_balance(&XELIS_ASSET).unwrap(), 100 * COIN_VALUE - 600 - fees);
 
-    assert_eq!(decrypted.0, message);
+    assert!(matches!(split_transfers(0, &alice.keypair, Vec::new(), FeeBuilder::Multiplier(1f64), &mut state), Err(GenerationError::Transaction(TransactionError::EmptyTransfers))));
 }
 
 #[test]
-fn test_encrypt_decrypt_two_parties() {
+fn test_split_transfers_failure_keeps_state() {
     let mut alice = Account::new();
-    alice.balances.insert(XELIS_ASSET, Balance {
-        balance: 100 * COIN_VALUE,
-        ciphertext: CiphertextCache::Decompressed(alice.keypair.get_public_key().encrypt(100 * COIN_VALUE)),
-    });
-
-    let bob = Account::new();
+    alice.set_balance(XELIS_ASSET, 100 * COIN_VALUE);
 
-    let payload = DataElement::Value(DataValue::String("Hello, World!".to_string()));
-    let tx = create_tx_for(alice.clone(), bob.address(), 50, Some(payload.clone()));
-    let TransactionType::Transfers(transfers) = tx.get_data() else {
-        unreachable!()
+    let mut state = AccountStateImpl {
+        balances: alice.balances.clone(),
+        nonce: 5,
+        reference: Reference {
+            topoheight: 0,
+            hash: Hash::zero(),
+        },
     };
 
-    let transfer = &transfers[0];
-    let cipher = transfer.extra_data.clone().unwrap();
-    // Verify the extra data from alice (sender)
-    {
-        let decrypted = cipher.decrypt_v2(&alice.keypair.get_private_key(), Role::Sender).unwrap();
-        assert_eq!(decrypted, payload);
-    }
+    // The first chunks are affordable, the last one is not
+    let mut transfers: Vec<_> = (0..600).map(|_| TransferBuilder {
+        amount: 1,
+        destination: Account::new().address(),
+        asset: XELIS_ASSET,
+        extra_data: None,
+    }).collect();
+    transfers.last_mut().unwrap().amount = 100 * COIN_VALUE;
 
-    // Verify the extra data from bob (receiver)
-    {
-        let decrypted = cipher.decrypt_v2(&bob.keypair.get_private_key(), Role::Receiver).unwrap();
-        assert_eq!(decrypted, payload);
-    }
+    let result = split_transfers(0, &alice.keypair, transfers, FeeBuilder::Multiplier(1f64), &mut state);
+    assert!(matches!(result, Err(GenerationError::Proof(ProofGenerationError::InsufficientFunds))));
 
-    // Verify the extra data from alice (sender) with the wrong key
-    {
-        let decrypted = cipher.decrypt_v2(&bob.keypair.get_private_key(), Role::Sender);
-        assert!(decrypted.is_err());
-    }
+    // Nothing from the transactions built before the failure is applied
+    assert_eq!(state.nonce, 5);
+    assert_eq!(state.get_account_balance(&XELIS_ASSET).unwrap(), 100 * COIN_VALUE);
+    let ciphertext = state.get_account_ciphertext(&XELIS_ASSET).unwrap().take_ciphertext().unwrap();
+    assert_eq!(alice.keypair.decrypt_to_point(&ciphertext), Scalar::from(100 * COIN_VALUE) * G);
 }
 
-
 #[tokio::test]
 async fn test_tx_verify() {
     let mut alice = Account::new();
@@ -175,6 +1798,7 @@ async fn test_tx_verify() {
 
     let mut state = ChainState {
         accounts: HashMap::new(),
+        multisig: HashMap::new(),
     };
 
     // Create the chain state
@@ -203,6 +1827,152 @@ async fn test_tx_verify() {
     tx.verify(&mut state).await.unwrap();
 }
 
+#[tokio::test]
+async fn test_tx_verify_range_proof() {
+    let mut alice = Account::new();
+    let mut bob = Account::new();
+
+    alice.set_balance(XELIS_ASSET, 100 * COIN_VALUE);
+    bob.set_balance(XELIS_ASSET, 0);
+
+    let tx = create_tx_for(alice.clone(), bob.address(), 50, None);
+    let other = create_tx_for(alice.clone(), bob.address(), 10, None);
+
+    // Verification updates the nonce, so each tx is checked against a fresh state
+    let chain_state = || {
+        let mut state = ChainState {
+            accounts: HashMap::new(),
+            multisig: HashMap::new(),
+        };
+
+        for account in [&alice, &bob] {
+            let mut balances = HashMap::new();
+            for (asset, balance) in &account.balances {
+                balances.insert(asset.clone(), balance.ciphertext.clone().take_ciphertext().unwrap());
+            }
+            state.accounts.insert(account.keypair.get_public_key().compress(), AccountChainState {
+                balances,
+                nonce: account.nonce,
+            });
+        }
+        state
+    };
+
+    // Transfer amounts are covered by the aggregated range proof
+    // A proof made for other amounts is rejected even when the tx is signed again
+    let mut tampered = Transaction::new(
+        tx.get_version(),
+        tx.get_source().clone(),
+        None,
+        tx.get_data().clone(),
+        tx.get_fee(),
+        None,
+        tx.get_nonce(),
+        tx.get_source_commitments().clone(),
+        other.get_range_proof().clone(),
+        tx.get_reference().clone(),
+        tx.get_signature().clone(),
+        Vec::new()
+    );
+    tampered.attach_signature(alice.keypair.sign(&tampered.signing_bytes()));
+    assert!(tampered.verify_signature());
+
+    assert!(matches!(
+        tampered.verify(&mut chain_state()).await,
+        Err(VerificationError::Proof(ProofVerificationError::RangeProof(_)))
+    ));
+
+    tx.verify(&mut chain_state()).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_verify_transactions_parallel() {
+    let mut alice = Account::new();
+    let mut bob = Account::new();
+    alice.set_balance(XELIS_ASSET, 100 * COIN_VALUE);
+    bob.set_balance(XELIS_ASSET, 0);
+
+    let rebuild = |tx: &Transaction, range_proof: &RangeProof, signature: &Signature| Transaction::new(
+        tx.get_version(),
+        tx.get_source().clone(),
+        None,
+        tx.get_data().clone(),
+        tx.get_fee(),
+        None,
+        tx.get_nonce(),
+        tx.get_source_commitments().clone(),
+        range_proof.clone(),
+        tx.get_reference().clone(),
+        signature.clone(),
+        Vec::new()
+    );
+
+    let valid: Vec<Transaction> = (1..=4)
+        .map(|amount| create_tx_for(alice.clone(), bob.address(), amount, None))
+        .collect();
+
+    // Signed by alice but its range proof was made for other amounts
+    let mut bad_range_proof = rebuild(&valid[0], valid[1].get_range_proof(), valid[0].get_signature());
+    bad_range_proof.attach_signature(alice.keypair.sign(&bad_range_proof.signing_bytes()));
+
+    // Signature of another transaction
+    let bad_signature = rebuild(&valid[2], valid[2].get_range_proof(), valid[3].get_signature());
+
+    // Fee changed after signing
+    let tampered = Transaction::new(
+        valid[1].get_version(),
+        valid[1].get_source().clone(),
+        None,
+        valid[1].get_data().clone(),
+        valid[1].get_fee() + 1,
+        None,
+        valid[1].get_nonce(),
+        valid[1].get_source_commitments().clone(),
+        valid[1].get_range_proof().clone(),
+        valid[1].get_reference().clone(),
+        valid[1].get_signature().clone(),
+        Vec::new()
+    );
+
+    let txs = vec![
+        valid[0].clone(),
+        bad_range_proof,
+        valid[1].clone(),
+        bad_signature,
+        valid[2].clone(),
+        tampered,
+        valid[3].clone(),
+    ];
+
+    let serial: Vec<_> = txs.iter().map(Transaction::verify_stateless).collect();
+    assert!(serial[0].is_ok());
+    assert!(matches!(serial[1], Err(TransactionError::Proof(ProofVerificationError::RangeProof(_)))));
+    assert!(serial[2].is_ok());
+    assert_eq!(serial[3], Err(TransactionError::BadSignature));
+    assert!(serial[4].is_ok());
+    assert_eq!(serial[5], Err(TransactionError::BadSignature));
+    assert!(serial[6].is_ok());
+
+    // Same outcome as the full verification against the chain state
+    for (tx, stateless) in txs.iter().zip(&serial) {
+        let mut chain = chain_state_for(&[&alice, &bob]);
+        let result = tx.verify(&mut chain).await;
+        match stateless {
+            Ok(()) => assert!(result.is_ok()),
+            Err(TransactionError::BadSignature) => assert!(matches!(result, Err(VerificationError::InvalidSignature))),
+            Err(TransactionError::Proof(ProofVerificationError::RangeProof(_))) => assert!(matches!(result, Err(VerificationError::Proof(ProofVerificationError::RangeProof(_))))),
+            Err(e) => panic!("unexpected error {}", e)
+        }
+    }
+
+    assert_eq!(verify_transactions_parallel(&txs), serial);
+    for threads in [1, 3] {
+        let pool = build_verification_pool(threads).unwrap();
+        assert_eq!(pool.current_num_threads(), threads);
+        assert_eq!(verify_transactions_parallel_in(&pool, &txs), serial);
+    }
+}
+
 #[tokio::test]
 async fn test_burn_tx_verify() {
     let mut alice = Account::new();
@@ -224,6 +1994,7 @@ async fn test_burn_tx_verify() {
         let data = TransactionTypeBuilder::Burn(BurnPayload {
             amount: 50 * COIN_VALUE,
             asset: XELIS_ASSET,
+            extra_data: None,
         });
         let builder = TransactionBuilder::new(0, alice.keypair.get_public_key().compress(), data, FeeBuilder::Multiplier(1f64));
         let estimated_size = builder.estimate_size();
@@ -236,6 +2007,7 @@ async fn test_burn_tx_verify() {
 
     let mut state = ChainState {
         accounts: HashMap::new(),
+        multisig: HashMap::new(),
     };
 
     // Create the chain state
@@ -266,18 +2038,31 @@ async fn test_burn_tx_verify() {
 
 #[tokio::test]
 async fn test_max_transfers() {
-    let mut alice = Account::new();
-    let mut bob = Account::new();
+    verify_max_transfers(0, MAX_TRANSFER_COUNT_V0).await;
+}
+
+#[tokio::test]
+async fn test_max_transfers_v1() {
+    verify_max_transfers(1, MAX_TRANSFER_COUNT).await;
+}
 
+async fn verify_max_transfers(version: u8, count: usize) {
+    let mut alice = Account::new();
     alice.set_balance(XELIS_ASSET, 100 * COIN_VALUE);
-    bob.set_balance(XELIS_ASSET, 0);
+
+    // Each transfer must go to a different destination
+    let receivers = (0..count).map(|_| {
+        let mut account = Account::new();
+        account.set_balance(XELIS_ASSET, 0);
+        account
+    }).collect::<Vec<_>>();
 
     let tx = {
         let mut transfers = Vec::new();
-        for _ in 0..MAX_TRANSFER_COUNT {
+        for receiver in receivers.iter() {
             transfers.push(TransferBuilder {
                 amount: 1,
-                destination: bob.address(),
+                destination: receiver.address(),
                 asset: XELIS_ASSET,
                 extra_data: None,
             });
@@ -293,11 +2078,12 @@ async fn test_max_transfers() {
         };
     
         let data = TransactionTypeBuilder::Transfers(transfers);
-        let builder = TransactionBuilder::new(0, alice.keypair.get_public_key().compress(), data, FeeBuilder::Multiplier(1f64));
+        let builder = TransactionBuilder::new(version, alice.keypair.get_public_key().compress(), data, FeeBuilder::Multiplier(1f64));
         let estimated_size = builder.estimate_size();
         let tx = builder.build(&mut state, &alice.keypair).unwrap();
         assert!(estimated_size == tx.size());
         assert!(tx.to_bytes().len() == estimated_size);
+        assert!(Transaction::from_bytes(&tx.to_bytes()).is_ok());
 
         tx
     };
@@ -305,6 +2091,7 @@ async fn test_max_transfers() {
     // Create the chain state
     let mut state = ChainState {
         accounts: HashMap::new(),
+        multisig: HashMap::new(),
     };
 
     // Alice
@@ -319,21 +2106,232 @@ async fn test_max_transfers() {
         });
     }
 
-    // Bob
-    {
+    // Receivers
+    for receiver in receivers {
         let mut balances = HashMap::new();
-        for (asset, balance) in bob.balances {
+        for (asset, balance) in receiver.balances {
             balances.insert(asset, balance.ciphertext.take_ciphertext().unwrap());
         }
-        state.accounts.insert(bob.keypair.get_public_key().compress(), AccountChainState {
+        state.accounts.insert(receiver.keypair.get_public_key().compress(), AccountChainState {
             balances,
-            nonce: alice.nonce,
+            nonce: receiver.nonce,
         });
     }
 
     assert!(tx.verify(&mut state).await.is_ok());
 }
 
+// Serialize the value and check that its computed size matches the bytes written
+fn assert_size_matches<T: Serializer>(value: &T) {
+    let mut writer = Writer::new();
+    value.write(&mut writer);
+    assert_eq!(writer.total_write(), value.size(), "size() doesn't match the bytes written for {}", std::any::type_name::<T>());
+}
+
+#[test]
+fn test_serializer_sizes() {
+    let mut alice = Account::new();
+    let bob = Account::new();
+    alice.set_balance(XELIS_ASSET, 100 * COIN_VALUE);
+
+    let tx = create_tx_for(alice.clone(), bob.address(), 1, None);
+    let tx_with_extra_data = create_tx_for(alice.clone(), bob.address(), 1, Some(DataElement::Value(DataValue::String("memo".to_string()))));
+    for tx in [&tx, &tx_with_extra_data] {
+        assert_size_matches(tx);
+        assert_size_matches(tx.get_reference());
+        for commitment in tx.get_source_commitments() {
+            assert_size_matches(commitment);
+        }
+
+        for transfer in tx.get_data().transfers() {
+            assert_size_matches(transfer);
+            if let Some(extra_data) = transfer.get_extra_data() {
+                assert_size_matches(extra_data);
+            }
+        }
+    }
+
+    let multisig = MultiSigPayload::new(1, vec![bob.keypair.get_public_key().compress()]);
+    let signature = SignatureId { id: 0, signature: bob.keypair.sign(&tx.signing_bytes()) };
+    assert_size_matches(&multisig);
+    assert_size_matches(&signature);
+
+    // Version 1 with all its optional fields
+    let v1 = Transaction::new(
+        1,
+        tx.get_source().clone(),
+        Some(multisig),
+        tx_with_extra_data.get_data().clone(),
+        tx.get_fee(),
+        Some(Hash::max()),
+        tx.get_nonce(),
+        tx.get_source_commitments().clone(),
+        tx.get_range_proof().clone(),
+        tx.get_reference().clone(),
+        tx.get_signature().clone(),
+        vec![signature]
+    );
+    assert_size_matches(&v1);
+
+    let burn = TransactionType::Burn(BurnPayload {
+        asset: XELIS_ASSET,
+        amount: 1,
+        extra_data: Some(b"burn".to_vec()),
+    });
+    for version in 0..=MAX_TX_VERSION {
+        let mut writer = Writer::new();
+        burn.write_with_version(&mut writer, version);
+        assert_eq!(writer.total_write(), burn.size_with_version(version));
+    }
+}
+
+// Real transaction used to get valid points and proofs for the generated ones
+static TEMPLATE: Lazy<Transaction> = Lazy::new(|| {
+    let mut alice = Account::new();
+    alice.set_balance(XELIS_ASSET, 100 * COIN_VALUE);
+    create_tx_for(alice, Account::new().address(), 1, None)
+});
+
+fn template_transfer() -> &'static TransferPayload {
+    TEMPLATE.get_data().transfers().next().unwrap()
+}
+
+fn arb_hash() -> impl Strategy<Value = Hash> {
+    any::<[u8; 32]>().prop_map(Hash::new)
+}
+
+fn arb_extra_data() -> impl Strategy<Value = Option<Vec<u8>>> {
+    prop::option::of(prop::collection::vec(any::<u8>(), 0..=EXTRA_DATA_LIMIT_SIZE))
+}
+
+fn arb_transfer() -> impl Strategy<Value = TransferPayload> {
+    (arb_hash(), arb_extra_data()).prop_map(|(asset, extra_data)| {
+        let template = template_transfer();
+        TransferPayload::new(
+            asset,
+            template.get_destination().clone(),
+            extra_data.map(UnknownExtraDataFormat),
+            template.get_commitment().clone(),
+            template.get_sender_handle().clone(),
+            template.get_receiver_handle().clone(),
+            template.get_proof().clone()
+        )
+    })
+}
+
+fn arb_burn(version: u8) -> impl Strategy<Value = BurnPayload> {
+    (arb_hash(), 1..=u64::MAX, arb_extra_data()).prop_map(move |(asset, amount, extra_data)| BurnPayload {
+        asset,
+        amount,
+        // Only serialized since version 1
+        extra_data: extra_data.filter(|_| version >= 1)
+    })
+}
+
+fn arb_multisig() -> impl Strategy<Value = (MultiSigPayload, Vec<SignatureId>)> {
+    (1..=8usize).prop_flat_map(|count| (1..=count as u8, 0..=count as u8, Just(count)))
+        .prop_map(|(threshold, signatures, count)| {
+            let multisig = MultiSigPayload::new(threshold, vec![TEMPLATE.get_source().clone(); count]);
+            let signatures = (0..signatures)
+                .map(|id| SignatureId { id, signature: TEMPLATE.get_signature().clone() })
+                .collect();
+            (multisig, signatures)
+        })
+}
+
+fn arb_transaction() -> impl Strategy<Value = Transaction> {
+    (0..=MAX_TX_VERSION).prop_flat_map(|version| {
+        let data = prop_oneof![
+            prop::collection::vec(arb_transfer(), 1..=max_transfer_count(version)).prop_map(TransactionType::Transfers),
+            arb_burn(version).prop_map(TransactionType::Burn)
+        ];
+        let v1 = version >= 1;
+        (
+            Just(version),
+            data,
+            any::<u64>(),
+            prop::option::of(arb_hash()).prop_map(move |asset| asset.filter(|_| v1)),
+            any::<u64>(),
+            1..=max_source_commitments(version),
+            (arb_hash(), any::<u64>()),
+            prop::option::of(arb_multisig()).prop_map(move |multisig| multisig.filter(|_| v1))
+        )
+    }).prop_map(|(version, data, fee, fee_asset, nonce, commitments, (hash, topoheight), multisig)| {
+        let (multisig, multisig_signatures) = match multisig {
+            Some((multisig, signatures)) => (Some(multisig), signatures),
+            None => (None, Vec::new())
+        };
+
+        let range_proof = fixture_range_proof(commitments + data.transfer_count());
+        Transaction::new(
+            version,
+            TEMPLATE.get_source().clone(),
+            multisig,
+            data,
+            fee,
+            fee_asset,
+            nonce,
+            vec![TEMPLATE.get_source_commitments()[0].clone(); commitments],
+            range_proof,
+            Reference { hash, topoheight },
+            TEMPLATE.get_signature().clone(),
+            multisig_signatures
+        )
+    })
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(64))]
+
+    #[test]
+    fn proptest_transfer_round_trip(transfer in arb_transfer()) {
+        let bytes = transfer.to_bytes();
+        prop_assert_eq!(bytes.len(), transfer.size());
+
+        let read = TransferPayload::from_bytes(&bytes).unwrap();
+        prop_assert_eq!(read.get_asset(), transfer.get_asset());
+        prop_assert_eq!(read.to_bytes(), bytes);
+    }
+
+    #[test]
+    fn proptest_transfer_ref_matches_owned(transfer in arb_transfer()) {
+        let bytes = transfer.to_bytes();
+        let mut reader = Reader::new(&bytes);
+        let view = TransferPayloadRef::read(&mut reader).unwrap();
+        prop_assert_eq!(reader.remaining(), 0);
+        assert_transfer_ref_eq(&view, &transfer);
+    }
+
+    #[test]
+    fn proptest_burn_round_trip((version, payload) in (0..=MAX_TX_VERSION).prop_flat_map(|version| (Just(version), arb_burn(version)))) {
+        let mut writer = Writer::new();
+        payload.write_with_version(&mut writer, version);
+        let bytes = writer.bytes();
+        prop_assert_eq!(bytes.len(), payload.size_with_version(version));
+
+        let mut reader = Reader::new(&bytes);
+        let read = BurnPayload::read_with_version(&mut reader, version).unwrap();
+        prop_assert_eq!(read.asset, payload.asset);
+        prop_assert_eq!(read.amount, payload.amount);
+        prop_assert_eq!(read.extra_data, payload.extra_data);
+        prop_assert_eq!(reader.total_read(), bytes.len());
+    }
+
+    #[test]
+    fn proptest_transaction_round_trip(tx in arb_transaction()) {
+        let bytes = tx.to_bytes();
+        prop_assert_eq!(bytes.len(), tx.size());
+
+        let read = Transaction::from_bytes_exact(&bytes).unwrap();
+        prop_assert_eq!(read.get_version(), tx.get_version());
+        prop_assert_eq!(read.get_fee_asset(), tx.get_fee_asset());
+        prop_assert_eq!(read.get_nonce(), tx.get_nonce());
+        prop_assert_eq!(read.get_multisig_signatures().len(), tx.get_multisig_signatures().len());
+        prop_assert_eq!(read.to_bytes(), bytes);
+        prop_assert_eq!(read.hash(), tx.hash());
+    }
+}
+
 #[async_trait]
 impl<'a> BlockchainVerificationState<'a, ()> for ChainState {
 
@@ -390,6 +2388,24 @@ impl<'a> BlockchainVerificationState<'a, ()> for ChainState {
     ) -> Result<(), ()> {
         self.accounts.get_mut(account).map(|account| account.nonce = new_nonce).ok_or(())
     }
+
+    /// Get the co-signers registered for an account
+    async fn get_multisig_state<'b>(
+        &'b mut self,
+        account: &'a PublicKey
+    ) -> Result<Option<&'b MultiSigPayload>, ()> {
+        Ok(self.multisig.get(account))
+    }
+
+    /// Register the co-signers of an account
+    async fn set_multisig_state(
+        &mut self,
+        account: &'a PublicKey,
+        multisig: &MultiSigPayload
+    ) -> Result<(), ()> {
+        self.multisig.insert(account.clone(), multisig.clone());
+        Ok(())
+    }
 }
 
 impl FeeHelper for AccountStateImpl {
@@ -433,4 +2449,139 @@ impl AccountState for AccountStateImpl {
         self.nonce = new_nonce;
         Ok(())
     }
-}
\ No newline at end of file
+}
+
+// Wire format snapshots
+// The bytes of a transaction are signed and hashed, so they must never change silently:
+// a different encoding would invalidate every signature and transaction hash already on chain.
+// These transactions are built from fixed values only, and their bytes are compared
+// to the hex fixtures in the fixtures directory.
+// If a change of the format is intended, it must be versioned and the fixtures regenerated
+// deliberately by running this test with XELIS_UPDATE_FIXTURES=1 and committing the result.
+
+// Point i * G, to get valid and stable points
+fn fixture_point(i: u64) -> CompressedRistretto {
+    (G * Scalar::from(i)).compress()
+}
+
+// Stable bytes made of the points then the scalars
+fn fixture_bytes(points: impl IntoIterator<Item = u64>, scalars: impl IntoIterator<Item = u64>) -> Vec<u8> {
+    let mut writer = Writer::new();
+    for i in points {
+        writer.write_bytes(fixture_point(i).as_bytes());
+    }
+    for i in scalars {
+        Scalar::from(i).write(&mut writer);
+    }
+    writer.bytes()
+}
+
+// Range proof having the size expected for the commitments count
+// Its points and scalars are stable but it doesn't prove anything
+fn fixture_range_proof(commitments: usize) -> RangeProof {
+    let lg_n = (BULLET_PROOF_SIZE * commitments).next_power_of_two().trailing_zeros() as u64;
+    // 4 points and 3 scalars, then the inner product proof: 2 * lg_n points and 2 scalars
+    let mut bytes = fixture_bytes(8..=11, 12..=14);
+    bytes.extend(fixture_bytes(15..15 + 2 * lg_n, 27..=28));
+    RangeProof::from_bytes(&bytes).unwrap()
+}
+
+fn fixture_transaction(version: u8, data: TransactionType) -> Transaction {
+    let commitment_proof = CommitmentEqProof::from_bytes(&fixture_bytes(1..=3, 4..=6)).unwrap();
+    let source_commitment = SourceCommitment::new(CompressedCommitment::new(fixture_point(7)), commitment_proof, XELIS_ASSET);
+
+    let range_proof = fixture_range_proof(1 + data.transfer_count());
+
+    Transaction::new(
+        version,
+        CompressedPublicKey::new(fixture_point(29)),
+        None,
+        data,
+        12_500,
+        None,
+        7,
+        vec![source_commitment],
+        range_proof,
+        Reference {
+            hash: Hash::new([0xAB; 32]),
+            topoheight: 42
+        },
+        Signature::new(Scalar::from(30u64), Scalar::from(31u64)),
+        Vec::new()
+    )
+}
+
+fn fixture_transfer(extra_data: Option<&[u8]>) -> TransactionType {
+    let proof = CiphertextValidityProof::from_bytes(&fixture_bytes(32..=33, 34..=35)).unwrap();
+    TransactionType::Transfers(vec![TransferPayload::new(
+        XELIS_ASSET,
+        CompressedPublicKey::new(fixture_point(36)),
+        extra_data.map(|data| UnknownExtraDataFormat(data.to_vec())),
+        CompressedCommitment::new(fixture_point(37)),
+        CompressedHandle::new(fixture_point(38)),
+        CompressedHandle::new(fixture_point(39)),
+        proof
+    )])
+}
+
+fn fixture_burn(extra_data: Option<&[u8]>) -> TransactionType {
+    TransactionType::Burn(BurnPayload {
+        asset: XELIS_ASSET,
+        amount: 1_000,
+        extra_data: extra_data.map(<[u8]>::to_vec)
+    })
+}
+
+#[test]
+fn test_tx_range_proof_size() {
+    let tx = fixture_transaction(1, fixture_transfer(None));
+    assert_eq!(tx.size(), tx.to_bytes().len());
+
+    // A proof aggregating another count of commitments can never be valid
+    let mut tx = fixture_transaction(1, fixture_burn(None));
+    tx.range_proof = fixture_range_proof(2);
+    let mut writer = Writer::new();
+    tx.write(&mut writer);
+    assert!(matches!(Transaction::from_bytes(&writer.bytes()), Err(ReaderError::InvalidSize)));
+}
+
+#[test]
+fn test_wire_format_snapshots() {
+    let fixtures = [
+        ("transfer_v0", fixture_transaction(0, fixture_transfer(None))),
+        ("transfer_v1", fixture_transaction(1, fixture_transfer(None))),
+        ("transfer_extra_data_v1", fixture_transaction(1, fixture_transfer(Some(b"wire format")))),
+        ("burn_v0", fixture_transaction(0, fixture_burn(None))),
+        ("burn_extra_data_v1", fixture_transaction(1, fixture_burn(Some(b"wire format")))),
+    ];
+
+    let update = std::env::var_os("XELIS_UPDATE_FIXTURES").is_some();
+    for (name, tx) in fixtures {
+        let path = format!("{}/src/transaction/fixtures/{}.hex", env!("CARGO_MANIFEST_DIR"), name);
+        let bytes = tx.to_bytes();
+        if update {
+            std::fs::write(&path, format!("{}\n", hex::encode(&bytes))).unwrap();
+            continue
+        }
+
+        let fixture = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(hex::encode(&bytes), fixture.trim(), "wire format of {} changed", name);
+
+        // The fixture must also be read back to the same transaction
+        let read = Transaction::from_bytes(&hex::decode(fixture.trim()).unwrap()).unwrap();
+        assert_eq!(read.to_bytes(), bytes);
+        assert_eq!(read.hash(), tx.hash());
+    }
+}
+
+#[test]
+#[should_panic(expected = "Transaction count 256 does not fit in a u8")]
+fn test_write_count_overflow_v0() {
+    write_count(&mut Writer::new(), MAX_TRANSFER_COUNT_V0 + 1, 0);
+}
+
+#[test]
+#[should_panic(expected = "Transaction count 65536 does not fit in a u16")]
+fn test_write_count_overflow() {
+    write_count(&mut Writer::new(), u16::MAX as usize + 1, 1);
+}
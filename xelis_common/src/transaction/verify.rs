@@ -2,11 +2,12 @@ use bulletproofs::RangeProof;
 use curve25519_dalek::{ristretto::CompressedRistretto, traits::Identity, RistrettoPoint, Scalar};
 use log::{debug, trace};
 use merlin::Transcript;
-use crate::{config::XELIS_ASSET, crypto::{elgamal::{Ciphertext, CompressedPublicKey, DecompressionError, DecryptHandle, PedersenCommitment}, proofs::{BatchCollector, ProofVerificationError, BP_GENS, BULLET_PROOF_SIZE, PC_GENS}, Hash, ProtocolTranscript, SIGNATURE_SIZE}, serializer::Serializer, transaction::{EXTRA_DATA_LIMIT_SIZE, MAX_TRANSFER_COUNT}};
-use super::{Reference, Role, Transaction, TransactionType, TransferPayload};
+use crate::{config::XELIS_ASSET, crypto::{elgamal::{Ciphertext, CompressedPublicKey, DecompressionError, DecryptHandle, PedersenCommitment, PublicKey}, proofs::{BatchCollector, ProofVerificationError, BP_GENS, BULLET_PROOF_SIZE, PC_GENS}, Hash, ProtocolTranscript}};
+use super::{multisig::MultiSigPayload, Reference, Role, SourceCommitment, Transaction, TransactionError, TransactionType, TransferPayload};
 use thiserror::Error;
-use std::iter;
+use std::{collections::HashSet, iter};
 use async_trait::async_trait;
+use rayon::{prelude::*, ThreadPool, ThreadPoolBuildError, ThreadPoolBuilder};
 
 /// This trait is used by the batch verification function.
 /// It is intended to represent a virtual snapshot of the current blockchain
@@ -59,6 +60,19 @@ pub trait BlockchainVerificationState<'a, E> {
         account: &'a CompressedPublicKey,
         new_nonce: u64
     ) -> Result<(), E>;
+
+    /// Get the co-signers registered for an account, if any
+    async fn get_multisig_state<'b>(
+        &'b mut self,
+        account: &'a CompressedPublicKey
+    ) -> Result<Option<&'b MultiSigPayload>, E>;
+
+    /// Register the co-signers of an account
+    async fn set_multisig_state(
+        &mut self,
+        account: &'a CompressedPublicKey,
+        multisig: &MultiSigPayload
+    ) -> Result<(), E>;
 }
 
 #[derive(Error, Debug, Clone)]
@@ -71,6 +85,10 @@ pub enum VerificationError<T> {
     SenderIsReceiver,
     #[error("Invalid signature")]
     InvalidSignature,
+    #[error("Multisig doesn't match the one registered for the account")]
+    MultiSigMismatch,
+    #[error(transparent)]
+    Transaction(#[from] TransactionError),
     #[error("Proof verification error: {0}")]
     Proof(#[from] ProofVerificationError),
 }
@@ -100,6 +118,25 @@ impl DecompressedTransferCt {
     }
 }
 
+// Points of a transaction decompressed once for all its proofs
+struct DecompressedTransaction {
+    owner: PublicKey,
+    transfers: Vec<DecompressedTransferCt>,
+    new_source_commitments: Vec<PedersenCommitment>,
+}
+
+impl<E> VerificationError<E> {
+    // Keep the dedicated variants for the errors of the checks shared with verify_stateless
+    fn from_stateless(err: TransactionError) -> Self {
+        match err {
+            TransactionError::SenderIsReceiver => Self::SenderIsReceiver,
+            TransactionError::BadSignature => Self::InvalidSignature,
+            TransactionError::Proof(e) => Self::Proof(e),
+            e => Self::Transaction(e),
+        }
+    }
+}
+
 impl Transaction {
     /// Get the new output ciphertext
     // This is used to substract the amount from the sender's balance
@@ -110,8 +147,8 @@ impl Transaction {
     ) -> Result<Ciphertext, DecompressionError> {
         let mut output = Ciphertext::zero();
 
-        if *asset == XELIS_ASSET {
-            // Fees are applied to the native blockchain asset only.
+        if asset == self.get_fee_asset() {
+            // Fees are applied to the fee asset only
             output += Scalar::from(self.fee);
         }
 
@@ -147,6 +184,105 @@ impl Transaction {
         transcript
     }
 
+    /// Verify the signature of the source key on this transaction.
+    /// The signed message is the output of `signing_bytes`.
+    pub fn verify_signature(&self) -> bool {
+        let Ok(owner) = self.source.decompress() else {
+            debug!("transaction source key is not a valid point");
+            return false
+        };
+
+        self.verify_signature_with_key(&owner)
+    }
+
+    // Verify the signature using the already decompressed source key
+    fn verify_signature_with_key(&self, owner: &PublicKey) -> bool {
+        self.signature.verify(&self.signing_bytes(), owner)
+    }
+
+    // Verify the source signature and the co-signers signatures
+    pub fn check_signatures(&self) -> Result<(), TransactionError> {
+        if !self.verify_signature() || !self.verify_multisig_signatures() {
+            return Err(TransactionError::BadSignature)
+        }
+
+        Ok(())
+    }
+
+    /// Verify that enough co-signers have signed this transaction.
+    /// Always valid when no multisig is set.
+    pub fn verify_multisig_signatures(&self) -> bool {
+        let Some(multisig) = self.multisig.as_ref() else {
+            return self.multisig_signatures.is_empty()
+        };
+
+        if self.multisig_signatures.len() < multisig.threshold as usize {
+            debug!("not enough multisig signatures: {} < {}", self.multisig_signatures.len(), multisig.threshold);
+            return false
+        }
+
+        let bytes = self.signing_bytes();
+        let mut ids = HashSet::with_capacity(self.multisig_signatures.len());
+        for signature in &self.multisig_signatures {
+            // A co-signer can only sign once
+            if !ids.insert(signature.id) {
+                debug!("duplicated multisig signature for id {}", signature.id);
+                return false
+            }
+
+            let Some(key) = multisig.participants.get(signature.id as usize) else {
+                debug!("multisig signature id {} is out of bounds", signature.id);
+                return false
+            };
+
+            let Ok(key) = key.decompress() else {
+                debug!("multisig participant key is not a valid point");
+                return false
+            };
+
+            if !signature.signature.verify(&bytes, &key) {
+                debug!("multisig signature for id {} is invalid", signature.id);
+                return false
+            }
+        }
+
+        true
+    }
+
+    // Co-signers are the ones registered for the source account, not the ones chosen by the sender
+    // A multisig account must provide its registered multisig, signatures are verified against it
+    // The first transaction carrying a multisig registers it for its source
+    async fn verify_multisig_state<'a, E, B: BlockchainVerificationState<'a, E>>(
+        &'a self,
+        state: &mut B,
+    ) -> Result<(), VerificationError<E>> {
+        let registered = state.get_multisig_state(&self.source).await
+            .map_err(VerificationError::State)?;
+
+        match (registered, &self.multisig) {
+            (Some(registered), Some(multisig)) if registered == multisig => Ok(()),
+            (Some(_), _) => {
+                debug!("transaction multisig doesn't match the one registered for the account");
+                Err(VerificationError::MultiSigMismatch)
+            },
+            (None, Some(multisig)) => state.set_multisig_state(&self.source, multisig).await
+                .map_err(VerificationError::State),
+            (None, None) => Ok(())
+        }
+    }
+
+    // The fee is credited to the miner and checked against the minimum fee as XELIS,
+    // so a fee paid in another asset can't be accepted until the chain supports it
+    fn verify_fee_asset(&self) -> Result<(), TransactionError> {
+        let asset = self.get_fee_asset();
+        if *asset != XELIS_ASSET {
+            debug!("fee asset {} is not accepted", asset);
+            return Err(TransactionError::FeeAssetNotAccepted(asset.clone()));
+        }
+
+        Ok(())
+    }
+
     // Verify that the commitment assets match the assets used in the tx
     fn verify_commitment_assets(&self) -> bool {
         let has_commitment_for_asset = |asset| {
@@ -155,8 +291,8 @@ impl Transaction {
                 .any(|c| &c.asset == asset)
         };
 
-        // XELIS_ASSET is always required for fees
-        if !has_commitment_for_asset(&XELIS_ASSET) {
+        // Fee asset is always required
+        if !has_commitment_for_asset(self.get_fee_asset()) {
             return false;
         }
 
@@ -184,58 +320,26 @@ impl Transaction {
         }
     }
 
-    // internal, does not verify the range proof
-    // returns (transcript, commitments for range proof)
-    async fn pre_verify<'a, E, B: BlockchainVerificationState<'a, E>>(
-        &'a self,
-        state: &mut B,
-        sigma_batch_collector: &mut BatchCollector,
-    ) -> Result<(Transcript, Vec<(RistrettoPoint, CompressedRistretto)>), VerificationError<E>>
-    {
-        trace!("Pre-verifying transaction");
-        state.pre_verify_tx(&self).await
-            .map_err(VerificationError::State)?;
-
-        // First, check the nonce
-        let account_nonce = state.get_account_nonce(&self.source).await
-            .map_err(VerificationError::State)?;
-
-        if account_nonce != self.nonce {
-            return Err(VerificationError::InvalidNonce(account_nonce, self.nonce));
-        }
-
-        // Nonce is valid, update it for next transactions if any
-        state
-            .update_account_nonce(&self.source, self.nonce + 1).await
-            .map_err(VerificationError::State)?;
-
+    // Checks done by both `pre_verify` and `verify_stateless` before any proof:
+    // format, fee asset and signatures, then decompress the points used by the proofs
+    fn verify_format_and_signatures(&self) -> Result<DecompressedTransaction, TransactionError> {
         if !self.verify_commitment_assets() {
             debug!("Invalid commitment assets");
-            return Err(VerificationError::Proof(ProofVerificationError::Format));
+            return Err(ProofVerificationError::Format.into());
         }
 
-        let transfers_decompressed = if let TransactionType::Transfers(transfers) = &self.data {
-            if transfers.len() > MAX_TRANSFER_COUNT || transfers.is_empty() {
-                debug!("incorrect transfers size: {}", transfers.len());
-                return Err(VerificationError::Proof(ProofVerificationError::Format));
-            }
-
-            let mut extra_data_size = 0;
-            // Prevent sending to ourself
-            for transfer in transfers.iter() {
-                if transfer.destination == self.source {
-                    debug!("sender cannot be the receiver in the same TX");
-                    return Err(VerificationError::SenderIsReceiver);
-                }
+        if let Err(e) = self.validate() {
+            debug!("transaction is invalid: {}", e);
+            return Err(e);
+        }
 
-                if let Some(extra_data) = transfer.extra_data.as_ref() {
-                    extra_data_size += extra_data.size();
-                }
-            }
+        self.verify_fee_asset()?;
 
-            if extra_data_size > EXTRA_DATA_LIMIT_SIZE {
-                debug!("extra data size is too large");
-                return Err(VerificationError::Proof(ProofVerificationError::Format));
+        let transfers = if let TransactionType::Transfers(transfers) = &self.data {
+            // Prevent sending to ourself
+            if transfers.iter().any(|transfer| transfer.destination == self.source) {
+                debug!("sender cannot be the receiver in the same TX");
+                return Err(TransactionError::SenderIsReceiver);
             }
 
             transfers
@@ -247,7 +351,7 @@ impl Transaction {
             vec![]
         };
 
-        let new_source_commitments_decompressed = self
+        let new_source_commitments = self
             .source_commitments
             .iter()
             .map(|commitment| commitment.commitment.decompress())
@@ -257,27 +361,157 @@ impl Transaction {
         let owner = self
             .source
             .decompress()
-            .map_err(|err| VerificationError::Proof(err.into()))?;
-
-        let mut transcript = Self::prepare_transcript(self.version, &self.source, self.fee, self.nonce);
+            .map_err(ProofVerificationError::from)?;
 
         // 0. Verify Signature
-        let bytes = self.to_bytes();
-        if !self.signature.verify(&bytes[..bytes.len() - SIGNATURE_SIZE], &owner) {
+        if !self.verify_signature_with_key(&owner) {
             debug!("transaction signature is invalid");
-            return Err(VerificationError::InvalidSignature);
+            return Err(TransactionError::BadSignature);
+        }
+
+        if !self.verify_multisig_signatures() {
+            debug!("transaction multisig signatures are invalid");
+            return Err(TransactionError::BadSignature);
+        }
+
+        Ok(DecompressedTransaction {
+            owner,
+            transfers,
+            new_source_commitments,
+        })
+    }
+
+    // Domain of a commitment equality proof, appended before the proof itself
+    fn append_source_commitment(transcript: &mut Transcript, commitment: &SourceCommitment) {
+        transcript.new_commitment_eq_proof_domain_separator();
+        transcript.append_hash(b"new_source_commitment_asset", &commitment.asset);
+        transcript
+            .append_commitment(b"new_source_commitment", &commitment.commitment);
+    }
+
+    // Append each transfer to the transcript and queue its ciphertext validity proof
+    fn pre_verify_transfer_proofs(
+        &self,
+        transfers_decompressed: &[DecompressedTransferCt],
+        transcript: &mut Transcript,
+        sigma_batch_collector: &mut BatchCollector,
+    ) -> Result<(), ProofVerificationError> {
+        let TransactionType::Transfers(transfers) = &self.data else {
+            return Ok(())
+        };
+
+        for (transfer, decompressed) in transfers.iter().zip(transfers_decompressed) {
+            let receiver = transfer
+                .destination
+                .decompress()
+                .map_err(ProofVerificationError::from)?;
+
+            transcript.transfer_proof_domain_separator();
+            transcript.append_public_key(b"dest_pubkey", &transfer.destination);
+            transcript.append_commitment(b"amount_commitment", &transfer.commitment);
+            transcript.append_handle(b"amount_sender_handle", &transfer.sender_handle);
+            transcript
+                .append_handle(b"amount_receiver_handle", &transfer.receiver_handle);
+
+            transfer.ct_validity_proof.pre_verify(
+                &decompressed.commitment,
+                &receiver,
+                &decompressed.receiver_handle,
+                transcript,
+                sigma_batch_collector,
+            )?;
         }
 
+        Ok(())
+    }
+
+    // Commitments proven by the aggregated range proof: the new source balances then the transfer amounts
+    // Fake commitments are added to make `m` (party size) of the bulletproof a power of two
+    fn range_proof_commitments(
+        &self,
+        new_source_commitments: &[PedersenCommitment],
+        transfers_decompressed: &[DecompressedTransferCt],
+    ) -> Result<Vec<(RistrettoPoint, CompressedRistretto)>, ProofVerificationError> {
+        let transfers = match &self.data {
+            TransactionType::Transfers(transfers) => transfers.as_slice(),
+            TransactionType::Burn(_) => &[]
+        };
+
+        let n_commitments = self.source_commitments.len() + transfers.len();
+        let n_dud_commitments = n_commitments
+            .checked_next_power_of_two()
+            .ok_or(ProofVerificationError::Format)?
+            - n_commitments;
+
+        let value_commitments = self
+            .source_commitments
+            .iter()
+            .zip(new_source_commitments)
+            .map(|(commitment, new_source_commitment)| {
+                (
+                    *new_source_commitment.as_point(),
+                    *commitment.commitment.as_point(),
+                )
+            })
+            .chain(transfers.iter().zip(transfers_decompressed).map(
+                |(transfer, decompressed)| {
+                    (
+                        *decompressed.commitment.as_point(),
+                        *transfer.commitment.as_point(),
+                    )
+                },
+            ))
+            .chain(
+                iter::repeat((RistrettoPoint::identity(), CompressedRistretto::identity()))
+                    .take(n_dud_commitments),
+            )
+            .collect();
+
+        Ok(value_commitments)
+    }
+
+    // internal, does not verify the range proof
+    // returns (transcript, commitments for range proof)
+    async fn pre_verify<'a, E, B: BlockchainVerificationState<'a, E>>(
+        &'a self,
+        state: &mut B,
+        sigma_batch_collector: &mut BatchCollector,
+    ) -> Result<(Transcript, Vec<(RistrettoPoint, CompressedRistretto)>), VerificationError<E>>
+    {
+        trace!("Pre-verifying transaction");
+        state.pre_verify_tx(&self).await
+            .map_err(VerificationError::State)?;
+
+        // First, check the nonce
+        let account_nonce = state.get_account_nonce(&self.source).await
+            .map_err(VerificationError::State)?;
+
+        if account_nonce != self.nonce {
+            return Err(VerificationError::InvalidNonce(account_nonce, self.nonce));
+        }
+
+        // Nonce is valid, update it for next transactions if any
+        state
+            .update_account_nonce(&self.source, self.nonce + 1).await
+            .map_err(VerificationError::State)?;
+
+        let decompressed = self.verify_format_and_signatures()
+            .map_err(VerificationError::from_stateless)?;
+
+        self.verify_multisig_state(state).await?;
+
+        let mut transcript = Self::prepare_transcript(self.version, &self.source, self.fee, self.nonce);
+
         // 1. Verify CommitmentEqProofs
         trace!("verifying commitments eq proofs");
 
         for (commitment, new_source_commitment) in self
             .source_commitments
             .iter()
-            .zip(&new_source_commitments_decompressed)
+            .zip(&decompressed.new_source_commitments)
         {
             // Ciphertext containing all the funds spent for this commitment
-            let output = self.get_sender_output_ct(&commitment.asset, &transfers_decompressed)
+            let output = self.get_sender_output_ct(&commitment.asset, &decompressed.transfers)
             .map_err(|err| VerificationError::Proof(err.into()))?;
 
             // Retrieve the balance of the sender
@@ -287,13 +521,10 @@ impl Transaction {
 
             // Compute the new final balance for account
             *source_verification_ciphertext -= &output;
-            transcript.new_commitment_eq_proof_domain_separator();
-            transcript.append_hash(b"new_source_commitment_asset", &commitment.asset);
-            transcript
-                .append_commitment(b"new_source_commitment", &commitment.commitment);
+            Self::append_source_commitment(&mut transcript, commitment);
 
             commitment.proof.pre_verify(
-                &owner,
+                &decompressed.owner,
                 &source_verification_ciphertext,
                 &new_source_commitment,
                 &mut transcript,
@@ -314,14 +545,8 @@ impl Transaction {
         trace!("verifying transfers ciphertext validity proofs");
 
         if let TransactionType::Transfers(transfers) = &self.data {
-            for (transfer, decompressed) in transfers.iter().zip(&transfers_decompressed) {
-                let receiver = transfer
-                    .destination
-                    .decompress()
-                    .map_err(ProofVerificationError::from)?;
-
-                // Update receiver balance
-
+            // Update receiver balances
+            for (transfer, decompressed) in transfers.iter().zip(&decompressed.transfers) {
                 let current_balance = state
                     .get_receiver_balance(
                         &transfer.destination,
@@ -331,73 +556,12 @@ impl Transaction {
 
                 let receiver_ct = decompressed.get_ciphertext(Role::Receiver);
                 *current_balance += receiver_ct;
-
-                // Validity proof
-
-                transcript.transfer_proof_domain_separator();
-                transcript.append_public_key(b"dest_pubkey", &transfer.destination);
-                transcript.append_commitment(b"amount_commitment", &transfer.commitment);
-                transcript.append_handle(b"amount_sender_handle", &transfer.sender_handle);
-                transcript
-                    .append_handle(b"amount_receiver_handle", &transfer.receiver_handle);
-
-                transfer.ct_validity_proof.pre_verify(
-                    &decompressed.commitment,
-                    &receiver,
-                    &decompressed.receiver_handle,
-                    &mut transcript,
-                    sigma_batch_collector,
-                )?;
             }
         }
 
-        // Prepare the new source commitments
-
-        let new_source_commitments = self
-            .source_commitments
-            .iter()
-            .zip(&new_source_commitments_decompressed)
-            .map(|(commitment, new_source_commitment)| {
-                (
-                    new_source_commitment.as_point().clone(),
-                    commitment.commitment.as_point().clone(),
-                )
-            });
-
-        let mut n_commitments = self.source_commitments.len();
-        if let TransactionType::Transfers(transfers) = &self.data {
-            n_commitments += transfers.len()
-        }
-
-        // Create fake commitments to make `m` (party size) of the bulletproof a power of two.
-        let n_dud_commitments = n_commitments
-            .checked_next_power_of_two()
-            .ok_or(ProofVerificationError::Format)?
-            - n_commitments;
+        self.pre_verify_transfer_proofs(&decompressed.transfers, &mut transcript, sigma_batch_collector)?;
 
-        let value_commitments: Vec<(RistrettoPoint, CompressedRistretto)> = if let TransactionType::Transfers(transfers) = &self.data {
-            new_source_commitments
-                .chain(transfers.iter().zip(&transfers_decompressed).map(
-                    |(transfer, decompressed)| {
-                        (
-                            decompressed.commitment.as_point().clone(),
-                            transfer.commitment.as_point().clone(),
-                        )
-                    },
-                ))
-                .chain(
-                    iter::repeat((RistrettoPoint::identity(), CompressedRistretto::identity()))
-                        .take(n_dud_commitments),
-                )
-                .collect()
-        } else {
-            new_source_commitments
-                .chain(
-                    iter::repeat((RistrettoPoint::identity(), CompressedRistretto::identity()))
-                        .take(n_dud_commitments),
-                )
-                .collect()
-        };
+        let value_commitments = self.range_proof_commitments(&decompressed.new_source_commitments, &decompressed.transfers)?;
 
         // 3. Verify the aggregated RangeProof
         trace!("verifying range proof");
@@ -465,6 +629,42 @@ impl Transaction {
         Ok(())
     }
 
+    /// Verify everything that doesn't require the chain state: format, signatures,
+    /// ciphertext validity proofs and the aggregated range proof.
+    /// Commitment equality proofs depend on the sender balances and are only checked by `verify`,
+    /// same for the multisig registered for the source account.
+    pub fn verify_stateless(&self) -> Result<(), TransactionError> {
+        let decompressed = self.verify_format_and_signatures()?;
+
+        let mut transcript = Self::prepare_transcript(self.version, &self.source, self.fee, self.nonce);
+        let mut sigma_batch_collector = BatchCollector::default();
+
+        // Commitment equality proofs are only appended to reach the same transcript as `pre_verify`
+        for commitment in &self.source_commitments {
+            Self::append_source_commitment(&mut transcript, commitment);
+            commitment.proof.append_to_transcript(&mut transcript)?;
+        }
+
+        self.pre_verify_transfer_proofs(&decompressed.transfers, &mut transcript, &mut sigma_batch_collector)?;
+
+        sigma_batch_collector
+            .verify()
+            .map_err(|_| ProofVerificationError::GenericProof)?;
+
+        let commitments = self.range_proof_commitments(&decompressed.new_source_commitments, &decompressed.transfers)?;
+        RangeProof::verify_multiple(
+            &self.range_proof,
+            &BP_GENS,
+            &PC_GENS,
+            &mut transcript,
+            &commitments,
+            BULLET_PROOF_SIZE,
+        )
+        .map_err(ProofVerificationError::from)?;
+
+        Ok(())
+    }
+
     /// Assume the tx is valid, apply it to `state`. May panic if a ciphertext is ill-formed.
     pub async fn apply_without_verify<'a, E, B: BlockchainVerificationState<'a, E>>(
         &'a self,
@@ -473,6 +673,13 @@ impl Transaction {
         // Update nonce
         state.update_account_nonce(self.get_source(), self.nonce + 1).await?;
 
+        // Register the co-signers if it's the first multisig of this account
+        if let Some(multisig) = &self.multisig {
+            if state.get_multisig_state(&self.source).await?.is_none() {
+                state.set_multisig_state(&self.source, multisig).await?;
+            }
+        }
+
         let transfers_decompressed = if let TransactionType::Transfers(transfers) = &self.data {
             transfers
                 .iter()
@@ -580,10 +787,7 @@ impl Transaction {
 
             // Compute the new final balance for account
             source_verification_ciphertext -= &output;
-            transcript.new_commitment_eq_proof_domain_separator();
-            transcript.append_hash(b"new_source_commitment_asset", &commitment.asset);
-            transcript
-                .append_commitment(b"new_source_commitment", &commitment.commitment);
+            Self::append_source_commitment(&mut transcript, commitment);
 
             commitment.proof.pre_verify(
                 &owner,
@@ -620,6 +824,16 @@ impl Transaction {
                 .map_err(VerificationError::State)?;
         }
 
+        // Register the co-signers if it's the first multisig of this account
+        if let Some(multisig) = &self.multisig {
+            let registered = state.get_multisig_state(&self.source).await
+                .map_err(VerificationError::State)?;
+            if registered.is_none() {
+                state.set_multisig_state(&self.source, multisig).await
+                    .map_err(VerificationError::State)?;
+            }
+        }
+
         // Apply receiver balances
         if let TransactionType::Transfers(transfers) = &self.data {
             for transfer in transfers {
@@ -642,4 +856,26 @@ impl Transaction {
 
         Ok(())
     }
-}
\ No newline at end of file
+}
+
+// Build a thread pool dedicated to the transactions verification
+// Zero threads lets rayon pick one thread per CPU
+pub fn build_verification_pool(threads: usize) -> Result<ThreadPool, ThreadPoolBuildError> {
+    ThreadPoolBuilder::new()
+        .num_threads(threads)
+        .thread_name(|i| format!("tx-verifier-{}", i))
+        .build()
+}
+
+// Run the stateless verification of each transaction on the rayon global pool
+// Results are in the same order as the transactions
+pub fn verify_transactions_parallel(txs: &[Transaction]) -> Vec<Result<(), TransactionError>> {
+    txs.par_iter()
+        .map(Transaction::verify_stateless)
+        .collect()
+}
+
+// Same as verify_transactions_parallel but using the provided thread pool
+pub fn verify_transactions_parallel_in(pool: &ThreadPool, txs: &[Transaction]) -> Vec<Result<(), TransactionError>> {
+    pool.install(|| verify_transactions_parallel(txs))
+}
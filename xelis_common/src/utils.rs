@@ -14,6 +14,7 @@ use crate::{
         FEE_PER_TRANSFER
     },
     difficulty::Difficulty,
+    transaction::amount::{format_amount, parse_amount},
     varuint::VarUint
 };
 
@@ -27,8 +28,9 @@ macro_rules! async_handler {
 }
 
 // Format any coin value using the requested decimals count
+// See format_amount, trailing zeros are removed
 pub fn format_coin(value: u64, decimals: u8) -> String {
-    format!("{:.1$}", value as f64 / 10usize.pow(decimals as u32) as f64, decimals as usize)
+    format_amount(value, decimals)
 }
 
 // Format value using XELIS decimals
@@ -42,14 +44,9 @@ pub fn from_xelis(value: impl Into<String>) -> Option<u64> {
 }
 
 // Convert a coin amount from string to a u64 based on the provided decimals
+// See parse_amount, amounts that can't be represented exactly are rejected
 pub fn from_coin(value: impl Into<String>, coin_decimals: u8) -> Option<u64> {
-    let value = value.into();
-    let mut split = value.split('.');
-    let value: u64 = split.next()?.parse::<u64>().ok()?;
-    let right_part = split.next().unwrap_or("0");
-    let decimals: String = right_part.chars().chain(std::iter::repeat('0')).take(coin_decimals as usize).collect();
-    let decimals_value = decimals.parse::<u64>().ok()?;
-    Some(value * 10u64.pow(coin_decimals as u32) + decimals_value)
+    parse_amount(&value.into(), coin_decimals).ok()
 }
 
 // return the fee for a transaction based on its size in bytes
@@ -186,5 +183,16 @@ mod tests {
     fn test_from_xelis() {
         let value = from_xelis("100.123");
         assert_eq!(value, Some(100_123_00000));
+
+        // Not rounded nor overflowing
+        assert_eq!(from_xelis("0.000000001"), None);
+        assert_eq!(from_xelis("184467440737.09551616"), None);
+    }
+
+    #[test]
+    fn test_format_xelis() {
+        assert_eq!(format_xelis(100_123_00000), "100.123");
+        assert_eq!(format_xelis(u64::MAX), "184467440737.09551615");
+        assert_eq!(from_xelis(format_xelis(u64::MAX)), Some(u64::MAX));
     }
 }
\ No newline at end of file
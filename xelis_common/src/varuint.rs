@@ -49,8 +49,9 @@ impl VarUint {
     }
 }
 
-impl Serializer for VarUint {
-    fn write(&self, writer: &mut Writer) {
+impl VarUint {
+    // Big endian bytes of the value and how much of them are written
+    fn encoded(&self) -> ([u8; 32], usize) {
         let mut buffer = [0u8; 32];
         self.0.to_big_endian(&mut buffer);
         let mut len = buffer.len();
@@ -60,12 +61,24 @@ impl Serializer for VarUint {
             len -= 1;
         }
 
+        (buffer, len)
+    }
+}
+
+impl Serializer for VarUint {
+    fn write(&self, writer: &mut Writer) {
+        let (buffer, len) = self.encoded();
         writer.write_u8(len as u8);
         if len > 0 {
             writer.write_bytes(&buffer[..len]);
         }
     }
 
+    fn size(&self) -> usize {
+        // 1 for the length
+        1 + self.encoded().1
+    }
+
     fn read(reader: &mut Reader) -> Result<Self, ReaderError> {
         let mut buffer = [0u8; 32];
         let len = reader.read_u8()? as usize;
@@ -285,6 +298,7 @@ mod tests {
         let compact: VarUint = U256::from_big_endian(&bytes).into();
         let bytes = compact.to_bytes();
         assert_eq!(bytes.len() - 1, expected_size); // - 1 for byte len
+        assert_eq!(compact.size(), bytes.len());
         let compact2 = VarUint::read(&mut Reader::new(&bytes)).unwrap();
         assert_eq!(compact.as_ref(), compact2.as_ref());
     }
@@ -0,0 +1,6 @@
+// Rejected inputs are covered by the unit tests of xelis_derive
+#[test]
+fn test_serializer_derive_ui() {
+    let t = trybuild::TestCases::new();
+    t.pass("tests/ui/pass_*.rs");
+}
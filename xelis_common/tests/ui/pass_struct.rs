@@ -0,0 +1,30 @@
+use xelis_common::{
+    crypto::Hash,
+    serializer::Serializer
+};
+
+#[derive(Serializer)]
+struct Payload {
+    asset: Hash,
+    amount: u64,
+    extra_data: Option<Vec<u8>>,
+    #[serializer(skip)]
+    cache: Option<Hash>
+}
+
+#[derive(Serializer)]
+struct Wrapper<T>(T, Vec<T>);
+
+fn main() {
+    let payload = Payload {
+        asset: Hash::zero(),
+        amount: 10,
+        extra_data: None,
+        cache: None
+    };
+    let bytes = payload.to_bytes();
+    assert_eq!(Payload::from_bytes_exact(&bytes).unwrap().amount, 10);
+
+    let wrapper = Wrapper(1u8, vec![2u8, 3]);
+    assert_eq!(wrapper.size(), 1 + 2 + 2);
+}
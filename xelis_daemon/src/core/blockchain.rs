@@ -1264,7 +1264,7 @@ impl<S: Storage> Blockchain<S> {
 
     // Add a tx to the mempool, its hash will be computed
     pub async fn add_tx_to_mempool(&self, tx: Transaction, broadcast: bool) -> Result<(), BlockchainError> {
-        let hash = tx.hash();
+        let hash = tx.hash().clone();
         self.add_tx_to_mempool_with_hash(tx, hash, broadcast).await
     }
 
@@ -1706,7 +1706,7 @@ impl<S: Storage> Blockchain<S> {
                 }
 
                 // verification that the real TX Hash is the same as in block header (and also check the correct order)
-                let tx_hash = tx.hash();
+                let tx_hash = tx.hash().clone();
                 if tx_hash != *hash {
                     debug!("Invalid tx {} vs {} in block header", tx_hash, hash);
                     return Err(BlockchainError::InvalidTxInBlock(tx_hash))
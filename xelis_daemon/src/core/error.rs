@@ -15,7 +15,7 @@ use xelis_common::{
     rpc_server::InternalRpcError,
     serializer::ReaderError,
     time::TimestampMillis,
-    transaction::verify::VerificationError,
+    transaction::{verify::VerificationError, TransactionError},
     utils::format_xelis
 };
 use human_bytes::human_bytes;
@@ -309,6 +309,8 @@ pub enum BlockchainError {
     TransactionProof(ProofVerificationError),
     #[error("Error while generating pow hash")]
     POWHashError(#[from] XelisHashError),
+    #[error("Invalid transaction: {}", _0)]
+    InvalidTransaction(TransactionError),
 }
 
 impl BlockchainError {
@@ -336,6 +338,7 @@ impl From<VerificationError<BlockchainError>> for BlockchainError {
             VerificationError::InvalidNonce(expected, got) => BlockchainError::InvalidNonce(expected, got),
             VerificationError::SenderIsReceiver => BlockchainError::NoSenderOutput,
             VerificationError::InvalidSignature => BlockchainError::InvalidTransactionSignature,
+            VerificationError::Transaction(e) => BlockchainError::InvalidTransaction(e),
             VerificationError::State(s) => s,
             VerificationError::Proof(proof) => BlockchainError::TransactionProof(proof)
         }
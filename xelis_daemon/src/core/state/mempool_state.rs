@@ -8,6 +8,7 @@ use xelis_common::{
         PublicKey
     },
     transaction::{
+        multisig::MultiSigPayload,
         verify::BlockchainVerificationState,
         Reference,
         Transaction
@@ -255,4 +256,24 @@ impl<'a, S: Storage> BlockchainVerificationState<'a, BlockchainError> for Mempoo
     ) -> Result<(), BlockchainError> {
         self.internal_update_account_nonce(account, new_nonce).await
     }
+
+    /// Get the co-signers registered for an account
+    /// Only version 1 transactions can register them and they are rejected by pre_verify_tx,
+    /// so no account has a multisig yet
+    async fn get_multisig_state<'b>(
+        &'b mut self,
+        _: &'a PublicKey
+    ) -> Result<Option<&'b MultiSigPayload>, BlockchainError> {
+        Ok(None)
+    }
+
+    /// Register the co-signers of an account
+    /// Not supported until version 1 transactions are accepted
+    async fn set_multisig_state(
+        &mut self,
+        _: &'a PublicKey,
+        _: &MultiSigPayload
+    ) -> Result<(), BlockchainError> {
+        Err(BlockchainError::InvalidTxVersion)
+    }
 }
\ No newline at end of file
@@ -109,4 +109,8 @@ impl Serializer for OrderedHashes {
 
         Ok(OrderedHashes(hashes))
     }
+
+    fn size(&self) -> usize {
+        self.0.len() * HASH_SIZE
+    }
 }
\ No newline at end of file
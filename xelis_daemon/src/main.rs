@@ -560,8 +560,8 @@ async fn add_tx<S: Storage>(manager: &CommandManager, mut arguments: ArgumentMan
         true
     };
 
-    let tx = Transaction::from_hex(hex).context("Error while decoding tx in hexadecimal format")?;
-    let hash = tx.hash();
+    let tx = Transaction::from_hex(&hex).context("Error while decoding tx in hexadecimal format")?;
+    let hash = tx.hash().clone();
     manager.message(format!("Adding TX {} to mempool...", hash));
 
     let context = manager.get_context().lock()?;
@@ -120,6 +120,26 @@ impl Packet<'_> {
             Packet::KeyExchange(_) => KEY_EXCHANGE_ID,
         }
     }
+
+    // Id of the packet with its content to serialize
+    fn get_content(&self) -> (u8, &dyn Serializer) {
+        match self {
+            Packet::KeyExchange(key) => (KEY_EXCHANGE_ID, key),
+            Packet::Handshake(handshake) => (HANDSHAKE_ID, handshake.as_ref()),
+            Packet::TransactionPropagation(tx) => (TX_PROPAGATION_ID, tx),
+            Packet::BlockPropagation(block) => (BLOCK_PROPAGATION_ID, block),
+            Packet::ChainRequest(request) => (CHAIN_REQUEST_ID, request),
+            Packet::ChainResponse(response) => (CHAIN_RESPONSE_ID, response),
+            Packet::Ping(ping) => (PING_ID, ping.as_ref()),
+            Packet::ObjectRequest(request) => (OBJECT_REQUEST_ID, request.as_ref()),
+            Packet::ObjectResponse(response) => (OBJECT_RESPONSE_ID, response),
+            Packet::NotifyInventoryRequest(request) => (NOTIFY_INV_REQUEST_ID, request),
+            Packet::NotifyInventoryResponse(inventory) => (NOTIFY_INV_RESPONSE_ID, inventory),
+            Packet::BootstrapChainRequest(request) => (BOOTSTRAP_CHAIN_REQUEST_ID, request),
+            Packet::BootstrapChainResponse(response) => (BOOTSTRAP_CHAIN_RESPONSE_ID, response),
+            Packet::PeerDisconnected(disconnected) => (PEER_DISCONNECTED_ID, disconnected),
+        }
+    }
 }
 
 impl<'a> Serializer for Packet<'a> {
@@ -155,25 +175,13 @@ impl<'a> Serializer for Packet<'a> {
     }
 
     fn write(&self, writer: &mut Writer) {
-        let (id, serializer): (u8, &dyn Serializer) = match self {
-            Packet::KeyExchange(key) => (KEY_EXCHANGE_ID, key),
-            Packet::Handshake(handshake) => (HANDSHAKE_ID, handshake.as_ref()),
-            Packet::TransactionPropagation(tx) => (TX_PROPAGATION_ID, tx),
-            Packet::BlockPropagation(block) => (BLOCK_PROPAGATION_ID, block),
-            Packet::ChainRequest(request) => (CHAIN_REQUEST_ID, request),
-            Packet::ChainResponse(response) => (CHAIN_RESPONSE_ID, response),
-            Packet::Ping(ping) => (PING_ID, ping.as_ref()),
-            Packet::ObjectRequest(request) => (OBJECT_REQUEST_ID, request.as_ref()),
-            Packet::ObjectResponse(response) => (OBJECT_RESPONSE_ID, response),
-            Packet::NotifyInventoryRequest(request) => (NOTIFY_INV_REQUEST_ID, request),
-            Packet::NotifyInventoryResponse(inventory) => (NOTIFY_INV_RESPONSE_ID, inventory),
-            Packet::BootstrapChainRequest(request) => (BOOTSTRAP_CHAIN_REQUEST_ID, request),
-            Packet::BootstrapChainResponse(response) => (BOOTSTRAP_CHAIN_RESPONSE_ID, response),
-            Packet::PeerDisconnected(disconnected) => (PEER_DISCONNECTED_ID, disconnected),
-        };
-
-        let packet = serializer.to_bytes();
+        let (id, serializer) = self.get_content();
         writer.write_u8(id);
-        writer.write_bytes(&packet);
+        serializer.write(writer);
+    }
+
+    fn size(&self) -> usize {
+        // 1 for the packet id
+        1 + self.get_content().1.size()
     }
 }
\ No newline at end of file
@@ -1,4 +1,5 @@
 use xelis_common::{
+    config::MAX_TRANSACTION_SIZE,
     crypto::{
         Hash,
         Hashable,
@@ -119,7 +120,7 @@ impl ObjectResponse<'_> {
         match &self {
             Self::Block(block) => Cow::Owned(ObjectRequest::Block(block.hash())),
             Self::BlockHeader(header) => Cow::Owned(ObjectRequest::BlockHeader(header.hash())),
-            Self::Transaction(tx) => Cow::Owned(ObjectRequest::Transaction(tx.hash())),
+            Self::Transaction(tx) => Cow::Owned(ObjectRequest::Transaction(tx.hash().clone())),
             Self::NotFound(request) => Cow::Borrowed(request)
         }
     }
@@ -137,7 +138,7 @@ impl ObjectResponse<'_> {
             },
             Self::Transaction(tx) => {
                 let tx = tx.into_owned();
-                let hash = tx.hash();
+                let hash = tx.hash().clone();
                 OwnedObjectResponse::Transaction(tx, hash)
             },
             ObjectResponse::NotFound(request) => OwnedObjectResponse::NotFound(request)
@@ -172,7 +173,7 @@ impl<'a> Serializer for ObjectResponse<'a> {
         Ok(match id {
             0 => Self::Block(Cow::Owned(Block::read(reader)?)),
             1 => Self::BlockHeader(Cow::Owned(BlockHeader::read(reader)?)),
-            2 => Self::Transaction(Cow::Owned(Transaction::read(reader)?)),
+            2 => Self::Transaction(Cow::Owned(Transaction::read_bounded(reader, MAX_TRANSACTION_SIZE)?)),
             3 => Self::NotFound(ObjectRequest::read(reader)?),
             _ => return Err(ReaderError::InvalidValue)
         })
@@ -73,7 +73,6 @@ use xelis_common::{
             GetTransactionExecutorResult
         },
         RPCTransaction,
-        RPCTransactionType as RPCTransactionType,
         SplitAddressParams,
         SplitAddressResult,
     },
@@ -167,18 +166,7 @@ pub async fn get_block_response<S: Storage>(blockchain: &Blockchain<S>, storage:
     let header = block.get_header();
     let transactions = block.get_transactions()
         .iter().zip(block.get_txs_hashes()).map(|(tx, hash)| {
-            RPCTransaction {
-                hash: Cow::Borrowed(hash),
-                version: tx.get_version(),
-                source: tx.get_source().as_address(mainnet),
-                data: RPCTransactionType::from_type(tx.get_data(), mainnet),
-                fee: tx.get_fee(),
-                nonce: tx.get_nonce(),
-                source_commitments: Cow::Borrowed(tx.get_source_commitments()),
-                range_proof: Cow::Borrowed(tx.get_range_proof()),
-                reference: Cow::Borrowed(tx.get_reference()),
-                signature: Cow::Borrowed(tx.get_signature()),
-            }
+            RPCTransaction::from_tx(tx, hash, mainnet)
         }).collect::<Vec<RPCTransaction<'_>>>();
 
     let (dev_reward, miner_reward) = get_block_rewards(header.get_height(), reward).map(|(dev_reward, miner_reward)| {
@@ -690,7 +678,7 @@ async fn submit_transaction<S: Storage>(context: &Context, body: Value) -> Resul
         return Err(InternalRpcError::InvalidJSONRequest).context(format!("Transaction size cannot be greater than {}", human_bytes(MAX_TRANSACTION_SIZE as f64)))?
     }
 
-    let transaction = Transaction::from_hex(params.data)
+    let transaction = Transaction::from_hex(&params.data)
         .map_err(|err| InternalRpcError::InvalidParamsAny(err.into()))?;
 
     let blockchain: &Arc<Blockchain<S>> = context.get()?;
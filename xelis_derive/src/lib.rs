@@ -0,0 +1,190 @@
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{format_ident, quote};
+use syn::{
+    parse_macro_input,
+    parse_quote,
+    Data,
+    DeriveInput,
+    Error,
+    Field,
+    Fields,
+    GenericParam,
+    Index,
+    Member
+};
+
+// Derive the Serializer trait from xelis_common
+// Fields are written, read and sized one by one in their declaration order
+// Option<T> and Vec<T> fields use their own Serializer impl (presence flag, length prefix)
+// A field marked with #[serializer(skip)] is never written and is set to its default value on read
+#[proc_macro_derive(Serializer, attributes(serializer))]
+pub fn derive_serializer(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(input)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+// Check if the field has the #[serializer(skip)] attribute
+fn is_skipped(field: &Field) -> syn::Result<bool> {
+    let mut skip = false;
+    for attr in field.attrs.iter().filter(|attr| attr.path().is_ident("serializer")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("skip") {
+                skip = true;
+                Ok(())
+            } else {
+                Err(meta.error("unsupported serializer attribute, expected `skip`"))
+            }
+        })?;
+    }
+
+    Ok(skip)
+}
+
+fn expand(mut input: DeriveInput) -> syn::Result<TokenStream2> {
+    let fields = match &input.data {
+        Data::Struct(data) => &data.fields,
+        Data::Enum(data) => return Err(Error::new_spanned(data.enum_token, "Serializer can only be derived for structs")),
+        Data::Union(data) => return Err(Error::new_spanned(data.union_token, "Serializer can only be derived for structs"))
+    };
+
+    let serializer = quote!(::xelis_common::serializer);
+
+    let mut writes = Vec::new();
+    let mut reads = Vec::new();
+    let mut sizes = Vec::new();
+    let mut members = Vec::new();
+    let mut variables = Vec::new();
+    for (i, field) in fields.iter().enumerate() {
+        let member = match &field.ident {
+            Some(ident) => Member::Named(ident.clone()),
+            None => Member::Unnamed(Index::from(i))
+        };
+        let variable = format_ident!("__field{}", i);
+        let ty = &field.ty;
+
+        if is_skipped(field)? {
+            reads.push(quote! {
+                let #variable: #ty = ::std::default::Default::default();
+            });
+        } else {
+            writes.push(quote! {
+                #serializer::Serializer::write(&self.#member, writer);
+            });
+            reads.push(quote! {
+                let #variable = <#ty as #serializer::Serializer>::read(reader)?;
+            });
+            sizes.push(quote! {
+                #serializer::Serializer::size(&self.#member)
+            });
+        }
+
+        members.push(member);
+        variables.push(variable);
+    }
+
+    let constructor = match fields {
+        Fields::Named(_) => quote!(Self { #(#members: #variables),* }),
+        Fields::Unnamed(_) => quote!(Self(#(#variables),*)),
+        Fields::Unit => quote!(Self)
+    };
+
+    let size = if sizes.is_empty() {
+        quote!(0)
+    } else {
+        quote!(#(#sizes)+*)
+    };
+
+    // Every type parameter must be serializable too
+    for param in input.generics.params.iter_mut() {
+        if let GenericParam::Type(param) = param {
+            param.bounds.push(parse_quote!(#serializer::Serializer));
+        }
+    }
+
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        #[automatically_derived]
+        impl #impl_generics #serializer::Serializer for #name #ty_generics #where_clause {
+            fn write(&self, writer: &mut #serializer::Writer) {
+                let _ = &writer;
+                #(#writes)*
+            }
+
+            fn read(reader: &mut #serializer::Reader) -> ::std::result::Result<Self, #serializer::ReaderError> {
+                let _ = &reader;
+                #(#reads)*
+                ::std::result::Result::Ok(#constructor)
+            }
+
+            fn size(&self) -> usize {
+                #size
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn expand_error(input: DeriveInput) -> String {
+        match expand(input) {
+            Ok(_) => panic!("derive should have been rejected"),
+            Err(e) => e.to_string()
+        }
+    }
+
+    #[test]
+    fn test_struct_expands() {
+        let tokens = expand(parse_quote! {
+            struct Payload<T> {
+                amount: u64,
+                value: T,
+                #[serializer(skip)]
+                cache: Option<u64>
+            }
+        }).unwrap().to_string();
+
+        assert!(tokens.contains("impl < T : :: xelis_common :: serializer :: Serializer >"));
+        // Skipped field is never written but set to its default value
+        assert!(!tokens.contains("self . cache"));
+        assert!(tokens.contains("let __field2 : Option < u64 > = :: std :: default :: Default :: default ()"));
+    }
+
+    #[test]
+    fn test_enum_rejected() {
+        let error = expand_error(parse_quote! {
+            enum Kind {
+                A,
+                B
+            }
+        });
+        assert_eq!(error, "Serializer can only be derived for structs");
+    }
+
+    #[test]
+    fn test_union_rejected() {
+        let error = expand_error(parse_quote! {
+            union Bits {
+                a: u32,
+                b: f32
+            }
+        });
+        assert_eq!(error, "Serializer can only be derived for structs");
+    }
+
+    #[test]
+    fn test_unknown_attribute_rejected() {
+        let error = expand_error(parse_quote! {
+            struct Payload {
+                #[serializer(rename = "value")]
+                amount: u64
+            }
+        });
+        assert_eq!(error, "unsupported serializer attribute, expected `skip`");
+    }
+}
@@ -258,7 +258,7 @@ async fn build_transaction(context: &Context, body: Value) -> Result<Value, Inte
             None
         },
         inner: DataHash {
-            hash: Cow::Owned(tx.hash()),
+            hash: Cow::Owned(tx.hash().clone()),
             data: Cow::Owned(tx)
         }
     }))
@@ -276,6 +276,10 @@ async fn estimate_fees(context: &Context, body: Value) -> Result<Value, Internal
 // List transactions from the wallet storage
 async fn list_transactions(context: &Context, body: Value) -> Result<Value, InternalRpcError> {
     let params: ListTransactionsParams = parse_params(body)?;
+    if let Some(query) = &params.query {
+        query.validate(true).map_err(|e| InternalRpcError::InvalidParamsAny(e.into()))?;
+    }
+
     if let Some(addr) = &params.address {
         if !addr.is_normal() {
             return Err(InternalRpcError::InvalidParams("Address should be in normal format (not integrated address)"))
@@ -365,9 +369,7 @@ async fn get_tree_name(context: &Context, tree: String) -> Result<String, Intern
 async fn get_matching_keys(context: &Context, body: Value) -> Result<Value, InternalRpcError> {
     let params: GetMatchingKeysParams = parse_params(body)?;
     if let Some(query) = &params.query {
-        if query.is_for_element() {
-            return Err(InternalRpcError::InvalidParams("Invalid key query, should be a QueryValue"))
-        }
+        query.validate(false).map_err(|e| InternalRpcError::InvalidParamsAny(e.into()))?;
     }
 
     let wallet: &Arc<Wallet> = context.get()?;
@@ -424,9 +426,11 @@ async fn has_key(context: &Context, body: Value) -> Result<Value, InternalRpcErr
 async fn query_db(context: &Context, body: Value) -> Result<Value, InternalRpcError> {
     let params: QueryDBParams = parse_params(body)?;
     if let Some(query) = &params.key {
-        if query.is_for_element() {
-            return Err(InternalRpcError::InvalidParams("Invalid key query, should be a QueryValue"))
-        }
+        query.validate(false).map_err(|e| InternalRpcError::InvalidParamsAny(e.into()))?;
+    }
+
+    if let Some(query) = &params.value {
+        query.validate(true).map_err(|e| InternalRpcError::InvalidParamsAny(e.into()))?;
     }
 
     let wallet: &Arc<Wallet> = context.get()?;
@@ -756,7 +756,8 @@ async fn burn(manager: &CommandManager, mut arguments: ArgumentManager) -> Resul
     }
     let payload = BurnPayload {
         amount,
-        asset
+        asset,
+        extra_data: None
     };
     let tx = wallet.create_transaction(TransactionTypeBuilder::Burn(payload), FeeBuilder::Multiplier(1f64)).await
         .context("Error while creating transaction")?;
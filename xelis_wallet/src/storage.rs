@@ -99,6 +99,10 @@ impl Serializer for Balance {
             ciphertext
         })
     }
+
+    fn size(&self) -> usize {
+        self.amount.size() + self.ciphertext.size()
+    }
 }
 
 // Use this struct to get access to non-encrypted keys (such as salt for KDF and encrypted master key)
@@ -653,7 +653,7 @@ impl Wallet {
         let transaction = builder.build(&mut state, &self.keypair)
             .map_err(|e| WalletError::Any(e.into()))?;
 
-        let tx_hash = transaction.hash();
+        let tx_hash = transaction.hash().clone();
         debug!("Transaction created: {} with nonce {} and reference {}", tx_hash, transaction.get_nonce(), transaction.get_reference());
         state.set_tx_hash_built(tx_hash);
 